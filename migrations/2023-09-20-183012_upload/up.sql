@@ -0,0 +1,14 @@
+-- Your SQL goes here
+
+CREATE TABLE "upload" (
+                          upload_id                 UUID                            PRIMARY KEY DEFAULT uuid_generate_v4(),
+                          user_id                   UUID                            NOT NULL,
+                          destination               VARCHAR                         NOT NULL,
+                          filename                  VARCHAR                         NOT NULL,
+                          upload_length             BIGINT                          NOT NULL,
+                          upload_offset             BIGINT                          NOT NULL DEFAULT 0,
+                          expiration                TIMESTAMP                       NOT NULL DEFAULT current_timestamp + interval '24' hour,
+                          FOREIGN KEY (user_id) REFERENCES "user"(user_id)
+                              ON UPDATE CASCADE
+                              ON DELETE CASCADE
+);
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+
+CREATE TABLE "dav_lock" (
+                            lock_token                UUID                            PRIMARY KEY DEFAULT uuid_generate_v4(),
+                            user_id                   UUID                            NOT NULL,
+                            path                      VARCHAR                         NOT NULL,
+                            deep                      BOOLEAN                         NOT NULL,
+                            exclusive                 BOOLEAN                         NOT NULL,
+                            owner                     VARCHAR,
+                            expiration                TIMESTAMP                       NOT NULL,
+                            FOREIGN KEY (user_id) REFERENCES "user"(user_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE
+);
+
+CREATE INDEX dav_lock_path ON "dav_lock"(path);
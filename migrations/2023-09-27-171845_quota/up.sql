@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE "quota" (
+                            quota_id                  UUID                            PRIMARY KEY DEFAULT uuid_generate_v4(),
+                            user_id                   UUID                            UNIQUE,
+                            role_id                   UUID                            UNIQUE,
+                            max_bytes                 BIGINT                          NOT NULL CHECK (max_bytes >= 0),
+                            FOREIGN KEY (user_id) REFERENCES "user"(user_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE,
+                            FOREIGN KEY (role_id) REFERENCES "role"(role_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE,
+                            CHECK ((user_id IS NULL) <> (role_id IS NULL))
+);
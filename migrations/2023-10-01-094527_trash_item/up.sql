@@ -0,0 +1,11 @@
+-- Your SQL goes here
+
+CREATE TABLE "trash_item" (
+                            trash_item_id             UUID                            PRIMARY KEY DEFAULT uuid_generate_v4(),
+                            user_id                   UUID                            NOT NULL,
+                            original_path             VARCHAR                         NOT NULL,
+                            deletion                  TIMESTAMP                       NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                            FOREIGN KEY (user_id) REFERENCES "user"(user_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE
+);
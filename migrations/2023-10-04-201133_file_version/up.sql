@@ -0,0 +1,18 @@
+-- Your SQL goes here
+
+CREATE TABLE "file_version" (
+                            file_version_id           UUID                            PRIMARY KEY DEFAULT uuid_generate_v4(),
+                            path                      VARCHAR                         NOT NULL,
+                            version                   INTEGER                         NOT NULL,
+                            size                      BIGINT                          NOT NULL,
+                            modified                  TIMESTAMP                       NOT NULL,
+                            author_id                 UUID,
+                            replaced_by               UUID,
+                            FOREIGN KEY (author_id) REFERENCES "user"(user_id)
+                                ON UPDATE CASCADE
+                                ON DELETE SET NULL,
+                            FOREIGN KEY (replaced_by) REFERENCES "user"(user_id)
+                                ON UPDATE CASCADE
+                                ON DELETE SET NULL,
+                            UNIQUE (path, version)
+);
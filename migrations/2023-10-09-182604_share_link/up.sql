@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE "share_link" (
+                              share_link_id             UUID                            PRIMARY KEY DEFAULT uuid_generate_v4(),
+                              token                     VARCHAR                         NOT NULL UNIQUE,
+                              user_id                   UUID                            NOT NULL,
+                              path                      VARCHAR                         NOT NULL,
+                              expiration                TIMESTAMP                       NOT NULL,
+                              password                  VARCHAR,
+                              max_downloads             INTEGER                         CHECK (max_downloads >= 0),
+                              downloads                 INTEGER                         NOT NULL DEFAULT 0,
+                              FOREIGN KEY (user_id) REFERENCES "user"(user_id)
+                                  ON UPDATE CASCADE
+                                  ON DELETE CASCADE
+);
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+
+CREATE TABLE "storage_grant" (
+                            storage_grant_id          UUID                            PRIMARY KEY DEFAULT uuid_generate_v4(),
+                            owner_id                  UUID                            NOT NULL,
+                            path                      VARCHAR                         NOT NULL,
+                            user_id                   UUID,
+                            role_id                   UUID,
+                            writable                  BOOLEAN                         NOT NULL DEFAULT FALSE,
+                            FOREIGN KEY (owner_id) REFERENCES "user"(user_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE,
+                            FOREIGN KEY (user_id) REFERENCES "user"(user_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE,
+                            FOREIGN KEY (role_id) REFERENCES "role"(role_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE,
+                            CHECK ((user_id IS NULL) <> (role_id IS NULL)),
+                            UNIQUE (path, user_id),
+                            UNIQUE (path, role_id)
+);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE "storage_change" (
+                            sequence                  BIGSERIAL                       PRIMARY KEY,
+                            root                      VARCHAR                         NOT NULL,
+                            path                      VARCHAR                         NOT NULL,
+                            operation                 VARCHAR                         NOT NULL,
+                            directory                 BOOLEAN                         NOT NULL,
+                            size                      BIGINT,
+                            modified                  TIMESTAMP,
+                            CHECK (operation IN ('created', 'modified', 'deleted'))
+);
+
+CREATE INDEX storage_change_root ON "storage_change"(root, sequence);
+CREATE INDEX storage_change_path ON "storage_change"(path, sequence);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE "team_member";
+DROP TABLE "team_folder";
\ No newline at end of file
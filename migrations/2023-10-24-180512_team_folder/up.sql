@@ -0,0 +1,26 @@
+-- Your SQL goes here
+
+CREATE TABLE "team_folder" (
+                            team_folder_id            UUID                            PRIMARY KEY DEFAULT uuid_generate_v4(),
+                            name                      VARCHAR                         NOT NULL UNIQUE
+);
+
+CREATE TABLE "team_member" (
+                            team_member_id            UUID                            PRIMARY KEY DEFAULT uuid_generate_v4(),
+                            team_folder_id            UUID                            NOT NULL,
+                            user_id                   UUID,
+                            role_id                   UUID,
+                            writable                  BOOLEAN                         NOT NULL DEFAULT FALSE,
+                            FOREIGN KEY (team_folder_id) REFERENCES "team_folder"(team_folder_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE,
+                            FOREIGN KEY (user_id) REFERENCES "user"(user_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE,
+                            FOREIGN KEY (role_id) REFERENCES "role"(role_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE,
+                            CHECK ((user_id IS NULL) <> (role_id IS NULL)),
+                            UNIQUE (team_folder_id, user_id),
+                            UNIQUE (team_folder_id, role_id)
+);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE "blob_reference";
+DROP TABLE "blob";
\ No newline at end of file
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE "blob" (
+                            hash                      VARCHAR                         PRIMARY KEY,
+                            size                      BIGINT                          NOT NULL
+);
+
+CREATE TABLE "blob_reference" (
+                            path                      VARCHAR                         PRIMARY KEY,
+                            hash                      VARCHAR                         NOT NULL,
+                            FOREIGN KEY (hash) REFERENCES "blob"(hash)
+                                ON UPDATE CASCADE
+);
+
+CREATE INDEX ON "blob_reference"(hash);
\ No newline at end of file
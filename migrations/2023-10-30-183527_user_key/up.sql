@@ -0,0 +1,12 @@
+-- Your SQL goes here
+
+CREATE TABLE "user_key" (
+                            user_id                   UUID                            PRIMARY KEY,
+                            salt                      BYTEA                           NOT NULL,
+                            iterations                INTEGER                         NOT NULL,
+                            nonce                     BYTEA                           NOT NULL,
+                            wrapped_key               BYTEA                           NOT NULL,
+                            FOREIGN KEY (user_id) REFERENCES "user"(user_id)
+                                ON UPDATE CASCADE
+                                ON DELETE CASCADE
+);
\ No newline at end of file
@@ -3,6 +3,7 @@
 #![warn(missing_docs)]
 
 pub mod os;
+pub mod team;
 pub mod user;
 
 use clap::{Parser, Subcommand};
@@ -31,6 +32,8 @@ enum Command {
     Reload,
     /// Role management commands.
     //Role(role::Role),
+    /// Team folder management commands.
+    Team(team::Team),
     /// User management commands.
     User(user::User)
 }
@@ -51,6 +54,7 @@ fn main() {
         Command::Stop => os::service_stop(),
         Command::Reload => os::service_reload(),
         //Command::Role(role) => role::main(role),
+        Command::Team(args) => team::main(args),
         Command::User(args) => user::main(args)
     };
 
@@ -0,0 +1,226 @@
+//! This module contains the necessary functions and data structures for the subcommand `team`.
+
+use clap::{Parser, Subcommand};
+use tabled::{Table, Tabled};
+use tabled::settings::Style;
+
+use tusk_core::config::TuskConfigurationFile;
+use tusk_core::error::{TuskError, TuskResult};
+use tusk_core::{Connection, DieselError, PgConnection};
+use tusk_core::resources::TeamFolder;
+
+/// Encapsulates the team folder data to be displayed in a table.
+#[derive(Tabled)]
+#[tabled(rename_all = "CamelCase")]
+pub struct TeamTable {
+    name: String,
+    read_write: String,
+    read_only: String
+}
+impl TeamTable {
+    /// Creates a table row from an existing team folder.
+    pub fn from_team_folder(db_connection: &mut PgConnection, team_folder: &TeamFolder) -> TuskResult<TeamTable> {
+        let mut read_write = Vec::new();
+        let mut read_only = Vec::new();
+        for member in team_folder.members(db_connection)? {
+            let name = match (member.user_id(), member.role_id()) {
+                (Some(user_id), _) => tusk_core::resources::User::from_id(db_connection, user_id)?
+                    .email()
+                    .to_owned(),
+                (None, Some(role_id)) => format!("@{}", tusk_core::resources::Role::from_id(db_connection, role_id)?.name()),
+                (None, None) => continue
+            };
+            if member.writable() { read_write.push(name); } else { read_only.push(name); }
+        }
+        Ok(TeamTable {
+            name: team_folder.name().to_owned(),
+            read_write: read_write.join(","),
+            read_only: read_only.join(",")
+        })
+    }
+}
+
+/// Team folder management.
+///
+/// This command allows to add, remove, list team folders and to manage their members, either
+/// users or roles.
+#[derive(Parser, Debug)]
+pub struct Team {
+    #[command(subcommand)]
+    command: TeamCommand,
+}
+
+/// Enumerator containing the possible `team` commands.
+#[derive(Subcommand, Debug)]
+pub enum TeamCommand {
+    /// Adds a new team folder in the database and creates its directory.
+    Add {
+        /// Name of the team folder.
+        name: String
+    },
+    /// Gives a user or a role access to a team folder.
+    ///
+    /// Giving access again to the same user or role replaces the previous access.
+    Grant {
+        /// Team folder to which give access.
+        team: String,
+        /// User to which give access.
+        #[clap(long, conflicts_with = "role", required_unless_present = "role")]
+        user: Option<String>,
+        /// Role to which give access.
+        #[clap(long)]
+        role: Option<String>,
+        /// Gives read-write access instead of read-only access.
+        #[clap(long)]
+        writable: bool
+    },
+    /// Lists all the team folders, together with their members.
+    ///
+    /// Roles are prefixed by `@`.
+    List,
+    /// Removes a team folder from the database.
+    ///
+    /// The contents of the team folder are not deleted.
+    Remove {
+        /// Name of the team folder.
+        name: String
+    },
+    /// Revokes the access of a user or a role to a team folder.
+    Revoke {
+        /// Team folder from which revoke access.
+        team: String,
+        /// User from which revoke access.
+        #[clap(long, conflicts_with = "role", required_unless_present = "role")]
+        user: Option<String>,
+        /// Role from which revoke access.
+        #[clap(long)]
+        role: Option<String>
+    },
+}
+
+/// Main entry point for the `team` command.
+pub fn main(args: Team) -> TuskResult<()> {
+    match args.command {
+        TeamCommand::Add { name } => add(name),
+        TeamCommand::Grant { team, user, role, writable } => grant(team, user, role, writable),
+        TeamCommand::List => list(),
+        TeamCommand::Remove { name } => remove(name),
+        TeamCommand::Revoke { team, user, role } => revoke(team, user, role),
+    }
+}
+
+/// Adds a new team folder with the given `name` and creates its directory.
+pub fn add(name: String) -> TuskResult<()> {
+    let tusk = TuskConfigurationFile::import_from_default_locations()?
+        .into_tusk()?;
+    let mut db_connection = tusk.db()?;
+
+    let team_folder = db_connection.transaction(|db_connection| {
+        TeamFolder::create(db_connection, &name)
+    })?;
+
+    let directory = team_folder.directory(&tusk);
+    if directory.exists() {
+        log::warn!("Warning: path `{}` already exists.", directory.display());
+    } else {
+        std::fs::create_dir_all(&directory)?;
+    }
+    log::info!("Team folder `{name}` created successfully.");
+
+    Ok(())
+}
+
+/// Gives the specified `user` or `role` access to the given `team` folder.
+pub fn grant(team: String, user: Option<String>, role: Option<String>, writable: bool) -> TuskResult<()> {
+    let tusk = TuskConfigurationFile::import_from_default_locations()?
+        .into_tusk()?;
+    let mut db_connection = tusk.db()?;
+
+    db_connection.transaction(|db_connection| {
+        let team_folder = TeamFolder::from_name(db_connection, &team)?
+            .ok_or(DieselError::NotFound)?;
+        if let Some(user) = user {
+            let user = tusk_core::resources::User::from_email(db_connection, &user)?
+                .ok_or(DieselError::NotFound)?;
+            team_folder.set_user(db_connection, user.id(), writable)?;
+        } else if let Some(role) = role {
+            let role = tusk_core::resources::Role::from_name(db_connection, &role)?
+                .ok_or(DieselError::NotFound)?;
+            team_folder.set_role(db_connection, role.id(), writable)?;
+        }
+        Ok::<_, TuskError>(())
+    })?;
+
+    log::info!("Done!");
+
+    Ok(())
+}
+
+/// Lists all the team folders.
+pub fn list() -> TuskResult<()> {
+    let tusk = TuskConfigurationFile::import_from_default_locations()?
+        .into_tusk()?;
+    let mut db_connection = tusk.db()?;
+
+    let table: Vec<TeamTable> = db_connection.transaction(|db_connection| {
+        TeamFolder::list_all(db_connection)?
+            .iter()
+            .map(|t| TeamTable::from_team_folder(db_connection, t))
+            .collect()
+    })?;
+
+    let mut table = Table::new(table);
+    table.with(Style::sharp());
+
+    println!("{table}");
+
+    Ok(())
+}
+
+/// Removes a team folder from the database.
+pub fn remove(name: String) -> TuskResult<()> {
+    let tusk = TuskConfigurationFile::import_from_default_locations()?
+        .into_tusk()?;
+    let mut db_connection = tusk.db()?;
+
+    let directory = db_connection.transaction(|db_connection| {
+        let team_folder = TeamFolder::from_name(db_connection, &name)?
+            .ok_or(DieselError::NotFound)?;
+        let directory = team_folder.directory(&tusk);
+        team_folder.delete(db_connection)?;
+        Ok::<_, TuskError>(directory)
+    })?;
+
+    if directory.exists() {
+        log::warn!("Warning: path `{}` still exists.", directory.display());
+    }
+    log::info!("Team folder has been deleted");
+
+    Ok(())
+}
+
+/// Revokes the access of the specified `user` or `role` to the given `team` folder.
+pub fn revoke(team: String, user: Option<String>, role: Option<String>) -> TuskResult<()> {
+    let tusk = TuskConfigurationFile::import_from_default_locations()?
+        .into_tusk()?;
+    let mut db_connection = tusk.db()?;
+
+    db_connection.transaction(|db_connection| {
+        let team_folder = TeamFolder::from_name(db_connection, &team)?
+            .ok_or(DieselError::NotFound)?;
+        if let Some(user) = user {
+            let user = tusk_core::resources::User::from_email(db_connection, &user)?
+                .ok_or(DieselError::NotFound)?;
+            team_folder.remove_user(db_connection, user.id())?;
+        } else if let Some(role) = role {
+            let role = tusk_core::resources::Role::from_name(db_connection, &role)?
+                .ok_or(DieselError::NotFound)?;
+            team_folder.remove_role(db_connection, role.id())?;
+        }
+        Ok::<_, TuskError>(())
+    })?;
+
+    log::info!("Done!");
+
+    Ok(())
+}
@@ -87,6 +87,15 @@ pub enum UserCommand {
         /// If omitted, will be asked.
         user: Option<String>
     },
+    /// Sets the storage quota of a given user.
+    Quota {
+        /// User whose quota is set.
+        user: String,
+        /// Maximum number of bytes the user can store.
+        ///
+        /// If omitted, the quota of the user is removed and the quotas of the user roles apply.
+        bytes: Option<u64>,
+    },
     /// Revokes a role from a given user.
     Revoke {
         /// User from which revoke the role.
@@ -255,6 +264,7 @@ pub fn main(args: User) -> TuskResult<()> {
         UserCommand::Add(add) => add.complete()?.run(),
         UserCommand::Assign { user, role } => assign(role, user),
         UserCommand::List{ uuid } => list(uuid),
+        UserCommand::Quota { user, bytes } => quota(user, bytes),
         UserCommand::Remove { user } => remove(user),
         UserCommand::Revoke { user, role } => revoke(role, user),
     }
@@ -309,6 +319,28 @@ pub fn list(uuid: bool) -> TuskResult<()> {
     Ok(())
 }
 
+/// Sets the storage quota of the given `user` to `bytes`, or removes it if `bytes` is `None`.
+pub fn quota(user: String, bytes: Option<u64>) -> TuskResult<()> {
+    let tusk = TuskConfigurationFile::import_from_default_locations()?
+        .into_tusk()?;
+    let mut db_connection = tusk.db()?;
+
+    db_connection.transaction(|db_connection| {
+        let user = tusk_core::resources::User::from_email(db_connection, &user)?
+            .ok_or(DieselError::NotFound)?;
+        if let Some(bytes) = bytes {
+            tusk_core::resources::Quota::set_for_user(db_connection, user.id(), bytes)?;
+        } else if let Some(quota) = tusk_core::resources::Quota::from_user_id(db_connection, user.id())? {
+            quota.delete(db_connection)?;
+        }
+        Ok::<_, TuskError>(())
+    })?;
+
+    log::info!("Done!");
+
+    Ok(())
+}
+
 /// Removes an user from the database.
 pub fn remove(email: Option<String>) -> TuskResult<()> {
     let tusk = TuskConfigurationFile::import_from_default_locations()?
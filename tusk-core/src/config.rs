@@ -13,6 +13,7 @@ use std::io::{ErrorKind};
 use std::path::{PathBuf};
 use std::pin::Pin;
 use std::sync::{Arc, LockResult, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
 
 use actix_web::{cookie, FromRequest, HttpRequest, web};
 use ::diesel::{r2d2::{ConnectionManager, Pool, PooledConnection}, PgConnection, Connection};
@@ -25,12 +26,18 @@ use lettre::{Message, SmtpTransport, Transport};
 use lettre::transport::smtp::response::Response;
 use serde::Deserialize;
 use tera::{Context, Tera};
+use uuid::Uuid;
 use crate::{DieselError, PooledPgConnection};
+use crate::encryption::{DataKey, KeyRing};
+use crate::storage::StorageBackend;
 
 use crate::error::{HttpOkOr, TuskError, TuskResult};
 use crate::resources::User;
 use crate::session::AuthenticatedSession;
 
+/// Time after which an unused session expires.
+const SESSION_TTL: Duration = Duration::from_secs(15 * 60);
+
 /// `actix_web::web::Data` wrapper for [`TuskConfiguration`].
 pub type TuskData = web::Data<TuskConfiguration>;
 /// Boxed async block type to deal with custom implementors of [`FromRequest`].
@@ -58,9 +65,33 @@ impl Tusk {
             .or_internal_server_error()
             .log_error()
     }
+    /// Keeps the data key of the user in memory until the session is deleted or expires.
+    ///
+    /// The user must be logged in first, with [`Tusk::log_in`].
+    pub fn unlock(&self, key: DataKey) -> TuskResult<()> {
+        let user_id = self.authenticate()?.user_id();
+        let token = self.config.key_ring().insert(user_id, key);
+        self.session.insert("key_token", token)
+            .or_internal_server_error()
+            .log_error()
+    }
+    /// Returns the data key of the logged user, if it has been kept with [`Tusk::unlock`].
+    pub fn data_key(&self) -> Option<DataKey> {
+        let user_id = self.authenticate().ok()?.user_id();
+        self.config.key_ring().get(self.key_token()?, user_id)
+    }
+    /// Returns the token identifying the data key of the logged user in the key ring, if it has
+    /// been kept with [`Tusk::unlock`].
+    pub fn key_token(&self) -> Option<Uuid> {
+        self.authenticate().ok()?;
+        self.session.get("key_token").ok()?
+    }
     /// Deletes the session from the browser and from the backend,
     /// effectively logging out the user.
     pub fn log_out(&self) {
+        if let Ok(Some(token)) = self.session.get("key_token") {
+            self.config.key_ring().remove(token);
+        }
         self.session.clear();
         self.session.purge();
     }
@@ -206,8 +237,11 @@ impl TuskConfigurationFile {
             log_level,
             www_domain,
             api_domain,
+            preview_domain,
             contacts,
             serve,
+            storage,
+            trash,
             ui: tusk::ui::Ui {
                 icon_filetype: ui_icon_filetype
             }
@@ -216,6 +250,13 @@ impl TuskConfigurationFile {
         let tera_templates = serve.tera_templates();
         let static_files = serve.static_files();
         let user_directories = serve.user_directories();
+        let uploads = serve.uploads();
+        let trash_directory = serve.trash();
+        let trash_retention = trash.retention();
+        let versions_directory = serve.versions();
+        let thumbnails_directory = serve.thumbnails();
+        let search_index_directory = serve.search_index();
+        let blobs_directory = serve.blobs();
 
         #[cfg(not(test))]
         log::set_max_level(log_level);
@@ -223,9 +264,24 @@ impl TuskConfigurationFile {
         log::info!("Loading Tera templates from `{}`", tera_templates.display());
         log::info!("Loading static files from `{}`", static_files.display());
         log::info!("Loading user directories from `{}`", user_directories.display());
+        log::info!("Storing partial uploads in `{}`", uploads.display());
+        log::info!("Storing deleted items in `{}` for {} days", trash_directory.display(), trash.retention_days);
+        log::info!("Storing previous versions of files in `{}`", versions_directory.display());
+        log::info!("Storing thumbnails in `{}`", thumbnails_directory.display());
+        log::info!("Storing the full-text search index in `{}`", search_index_directory.display());
+        if serve.deduplicate() {
+            log::info!("Storing the deduplicated contents of the files in `{}`", blobs_directory.display());
+        }
+        if serve.encrypt() {
+            log::info!("Encrypting the files of the users at rest");
+        }
+        if let Some(preview_domain) = &preview_domain {
+            log::info!("Serving the previews of the files from `{preview_domain}`");
+        }
 
         let tera = serve.tera()?;
         let database_pool = self.diesel.pool()?;
+        let storage = storage.backend(&user_directories)?;
         let tls_server_configuration = self.ssl.into_server_configuration()?;
         let mailer = self.mail.mailer()?;
 
@@ -237,13 +293,17 @@ impl TuskConfigurationFile {
             serve,
             www_domain,
             api_domain,
+            preview_domain,
             database_pool,
             session_key,
             session_store,
             tls_server_configuration,
             ui_icon_filetype,
             mailer,
-            email_contacts: contacts
+            email_contacts: contacts,
+            trash_retention,
+            storage,
+            key_ring: Arc::new(KeyRing::new(SESSION_TTL))
         };
 
         Ok(config)
@@ -257,13 +317,17 @@ pub struct TuskConfiguration {
     serve: tusk::serve::Serve,
     www_domain: String,
     api_domain: String,
+    preview_domain: Option<String>,
     database_pool: Arc<Pool<ConnectionManager<PgConnection>>>,
     session_key: cookie::Key,
     session_store: RedisSessionStore,
     tls_server_configuration: rustls::ServerConfig,
     ui_icon_filetype: String,
     mailer: SmtpTransport,
-    email_contacts: tusk::contacts::Contacts
+    email_contacts: tusk::contacts::Contacts,
+    trash_retention: Duration,
+    storage: Arc<dyn StorageBackend>,
+    key_ring: Arc<KeyRing>
 }
 impl TuskConfiguration {
     /// Returns a configuration wrapped in `actix_web::web::Data` to store into the web server.
@@ -278,6 +342,10 @@ impl TuskConfiguration {
     pub fn api_domain(&self) -> &str {
         &self.api_domain
     }
+    /// Returns the domain from which the previews of the files are served, if any.
+    pub fn preview_domain(&self) -> Option<&str> {
+        self.preview_domain.as_deref()
+    }
     /// Returns the path from which the Tera templates are loaded.
     pub fn tera_templates(&self) -> PathBuf {
         self.serve.tera_templates()
@@ -290,6 +358,68 @@ impl TuskConfiguration {
     pub fn user_directories(&self) -> PathBuf {
         self.serve.user_directories()
     }
+    /// Returns the path where partially uploaded files are stored.
+    pub fn upload_directory(&self) -> PathBuf {
+        self.serve.uploads()
+    }
+    /// Returns the path where deleted items are stored until they are purged.
+    pub fn trash_directory(&self) -> PathBuf {
+        self.serve.trash()
+    }
+    /// Returns the time after which deleted items are purged.
+    pub fn trash_retention(&self) -> Duration {
+        self.trash_retention
+    }
+    /// Returns the backend in which user files are stored.
+    pub fn storage(&self) -> &dyn StorageBackend {
+        self.storage.as_ref()
+    }
+    /// Returns the path where the previous versions of the files are stored.
+    pub fn versions_directory(&self) -> PathBuf {
+        self.serve.versions()
+    }
+    /// Returns the path where the thumbnails of the images are cached.
+    pub fn thumbnails_directory(&self) -> PathBuf {
+        self.serve.thumbnails()
+    }
+    /// Returns the path where the full-text search index of the files is stored.
+    pub fn search_index_directory(&self) -> PathBuf {
+        self.serve.search_index()
+    }
+    /// Returns the path where the contents of the files are stored once, if deduplication is
+    /// enabled.
+    pub fn blobs_directory(&self) -> PathBuf {
+        self.serve.blobs()
+    }
+    /// Returns `true` if the contents of the uploaded files are deduplicated.
+    pub fn deduplicate(&self) -> bool {
+        self.serve.deduplicate()
+    }
+    /// Returns `true` if the files written in the root of each user are encrypted with the data
+    /// key of the user.
+    pub fn encrypt(&self) -> bool {
+        self.serve.encrypt()
+    }
+    /// Returns the data keys of the users that are currently logged in.
+    pub fn key_ring(&self) -> &KeyRing {
+        self.key_ring.as_ref()
+    }
+    /// Returns the maximum number of versions kept for each file, or `None` if there is no limit.
+    pub fn keep_versions(&self) -> Option<usize> {
+        self.serve.keep_versions()
+    }
+    /// Returns the age after which the versions of a file are thinned out to one per day, if any.
+    pub fn thin_versions_after(&self) -> Option<Duration> {
+        self.serve.thin_versions_after()
+    }
+    /// Returns the maximum total size, in bytes, of the files extracted from an uploaded archive.
+    pub fn max_archive_size(&self) -> u64 {
+        self.serve.max_archive_size()
+    }
+    /// Returns the maximum number of entries of an uploaded archive.
+    pub fn max_archive_entries(&self) -> usize {
+        self.serve.max_archive_entries()
+    }
     /// Returns the file extension for the UI icons.
     pub fn ui_icon_filetype(&self) -> &str {
         &self.ui_icon_filetype
@@ -354,7 +484,7 @@ impl TuskConfiguration {
     pub fn session_middleware(&self) -> SessionMiddleware<RedisSessionStore> {
         SessionMiddleware::builder(self.session_store.clone(), self.session_key.clone())
             .session_lifecycle(PersistentSession::default()
-                .session_ttl(cookie::time::Duration::minutes(15))
+                .session_ttl(cookie::time::Duration::seconds(SESSION_TTL.as_secs() as i64))
                 .session_ttl_extension_policy(TtlExtensionPolicy::OnEveryRequest)
             ).build()
     }
@@ -2,6 +2,8 @@ use serde::Deserialize;
 
 pub mod contacts;
 pub mod serve;
+pub mod storage;
+pub mod trash;
 pub mod ui;
 
 /// Represents the `tusk` section of the `tusk.toml` file.
@@ -10,7 +12,12 @@ pub struct Tusk {
     pub log_level: log::LevelFilter,
     pub www_domain: String,
     pub api_domain: String,
+    pub preview_domain: Option<String>,
     pub contacts: contacts::Contacts,
     pub serve: serve::Serve,
+    #[serde(default)]
+    pub storage: storage::Storage,
+    #[serde(default)]
+    pub trash: trash::Trash,
     pub ui: ui::Ui
 }
\ No newline at end of file
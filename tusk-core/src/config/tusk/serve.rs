@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::time::Duration;
 use std::sync::{Arc, RwLock};
 use serde::Deserialize;
 use tera::Tera;
@@ -10,7 +11,19 @@ pub struct Serve {
     root: String,
     tera_templates: Option<String>,
     static_files: Option<String>,
-    user_directories: Option<String>
+    user_directories: Option<String>,
+    uploads: Option<String>,
+    trash: Option<String>,
+    versions: Option<String>,
+    thumbnails: Option<String>,
+    search_index: Option<String>,
+    blobs: Option<String>,
+    deduplicate: Option<bool>,
+    encrypt: Option<bool>,
+    keep_versions: Option<usize>,
+    thin_versions_after_days: Option<u64>,
+    max_archive_size: Option<u64>,
+    max_archive_entries: Option<usize>
 }
 impl Serve {
     pub fn root(&self) -> PathBuf {
@@ -61,11 +74,97 @@ impl Serve {
             path
         }
     }
+
+    pub fn uploads(&self) -> PathBuf {
+        if let Some(path) = &self.uploads {
+            PathBuf::from(path)
+        } else {
+            let mut path = self.root();
+            path.push("uploads");
+            path
+        }
+    }
+
+    pub fn trash(&self) -> PathBuf {
+        if let Some(path) = &self.trash {
+            PathBuf::from(path)
+        } else {
+            let mut path = self.root();
+            path.push("trash");
+            path
+        }
+    }
+
+    pub fn versions(&self) -> PathBuf {
+        if let Some(path) = &self.versions {
+            PathBuf::from(path)
+        } else {
+            let mut path = self.root();
+            path.push("versions");
+            path
+        }
+    }
+
+    pub fn thumbnails(&self) -> PathBuf {
+        if let Some(path) = &self.thumbnails {
+            PathBuf::from(path)
+        } else {
+            let mut path = self.root();
+            path.push("thumbnails");
+            path
+        }
+    }
+
+    pub fn search_index(&self) -> PathBuf {
+        if let Some(path) = &self.search_index {
+            PathBuf::from(path)
+        } else {
+            let mut path = self.root();
+            path.push("index");
+            path
+        }
+    }
+
+    pub fn blobs(&self) -> PathBuf {
+        if let Some(path) = &self.blobs {
+            PathBuf::from(path)
+        } else {
+            let mut path = self.root();
+            path.push("blobs");
+            path
+        }
+    }
+
+    pub fn deduplicate(&self) -> bool {
+        self.deduplicate.unwrap_or(false)
+    }
+
+    pub fn encrypt(&self) -> bool {
+        self.encrypt.unwrap_or(false)
+    }
+
+    pub fn keep_versions(&self) -> Option<usize> {
+        self.keep_versions
+    }
+
+    pub fn thin_versions_after(&self) -> Option<Duration> {
+        self.thin_versions_after_days
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60))
+    }
+
+    pub fn max_archive_size(&self) -> u64 {
+        self.max_archive_size.unwrap_or(1 << 30)
+    }
+
+    pub fn max_archive_entries(&self) -> usize {
+        self.max_archive_entries.unwrap_or(10_000)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
+    use std::time::Duration;
     use crate::config::tusk::serve::Serve;
 
     const TEST_FILE: &'static str = r#"
@@ -73,6 +172,18 @@ mod tests {
     tera_templates = "/server/other_tera"
     static_files = "/server/other_static"
     user_directories = "/server/other_storage"
+    uploads = "/server/other_uploads"
+    trash = "/server/other_trash"
+    versions = "/server/other_versions"
+    thumbnails = "/server/other_thumbnails"
+    search_index = "/server/other_index"
+    blobs = "/server/other_blobs"
+    deduplicate = true
+    encrypt = true
+    keep_versions = 5
+    thin_versions_after_days = 7
+    max_archive_size = 1048576
+    max_archive_entries = 100
     "#;
 
     #[test]
@@ -84,6 +195,18 @@ mod tests {
         assert_eq!(test_file.tera_templates(), PathBuf::from("/server/other_tera"));
         assert_eq!(test_file.static_files(), PathBuf::from("/server/other_static"));
         assert_eq!(test_file.user_directories(), PathBuf::from("/server/other_storage"));
+        assert_eq!(test_file.uploads(), PathBuf::from("/server/other_uploads"));
+        assert_eq!(test_file.trash(), PathBuf::from("/server/other_trash"));
+        assert_eq!(test_file.versions(), PathBuf::from("/server/other_versions"));
+        assert_eq!(test_file.thumbnails(), PathBuf::from("/server/other_thumbnails"));
+        assert_eq!(test_file.search_index(), PathBuf::from("/server/other_index"));
+        assert_eq!(test_file.blobs(), PathBuf::from("/server/other_blobs"));
+        assert!(test_file.deduplicate());
+        assert!(test_file.encrypt());
+        assert_eq!(test_file.keep_versions(), Some(5));
+        assert_eq!(test_file.thin_versions_after(), Some(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(test_file.max_archive_size(), 1048576);
+        assert_eq!(test_file.max_archive_entries(), 100);
     }
 
     #[test]
@@ -95,5 +218,17 @@ mod tests {
         assert_eq!(test_file.tera_templates(), PathBuf::from("/main/tera"));
         assert_eq!(test_file.static_files(), PathBuf::from("/main/static"));
         assert_eq!(test_file.user_directories(), PathBuf::from("/main/storage"));
+        assert_eq!(test_file.uploads(), PathBuf::from("/main/uploads"));
+        assert_eq!(test_file.trash(), PathBuf::from("/main/trash"));
+        assert_eq!(test_file.versions(), PathBuf::from("/main/versions"));
+        assert_eq!(test_file.thumbnails(), PathBuf::from("/main/thumbnails"));
+        assert_eq!(test_file.search_index(), PathBuf::from("/main/index"));
+        assert_eq!(test_file.blobs(), PathBuf::from("/main/blobs"));
+        assert!(!test_file.deduplicate());
+        assert!(!test_file.encrypt());
+        assert_eq!(test_file.keep_versions(), None);
+        assert_eq!(test_file.thin_versions_after(), None);
+        assert_eq!(test_file.max_archive_size(), 1 << 30);
+        assert_eq!(test_file.max_archive_entries(), 10_000);
     }
 }
\ No newline at end of file
@@ -0,0 +1,23 @@
+use std::path::Path;
+use std::sync::Arc;
+use serde::Deserialize;
+use crate::error::TuskResult;
+use crate::storage::{LocalStorage, StorageBackend};
+
+/// Represents the `tusk.storage` section of the `tusk.toml` file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum Storage {
+    #[default]
+    Local
+}
+impl Storage {
+    pub fn backend(self, user_directories: &Path) -> TuskResult<Arc<dyn StorageBackend>> {
+        match self {
+            Storage::Local => {
+                log::info!("Storing user files in the local filesystem");
+                Ok(Arc::new(LocalStorage::new(user_directories)))
+            }
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use std::time::Duration;
+use serde::Deserialize;
+
+/// Default number of days after which deleted items are purged.
+const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+/// Represents the `tusk.trash` section of the `tusk.toml` file.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Trash {
+    pub retention_days: u64
+}
+impl Trash {
+    pub fn retention(&self) -> Duration {
+        Duration::from_secs(self.retention_days * 24 * 60 * 60)
+    }
+}
+impl Default for Trash {
+    fn default() -> Self {
+        Trash { retention_days: DEFAULT_RETENTION_DAYS }
+    }
+}
@@ -0,0 +1,529 @@
+//! This module contains the structures needed to encrypt the files of the users at rest.
+//!
+//! Each user owns a random [`DataKey`], with which the files in the root of the user are
+//! encrypted; the data key is only stored as a [`WrappedKey`], that is, encrypted with a key
+//! derived from the password of the user, so that it can only be recovered when the user logs in.
+//! Recovered keys are kept in memory by the [`KeyRing`] while the session of the user is active.
+//!
+//! # Format
+//! An encrypted file starts with a [`Header`] of 40 bytes, containing:
+//! - the magic bytes `TUSKENC1`;
+//! - the ID of the user owning the data key, as 16 bytes;
+//! - a random nonce prefix of 8 bytes;
+//! - the size of the plain contents, as a little-endian 64-bit integer.
+//!
+//! The header is followed by the contents, split into chunks of 64 KiB, each encrypted
+//! independently with AES-256-GCM and followed by its 16-byte authentication tag.
+//! The nonce of each chunk is the nonce prefix followed by the big-endian 32-bit index of the
+//! chunk, and the header is authenticated together with every chunk; empty contents are encrypted
+//! as a single empty chunk.
+//!
+//! Hence, any range of the contents is decrypted by reading only the chunks containing it, while
+//! truncating, reordering or tampering with the chunks, as well as with the header, is detected.
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use aes_gcm::aead::{Aead, Payload};
+use rand::RngCore;
+use secrecy::{ExposeSecret, Secret};
+use secrecy::zeroize::Zeroize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// Magic bytes with which every encrypted file starts.
+const MAGIC: &[u8; 8] = b"TUSKENC1";
+/// Size, in bytes, of the header of encrypted files.
+pub const HEADER_SIZE: u64 = 40;
+/// Size, in bytes, of the chunks in which the plain contents are encrypted.
+pub const CHUNK_SIZE: u64 = 64 * 1024;
+/// Size, in bytes, of the authentication tag following every encrypted chunk.
+const TAG_SIZE: u64 = 16;
+/// Number of iterations of PBKDF2 with which the keys wrapping the data keys are derived.
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Returns an error for contents that cannot be encrypted or decrypted.
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Derives a key from the given password with PBKDF2-HMAC-SHA256.
+fn derive_key(password: &Secret<String>, salt: &[u8], iterations: u32) -> DataKey {
+    let mut key = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.expose_secret().as_bytes(), salt, iterations, &mut key);
+    let derived = DataKey(Secret::new(key));
+    key.zeroize();
+    derived
+}
+
+/// Represents the random key with which the files of a user are encrypted.
+#[derive(Clone)]
+pub struct DataKey(Secret<[u8; 32]>);
+impl DataKey {
+    /// Generates a new random key.
+    pub fn generate() -> DataKey {
+        let mut key = [0; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        let generated = DataKey(Secret::new(key));
+        key.zeroize();
+        generated
+    }
+
+    /// Returns the cipher encrypting with this key.
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(self.0.expose_secret().into())
+    }
+    /// Encrypts this key with a key derived from the given password.
+    pub fn wrap(&self, password: &Secret<String>) -> WrappedKey {
+        let mut salt = vec![0; 16];
+        let mut nonce = vec![0; 12];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let key = derive_key(password, &salt, PBKDF2_ITERATIONS)
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), self.0.expose_secret().as_slice())
+            .expect("Keys fit in a single message");
+
+        WrappedKey { salt, iterations: PBKDF2_ITERATIONS, nonce, key }
+    }
+}
+impl Debug for DataKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DataKey([REDACTED])")
+    }
+}
+
+/// Represents a [`DataKey`] encrypted with a key derived from the password of its owner.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct WrappedKey {
+    salt: Vec<u8>,
+    iterations: u32,
+    nonce: Vec<u8>,
+    key: Vec<u8>
+}
+impl WrappedKey {
+    /// Creates the wrapped key from its parts, as returned by the corresponding accessors.
+    pub fn new(salt: Vec<u8>, iterations: u32, nonce: Vec<u8>, key: Vec<u8>) -> WrappedKey {
+        WrappedKey { salt, iterations, nonce, key }
+    }
+
+    /// Returns the salt with which the wrapping key is derived from the password.
+    pub fn salt(&self) -> &[u8] { &self.salt }
+    /// Returns the number of iterations with which the wrapping key is derived from the password.
+    pub fn iterations(&self) -> u32 { self.iterations }
+    /// Returns the nonce with which the data key is encrypted.
+    pub fn nonce(&self) -> &[u8] { &self.nonce }
+    /// Returns the encrypted data key.
+    pub fn key(&self) -> &[u8] { &self.key }
+
+    /// Decrypts the data key with a key derived from the given password, and returns `None` if
+    /// the password is wrong.
+    pub fn unwrap_with(&self, password: &Secret<String>) -> Option<DataKey> {
+        if self.nonce.len() != 12 { return None; }
+        let mut plain = derive_key(password, &self.salt, self.iterations)
+            .cipher()
+            .decrypt(Nonce::from_slice(&self.nonce), self.key.as_slice())
+            .ok()?;
+        let key: Option<[u8; 32]> = plain.as_slice().try_into().ok();
+        plain.zeroize();
+
+        key.map(|mut key| {
+            let unwrapped = DataKey(Secret::new(key));
+            key.zeroize();
+            unwrapped
+        })
+    }
+}
+
+/// Contains the header of an encrypted file.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Header {
+    owner: Uuid,
+    nonce: [u8; 8],
+    size: u64
+}
+impl Header {
+    /// Creates the header of new contents of the given size, with a random nonce prefix.
+    fn new(owner: Uuid, size: u64) -> Header {
+        let mut nonce = [0; 8];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        Header { owner, nonce, size }
+    }
+    /// Reads the header at the beginning of `reader`, and returns `None` if the contents are not
+    /// encrypted.
+    pub fn read<R: Read>(reader: &mut R) -> std::io::Result<Option<Header>> {
+        let mut bytes = [0; HEADER_SIZE as usize];
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => {},
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e)
+        }
+        if &bytes[..8] != MAGIC { return Ok(None); }
+
+        let owner = Uuid::from_slice(&bytes[8..24])
+            .map_err(|_| invalid_data("Invalid owner"))?;
+        let mut nonce = [0; 8];
+        nonce.copy_from_slice(&bytes[24..32]);
+        let mut size = [0; 8];
+        size.copy_from_slice(&bytes[32..40]);
+
+        Ok(Some(Header { owner, nonce, size: u64::from_le_bytes(size) }))
+    }
+    /// Reads the header of the file at `path`, and returns `None` if the file is not encrypted.
+    pub fn of<P: AsRef<Path>>(path: P) -> std::io::Result<Option<Header>> {
+        Header::read(&mut File::open(path)?)
+    }
+
+    /// Returns the ID of the user whose data key encrypted the contents.
+    pub fn owner(&self) -> Uuid { self.owner }
+    /// Returns the size, in bytes, of the plain contents.
+    pub fn size(&self) -> u64 { self.size }
+    /// Returns the size, in bytes, of the encrypted contents, including the header.
+    pub fn encrypted_size(&self) -> u64 {
+        HEADER_SIZE + self.size + self.chunks() * TAG_SIZE
+    }
+
+    /// Returns the header as it is stored at the beginning of the encrypted contents.
+    fn to_bytes(self) -> [u8; HEADER_SIZE as usize] {
+        let mut bytes = [0; HEADER_SIZE as usize];
+        bytes[..8].copy_from_slice(MAGIC);
+        bytes[8..24].copy_from_slice(self.owner.as_bytes());
+        bytes[24..32].copy_from_slice(&self.nonce);
+        bytes[32..40].copy_from_slice(&self.size.to_le_bytes());
+        bytes
+    }
+    /// Returns the number of chunks of the contents.
+    fn chunks(&self) -> u64 {
+        self.size.div_ceil(CHUNK_SIZE).max(1)
+    }
+    /// Returns the nonce of the chunk with the given index.
+    fn nonce(&self, index: u64) -> std::io::Result<[u8; 12]> {
+        let index = u32::try_from(index)
+            .map_err(|_| invalid_data("Too many chunks"))?;
+        let mut nonce = [0; 12];
+        nonce[..8].copy_from_slice(&self.nonce);
+        nonce[8..].copy_from_slice(&index.to_be_bytes());
+        Ok(nonce)
+    }
+}
+
+/// Encrypts with `key` the `size` bytes read from `reader`, owned by the user with ID `owner`,
+/// and writes them to `writer`.
+///
+/// # Errors
+/// If `reader` contains less than `size` bytes, this function returns an `UnexpectedEof` error.
+pub fn encrypt<R: Read, W: Write>(key: &DataKey, owner: Uuid, size: u64, mut reader: R, mut writer: W) -> std::io::Result<()> {
+    let header = Header::new(owner, size);
+    let aad = header.to_bytes();
+    let cipher = key.cipher();
+    writer.write_all(&aad)?;
+
+    let mut remaining = size;
+    for index in 0..header.chunks() {
+        let mut chunk = vec![0; remaining.min(CHUNK_SIZE) as usize];
+        reader.read_exact(&mut chunk)?;
+        remaining -= chunk.len() as u64;
+        let encrypted = cipher.encrypt(Nonce::from_slice(&header.nonce(index)?), Payload { msg: &chunk, aad: &aad })
+            .map_err(|_| invalid_data("Cannot encrypt the contents"))?;
+        chunk.zeroize();
+        writer.write_all(&encrypted)?;
+    }
+
+    writer.flush()
+}
+
+/// Reader of the plain contents of encrypted contents, supporting seeking.
+///
+/// Chunks are decrypted and authenticated as they are read; any chunk that cannot be
+/// authenticated results in an `InvalidData` error.
+pub struct Decryptor<R> {
+    reader: R,
+    cipher: Aes256Gcm,
+    header: Header,
+    position: u64,
+    chunk: Option<(u64, Vec<u8>)>
+}
+impl<R: Read + Seek> Decryptor<R> {
+    /// Starts decrypting with `key` the contents of `reader`.
+    ///
+    /// # Errors
+    /// If the contents are not encrypted, or if they are empty and cannot be authenticated, this
+    /// function returns an `InvalidData` error.
+    pub fn new(key: &DataKey, mut reader: R) -> std::io::Result<Decryptor<R>> {
+        reader.seek(SeekFrom::Start(0))?;
+        let header = Header::read(&mut reader)?
+            .ok_or_else(|| invalid_data("The contents are not encrypted"))?;
+        let mut decryptor = Decryptor { reader, cipher: key.cipher(), header, position: 0, chunk: None };
+        // Empty contents are never read, hence they are authenticated in advance.
+        if header.size == 0 { decryptor.load(0)?; }
+
+        Ok(decryptor)
+    }
+
+    /// Returns the header of the contents.
+    pub fn header(&self) -> &Header { &self.header }
+
+    /// Decrypts the chunk with the given index.
+    fn load(&mut self, index: u64) -> std::io::Result<()> {
+        let offset = HEADER_SIZE + index * (CHUNK_SIZE + TAG_SIZE);
+        let length = self.header.size.saturating_sub(index * CHUNK_SIZE).min(CHUNK_SIZE) + TAG_SIZE;
+        let mut encrypted = vec![0; length as usize];
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read_exact(&mut encrypted)?;
+
+        let aad = self.header.to_bytes();
+        let nonce = self.header.nonce(index)?;
+        let plain = self.cipher.decrypt(Nonce::from_slice(&nonce), Payload { msg: &encrypted, aad: &aad })
+            .map_err(|_| invalid_data("The contents have been tampered with"))?;
+        if let Some((_, mut previous)) = self.chunk.replace((index, plain)) {
+            previous.zeroize();
+        }
+        Ok(())
+    }
+}
+impl<R: Read + Seek> Read for Decryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.position >= self.header.size { return Ok(0); }
+
+        let index = self.position / CHUNK_SIZE;
+        if !matches!(self.chunk, Some((loaded, _)) if loaded == index) {
+            self.load(index)?;
+        }
+        let Some((_, chunk)) = &self.chunk else { return Ok(0); };
+        let offset = (self.position - index * CHUNK_SIZE) as usize;
+        let read = buf.len().min(chunk.len().saturating_sub(offset));
+        buf[..read].copy_from_slice(&chunk[offset..offset + read]);
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+impl<R: Read + Seek> Seek for Decryptor<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.header.size.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta)
+        };
+        self.position = position
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Invalid seek to a negative or overflowing position"))?;
+        Ok(self.position)
+    }
+}
+impl<R> Debug for Decryptor<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Decryptor")
+            .field("header", &self.header)
+            .field("position", &self.position)
+            .finish_non_exhaustive()
+    }
+}
+impl<R> Drop for Decryptor<R> {
+    fn drop(&mut self) {
+        if let Some((_, chunk)) = &mut self.chunk {
+            chunk.zeroize();
+        }
+    }
+}
+
+/// Entry of the [`KeyRing`].
+struct KeyRingEntry {
+    user_id: Uuid,
+    key: DataKey,
+    last_used: Instant
+}
+
+/// Keeps the data keys of the users in memory while their sessions are active.
+///
+/// Keys are identified by random tokens, which are stored in the sessions; keys are forgotten
+/// when their sessions are deleted, or when they are not used for longer than the lifetime of the
+/// sessions.
+///
+/// Keys recovered with the credentials of a user outside of a session are identified by a salted
+/// digest of the credentials instead, so that they need not be recovered again on every request.
+pub struct KeyRing {
+    ttl: Duration,
+    salt: [u8; 32],
+    keys: Mutex<HashMap<Uuid, KeyRingEntry>>
+}
+impl KeyRing {
+    /// Creates an empty key ring, forgetting the keys that are not used for `ttl`.
+    pub fn new(ttl: Duration) -> KeyRing {
+        let mut salt = [0; 32];
+        rand::thread_rng().fill_bytes(&mut salt);
+        KeyRing { ttl, salt, keys: Mutex::new(HashMap::new()) }
+    }
+
+    /// Returns the token identifying the data key recovered with the given credentials.
+    fn credentials_token(&self, user_id: Uuid, password: &Secret<String>) -> Uuid {
+        let digest = Sha256::new()
+            .chain_update(self.salt)
+            .chain_update(user_id.as_bytes())
+            .chain_update(password.expose_secret().as_bytes())
+            .finalize();
+        let mut token = [0; 16];
+        token.copy_from_slice(&digest[..16]);
+        Uuid::from_bytes(token)
+    }
+    /// Stores the data key of the given user, recovered with the password of the user.
+    pub fn insert_recovered(&self, user_id: Uuid, password: &Secret<String>, key: DataKey) {
+        let token = self.credentials_token(user_id, password);
+        let mut keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+        keys.insert(token, KeyRingEntry { user_id, key, last_used: Instant::now() });
+    }
+    /// Returns the data key of the given user recovered with the same password, if it has not been
+    /// forgotten yet.
+    pub fn get_recovered(&self, user_id: Uuid, password: &Secret<String>) -> Option<DataKey> {
+        self.get(self.credentials_token(user_id, password), user_id)
+    }
+
+    /// Stores the data key of the given user and returns the token identifying it.
+    pub fn insert(&self, user_id: Uuid, key: DataKey) -> Uuid {
+        let token = Uuid::new_v4();
+        let mut keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+        keys.insert(token, KeyRingEntry { user_id, key, last_used: Instant::now() });
+        token
+    }
+    /// Returns the data key identified by `token`, if it belongs to the given user and it has not
+    /// been forgotten yet.
+    pub fn get(&self, token: Uuid, user_id: Uuid) -> Option<DataKey> {
+        let mut keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+        keys.retain(|_, entry| entry.last_used.elapsed() < self.ttl);
+        let entry = keys.get_mut(&token)
+            .filter(|entry| entry.user_id == user_id)?;
+        entry.last_used = Instant::now();
+        Some(entry.key.clone())
+    }
+    /// Returns a data key of the given user, while the user has a session in which the key has
+    /// not been forgotten yet.
+    ///
+    /// Unlike [`KeyRing::get`], this function does not keep the key from expiring.
+    pub fn find(&self, user_id: Uuid) -> Option<DataKey> {
+        let mut keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+        keys.retain(|_, entry| entry.last_used.elapsed() < self.ttl);
+        keys.values()
+            .find(|entry| entry.user_id == user_id)
+            .map(|entry| entry.key.clone())
+    }
+    /// Forgets the data key identified by `token`.
+    pub fn remove(&self, token: Uuid) {
+        let mut keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+        keys.remove(&token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+    use std::time::Duration;
+    use secrecy::Secret;
+    use uuid::Uuid;
+    use crate::encryption::{CHUNK_SIZE, DataKey, Decryptor, encrypt, Header, HEADER_SIZE, KeyRing};
+
+    fn encrypted(key: &DataKey, owner: Uuid, contents: &[u8]) -> Vec<u8> {
+        let mut encrypted = Vec::new();
+        encrypt(key, owner, contents.len() as u64, contents, &mut encrypted).unwrap();
+        encrypted
+    }
+
+    #[test]
+    fn test_encryption() {
+        let key = DataKey::generate();
+        let owner = Uuid::new_v4();
+        let contents: Vec<u8> = (0..3 * CHUNK_SIZE + 100).map(|i| (i % 251) as u8).collect();
+        let data = encrypted(&key, owner, &contents);
+
+        let header = Header::read(&mut data.as_slice()).unwrap().unwrap();
+        assert_eq!(header.owner(), owner);
+        assert_eq!(header.size(), contents.len() as u64);
+        assert_eq!(header.encrypted_size(), data.len() as u64);
+        assert_eq!(Header::read(&mut contents.as_slice()).unwrap(), None);
+
+        let mut decryptor = Decryptor::new(&key, Cursor::new(&data)).unwrap();
+        let mut plain = Vec::new();
+        decryptor.read_to_end(&mut plain).unwrap();
+        assert_eq!(plain, contents);
+
+        // Ranges across the chunks are decrypted.
+        let start = CHUNK_SIZE - 10;
+        decryptor.seek(SeekFrom::Start(start)).unwrap();
+        let mut range = vec![0; CHUNK_SIZE as usize + 20];
+        decryptor.read_exact(&mut range).unwrap();
+        assert_eq!(range, contents[start as usize..(start + CHUNK_SIZE + 20) as usize]);
+
+        // Empty contents are encrypted as well.
+        let empty = encrypted(&key, owner, &[]);
+        let mut plain = Vec::new();
+        Decryptor::new(&key, Cursor::new(&empty)).unwrap().read_to_end(&mut plain).unwrap();
+        assert!(plain.is_empty());
+    }
+
+    #[test]
+    fn test_tampering() {
+        let key = DataKey::generate();
+        let contents = vec![7; 2 * CHUNK_SIZE as usize];
+        let encrypted = encrypted(&key, Uuid::new_v4(), &contents);
+        let read = |encrypted: Vec<u8>, key: &DataKey| -> std::io::Result<Vec<u8>> {
+            let mut plain = Vec::new();
+            Decryptor::new(key, Cursor::new(encrypted))?.read_to_end(&mut plain)?;
+            Ok(plain)
+        };
+
+        assert!(read(encrypted.clone(), &DataKey::generate()).is_err());
+        let mut tampered = encrypted.clone();
+        tampered[HEADER_SIZE as usize + 100] ^= 1;
+        assert_eq!(read(tampered, &key).unwrap_err().kind(), ErrorKind::InvalidData);
+        let mut resized = encrypted.clone();
+        resized[32] = 1;
+        assert!(read(resized, &key).is_err());
+        let mut truncated = encrypted;
+        truncated.truncate(truncated.len() - 1);
+        assert!(read(truncated, &key).is_err());
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let key = DataKey::generate();
+        let owner = Uuid::new_v4();
+        let wrapped = key.wrap(&Secret::new("correct horse battery staple".to_owned()));
+        assert!(wrapped.unwrap_with(&Secret::new("wrong password".to_owned())).is_none());
+        let unwrapped = wrapped.unwrap_with(&Secret::new("correct horse battery staple".to_owned())).unwrap();
+
+        let encrypted = encrypted(&key, owner, b"Contents");
+        let mut plain = Vec::new();
+        Decryptor::new(&unwrapped, Cursor::new(encrypted)).unwrap().read_to_end(&mut plain).unwrap();
+        assert_eq!(plain, b"Contents");
+    }
+
+    #[test]
+    fn test_key_ring() {
+        let (user, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let ring = KeyRing::new(Duration::from_secs(60));
+        let token = ring.insert(user, DataKey::generate());
+        assert!(ring.get(token, user).is_some());
+        assert!(ring.get(token, other).is_none());
+        assert!(ring.get(Uuid::new_v4(), user).is_none());
+        assert!(ring.find(user).is_some());
+        assert!(ring.find(other).is_none());
+        ring.remove(token);
+        assert!(ring.find(user).is_none());
+        assert!(ring.get(token, user).is_none());
+
+        let password = Secret::new("password".to_owned());
+        ring.insert_recovered(user, &password, DataKey::generate());
+        assert!(ring.get_recovered(user, &password).is_some());
+        assert!(ring.get_recovered(user, &Secret::new("other".to_owned())).is_none());
+        assert!(ring.get_recovered(other, &password).is_none());
+
+        let expiring = KeyRing::new(Duration::ZERO);
+        let token = expiring.insert(user, DataKey::generate());
+        assert!(expiring.get(token, user).is_none());
+        expiring.insert_recovered(user, &password, DataKey::generate());
+        assert!(expiring.get_recovered(user, &password).is_none());
+    }
+}
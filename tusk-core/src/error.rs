@@ -206,6 +206,32 @@ impl TuskError {
     pub fn gone() -> Self {
         TuskError::from(StatusCode::GONE)
     }
+    /// Creates a new instance of `TuskError` with status code `PRECONDITION FAILED`.
+    ///
+    /// ## 412 -- PRECONDITION FAILED
+    ///
+    /// The client has indicated preconditions in its headers which the server does not meet.
+    pub fn precondition_failed() -> Self {
+        TuskError::from(StatusCode::PRECONDITION_FAILED)
+    }
+    /// Creates a new instance of `TuskError` with status code `PAYLOAD TOO LARGE`.
+    ///
+    /// ## 413 -- PAYLOAD TOO LARGE
+    ///
+    /// Request entity is larger than limits defined by server. The server might close
+    /// the connection or return a `Retry-After` header field.
+    pub fn payload_too_large() -> Self {
+        TuskError::from(StatusCode::PAYLOAD_TOO_LARGE)
+    }
+    /// Creates a new instance of `TuskError` with status code `UNSUPPORTED MEDIA TYPE`.
+    ///
+    /// ## 415 -- UNSUPPORTED MEDIA TYPE
+    ///
+    /// The media format of the requested data is not supported by the server, so the server
+    /// is rejecting the request.
+    pub fn unsupported_media_type() -> Self {
+        TuskError::from(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+    }
     /// Creates a new instance of `TuskError` with status code `I'M A TEAPOT`.
     ///
     /// ## 418 -- I'M A TEAPOT
@@ -222,6 +248,23 @@ impl TuskError {
     pub fn unprocessable_entity() -> Self {
         TuskError::from(StatusCode::UNPROCESSABLE_ENTITY)
     }
+    /// Creates a new instance of `TuskError` with status code `LOCKED`.
+    ///
+    /// ## 423 -- LOCKED
+    ///
+    /// The resource that is being accessed is locked.
+    pub fn locked() -> Self {
+        TuskError::from(StatusCode::LOCKED)
+    }
+    /// Creates a new instance of `TuskError` with status code `PRECONDITION REQUIRED`.
+    ///
+    /// ## 428 -- PRECONDITION REQUIRED
+    ///
+    /// The origin server requires the request to be conditional, to prevent the "lost update"
+    /// problem, where a client overwrites changes made by another client in the meantime.
+    pub fn precondition_required() -> Self {
+        TuskError::from(StatusCode::PRECONDITION_REQUIRED)
+    }
 
     /// Creates a new instance of `TuskError` with status code `INTERNAL SERVER ERROR`.
     ///
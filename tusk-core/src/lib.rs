@@ -3,11 +3,13 @@
 #![warn(missing_docs)]
 
 pub mod config;
+pub mod encryption;
 pub mod error;
 pub mod resources;
 #[allow(missing_docs)]
 pub mod schema;
 pub mod session;
+pub mod storage;
 
 pub use diesel::PgConnection;
 pub use diesel::Connection;
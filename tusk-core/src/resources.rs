@@ -1,9 +1,31 @@
 //! This module contains all the database resources, parsed as Rust data structures.
 
+pub mod blob;
+pub mod dav_lock;
+pub mod file_version;
 pub mod role;
 pub mod password_reset;
+pub mod quota;
+pub mod share_link;
+pub mod storage_change;
+pub mod storage_grant;
+pub mod team_folder;
+pub mod trash_item;
+pub mod upload;
 pub mod user;
+pub mod user_key;
 
+pub use blob::{Blob, BlobStats};
+pub use dav_lock::DavLock;
+pub use file_version::FileVersion;
 pub use role::Role;
 pub use password_reset::PasswordResetRequest;
-pub use user::User;
\ No newline at end of file
+pub use quota::Quota;
+pub use share_link::ShareLink;
+pub use storage_change::{StorageChange, StorageOperation};
+pub use storage_grant::StorageGrant;
+pub use team_folder::{TeamFolder, TeamMember};
+pub use trash_item::TrashItem;
+pub use upload::Upload;
+pub use user::User;
+pub use user_key::UserKey;
\ No newline at end of file
@@ -0,0 +1,227 @@
+//! Data structures for the `blob` and `blob_reference` tables.
+
+use std::path::PathBuf;
+use diesel::dsl::{exists, not, sql};
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use serde::{Deserialize, Serialize};
+use crate::config::TuskConfiguration;
+use crate::error::TuskResult;
+
+/// Escapes the given text so that it is matched literally by a `LIKE` pattern with `\` as the
+/// escape character.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Represents contents that are stored once for all the files having them, identified by their
+/// SHA-256 hash.
+///
+/// Files refer to blobs through their path, relative to the storage root; the contents are stored
+/// in the path returned by [`Blob::file`], whose storage every referring file shares.
+/// A blob is deleted as soon as no file refers to it anymore.
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::blob)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Blob {
+    hash: String,
+    size: i64
+}
+impl Blob {
+    /// Records the blob with the given hexadecimal SHA-256 `hash` and `size`, unless it is already
+    /// recorded, and returns it.
+    ///
+    /// The blob is referred to by `reservation`, a path which is not in any storage, so that it is
+    /// not deleted before the file being written refers to it; the reservation must then be
+    /// released with [`Blob::release`].
+    pub fn create<S: AsRef<str>, R: AsRef<str>>(db_connection: &mut PgConnection, hash: S, size: u64, reservation: R) -> TuskResult<Blob> {
+        use crate::schema::{blob, blob_reference};
+
+        let hash = hash.as_ref();
+        let size = size.min(i64::MAX as u64) as i64;
+        let blob = db_connection.transaction(|db_connection| {
+            // Updating the existing record locks it until the reservation is committed, so that
+            // the blob cannot be deleted in the meantime.
+            let blob = diesel::insert_into(blob::table)
+                .values((blob::hash.eq(hash), blob::size.eq(size)))
+                .on_conflict(blob::hash)
+                .do_update()
+                .set(blob::size.eq(size))
+                .get_result(db_connection)?;
+            diesel::insert_into(blob_reference::table)
+                .values((blob_reference::path.eq(reservation.as_ref()), blob_reference::hash.eq(hash)))
+                .execute(db_connection)?;
+
+            Ok::<_, diesel::result::Error>(blob)
+        })?;
+
+        Ok(blob)
+    }
+
+    /// Returns the hexadecimal SHA-256 hash of the contents.
+    pub fn hash(&self) -> &str { &self.hash }
+    /// Returns the size, in bytes, of the contents.
+    pub fn size(&self) -> u64 { self.size.max(0) as u64 }
+    /// Returns the path in which the contents are stored.
+    pub fn file(&self, tusk: &TuskConfiguration) -> PathBuf {
+        let mut path = tusk.blobs_directory();
+        path.push(&self.hash[..2.min(self.hash.len())]);
+        path.push(&self.hash);
+        path
+    }
+
+    /// Returns the number of files referring to the blob.
+    pub fn references(&self, db_connection: &mut PgConnection) -> TuskResult<u64> {
+        use crate::schema::blob_reference;
+
+        let count: i64 = blob_reference::table
+            .filter(blob_reference::hash.eq(&self.hash))
+            .count()
+            .get_result(db_connection)?;
+
+        Ok(count.max(0) as u64)
+    }
+    /// Records that the file at `path`, relative to the storage root, refers to this blob, in place
+    /// of the blob it referred to, if any.
+    ///
+    /// Returns the blobs that are no longer referred to by any file, whose records are deleted.
+    pub fn refer<P: AsRef<str>>(&self, db_connection: &mut PgConnection, path: P) -> TuskResult<Vec<Blob>> {
+        use crate::schema::blob_reference;
+
+        let path = path.as_ref();
+        let orphans = db_connection.transaction(|db_connection| {
+            let previous: Vec<String> = blob_reference::table
+                .filter(blob_reference::path.eq(path))
+                .select(blob_reference::hash)
+                .load(db_connection)?;
+            diesel::insert_into(blob_reference::table)
+                .values((blob_reference::path.eq(path), blob_reference::hash.eq(&self.hash)))
+                .on_conflict(blob_reference::path)
+                .do_update()
+                .set(blob_reference::hash.eq(&self.hash))
+                .execute(db_connection)?;
+
+            Self::delete_orphans(db_connection, previous)
+        })?;
+
+        Ok(orphans)
+    }
+
+    /// Removes the reference of the file at `path`, relative to the storage root, together with
+    /// the references of the files inside it, if it is a storage.
+    ///
+    /// Returns the blobs that are no longer referred to by any file, whose records are deleted.
+    pub fn release<P: AsRef<str>>(db_connection: &mut PgConnection, path: P) -> TuskResult<Vec<Blob>> {
+        use crate::schema::blob_reference;
+
+        let path = path.as_ref();
+        let orphans = db_connection.transaction(|db_connection| {
+            let selected = blob_reference::table
+                .filter(blob_reference::path.eq(path)
+                    .or(blob_reference::path.like(format!("{}/%", escape_like(path))).escape('\\')));
+            let released: Vec<String> = diesel::delete(selected)
+                .returning(blob_reference::hash)
+                .get_results(db_connection)?;
+
+            Self::delete_orphans(db_connection, released)
+        })?;
+
+        Ok(orphans)
+    }
+    /// Moves the reference of the file at `from`, relative to the storage root, together with the
+    /// references of the files inside it, if it is a storage, to the path `to`.
+    pub fn rename<P: AsRef<str>, Q: AsRef<str>>(db_connection: &mut PgConnection, from: P, to: Q) -> TuskResult<()> {
+        use crate::schema::blob_reference;
+
+        let (from, to) = (from.as_ref(), to.as_ref());
+        db_connection.transaction(|db_connection| {
+            let paths: Vec<String> = blob_reference::table
+                .filter(blob_reference::path.eq(from)
+                    .or(blob_reference::path.like(format!("{}/%", escape_like(from))).escape('\\')))
+                .select(blob_reference::path)
+                .load(db_connection)?;
+            for path in paths {
+                diesel::update(blob_reference::table.filter(blob_reference::path.eq(&path)))
+                    .set(blob_reference::path.eq(format!("{to}{}", &path[from.len()..])))
+                    .execute(db_connection)?;
+            }
+            Ok::<_, diesel::result::Error>(())
+        })?;
+
+        Ok(())
+    }
+    /// Deletes the records of the blobs with the given hashes that are no longer referred to by any
+    /// file, and returns them.
+    fn delete_orphans(db_connection: &mut PgConnection, hashes: Vec<String>) -> QueryResult<Vec<Blob>> {
+        use crate::schema::{blob, blob_reference};
+
+        // Waits for the blobs being reserved by [`Blob::create`], so that the new references are
+        // visible to the deletion.
+        let _: Vec<String> = blob::table
+            .filter(blob::hash.eq_any(&hashes))
+            .select(blob::hash)
+            .for_update()
+            .load(db_connection)?;
+        let orphans = blob::table
+            .filter(blob::hash.eq_any(hashes))
+            .filter(not(exists(blob_reference::table.filter(blob_reference::hash.eq(blob::hash)))));
+
+        diesel::delete(orphans)
+            .get_results(db_connection)
+    }
+
+    /// Reads the blob with the given hexadecimal SHA-256 hash, if any.
+    pub fn from_hash<S: AsRef<str>>(db_connection: &mut PgConnection, hash: S) -> TuskResult<Option<Blob>> {
+        use crate::schema::blob;
+
+        let blob = blob::table
+            .filter(blob::hash.eq(hash.as_ref()))
+            .first(db_connection)
+            .optional()?;
+
+        Ok(blob)
+    }
+}
+
+/// Contains the statistics of the deduplicated contents of the files.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BlobStats {
+    blobs: u64,
+    references: u64,
+    stored_size: u64,
+    logical_size: u64
+}
+impl BlobStats {
+    /// Computes the statistics of all the blobs.
+    pub fn compute(db_connection: &mut PgConnection) -> TuskResult<BlobStats> {
+        use crate::schema::{blob, blob_reference};
+
+        let (blobs, stored_size): (i64, i64) = blob::table
+            .select((sql::<BigInt>("COUNT(*)"), sql::<BigInt>("COALESCE(SUM(blob.size), 0)::BIGINT")))
+            .get_result(db_connection)?;
+        let (references, logical_size): (i64, i64) = blob_reference::table
+            .inner_join(blob::table)
+            .select((sql::<BigInt>("COUNT(*)"), sql::<BigInt>("COALESCE(SUM(blob.size), 0)::BIGINT")))
+            .get_result(db_connection)?;
+
+        Ok(BlobStats {
+            blobs: blobs.max(0) as u64,
+            references: references.max(0) as u64,
+            stored_size: stored_size.max(0) as u64,
+            logical_size: logical_size.max(0) as u64
+        })
+    }
+
+    /// Returns the number of blobs.
+    pub fn blobs(&self) -> u64 { self.blobs }
+    /// Returns the number of files referring to the blobs.
+    pub fn references(&self) -> u64 { self.references }
+    /// Returns the number of bytes stored in the blobs.
+    pub fn stored_size(&self) -> u64 { self.stored_size }
+    /// Returns the total size, in bytes, of the files referring to the blobs.
+    pub fn logical_size(&self) -> u64 { self.logical_size }
+    /// Returns the number of bytes saved by storing the contents of the files once.
+    pub fn saved(&self) -> u64 { self.logical_size.saturating_sub(self.stored_size) }
+}
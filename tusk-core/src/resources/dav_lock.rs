@@ -0,0 +1,194 @@
+//! Data structures for the `dav_lock` table.
+
+use std::time::{Duration, SystemTime};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::error::{TuskError, TuskResult};
+
+/// Escapes the wildcards of a `LIKE` pattern.
+fn escape_like(pattern: &str) -> String {
+    pattern.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Represents a WebDAV write lock on an item of the storage.
+///
+/// The locked item is identified by its path relative to the storage root.
+/// Every interaction with the database removes from the table all the expired locks, so that
+/// the table does not grow with time.
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::dav_lock)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DavLock {
+    lock_token: Uuid,
+    user_id: Uuid,
+    path: String,
+    deep: bool,
+    exclusive: bool,
+    owner: Option<String>,
+    expiration: SystemTime
+}
+impl DavLock {
+    fn update_table(db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::dav_lock;
+
+        let selected = dav_lock::table
+            .filter(dav_lock::expiration.lt(SystemTime::now()));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+
+    /// Creates a new lock for the given user on the item at `path`, valid for the given `timeout`.
+    ///
+    /// If `deep` is `true`, the lock also applies to all the members of the item.
+    /// If `exclusive` is `false`, the lock can be shared with other shared locks.
+    ///
+    /// # Errors
+    /// If the lock conflicts with an existing lock, this function returns an HTTP error
+    /// 423 `LOCKED`.
+    pub fn create<P: AsRef<str>>(db_connection: &mut PgConnection, user_id: Uuid, path: P, deep: bool, exclusive: bool, owner: Option<&str>, timeout: Duration) -> TuskResult<DavLock> {
+        use crate::schema::dav_lock;
+
+        let path = path.as_ref();
+
+        db_connection.transaction(|db_connection| {
+            Self::update_table(db_connection)?;
+
+            let conflicts = Self::locks_on(db_connection, path, deep)?;
+            if conflicts.iter().any(|lock| exclusive || lock.exclusive) {
+                return TuskError::locked().bail();
+            }
+
+            let lock = diesel::insert_into(dav_lock::table)
+                .values((
+                    dav_lock::user_id.eq(user_id),
+                    dav_lock::path.eq(path),
+                    dav_lock::deep.eq(deep),
+                    dav_lock::exclusive.eq(exclusive),
+                    dav_lock::owner.eq(owner),
+                    dav_lock::expiration.eq(SystemTime::now() + timeout)
+                )).get_result(db_connection)?;
+
+            Ok(lock)
+        })
+    }
+
+    /// Returns the token of the lock.
+    pub fn token(&self) -> Uuid { self.lock_token }
+    /// Returns the ID of the user who owns the lock.
+    pub fn user_id(&self) -> Uuid { self.user_id }
+    /// Returns the path of the locked item, relative to the storage root.
+    pub fn path(&self) -> &str { &self.path }
+    /// Returns `true` if the lock also applies to the members of the locked item,
+    /// and `false` otherwise.
+    pub fn is_deep(&self) -> bool { self.deep }
+    /// Returns `true` if the lock is exclusive and `false` if it is shared.
+    pub fn is_exclusive(&self) -> bool { self.exclusive }
+    /// Returns the owner information provided by the client, if any.
+    pub fn owner(&self) -> Option<&str> { self.owner.as_deref() }
+    /// Returns the time left before the lock expires.
+    pub fn timeout(&self) -> Duration {
+        self.expiration.duration_since(SystemTime::now())
+            .unwrap_or_default()
+    }
+
+    /// Postpones the expiration of the lock by the given `timeout`.
+    pub fn refresh(&mut self, db_connection: &mut PgConnection, timeout: Duration) -> TuskResult<()> {
+        use crate::schema::dav_lock;
+
+        let expiration = SystemTime::now() + timeout;
+        diesel::update(dav_lock::table)
+            .filter(dav_lock::lock_token.eq(self.lock_token))
+            .set(dav_lock::expiration.eq(expiration))
+            .execute(db_connection)?;
+
+        self.expiration = expiration;
+        Ok(())
+    }
+    /// Deletes the lock.
+    pub fn delete(self, db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::dav_lock;
+
+        let selected = dav_lock::table
+            .filter(dav_lock::lock_token.eq(self.lock_token));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Self::update_table(db_connection)?;
+
+        Ok(())
+    }
+    /// Deletes all the locks on the item at `path` and on its members.
+    pub fn delete_under<P: AsRef<str>>(db_connection: &mut PgConnection, path: P) -> TuskResult<()> {
+        use crate::schema::dav_lock;
+
+        let path = path.as_ref();
+        let selected = dav_lock::table
+            .filter(dav_lock::path.eq(path).or(dav_lock::path.like(format!("{}/%", escape_like(path)))));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Self::update_table(db_connection)?;
+
+        Ok(())
+    }
+    /// Retrieves a valid lock given its token.
+    pub fn from_token(db_connection: &mut PgConnection, token: Uuid) -> TuskResult<DavLock> {
+        use crate::schema::dav_lock;
+
+        db_connection.transaction(|db_connection| {
+            Self::update_table(db_connection)?;
+
+            let lock = dav_lock::table
+                .filter(dav_lock::lock_token.eq(token))
+                .first(db_connection)?;
+
+            Ok(lock)
+        })
+    }
+    /// Returns all the valid locks that apply to the item at `path`, that is, the locks on the
+    /// item itself and the deep locks on its ancestors.
+    ///
+    /// If `members` is `true`, the locks on the members of the item are returned as well.
+    pub fn locks_on<P: AsRef<str>>(db_connection: &mut PgConnection, path: P, members: bool) -> TuskResult<Vec<DavLock>> {
+        use crate::schema::dav_lock;
+
+        let path = path.as_ref();
+        let ancestors: Vec<&str> = path.match_indices('/')
+            .map(|(index, _)| &path[..index])
+            .collect();
+
+        let mut locks: Vec<DavLock> = dav_lock::table
+            .filter(dav_lock::expiration.ge(SystemTime::now()))
+            .filter(dav_lock::path.eq(path).or(dav_lock::deep.and(dav_lock::path.eq_any(ancestors))))
+            .load(db_connection)?;
+
+        if members {
+            let member_locks: Vec<DavLock> = dav_lock::table
+                .filter(dav_lock::expiration.ge(SystemTime::now()))
+                .filter(dav_lock::path.like(format!("{}/%", escape_like(path))))
+                .load(db_connection)?;
+            locks.extend(member_locks);
+        }
+
+        Ok(locks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resources::dav_lock::escape_like;
+
+    #[test]
+    fn like_patterns_are_escaped() {
+        assert_eq!(escape_like("Documents"), "Documents");
+        assert_eq!(escape_like("100%_done\\"), "100\\%\\_done\\\\");
+    }
+}
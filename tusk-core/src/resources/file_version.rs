@@ -0,0 +1,185 @@
+//! Data structures for the `file_version` table.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::config::TuskConfiguration;
+use crate::error::TuskResult;
+
+/// Number of seconds in a day, used to thin out old versions.
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Escapes the given text so that it is matched literally by a `LIKE` pattern with `\` as the
+/// escape character.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Represents a previous version of a file of the storage, that has been replaced by newer
+/// contents.
+///
+/// Versions are attached to the path of the file, relative to the storage root, and are numbered
+/// from `1` in the order in which they have been replaced.
+/// The contents of the version are stored in the path returned by [`FileVersion::file`].
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::file_version)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct FileVersion {
+    file_version_id: Uuid,
+    path: String,
+    version: i32,
+    size: i64,
+    modified: SystemTime,
+    author_id: Option<Uuid>,
+    replaced_by: Option<Uuid>
+}
+impl FileVersion {
+    /// Records that the given user is replacing the contents of the file at `path`, relative to
+    /// the storage root, which have the given `size` and modification time.
+    ///
+    /// The author of the replaced contents is the user who replaced the previous version, if any.
+    pub fn create<P: AsRef<str>>(db_connection: &mut PgConnection, path: P, size: u64, modified: SystemTime, replaced_by: Uuid) -> TuskResult<FileVersion> {
+        use crate::schema::file_version;
+
+        let path = path.as_ref();
+        let size = size.min(i64::MAX as u64) as i64;
+        let version = db_connection.transaction(|db_connection| {
+            let previous: Option<FileVersion> = file_version::table
+                .filter(file_version::path.eq(path))
+                .order(file_version::version.desc())
+                .first(db_connection)
+                .optional()?;
+            let (version, author_id) = match previous {
+                Some(previous) => (previous.version + 1, previous.replaced_by),
+                None => (1, None)
+            };
+
+            diesel::insert_into(file_version::table)
+                .values((
+                    file_version::path.eq(path),
+                    file_version::version.eq(version),
+                    file_version::size.eq(size),
+                    file_version::modified.eq(modified),
+                    file_version::author_id.eq(author_id),
+                    file_version::replaced_by.eq(replaced_by)
+                )).get_result(db_connection)
+        })?;
+
+        Ok(version)
+    }
+
+    /// Returns the ID of the version.
+    pub fn id(&self) -> Uuid { self.file_version_id }
+    /// Returns the path of the versioned file, relative to the storage root.
+    pub fn path(&self) -> &str { &self.path }
+    /// Returns the number of the version.
+    pub fn version(&self) -> u32 { self.version.max(0) as u32 }
+    /// Returns the size, in bytes, of the contents of the version.
+    pub fn size(&self) -> u64 { self.size.max(0) as u64 }
+    /// Returns the moment in which the contents of the version have been last modified.
+    pub fn modified(&self) -> SystemTime { self.modified }
+    /// Returns the ID of the user who wrote the contents of the version, if known.
+    pub fn author_id(&self) -> Option<Uuid> { self.author_id }
+    /// Returns the ID of the user who replaced the contents of the version, if the user still
+    /// exists.
+    pub fn replaced_by(&self) -> Option<Uuid> { self.replaced_by }
+    /// Returns the path in which the contents of the version are stored.
+    pub fn file(&self, tusk: &TuskConfiguration) -> PathBuf {
+        let mut path = tusk.versions_directory();
+        path.push(self.file_version_id.to_string());
+        path
+    }
+
+    /// Deletes the record of the version.
+    ///
+    /// **Warning:** this operation does not delete the contents stored in [`FileVersion::file`].
+    pub fn delete(self, db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::file_version;
+
+        let selected = file_version::table
+            .filter(file_version::file_version_id.eq(self.file_version_id));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+    /// Deletes the records of the versions of the file at `path` that do not satisfy the pruning
+    /// rules, and returns them, so that the respective contents can be removed.
+    ///
+    /// Only the most recent `keep_last` versions are kept, if specified.
+    /// Moreover, among the versions modified longer than `thin_after` ago, only the most recent
+    /// version of each day is kept.
+    pub fn prune<P: AsRef<str>>(db_connection: &mut PgConnection, path: P, keep_last: Option<usize>, thin_after: Option<Duration>) -> TuskResult<Vec<FileVersion>> {
+        use crate::schema::file_version;
+
+        let now = SystemTime::now();
+        let mut days = HashSet::new();
+        let pruned: Vec<Uuid> = Self::list_for_path(db_connection, path)?
+            .into_iter()
+            .enumerate()
+            .filter(|(index, version)| {
+                if keep_last.is_some_and(|keep_last| *index >= keep_last) { return true; }
+                match thin_after {
+                    Some(thin_after) if version.modified + thin_after < now => {
+                        let day = version.modified.duration_since(SystemTime::UNIX_EPOCH)
+                            .map(|delta| delta.as_secs() / SECONDS_PER_DAY)
+                            .unwrap_or(0);
+                        !days.insert(day)
+                    },
+                    _ => false
+                }
+            })
+            .map(|(_, version)| version.file_version_id)
+            .collect();
+
+        let selected = file_version::table
+            .filter(file_version::file_version_id.eq_any(pruned));
+
+        let versions = diesel::delete(selected)
+            .get_results(db_connection)?;
+
+        Ok(versions)
+    }
+    /// Reads the version of the file at `path` with the given number.
+    pub fn from_version<P: AsRef<str>>(db_connection: &mut PgConnection, path: P, version: u32) -> TuskResult<FileVersion> {
+        use crate::schema::file_version;
+
+        let version = file_version::table
+            .filter(file_version::path.eq(path.as_ref()))
+            .filter(file_version::version.eq(version.min(i32::MAX as u32) as i32))
+            .first(db_connection)?;
+
+        Ok(version)
+    }
+    /// Returns the total size, in bytes, of the versions of the files inside the storage at
+    /// `path`, recursively.
+    pub fn total_size_inside<P: AsRef<str>>(db_connection: &mut PgConnection, path: P) -> TuskResult<u64> {
+        use crate::schema::file_version;
+
+        let size: i64 = file_version::table
+            .filter(file_version::path.like(format!("{}/%", escape_like(path.as_ref()))).escape('\\'))
+            .select(sql::<BigInt>("COALESCE(SUM(size), 0)::BIGINT"))
+            .get_result(db_connection)?;
+
+        Ok(size.max(0) as u64)
+    }
+    /// Lists all the versions of the file at `path`, from the most recent.
+    pub fn list_for_path<P: AsRef<str>>(db_connection: &mut PgConnection, path: P) -> TuskResult<Vec<FileVersion>> {
+        use crate::schema::file_version;
+
+        let versions = file_version::table
+            .filter(file_version::path.eq(path.as_ref()))
+            .order(file_version::version.desc())
+            .load(db_connection)?;
+
+        Ok(versions)
+    }
+}
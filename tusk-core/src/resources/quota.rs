@@ -0,0 +1,112 @@
+//! Data structures for the `quota` table.
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::error::TuskResult;
+
+/// Represents the maximum number of bytes that can be stored in the root of a user.
+///
+/// A quota is assigned either to a single user or to a role; the quota of a user takes precedence
+/// over the quotas of the roles the user belongs to.
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::quota)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Quota {
+    #[serde(skip_serializing)]
+    quota_id: Uuid,
+    user_id: Option<Uuid>,
+    role_id: Option<Uuid>,
+    max_bytes: i64
+}
+impl Quota {
+    /// Sets the quota of the given user to `max_bytes`, replacing the existing quota if any.
+    pub fn set_for_user(db_connection: &mut PgConnection, user_id: Uuid, max_bytes: u64) -> TuskResult<Quota> {
+        use crate::schema::quota;
+
+        let max_bytes = max_bytes.min(i64::MAX as u64) as i64;
+        let quota = diesel::insert_into(quota::table)
+            .values((quota::user_id.eq(user_id), quota::max_bytes.eq(max_bytes)))
+            .on_conflict(quota::user_id)
+            .do_update()
+            .set(quota::max_bytes.eq(max_bytes))
+            .get_result(db_connection)?;
+
+        Ok(quota)
+    }
+    /// Sets the quota of the given role to `max_bytes`, replacing the existing quota if any.
+    pub fn set_for_role(db_connection: &mut PgConnection, role_id: Uuid, max_bytes: u64) -> TuskResult<Quota> {
+        use crate::schema::quota;
+
+        let max_bytes = max_bytes.min(i64::MAX as u64) as i64;
+        let quota = diesel::insert_into(quota::table)
+            .values((quota::role_id.eq(role_id), quota::max_bytes.eq(max_bytes)))
+            .on_conflict(quota::role_id)
+            .do_update()
+            .set(quota::max_bytes.eq(max_bytes))
+            .get_result(db_connection)?;
+
+        Ok(quota)
+    }
+
+    /// Returns the ID of the user to which the quota is assigned, if any.
+    pub fn user_id(&self) -> Option<Uuid> { self.user_id }
+    /// Returns the ID of the role to which the quota is assigned, if any.
+    pub fn role_id(&self) -> Option<Uuid> { self.role_id }
+    /// Returns the maximum number of bytes allowed by the quota.
+    pub fn max_bytes(&self) -> u64 { self.max_bytes.max(0) as u64 }
+
+    /// Deletes the quota.
+    pub fn delete(self, db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::quota;
+
+        let selected = quota::table
+            .filter(quota::quota_id.eq(self.quota_id));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+    /// Reads the quota assigned to the given user, if any.
+    pub fn from_user_id(db_connection: &mut PgConnection, user_id: Uuid) -> TuskResult<Option<Quota>> {
+        use crate::schema::quota;
+
+        let quota = quota::table
+            .filter(quota::user_id.eq(user_id))
+            .first(db_connection)
+            .optional()?;
+
+        Ok(quota)
+    }
+    /// Reads the quota assigned to the given role, if any.
+    pub fn from_role_id(db_connection: &mut PgConnection, role_id: Uuid) -> TuskResult<Option<Quota>> {
+        use crate::schema::quota;
+
+        let quota = quota::table
+            .filter(quota::role_id.eq(role_id))
+            .first(db_connection)
+            .optional()?;
+
+        Ok(quota)
+    }
+    /// Returns the maximum number of bytes that the given user can store, or `None` if the user
+    /// has no limit.
+    ///
+    /// If no quota is assigned to the user, the largest quota among the roles of the user applies.
+    pub fn effective_for_user(db_connection: &mut PgConnection, user_id: Uuid) -> TuskResult<Option<u64>> {
+        use crate::schema::{quota, user_role};
+
+        if let Some(quota) = Self::from_user_id(db_connection, user_id)? {
+            return Ok(Some(quota.max_bytes()));
+        }
+
+        let role_quotas: Vec<Quota> = quota::table
+            .inner_join(user_role::table.on(quota::role_id.eq(user_role::role_id.nullable())))
+            .filter(user_role::user_id.eq(user_id))
+            .select(Quota::as_select())
+            .load(db_connection)?;
+
+        Ok(role_quotas.iter().map(Quota::max_bytes).max())
+    }
+}
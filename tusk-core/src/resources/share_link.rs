@@ -0,0 +1,291 @@
+//! Data structures for the `share_link` table.
+
+use std::time::SystemTime;
+use diesel::prelude::*;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::error::TuskResult;
+
+/// Escapes the given text so that it is matched literally by a `LIKE` pattern with `\` as the
+/// escape character.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Length of the random token identifying a share link.
+const TOKEN_LENGTH: usize = 32;
+
+/// Represents a public link to an item of the storage, which can be accessed without an account.
+///
+/// Every interaction with the database removes from the table all the expired links, so that
+/// the table does not grow with time.
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::share_link)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ShareLink {
+    share_link_id: Uuid,
+    token: String,
+    user_id: Uuid,
+    path: String,
+    expiration: SystemTime,
+    #[serde(skip_serializing)]
+    password: Option<String>,
+    max_downloads: Option<i32>,
+    downloads: i32
+}
+impl ShareLink {
+    fn update_table(db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::share_link;
+
+        let selected = share_link::table
+            .filter(share_link::expiration.lt(SystemTime::now()));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+
+    /// Creates a new link, owned by the given user, to the item at `path`, relative to the storage
+    /// root.
+    ///
+    /// If a password is given, the link can only be accessed with that password; if
+    /// `max_downloads` is given, the files of the link can only be downloaded that many times.
+    pub fn create<P: AsRef<str>>(db_connection: &mut PgConnection, user_id: Uuid, path: P, expiration: SystemTime, password: Option<&Secret<String>>, max_downloads: Option<u32>) -> TuskResult<ShareLink> {
+        use crate::schema::share_link;
+
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        let password = password.map(|password| bcrypt::hash(password.expose_secret(), bcrypt::DEFAULT_COST)
+            .unwrap());
+        let max_downloads = max_downloads.map(|max_downloads| max_downloads.min(i32::MAX as u32) as i32);
+
+        db_connection.transaction(|db_connection| {
+            Self::update_table(db_connection)?;
+
+            let link = diesel::insert_into(share_link::table)
+                .values((
+                    share_link::token.eq(token),
+                    share_link::user_id.eq(user_id),
+                    share_link::path.eq(path.as_ref()),
+                    share_link::expiration.eq(expiration),
+                    share_link::password.eq(password),
+                    share_link::max_downloads.eq(max_downloads)
+                )).get_result(db_connection)?;
+
+            Ok(link)
+        })
+    }
+
+    /// Returns the ID of the link.
+    pub fn id(&self) -> Uuid { self.share_link_id }
+    /// Returns the token of the link.
+    pub fn token(&self) -> &str { &self.token }
+    /// Returns the ID of the user who owns the link.
+    pub fn user_id(&self) -> Uuid { self.user_id }
+    /// Returns the path of the shared item, relative to the storage root.
+    pub fn path(&self) -> &str { &self.path }
+    /// Returns the moment in which the link expires.
+    pub fn expiration(&self) -> SystemTime { self.expiration }
+    /// Returns `true` if the link is protected by a password and `false` otherwise.
+    pub fn has_password(&self) -> bool { self.password.is_some() }
+    /// Returns the maximum number of downloads allowed by the link, if any.
+    pub fn max_downloads(&self) -> Option<u32> { self.max_downloads.map(|max_downloads| max_downloads.max(0) as u32) }
+    /// Returns the number of files downloaded through the link.
+    pub fn downloads(&self) -> u32 { self.downloads.max(0) as u32 }
+    /// Returns `true` if the link is not expired and its download limit has not been reached, and
+    /// `false` otherwise.
+    pub fn valid(&self) -> bool {
+        self.expiration >= SystemTime::now()
+            && self.max_downloads.is_none_or(|max_downloads| self.downloads < max_downloads)
+    }
+    /// Verifies the given password against the password of the link.
+    ///
+    /// If the link is not protected by a password, any password is accepted.
+    pub fn verify_password(&self, password: Option<&Secret<String>>) -> bool {
+        match (&self.password, password) {
+            (None, _) => true,
+            (Some(hash), Some(password)) => bcrypt::verify(password.expose_secret(), hash)
+                .unwrap_or(false),
+            (Some(_), None) => false
+        }
+    }
+
+    /// Counts a new download through the link.
+    ///
+    /// Returns `false` if the download limit of the link has already been reached.
+    pub fn register_download(&mut self, db_connection: &mut PgConnection) -> TuskResult<bool> {
+        use crate::schema::share_link;
+
+        let link: Option<ShareLink> = diesel::update(share_link::table)
+            .filter(share_link::share_link_id.eq(self.share_link_id))
+            .filter(share_link::max_downloads.is_null().or(share_link::downloads.nullable().lt(share_link::max_downloads)))
+            .set(share_link::downloads.eq(share_link::downloads + 1))
+            .get_result(db_connection)
+            .optional()?;
+
+        match link {
+            Some(link) => {
+                *self = link;
+                Ok(true)
+            },
+            None => Ok(false)
+        }
+    }
+    /// Deletes the link.
+    pub fn delete(self, db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::share_link;
+
+        let selected = share_link::table
+            .filter(share_link::share_link_id.eq(self.share_link_id));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Self::update_table(db_connection)?;
+
+        Ok(())
+    }
+    /// Deletes all the links to the item at `path`, relative to the storage root, and to the items
+    /// inside it.
+    pub fn delete_under<P: AsRef<str>>(db_connection: &mut PgConnection, path: P) -> TuskResult<()> {
+        use crate::schema::share_link;
+
+        let path = path.as_ref();
+        let selected = share_link::table
+            .filter(share_link::path.eq(path)
+                .or(share_link::path.like(format!("{}/%", escape_like(path))).escape('\\')));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Self::update_table(db_connection)?;
+
+        Ok(())
+    }
+    /// Moves the links to the item at `from`, relative to the storage root, and to the items inside
+    /// it, to the path `to`.
+    pub fn rename<P: AsRef<str>, Q: AsRef<str>>(db_connection: &mut PgConnection, from: P, to: Q) -> TuskResult<()> {
+        use crate::schema::share_link;
+
+        let (from, to) = (from.as_ref(), to.as_ref());
+        db_connection.transaction(|db_connection| {
+            let links: Vec<(Uuid, String)> = share_link::table
+                .filter(share_link::path.eq(from)
+                    .or(share_link::path.like(format!("{}/%", escape_like(from))).escape('\\')))
+                .select((share_link::share_link_id, share_link::path))
+                .load(db_connection)?;
+            for (id, path) in links {
+                diesel::update(share_link::table.filter(share_link::share_link_id.eq(id)))
+                    .set(share_link::path.eq(format!("{to}{}", &path[from.len()..])))
+                    .execute(db_connection)?;
+            }
+            Ok::<_, diesel::result::Error>(())
+        })?;
+
+        Ok(())
+    }
+    /// Retrieves a link that is not expired, given its token.
+    pub fn from_token<T: AsRef<str>>(db_connection: &mut PgConnection, token: T) -> TuskResult<ShareLink> {
+        use crate::schema::share_link;
+
+        db_connection.transaction(|db_connection| {
+            Self::update_table(db_connection)?;
+
+            let link = share_link::table
+                .filter(share_link::token.eq(token.as_ref()))
+                .first(db_connection)?;
+
+            Ok(link)
+        })
+    }
+    /// Reads a link owned by the given user, given the link ID.
+    pub fn from_id(db_connection: &mut PgConnection, share_link_id: Uuid, user_id: Uuid) -> TuskResult<ShareLink> {
+        use crate::schema::share_link;
+
+        db_connection.transaction(|db_connection| {
+            Self::update_table(db_connection)?;
+
+            let link = share_link::table
+                .filter(share_link::share_link_id.eq(share_link_id))
+                .filter(share_link::user_id.eq(user_id))
+                .first(db_connection)?;
+
+            Ok(link)
+        })
+    }
+    /// Lists all the links owned by the given user, from the one expiring first.
+    pub fn list_for_user(db_connection: &mut PgConnection, user_id: Uuid) -> TuskResult<Vec<ShareLink>> {
+        use crate::schema::share_link;
+
+        db_connection.transaction(|db_connection| {
+            Self::update_table(db_connection)?;
+
+            let links = share_link::table
+                .filter(share_link::user_id.eq(user_id))
+                .order(share_link::expiration.asc())
+                .load(db_connection)?;
+
+            Ok(links)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::{Add, Sub};
+    use std::time::{Duration, SystemTime};
+    use secrecy::Secret;
+    use uuid::Uuid;
+    use crate::resources::ShareLink;
+
+    fn link() -> ShareLink {
+        ShareLink {
+            share_link_id: Uuid::new_v4(),
+            token: "token".to_owned(),
+            user_id: Uuid::new_v4(),
+            path: "user/file.txt".to_owned(),
+            expiration: SystemTime::now().add(Duration::from_secs(60)),
+            password: None,
+            max_downloads: None,
+            downloads: 0
+        }
+    }
+
+    #[test]
+    fn link_validity() {
+        let mut link = link();
+        // Link expires in the future and has no download limit, so it is valid.
+        assert!(link.valid());
+
+        link.max_downloads = Some(2);
+        link.downloads = 2;
+        // Download limit has been reached, so the link is not valid anymore.
+        assert!(!link.valid());
+
+        link.max_downloads = None;
+        link.expiration = SystemTime::now().sub(Duration::from_secs(60));
+        // Link expired in the past, so it is not valid anymore.
+        assert!(!link.valid());
+    }
+
+    #[test]
+    fn link_password() {
+        let mut link = link();
+        // Link without password accepts any request.
+        assert!(link.verify_password(None));
+
+        link.password = Some(bcrypt::hash("secret", 4).unwrap());
+        assert!(!link.verify_password(None));
+        assert!(!link.verify_password(Some(&Secret::new("wrong".to_owned()))));
+        assert!(link.verify_password(Some(&Secret::new("secret".to_owned()))));
+    }
+}
@@ -0,0 +1,222 @@
+//! Data structures for the `storage_change` table.
+
+use std::time::{Duration, SystemTime};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Text};
+use serde::{Deserialize, Serialize};
+use crate::error::TuskResult;
+
+/// Describes how an item of the storage changed.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageOperation {
+    /// The item has been created, or moved to its path.
+    Created,
+    /// The contents or the attributes of the item have been modified.
+    Modified,
+    /// The item has been deleted, or moved from its path.
+    Deleted
+}
+impl StorageOperation {
+    /// Returns the name of the operation, as stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageOperation::Created => "created",
+            StorageOperation::Modified => "modified",
+            StorageOperation::Deleted => "deleted"
+        }
+    }
+}
+
+/// Truncates the given time to whole seconds, which is the precision of the modification times
+/// known to the server, so that the same state compares equal whatever recorded it.
+fn truncate(time: SystemTime) -> SystemTime {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(delta) => SystemTime::UNIX_EPOCH + Duration::from_secs(delta.as_secs()),
+        Err(_) => time
+    }
+}
+
+/// Returns the storage root containing the item at `path`, relative to the storage root, that is,
+/// the first component of the path.
+fn root_of(path: &str) -> &str {
+    path.split('/').next().unwrap_or(path)
+}
+
+/// Escapes the given text so that it is matched literally by a `LIKE` pattern with `\` as the
+/// escape character.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Represents a change to an item of the storage, as recorded in the change journal.
+///
+/// Changes are attached to the path of the item, relative to the storage root, and are numbered
+/// by an increasing sequence number, which clients use as a cursor to ask for the changes
+/// following the ones they already know.
+/// Changes to a storage apply to everything inside it.
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::storage_change)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct StorageChange {
+    sequence: i64,
+    root: String,
+    path: String,
+    operation: String,
+    directory: bool,
+    size: Option<i64>,
+    modified: Option<SystemTime>
+}
+impl StorageChange {
+    /// Records the change to the item at `path`, relative to the storage root, after which the item
+    /// is a storage if `directory` is `true`, and has the given `size` and modification time.
+    ///
+    /// Nothing is recorded if the latest change to the same path already left the item in the
+    /// same state, so that the same change can be safely recorded more than once; in that case,
+    /// this function returns `None`.
+    /// Deletions take the kind of the item from the latest change to the same path, if any.
+    pub fn record<P: AsRef<str>>(db_connection: &mut PgConnection, path: P, operation: StorageOperation, directory: bool, size: Option<u64>, modified: Option<SystemTime>) -> TuskResult<Option<StorageChange>> {
+        use crate::schema::storage_change;
+
+        let path = path.as_ref();
+        let size = size.map(|size| size.min(i64::MAX as u64) as i64);
+        let modified = modified.map(truncate);
+        let change = db_connection.transaction(|db_connection| {
+            // The same change can be recorded concurrently by the server and by the watcher.
+            diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+                .bind::<Text, _>(path)
+                .execute(db_connection)?;
+            let latest: Option<StorageChange> = storage_change::table
+                .filter(storage_change::path.eq(path))
+                .order(storage_change::sequence.desc())
+                .first(db_connection)
+                .optional()?;
+            let directory = match &latest {
+                Some(latest) if latest.same_state(operation, directory, size, modified) => return Ok(None),
+                // The kind of a deleted item is not always known to the caller.
+                Some(latest) if operation == StorageOperation::Deleted => latest.directory,
+                _ => directory
+            };
+
+            diesel::insert_into(storage_change::table)
+                .values((
+                    storage_change::root.eq(root_of(path)),
+                    storage_change::path.eq(path),
+                    storage_change::operation.eq(operation.as_str()),
+                    storage_change::directory.eq(directory),
+                    storage_change::size.eq(size),
+                    storage_change::modified.eq(modified)
+                )).get_result(db_connection)
+                .map(Some)
+        })?;
+
+        Ok(change)
+    }
+
+    /// Returns `true` if, after this change, the item is in the same state as after the change
+    /// with the given attributes, and `false` otherwise.
+    fn same_state(&self, operation: StorageOperation, directory: bool, size: Option<i64>, modified: Option<SystemTime>) -> bool {
+        match (self.operation(), operation) {
+            (StorageOperation::Deleted, StorageOperation::Deleted) => true,
+            (StorageOperation::Deleted, _) | (_, StorageOperation::Deleted) => false,
+            // The modification time of a storage changes with its contents, which are recorded
+            // separately.
+            _ if self.directory && directory => true,
+            _ => self.directory == directory && self.size == size && self.modified == modified
+        }
+    }
+
+    /// Returns the sequence number of the change.
+    pub fn sequence(&self) -> i64 { self.sequence }
+    /// Returns the storage root containing the changed item.
+    pub fn root(&self) -> &str { &self.root }
+    /// Returns the path of the changed item, relative to the storage root.
+    pub fn path(&self) -> &str { &self.path }
+    /// Returns how the item changed.
+    pub fn operation(&self) -> StorageOperation {
+        match self.operation.as_str() {
+            "created" => StorageOperation::Created,
+            "deleted" => StorageOperation::Deleted,
+            _ => StorageOperation::Modified
+        }
+    }
+    /// Returns `true` if the item is a storage and `false` if it is a file.
+    pub fn is_directory(&self) -> bool { self.directory }
+    /// Returns the size, in bytes, of the item after the change, if it is a file that still exists.
+    pub fn size(&self) -> Option<u64> { self.size.map(|size| size.max(0) as u64) }
+    /// Returns the modification time of the item after the change, if it still exists.
+    pub fn modified(&self) -> Option<SystemTime> { self.modified }
+
+    /// Returns the sequence number of the latest change recorded, or `0` if no change has been
+    /// recorded yet.
+    pub fn latest_sequence(db_connection: &mut PgConnection) -> TuskResult<i64> {
+        use crate::schema::storage_change;
+
+        let sequence: Option<i64> = storage_change::table
+            .select(diesel::dsl::max(storage_change::sequence))
+            .first(db_connection)?;
+
+        Ok(sequence.unwrap_or(0))
+    }
+    /// Lists at most `limit` changes following the change with sequence number `since`, in the
+    /// order in which they have been recorded, among the changes to the storages at `scopes`,
+    /// relative to the storage root, and to everything inside them.
+    pub fn list_since<S: AsRef<str>>(db_connection: &mut PgConnection, since: i64, scopes: &[S], limit: usize) -> TuskResult<Vec<StorageChange>> {
+        use crate::schema::storage_change;
+
+        let roots: Vec<&str> = scopes.iter()
+            .map(|scope| root_of(scope.as_ref()))
+            .collect();
+        let mut inside: Box<dyn BoxableExpression<storage_change::table, Pg, SqlType = Bool>> = Box::new(false.into_sql::<Bool>());
+        for scope in scopes {
+            let scope = scope.as_ref();
+            inside = Box::new(inside
+                .or(storage_change::path.eq(scope.to_owned()))
+                .or(storage_change::path.like(format!("{}/%", escape_like(scope))).escape('\\')));
+        }
+
+        let changes = storage_change::table
+            .filter(storage_change::sequence.gt(since))
+            .filter(storage_change::root.eq_any(roots))
+            .filter(inside)
+            .order(storage_change::sequence.asc())
+            .limit(limit.min(i64::MAX as usize) as i64)
+            .load(db_connection)?;
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+    use crate::resources::storage_change::{escape_like, root_of, truncate};
+    use crate::resources::{StorageChange, StorageOperation};
+
+    #[test]
+    fn change_state() {
+        let modified = truncate(SystemTime::UNIX_EPOCH + Duration::from_millis(1_000_000_000_123));
+        assert_eq!(modified, SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000));
+
+        let change = StorageChange {
+            sequence: 1,
+            root: "user".to_owned(),
+            path: "user/file.txt".to_owned(),
+            operation: "created".to_owned(),
+            directory: false,
+            size: Some(5),
+            modified: Some(modified)
+        };
+        // Replacing a file with the same contents does not change its state.
+        assert!(change.same_state(StorageOperation::Modified, false, Some(5), Some(modified)));
+        assert!(!change.same_state(StorageOperation::Modified, false, Some(6), Some(modified)));
+        assert!(!change.same_state(StorageOperation::Deleted, false, None, None));
+
+        assert_eq!(root_of("user/file.txt"), "user");
+        assert_eq!(root_of(".public"), ".public");
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+    }
+}
@@ -0,0 +1,192 @@
+//! Data structures for the `storage_grant` table.
+
+use std::path::Path;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::error::TuskResult;
+
+/// Escapes the given text so that it is matched literally by a `LIKE` pattern with `\` as the
+/// escape character.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Represents the access to a storage in the root of a user, granted by that user to another user
+/// or to a role.
+///
+/// The storage is identified by its path relative to the storage root; the access extends to
+/// everything inside the storage, and is either read-only or read-write.
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::storage_grant)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct StorageGrant {
+    storage_grant_id: Uuid,
+    owner_id: Uuid,
+    path: String,
+    user_id: Option<Uuid>,
+    role_id: Option<Uuid>,
+    writable: bool
+}
+impl StorageGrant {
+    /// Grants the given user access to the storage at `path`, owned by `owner_id`, replacing the
+    /// existing grant if any.
+    pub fn set_for_user<P: AsRef<str>>(db_connection: &mut PgConnection, owner_id: Uuid, path: P, user_id: Uuid, writable: bool) -> TuskResult<StorageGrant> {
+        use crate::schema::storage_grant;
+
+        let grant = diesel::insert_into(storage_grant::table)
+            .values((
+                storage_grant::owner_id.eq(owner_id),
+                storage_grant::path.eq(path.as_ref()),
+                storage_grant::user_id.eq(user_id),
+                storage_grant::writable.eq(writable)
+            ))
+            .on_conflict((storage_grant::path, storage_grant::user_id))
+            .do_update()
+            .set(storage_grant::writable.eq(writable))
+            .get_result(db_connection)?;
+
+        Ok(grant)
+    }
+    /// Grants the given role access to the storage at `path`, owned by `owner_id`, replacing the
+    /// existing grant if any.
+    pub fn set_for_role<P: AsRef<str>>(db_connection: &mut PgConnection, owner_id: Uuid, path: P, role_id: Uuid, writable: bool) -> TuskResult<StorageGrant> {
+        use crate::schema::storage_grant;
+
+        let grant = diesel::insert_into(storage_grant::table)
+            .values((
+                storage_grant::owner_id.eq(owner_id),
+                storage_grant::path.eq(path.as_ref()),
+                storage_grant::role_id.eq(role_id),
+                storage_grant::writable.eq(writable)
+            ))
+            .on_conflict((storage_grant::path, storage_grant::role_id))
+            .do_update()
+            .set(storage_grant::writable.eq(writable))
+            .get_result(db_connection)?;
+
+        Ok(grant)
+    }
+
+    /// Returns the ID of the grant.
+    pub fn id(&self) -> Uuid { self.storage_grant_id }
+    /// Returns the ID of the user who owns the shared storage.
+    pub fn owner_id(&self) -> Uuid { self.owner_id }
+    /// Returns the path of the shared storage, relative to the storage root.
+    pub fn path(&self) -> &str { &self.path }
+    /// Returns the ID of the user to which the access is granted, if any.
+    pub fn user_id(&self) -> Option<Uuid> { self.user_id }
+    /// Returns the ID of the role to which the access is granted, if any.
+    pub fn role_id(&self) -> Option<Uuid> { self.role_id }
+    /// Returns `true` if the access is read-write and `false` if it is read-only.
+    pub fn writable(&self) -> bool { self.writable }
+    /// Returns `true` if the given path, relative to the storage root, is the shared storage or
+    /// one of its children.
+    pub fn covers<P: AsRef<Path>>(&self, path: P) -> bool {
+        path.as_ref().starts_with(&self.path)
+    }
+
+    /// Deletes the grant.
+    pub fn delete(self, db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::storage_grant;
+
+        let selected = storage_grant::table
+            .filter(storage_grant::storage_grant_id.eq(self.storage_grant_id));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+    /// Deletes all the grants to the storage at `path`, relative to the storage root, and to the
+    /// storages inside it.
+    pub fn delete_under<P: AsRef<str>>(db_connection: &mut PgConnection, path: P) -> TuskResult<()> {
+        use crate::schema::storage_grant;
+
+        let path = path.as_ref();
+        let selected = storage_grant::table
+            .filter(storage_grant::path.eq(path)
+                .or(storage_grant::path.like(format!("{}/%", escape_like(path))).escape('\\')));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+    /// Moves the grants to the storage at `from`, relative to the storage root, and to the storages
+    /// inside it, to the path `to`.
+    pub fn rename<P: AsRef<str>, Q: AsRef<str>>(db_connection: &mut PgConnection, from: P, to: Q) -> TuskResult<()> {
+        use crate::schema::storage_grant;
+
+        let (from, to) = (from.as_ref(), to.as_ref());
+        db_connection.transaction(|db_connection| {
+            let grants: Vec<(Uuid, String)> = storage_grant::table
+                .filter(storage_grant::path.eq(from)
+                    .or(storage_grant::path.like(format!("{}/%", escape_like(from))).escape('\\')))
+                .select((storage_grant::storage_grant_id, storage_grant::path))
+                .load(db_connection)?;
+            for (id, path) in grants {
+                diesel::update(storage_grant::table.filter(storage_grant::storage_grant_id.eq(id)))
+                    .set(storage_grant::path.eq(format!("{to}{}", &path[from.len()..])))
+                    .execute(db_connection)?;
+            }
+            Ok::<_, diesel::result::Error>(())
+        })?;
+
+        Ok(())
+    }
+    /// Reads a grant of the given owner, given the grant ID.
+    pub fn from_id(db_connection: &mut PgConnection, storage_grant_id: Uuid, owner_id: Uuid) -> TuskResult<StorageGrant> {
+        use crate::schema::storage_grant;
+
+        let grant = storage_grant::table
+            .filter(storage_grant::storage_grant_id.eq(storage_grant_id))
+            .filter(storage_grant::owner_id.eq(owner_id))
+            .first(db_connection)?;
+
+        Ok(grant)
+    }
+    /// Lists all the grants given by the given owner.
+    pub fn list_for_owner(db_connection: &mut PgConnection, owner_id: Uuid) -> TuskResult<Vec<StorageGrant>> {
+        use crate::schema::storage_grant;
+
+        let grants = storage_grant::table
+            .filter(storage_grant::owner_id.eq(owner_id))
+            .order(storage_grant::path.asc())
+            .load(db_connection)?;
+
+        Ok(grants)
+    }
+    /// Lists all the grants that apply to the given user, either directly or through the roles
+    /// of the user.
+    pub fn list_for_grantee(db_connection: &mut PgConnection, user_id: Uuid) -> TuskResult<Vec<StorageGrant>> {
+        use crate::schema::{storage_grant, user_role};
+
+        let roles = user_role::table
+            .filter(user_role::user_id.eq(user_id))
+            .select(user_role::role_id.nullable());
+        let grants = storage_grant::table
+            .filter(storage_grant::user_id.eq(user_id).or(storage_grant::role_id.eq_any(roles)))
+            .order(storage_grant::path.asc())
+            .load(db_connection)?;
+
+        Ok(grants)
+    }
+    /// Returns the grant through which the given user can access the given path, relative to the
+    /// storage root, if any.
+    ///
+    /// If more grants apply, read-write grants take precedence over read-only grants, and grants
+    /// to outer storages take precedence over grants to inner storages.
+    pub fn covering<P: AsRef<Path>>(db_connection: &mut PgConnection, user_id: Uuid, path: P) -> TuskResult<Option<StorageGrant>> {
+        let path = path.as_ref();
+
+        let grant = Self::list_for_grantee(db_connection, user_id)?
+            .into_iter()
+            .filter(|grant| grant.covers(path))
+            .min_by_key(|grant| (!grant.writable, grant.path.len()));
+
+        Ok(grant)
+    }
+}
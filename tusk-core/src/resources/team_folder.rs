@@ -0,0 +1,232 @@
+//! Data structures for the `team_folder` and `team_member` tables.
+
+use std::path::PathBuf;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::config::TuskConfiguration;
+use crate::error::{TuskError, TuskResult};
+
+/// Represents a storage shared by a team, whose members are users or roles.
+///
+/// Team folders are stored in the `teams` directory of the storage root, each in the directory
+/// named after the team folder, so that the path of a team folder relative to the storage root is
+/// `teams/<name>`.
+/// Every member can either read or read and write the contents of the team folder.
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::team_folder)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TeamFolder {
+    team_folder_id: Uuid,
+    name: String
+}
+impl TeamFolder {
+    /// Name of the directory of the storage root containing the team folders.
+    pub const ROOT: &'static str = "teams";
+
+    /// Inserts a new team folder with the given name in the table and returns it.
+    ///
+    /// # Errors
+    /// If the name is empty, is `.` or `..`, or contains the symbols `\` or `/`, this function
+    /// returns an HTTP error 400 `BAD REQUEST`.
+    pub fn create<N: AsRef<str>>(db_connection: &mut PgConnection, name: N) -> TuskResult<TeamFolder> {
+        use crate::schema::team_folder;
+
+        let name = name.as_ref();
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return TuskError::bad_request().bail();
+        }
+
+        let team_folder = diesel::insert_into(team_folder::table)
+            .values(team_folder::name.eq(name))
+            .get_result(db_connection)?;
+
+        Ok(team_folder)
+    }
+
+    /// Returns the ID of the team folder.
+    pub fn id(&self) -> Uuid { self.team_folder_id }
+    /// Returns the name of the team folder.
+    pub fn name(&self) -> &str { &self.name }
+    /// Returns the path of the team folder, relative to the storage root.
+    pub fn path(&self) -> String {
+        format!("{}/{}", Self::ROOT, self.name)
+    }
+    /// Returns the directory in which the contents of the team folder are stored.
+    pub fn directory(&self, tusk: &TuskConfiguration) -> PathBuf {
+        let mut path = tusk.user_directories();
+        path.push(Self::ROOT);
+        path.push(&self.name);
+        path
+    }
+
+    /// Adds the given user to the members of the team folder, with read-write access if
+    /// `writable` is `true` and read-only access otherwise, replacing the existing membership if
+    /// any.
+    pub fn set_user(&self, db_connection: &mut PgConnection, user_id: Uuid, writable: bool) -> TuskResult<TeamMember> {
+        use crate::schema::team_member;
+
+        let member = diesel::insert_into(team_member::table)
+            .values((
+                team_member::team_folder_id.eq(self.team_folder_id),
+                team_member::user_id.eq(user_id),
+                team_member::writable.eq(writable)
+            ))
+            .on_conflict((team_member::team_folder_id, team_member::user_id))
+            .do_update()
+            .set(team_member::writable.eq(writable))
+            .get_result(db_connection)?;
+
+        Ok(member)
+    }
+    /// Adds the given role to the members of the team folder, with read-write access if
+    /// `writable` is `true` and read-only access otherwise, replacing the existing membership if
+    /// any.
+    pub fn set_role(&self, db_connection: &mut PgConnection, role_id: Uuid, writable: bool) -> TuskResult<TeamMember> {
+        use crate::schema::team_member;
+
+        let member = diesel::insert_into(team_member::table)
+            .values((
+                team_member::team_folder_id.eq(self.team_folder_id),
+                team_member::role_id.eq(role_id),
+                team_member::writable.eq(writable)
+            ))
+            .on_conflict((team_member::team_folder_id, team_member::role_id))
+            .do_update()
+            .set(team_member::writable.eq(writable))
+            .get_result(db_connection)?;
+
+        Ok(member)
+    }
+    /// Removes the given user from the members of the team folder.
+    ///
+    /// The user can still access the team folder through its roles.
+    pub fn remove_user(&self, db_connection: &mut PgConnection, user_id: Uuid) -> TuskResult<()> {
+        use crate::schema::team_member;
+
+        let selected = team_member::table
+            .filter(team_member::team_folder_id.eq(self.team_folder_id))
+            .filter(team_member::user_id.eq(user_id));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+    /// Removes the given role from the members of the team folder.
+    pub fn remove_role(&self, db_connection: &mut PgConnection, role_id: Uuid) -> TuskResult<()> {
+        use crate::schema::team_member;
+
+        let selected = team_member::table
+            .filter(team_member::team_folder_id.eq(self.team_folder_id))
+            .filter(team_member::role_id.eq(role_id));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+    /// Lists the members of the team folder.
+    pub fn members(&self, db_connection: &mut PgConnection) -> TuskResult<Vec<TeamMember>> {
+        use crate::schema::team_member;
+
+        let members = team_member::table
+            .filter(team_member::team_folder_id.eq(self.team_folder_id))
+            .load(db_connection)?;
+
+        Ok(members)
+    }
+    /// Returns the access of the given user to the team folder, either directly or through the
+    /// roles of the user: `Some(true)` for read-write access, `Some(false)` for read-only access
+    /// and `None` if the user is not a member.
+    ///
+    /// If more memberships apply, read-write access takes precedence over read-only access.
+    pub fn access_for(&self, db_connection: &mut PgConnection, user_id: Uuid) -> TuskResult<Option<bool>> {
+        use crate::schema::{team_member, user_role};
+
+        let roles = user_role::table
+            .filter(user_role::user_id.eq(user_id))
+            .select(user_role::role_id.nullable());
+        let writable: Vec<bool> = team_member::table
+            .filter(team_member::team_folder_id.eq(self.team_folder_id))
+            .filter(team_member::user_id.eq(user_id).or(team_member::role_id.eq_any(roles)))
+            .select(team_member::writable)
+            .load(db_connection)?;
+
+        Ok(writable.into_iter().reduce(|a, b| a || b))
+    }
+
+    /// Deletes the team folder, together with its members.
+    ///
+    /// **Warning:** this operation does not delete the contents stored in
+    /// [`TeamFolder::directory`].
+    pub fn delete(self, db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::team_folder;
+
+        let selected = team_folder::table
+            .filter(team_folder::team_folder_id.eq(self.team_folder_id));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+    /// Reads a team folder, given its name.
+    pub fn from_name<N: AsRef<str>>(db_connection: &mut PgConnection, name: N) -> TuskResult<Option<TeamFolder>> {
+        use crate::schema::team_folder;
+
+        let team_folder = team_folder::table
+            .filter(team_folder::name.eq(name.as_ref()))
+            .first(db_connection)
+            .optional()?;
+
+        Ok(team_folder)
+    }
+    /// Lists all the team folders.
+    pub fn list_all(db_connection: &mut PgConnection) -> TuskResult<Vec<TeamFolder>> {
+        use crate::schema::team_folder;
+
+        let team_folders = team_folder::table
+            .order(team_folder::name.asc())
+            .load(db_connection)?;
+
+        Ok(team_folders)
+    }
+    /// Lists all the team folders of which the given user is a member, either directly or through
+    /// the roles of the user, together with the access of the user to each of them.
+    pub fn list_for_member(db_connection: &mut PgConnection, user_id: Uuid) -> TuskResult<Vec<(TeamFolder, bool)>> {
+        let mut team_folders = Vec::new();
+        for team_folder in Self::list_all(db_connection)? {
+            if let Some(writable) = team_folder.access_for(db_connection, user_id)? {
+                team_folders.push((team_folder, writable));
+            }
+        }
+
+        Ok(team_folders)
+    }
+}
+
+/// Represents the membership of a user or of a role to a team folder.
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::team_member)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TeamMember {
+    team_member_id: Uuid,
+    team_folder_id: Uuid,
+    user_id: Option<Uuid>,
+    role_id: Option<Uuid>,
+    writable: bool
+}
+impl TeamMember {
+    /// Returns the ID of the membership.
+    pub fn id(&self) -> Uuid { self.team_member_id }
+    /// Returns the ID of the team folder.
+    pub fn team_folder_id(&self) -> Uuid { self.team_folder_id }
+    /// Returns the ID of the member user, if any.
+    pub fn user_id(&self) -> Option<Uuid> { self.user_id }
+    /// Returns the ID of the member role, if any.
+    pub fn role_id(&self) -> Option<Uuid> { self.role_id }
+    /// Returns `true` if the member can modify the contents of the team folder and `false`
+    /// otherwise.
+    pub fn writable(&self) -> bool { self.writable }
+}
@@ -0,0 +1,120 @@
+//! Data structures for the `trash_item` table.
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::config::TuskConfiguration;
+use crate::error::TuskResult;
+
+/// Escapes the given text so that it is matched literally by a `LIKE` pattern with `\` as the
+/// escape character.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Represents an item of the storage that has been deleted, but not purged yet.
+///
+/// The item itself is stored in the path returned by [`TrashItem::file`], until it is either
+/// restored to its original path or purged.
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::trash_item)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TrashItem {
+    trash_item_id: Uuid,
+    user_id: Uuid,
+    original_path: String,
+    deletion: SystemTime
+}
+impl TrashItem {
+    /// Records that the item at `original_path`, relative to the storage root, has been deleted
+    /// into the trash of the given user.
+    pub fn create<P: AsRef<str>>(db_connection: &mut PgConnection, user_id: Uuid, original_path: P) -> TuskResult<TrashItem> {
+        use crate::schema::trash_item;
+
+        let item = diesel::insert_into(trash_item::table)
+            .values((
+                trash_item::user_id.eq(user_id),
+                trash_item::original_path.eq(original_path.as_ref()),
+                trash_item::deletion.eq(SystemTime::now())
+            )).get_result(db_connection)?;
+
+        Ok(item)
+    }
+
+    /// Returns the ID of the deleted item.
+    pub fn id(&self) -> Uuid { self.trash_item_id }
+    /// Returns the ID of the user in whose trash the item is.
+    pub fn user_id(&self) -> Uuid { self.user_id }
+    /// Returns the path of the item before its deletion, relative to the storage root.
+    pub fn original_path(&self) -> &str { &self.original_path }
+    /// Returns the moment in which the item has been deleted.
+    pub fn deletion(&self) -> SystemTime { self.deletion }
+    /// Returns the path in which the deleted item is stored.
+    pub fn file(&self, tusk: &TuskConfiguration) -> PathBuf {
+        let mut path = tusk.trash_directory();
+        path.push(self.user_id.to_string());
+        path.push(self.trash_item_id.to_string());
+        path
+    }
+
+    /// Deletes the record of the deleted item.
+    ///
+    /// **Warning:** this operation does not delete the item stored in [`TrashItem::file`].
+    pub fn delete(self, db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::trash_item;
+
+        let selected = trash_item::table
+            .filter(trash_item::trash_item_id.eq(self.trash_item_id));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+    /// Lists all the items deleted longer than `retention` ago, so that they can be purged.
+    pub fn list_expired(db_connection: &mut PgConnection, retention: Duration) -> TuskResult<Vec<TrashItem>> {
+        use crate::schema::trash_item;
+
+        let items = trash_item::table
+            .filter(trash_item::deletion.lt(SystemTime::now() - retention))
+            .load(db_connection)?;
+
+        Ok(items)
+    }
+    /// Reads an item deleted by the given user, given the item ID.
+    pub fn from_id(db_connection: &mut PgConnection, trash_item_id: Uuid, user_id: Uuid) -> TuskResult<TrashItem> {
+        use crate::schema::trash_item;
+
+        let item = trash_item::table
+            .filter(trash_item::trash_item_id.eq(trash_item_id))
+            .filter(trash_item::user_id.eq(user_id))
+            .first(db_connection)?;
+
+        Ok(item)
+    }
+    /// Lists all the items deleted by the given user, from the most recent.
+    pub fn list_for_user(db_connection: &mut PgConnection, user_id: Uuid) -> TuskResult<Vec<TrashItem>> {
+        use crate::schema::trash_item;
+
+        let items = trash_item::table
+            .filter(trash_item::user_id.eq(user_id))
+            .order(trash_item::deletion.desc())
+            .load(db_connection)?;
+
+        Ok(items)
+    }
+    /// Lists all the deleted items that were inside the storage at `path`, recursively.
+    pub fn list_inside<P: AsRef<str>>(db_connection: &mut PgConnection, path: P) -> TuskResult<Vec<TrashItem>> {
+        use crate::schema::trash_item;
+
+        let items = trash_item::table
+            .filter(trash_item::original_path.like(format!("{}/%", escape_like(path.as_ref()))).escape('\\'))
+            .load(db_connection)?;
+
+        Ok(items)
+    }
+}
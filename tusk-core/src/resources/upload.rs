@@ -0,0 +1,145 @@
+//! Data structures for the `upload` table.
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::config::TuskConfiguration;
+use crate::error::TuskResult;
+
+/// Represents a resumable upload of a file that has not been completed yet.
+///
+/// The contents uploaded so far are stored in the file returned by [`Upload::file`].
+/// Every upload expires 24 hours after its last modification.
+#[derive(Clone, Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::upload)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Upload {
+    upload_id: Uuid,
+    user_id: Uuid,
+    destination: String,
+    filename: String,
+    upload_length: i64,
+    upload_offset: i64,
+    expiration: SystemTime
+}
+impl Upload {
+    /// Creates a new upload of `length` bytes for the given user, to be stored with name
+    /// `filename` in the storage `destination`.
+    pub fn create<D: AsRef<str>, F: AsRef<str>>(db_connection: &mut PgConnection, user_id: Uuid, destination: D, filename: F, length: i64) -> TuskResult<Upload> {
+        use crate::schema::upload;
+
+        let upload = diesel::insert_into(upload::table)
+            .values((
+                upload::user_id.eq(user_id),
+                upload::destination.eq(destination.as_ref()),
+                upload::filename.eq(filename.as_ref()),
+                upload::upload_length.eq(length)
+            )).get_result(db_connection)?;
+
+        Ok(upload)
+    }
+
+    /// Returns the ID of the upload.
+    pub fn id(&self) -> Uuid { self.upload_id }
+    /// Returns the ID of the user who started the upload.
+    pub fn user_id(&self) -> Uuid { self.user_id }
+    /// Returns the path of the storage in which the file will be created, relative to the
+    /// storage root.
+    pub fn destination(&self) -> &str { &self.destination }
+    /// Returns the name of the file to be created.
+    pub fn filename(&self) -> &str { &self.filename }
+    /// Returns the total size, in bytes, of the file.
+    pub fn length(&self) -> i64 { self.upload_length }
+    /// Returns the number of bytes uploaded so far.
+    pub fn offset(&self) -> i64 { self.upload_offset }
+    /// Returns the moment after which the upload is discarded.
+    pub fn expiration(&self) -> SystemTime { self.expiration }
+    /// Returns `true` if all the bytes of the file have been uploaded, and `false` otherwise.
+    pub fn is_complete(&self) -> bool { self.upload_offset >= self.upload_length }
+    /// Returns the path of the file containing the bytes uploaded so far.
+    pub fn file(&self, tusk: &TuskConfiguration) -> PathBuf {
+        let mut path = tusk.upload_directory();
+        path.push(self.upload_id.to_string());
+        path
+    }
+
+    /// Sets the number of bytes uploaded so far, and postpones the expiration of the upload.
+    pub fn update_offset(&mut self, db_connection: &mut PgConnection, offset: i64) -> TuskResult<()> {
+        use crate::schema::upload;
+
+        let expiration = SystemTime::now() + Duration::from_secs(24 * 60 * 60);
+        diesel::update(upload::table)
+            .filter(upload::upload_id.eq(self.upload_id))
+            .set((upload::upload_offset.eq(offset), upload::expiration.eq(expiration)))
+            .execute(db_connection)?;
+
+        self.upload_offset = offset;
+        self.expiration = expiration;
+        Ok(())
+    }
+    /// Deletes the upload.
+    ///
+    /// **Warning:** this operation does not delete the file returned by [`Upload::file`].
+    pub fn delete(self, db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::upload;
+
+        let selected = upload::table
+            .filter(upload::upload_id.eq(self.upload_id));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+    /// Deletes all the expired uploads and returns them, so that the respective files can be
+    /// removed.
+    pub fn delete_expired(db_connection: &mut PgConnection) -> TuskResult<Vec<Upload>> {
+        use crate::schema::upload;
+
+        let selected = upload::table
+            .filter(upload::expiration.lt(SystemTime::now()));
+
+        let uploads = diesel::delete(selected)
+            .get_results(db_connection)?;
+
+        Ok(uploads)
+    }
+    /// Reads a valid upload started by the given user, given the upload ID.
+    pub fn from_id(db_connection: &mut PgConnection, upload_id: Uuid, user_id: Uuid) -> TuskResult<Upload> {
+        use crate::schema::upload;
+
+        let upload = upload::table
+            .filter(upload::expiration.ge(SystemTime::now()))
+            .filter(upload::upload_id.eq(upload_id))
+            .filter(upload::user_id.eq(user_id))
+            .first(db_connection)?;
+
+        Ok(upload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+    use uuid::Uuid;
+    use crate::resources::Upload;
+
+    #[test]
+    fn upload_completion() {
+        let mut upload = Upload {
+            upload_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            destination: ".public".to_owned(),
+            filename: "file.txt".to_owned(),
+            upload_length: 1024,
+            upload_offset: 0,
+            expiration: SystemTime::now()
+        };
+        assert!(!upload.is_complete());
+
+        upload.upload_offset = 1024;
+        assert!(upload.is_complete());
+    }
+}
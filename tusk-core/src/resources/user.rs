@@ -13,7 +13,7 @@ use uuid::Uuid;
 use crate::config::TuskConfiguration;
 
 use crate::error::{TuskResult};
-use crate::resources::{PasswordResetRequest, Role};
+use crate::resources::{PasswordResetRequest, Quota, Role};
 
 /// Wraps a `Secret` so that it is possible to query it from an SQL table.
 #[derive(Clone, Debug, Deserialize)]
@@ -194,6 +194,12 @@ impl User {
     pub fn request_password_reset(&self, db_connection: &mut PgConnection) -> TuskResult<PasswordResetRequest> {
         PasswordResetRequest::create(db_connection, self.user_id)
     }
+    /// Returns the maximum number of bytes this user can store, or `None` if there is no limit.
+    ///
+    /// See [`Quota::effective_for_user`] for more information.
+    pub fn quota(&self, db_connection: &mut PgConnection) -> TuskResult<Option<u64>> {
+        Quota::effective_for_user(db_connection, self.user_id)
+    }
     /// Returns the roles this user belongs to.
     pub fn roles(&self, db_connection: &mut PgConnection) -> TuskResult<Vec<Role>> {
         use crate::schema::{user, role, user_role};
@@ -0,0 +1,126 @@
+//! Data structures for the `user_key` table.
+
+use diesel::prelude::*;
+use secrecy::Secret;
+use uuid::Uuid;
+use crate::encryption::{DataKey, WrappedKey};
+use crate::error::TuskResult;
+
+/// Represents the data key of a user, wrapped with a key derived from the password of the user.
+///
+/// See the [`encryption`](crate::encryption) module for more information.
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = crate::schema::user_key)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UserKey {
+    user_id: Uuid,
+    salt: Vec<u8>,
+    iterations: i32,
+    nonce: Vec<u8>,
+    wrapped_key: Vec<u8>
+}
+impl UserKey {
+    /// Stores the given data key of the user, wrapped with `password`, in place of the current
+    /// data key of the user, if any.
+    ///
+    /// **Warning:** the files encrypted with the replaced data key cannot be decrypted anymore;
+    /// use with caution.
+    pub fn store(db_connection: &mut PgConnection, user_id: Uuid, key: &DataKey, password: &Secret<String>) -> TuskResult<UserKey> {
+        use crate::schema::user_key;
+
+        let wrapped = key.wrap(password);
+        let values = (
+            user_key::salt.eq(wrapped.salt()),
+            user_key::iterations.eq(wrapped.iterations() as i32),
+            user_key::nonce.eq(wrapped.nonce()),
+            user_key::wrapped_key.eq(wrapped.key())
+        );
+        let user_key = diesel::insert_into(user_key::table)
+            .values((user_key::user_id.eq(user_id), values))
+            .on_conflict(user_key::user_id)
+            .do_update()
+            .set(values)
+            .get_result(db_connection)?;
+
+        Ok(user_key)
+    }
+
+    /// Returns the ID of the user owning the data key.
+    pub fn user_id(&self) -> Uuid { self.user_id }
+    /// Returns the wrapped data key.
+    pub fn wrapped(&self) -> WrappedKey {
+        WrappedKey::new(self.salt.clone(), self.iterations.max(0) as u32, self.nonce.clone(), self.wrapped_key.clone())
+    }
+    /// Recovers the data key with the given password, and returns `None` if the password is wrong.
+    pub fn unwrap_with(&self, password: &Secret<String>) -> Option<DataKey> {
+        self.wrapped().unwrap_with(password)
+    }
+
+    /// Recovers the data key of the given user with the given password, which is the current
+    /// password of the user; if the user has no data key yet, a new one is generated and stored.
+    ///
+    /// Returns `None` if the data key cannot be recovered with the given password.
+    pub fn unlock(db_connection: &mut PgConnection, user_id: Uuid, password: &Secret<String>) -> TuskResult<Option<DataKey>> {
+        use crate::schema::user_key;
+
+        if let Some(user_key) = UserKey::from_user(db_connection, user_id)? {
+            return Ok(user_key.unwrap_with(password));
+        }
+
+        // Another session could be storing a data key in the meantime, hence the stored key is
+        // read back instead of replaced.
+        let wrapped = DataKey::generate().wrap(password);
+        diesel::insert_into(user_key::table)
+            .values((
+                user_key::user_id.eq(user_id),
+                user_key::salt.eq(wrapped.salt()),
+                user_key::iterations.eq(wrapped.iterations() as i32),
+                user_key::nonce.eq(wrapped.nonce()),
+                user_key::wrapped_key.eq(wrapped.key())
+            ))
+            .on_conflict_do_nothing()
+            .execute(db_connection)?;
+
+        Ok(UserKey::from_user(db_connection, user_id)?
+            .and_then(|user_key| user_key.unwrap_with(password)))
+    }
+    /// Wraps the data key of the given user, if any, with `new_password` in place of `password`,
+    /// which is the current password of the user.
+    ///
+    /// If the data key cannot be recovered with `password`, it is left untouched.
+    pub fn rewrap(db_connection: &mut PgConnection, user_id: Uuid, password: &Secret<String>, new_password: &Secret<String>) -> TuskResult<()> {
+        let Some(user_key) = UserKey::from_user(db_connection, user_id)? else { return Ok(()); };
+        match user_key.unwrap_with(password) {
+            Some(key) => { UserKey::store(db_connection, user_id, &key, new_password)?; },
+            None => log::warn!("Cannot recover the data key of user `{user_id}` to wrap it with the new password")
+        }
+
+        Ok(())
+    }
+    /// Reads the data key of the given user, if any.
+    pub fn from_user(db_connection: &mut PgConnection, user_id: Uuid) -> TuskResult<Option<UserKey>> {
+        use crate::schema::user_key;
+
+        let user_key = user_key::table
+            .filter(user_key::user_id.eq(user_id))
+            .first(db_connection)
+            .optional()?;
+
+        Ok(user_key)
+    }
+    /// Deletes the data key.
+    ///
+    /// **Warning:** this operation is irreversible, and the files encrypted with the data key
+    /// cannot be decrypted anymore; use with caution.
+    pub fn delete(self, db_connection: &mut PgConnection) -> TuskResult<()> {
+        use crate::schema::user_key;
+
+        let selected = user_key::table
+            .filter(user_key::user_id.eq(self.user_id));
+
+        let _ = diesel::delete(selected)
+            .execute(db_connection)?;
+
+        Ok(())
+    }
+}
@@ -1,5 +1,43 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    blob (hash) {
+        hash -> Varchar,
+        size -> Int8,
+    }
+}
+
+diesel::table! {
+    blob_reference (path) {
+        path -> Varchar,
+        hash -> Varchar,
+    }
+}
+
+diesel::table! {
+    dav_lock (lock_token) {
+        lock_token -> Uuid,
+        user_id -> Uuid,
+        path -> Varchar,
+        deep -> Bool,
+        exclusive -> Bool,
+        owner -> Nullable<Varchar>,
+        expiration -> Timestamp,
+    }
+}
+
+diesel::table! {
+    file_version (file_version_id) {
+        file_version_id -> Uuid,
+        path -> Varchar,
+        version -> Int4,
+        size -> Int8,
+        modified -> Timestamp,
+        author_id -> Nullable<Uuid>,
+        replaced_by -> Nullable<Uuid>,
+    }
+}
+
 diesel::table! {
     password_reset (request_id) {
         request_id -> Uuid,
@@ -8,6 +46,15 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    quota (quota_id) {
+        quota_id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        role_id -> Nullable<Uuid>,
+        max_bytes -> Int8,
+    }
+}
+
 diesel::table! {
     role (role_id) {
         role_id -> Uuid,
@@ -16,6 +63,80 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    share_link (share_link_id) {
+        share_link_id -> Uuid,
+        token -> Varchar,
+        user_id -> Uuid,
+        path -> Varchar,
+        expiration -> Timestamp,
+        password -> Nullable<Varchar>,
+        max_downloads -> Nullable<Int4>,
+        downloads -> Int4,
+    }
+}
+
+diesel::table! {
+    storage_change (sequence) {
+        sequence -> Int8,
+        root -> Varchar,
+        path -> Varchar,
+        operation -> Varchar,
+        directory -> Bool,
+        size -> Nullable<Int8>,
+        modified -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    storage_grant (storage_grant_id) {
+        storage_grant_id -> Uuid,
+        owner_id -> Uuid,
+        path -> Varchar,
+        user_id -> Nullable<Uuid>,
+        role_id -> Nullable<Uuid>,
+        writable -> Bool,
+    }
+}
+
+diesel::table! {
+    team_folder (team_folder_id) {
+        team_folder_id -> Uuid,
+        name -> Varchar,
+    }
+}
+
+diesel::table! {
+    team_member (team_member_id) {
+        team_member_id -> Uuid,
+        team_folder_id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        role_id -> Nullable<Uuid>,
+        writable -> Bool,
+    }
+}
+
+diesel::table! {
+    trash_item (trash_item_id) {
+        trash_item_id -> Uuid,
+        user_id -> Uuid,
+        original_path -> Varchar,
+        deletion -> Timestamp,
+    }
+}
+
+diesel::table! {
+    upload (upload_id) {
+        upload_id -> Uuid,
+        user_id -> Uuid,
+        destination -> Varchar,
+        filename -> Varchar,
+        upload_length -> Int8,
+        upload_offset -> Int8,
+        expiration -> Timestamp,
+    }
+}
+
 diesel::table! {
     user (user_id) {
         user_id -> Uuid,
@@ -25,6 +146,16 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    user_key (user_id) {
+        user_id -> Uuid,
+        salt -> Bytea,
+        iterations -> Int4,
+        nonce -> Bytea,
+        wrapped_key -> Bytea,
+    }
+}
+
 diesel::table! {
     user_role (user_role_id) {
         user_role_id -> Uuid,
@@ -33,13 +164,38 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(blob_reference -> blob (hash));
+diesel::joinable!(dav_lock -> user (user_id));
 diesel::joinable!(password_reset -> user (user_id));
+diesel::joinable!(quota -> role (role_id));
+diesel::joinable!(quota -> user (user_id));
+diesel::joinable!(share_link -> user (user_id));
+diesel::joinable!(storage_grant -> role (role_id));
+diesel::joinable!(team_member -> role (role_id));
+diesel::joinable!(team_member -> team_folder (team_folder_id));
+diesel::joinable!(team_member -> user (user_id));
+diesel::joinable!(trash_item -> user (user_id));
+diesel::joinable!(upload -> user (user_id));
+diesel::joinable!(user_key -> user (user_id));
 diesel::joinable!(user_role -> role (role_id));
 diesel::joinable!(user_role -> user (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    blob,
+    blob_reference,
+    dav_lock,
+    file_version,
     password_reset,
+    quota,
     role,
+    share_link,
+    storage_change,
+    storage_grant,
+    team_folder,
+    team_member,
+    trash_item,
+    upload,
     user,
+    user_key,
     user_role,
 );
@@ -0,0 +1,204 @@
+//! This module contains the backends in which the files of the users are stored.
+//!
+//! Items are identified by keys, which are their paths relative to the storage root, with the
+//! components separated by `/` and without leading or trailing separators; the storage root
+//! itself is identified by the empty key.
+//!
+//! The only backend available is [`LocalStorage`], which stores the items in the
+//! `user_directories` of the local filesystem.
+//!
+//! The backend is selected by the `tusk.storage` section of `tusk.toml`, and is returned by
+//! [`TuskConfiguration::storage`](crate::config::TuskConfiguration::storage).
+
+mod local;
+
+use std::fmt::Debug;
+use std::fs::File;
+use std::future::Future;
+use std::io::Read;
+use std::path::Path;
+use std::pin::Pin;
+use std::time::SystemTime;
+use actix_web::web::Bytes;
+use futures_util::{stream, Stream};
+use crate::error::TuskResult;
+
+pub use local::LocalStorage;
+
+/// Size of the chunks in which files are read.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Boxed future returned by the operations of a [`StorageBackend`].
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = TuskResult<T>> + 'a>>;
+/// Boxed stream of the contents of a file.
+pub type ByteStream = Pin<Box<dyn Stream<Item = TuskResult<Bytes>>>>;
+
+/// Contains the attributes of an item of a [`StorageBackend`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct StorageEntry {
+    name: String,
+    directory: bool,
+    size: u64,
+    modified: SystemTime
+}
+impl StorageEntry {
+    /// Creates the attributes of a file with the given name, size and last modification time.
+    pub fn file<S: Into<String>>(name: S, size: u64, modified: SystemTime) -> StorageEntry {
+        StorageEntry { name: name.into(), directory: false, size, modified }
+    }
+    /// Creates the attributes of a storage with the given name and last modification time.
+    pub fn directory<S: Into<String>>(name: S, modified: SystemTime) -> StorageEntry {
+        StorageEntry { name: name.into(), directory: true, size: 0, modified }
+    }
+
+    /// Returns the name of the item, that is, the last component of its key.
+    pub fn name(&self) -> &str { &self.name }
+    /// Returns `true` if the item is a storage and `false` if it is a file.
+    pub fn is_directory(&self) -> bool { self.directory }
+    /// Returns the size, in bytes, of the item if it is a file, and `0` otherwise.
+    pub fn size(&self) -> u64 { self.size }
+    /// Returns the last modification time of the item.
+    pub fn modified(&self) -> SystemTime { self.modified }
+}
+
+/// Returns a stream of the contents of the given file, read in chunks of 64 KiB.
+pub fn file_stream(file: File) -> ByteStream {
+    reader_stream(file)
+}
+
+/// Returns a stream of the contents of the given reader, read in chunks of 64 KiB.
+pub fn reader_stream<R: Read + 'static>(reader: R) -> ByteStream {
+    let chunks = stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        let mut buffer = vec![0; CHUNK_SIZE];
+        match reader.read(&mut buffer) {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                Some((Ok(Bytes::from(buffer)), Some(reader)))
+            },
+            Err(e) => Some((Err(e.into()), None))
+        }
+    });
+    Box::pin(chunks)
+}
+
+/// Returns the name of the item with the given key, that is, its last component.
+fn name_of(key: &str) -> &str {
+    key.rsplit('/').next().unwrap_or(key)
+}
+
+/// Represents a backend in which the files of the users are stored.
+///
+/// All the operations are relative to the keys described in the [module documentation](self).
+pub trait StorageBackend: Debug + Send + Sync {
+    /// Returns the directory in which the items are stored, if the backend stores them in the
+    /// local filesystem, and `None` otherwise.
+    fn local_root(&self) -> Option<&Path> { None }
+
+    /// Lists the attributes of the children of the storage with the given key, sorted by name.
+    ///
+    /// # Errors
+    /// If the item does not exist, this function returns an HTTP error 404 `NOT FOUND`; if it is
+    /// a file, this function returns an HTTP error 409 `CONFLICT`.
+    fn list<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Vec<StorageEntry>>;
+    /// Returns the attributes of the item with the given key, or `None` if the item does not
+    /// exist.
+    fn stat<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<StorageEntry>>;
+    /// Returns a stream of the contents of the file with the given key.
+    ///
+    /// # Errors
+    /// If the item does not exist, this function returns an HTTP error 404 `NOT FOUND`; if it is
+    /// a storage, this function returns an HTTP error 409 `CONFLICT`.
+    fn read<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ByteStream>;
+    /// Writes the `size` bytes of `contents` to the file with the given key, replacing the file if
+    /// it already exists, and returns the attributes of the file.
+    ///
+    /// # Errors
+    /// If the parent storage does not exist, this function returns an HTTP error 404 `NOT FOUND`;
+    /// if the item is a storage, this function returns an HTTP error 409 `CONFLICT`.
+    fn write<'a>(&'a self, key: &'a str, size: u64, contents: ByteStream) -> StorageFuture<'a, StorageEntry>;
+    /// Creates the storage with the given key and returns its attributes.
+    ///
+    /// # Errors
+    /// If the parent storage does not exist, this function returns an HTTP error 404 `NOT FOUND`;
+    /// if the item already exists, this function returns an HTTP error 409 `CONFLICT`.
+    fn create_dir<'a>(&'a self, key: &'a str) -> StorageFuture<'a, StorageEntry>;
+    /// Deletes the item with the given key, together with its contents if it is a storage.
+    ///
+    /// # Errors
+    /// If the item does not exist, this function returns an HTTP error 404 `NOT FOUND`.
+    fn delete<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()>;
+    /// Moves the item with key `from`, together with its contents if it is a storage, to the key
+    /// `to`.
+    ///
+    /// # Errors
+    /// If the item does not exist, this function returns an HTTP error 404 `NOT FOUND`; if an item
+    /// with key `to` already exists, this function returns an HTTP error 409 `CONFLICT`.
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> StorageFuture<'a, ()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::ResponseError;
+    use actix_web::web::Bytes;
+    use futures_util::{stream, StreamExt};
+    use crate::storage::{ByteStream, StorageBackend};
+
+    /// Returns a stream yielding the given contents.
+    pub fn contents(data: &'static str) -> ByteStream {
+        Box::pin(stream::iter([Ok(Bytes::from_static(data.as_bytes()))]))
+    }
+
+    /// Reads the whole contents of the file with the given key.
+    pub async fn read_all(backend: &dyn StorageBackend, key: &str) -> String {
+        let mut stream = backend.read(key).await.unwrap();
+        let mut data = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            data.extend_from_slice(&chunk.unwrap());
+        }
+        String::from_utf8(data).unwrap()
+    }
+
+    /// Checks that the given backend, whose storage root is empty, behaves as documented.
+    pub async fn check_backend(backend: &dyn StorageBackend) {
+        assert!(backend.stat("").await.unwrap().unwrap().is_directory());
+        assert!(backend.list("").await.unwrap().is_empty());
+
+        let created = backend.create_dir("Documents").await.unwrap();
+        assert!(created.is_directory());
+        assert_eq!(backend.create_dir("Documents").await.unwrap_err().status_code(), 409);
+        assert_eq!(backend.create_dir("Missing/Documents").await.unwrap_err().status_code(), 404);
+
+        let written = backend.write("Documents/Bill.txt", 5, contents("Hello")).await.unwrap();
+        assert_eq!((written.name(), written.size(), written.is_directory()), ("Bill.txt", 5, false));
+        assert_eq!(read_all(backend, "Documents/Bill.txt").await, "Hello");
+        backend.write("Documents/Bill.txt", 3, contents("Bye")).await.unwrap();
+        assert_eq!(read_all(backend, "Documents/Bill.txt").await, "Bye");
+        backend.write("Notes.txt", 4, contents("Note")).await.unwrap();
+        assert_eq!(backend.write("Missing/Notes.txt", 4, contents("Note")).await.unwrap_err().status_code(), 404);
+
+        let names = |entries: Vec<crate::storage::StorageEntry>| entries.into_iter()
+            .map(|entry| (entry.name().to_owned(), entry.is_directory()))
+            .collect::<Vec<_>>();
+        assert_eq!(names(backend.list("").await.unwrap()), [("Documents".to_owned(), true), ("Notes.txt".to_owned(), false)]);
+        assert_eq!(names(backend.list("Documents").await.unwrap()), [("Bill.txt".to_owned(), false)]);
+        assert_eq!(backend.list("Notes.txt").await.unwrap_err().status_code(), 409);
+        assert_eq!(backend.list("Missing").await.unwrap_err().status_code(), 404);
+        assert_eq!(backend.read("Documents").await.err().unwrap().status_code(), 409);
+        assert_eq!(backend.read("Missing.txt").await.err().unwrap().status_code(), 404);
+        assert!(backend.stat("Missing.txt").await.unwrap().is_none());
+
+        backend.rename("Documents", "Archive").await.unwrap();
+        assert!(backend.stat("Documents").await.unwrap().is_none());
+        assert_eq!(read_all(backend, "Archive/Bill.txt").await, "Bye");
+        assert_eq!(backend.rename("Notes.txt", "Archive").await.unwrap_err().status_code(), 409);
+        backend.rename("Notes.txt", "Archive/Notes.txt").await.unwrap();
+        assert_eq!(names(backend.list("Archive").await.unwrap()), [("Bill.txt".to_owned(), false), ("Notes.txt".to_owned(), false)]);
+
+        backend.delete("Archive/Notes.txt").await.unwrap();
+        assert_eq!(backend.delete("Archive/Notes.txt").await.unwrap_err().status_code(), 404);
+        backend.delete("Archive").await.unwrap();
+        assert!(backend.list("").await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+//! Contains the backend storing the items in the local filesystem.
+
+use std::fs::{File, Metadata};
+use std::io::{ErrorKind, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use futures_util::StreamExt;
+use uuid::Uuid;
+use crate::error::{TuskError, TuskResult};
+use crate::storage::{ByteStream, file_stream, name_of, StorageBackend, StorageEntry, StorageFuture};
+
+/// Returns the attributes of the item with the given name and metadata.
+fn entry_of(name: &str, metadata: &Metadata) -> StorageEntry {
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    if metadata.is_dir() {
+        StorageEntry::directory(name, modified)
+    } else {
+        StorageEntry::file(name, metadata.len(), modified)
+    }
+}
+
+/// Represents the backend storing the items in a directory of the local filesystem.
+///
+/// Every item is stored at the path given by its key, relative to the directory.
+#[derive(Clone, Debug)]
+pub struct LocalStorage {
+    root: PathBuf
+}
+impl LocalStorage {
+    /// Creates a backend storing the items in the given directory.
+    pub fn new<P: Into<PathBuf>>(root: P) -> LocalStorage {
+        LocalStorage { root: root.into() }
+    }
+
+    /// Returns the path of the item with the given key.
+    ///
+    /// # Errors
+    /// If the key points outside of the directory, this function returns an HTTP error
+    /// 400 `BAD REQUEST`.
+    fn path_of(&self, key: &str) -> TuskResult<PathBuf> {
+        let key = Path::new(key);
+        if !key.components().all(|component| matches!(component, Component::Normal(_))) {
+            return TuskError::bad_request().bail();
+        }
+        Ok(self.root.join(key))
+    }
+
+    /// Writes `contents` to the file with the given key, through a temporary file in the same
+    /// storage, so that the file is replaced atomically.
+    async fn write_file(&self, key: &str, mut contents: ByteStream) -> TuskResult<StorageEntry> {
+        let path = self.path_of(key)?;
+        if path.is_dir() { return TuskError::conflict().bail(); }
+        let parent = path.parent()
+            .ok_or_else(TuskError::conflict)?;
+        let temp = parent.join(format!(".tusk-{}", Uuid::new_v4()));
+
+        let result = async {
+            let mut file = File::create(&temp)?;
+            while let Some(chunk) = contents.next().await {
+                file.write_all(&chunk?)?;
+            }
+            file.sync_all()?;
+            std::fs::rename(&temp, &path)?;
+            Ok(entry_of(name_of(key), &path.metadata()?))
+        }.await;
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        result
+    }
+}
+impl StorageBackend for LocalStorage {
+    fn local_root(&self) -> Option<&Path> {
+        Some(&self.root)
+    }
+
+    fn list<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Vec<StorageEntry>> {
+        Box::pin(async move {
+            let path = self.path_of(key)?;
+            if !path.metadata()?.is_dir() { return TuskError::conflict().bail(); }
+
+            let mut entries: Vec<StorageEntry> = std::fs::read_dir(path)?
+                .filter_map(Result::ok)
+                .filter_map(|entry| {
+                    let metadata = entry.metadata().ok()?;
+                    if !metadata.is_dir() && !metadata.is_file() { return None; }
+                    Some(entry_of(&entry.file_name().to_string_lossy(), &metadata))
+                })
+                .collect();
+            entries.sort_by(|a, b| a.name().cmp(b.name()));
+            Ok(entries)
+        })
+    }
+
+    fn stat<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<StorageEntry>> {
+        Box::pin(async move {
+            match self.path_of(key)?.metadata() {
+                Ok(metadata) => Ok(Some(entry_of(name_of(key), &metadata))),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into())
+            }
+        })
+    }
+
+    fn read<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ByteStream> {
+        Box::pin(async move {
+            let path = self.path_of(key)?;
+            if path.metadata()?.is_dir() { return TuskError::conflict().bail(); }
+            Ok(file_stream(File::open(path)?))
+        })
+    }
+
+    fn write<'a>(&'a self, key: &'a str, _size: u64, contents: ByteStream) -> StorageFuture<'a, StorageEntry> {
+        Box::pin(self.write_file(key, contents))
+    }
+
+    fn create_dir<'a>(&'a self, key: &'a str) -> StorageFuture<'a, StorageEntry> {
+        Box::pin(async move {
+            let path = self.path_of(key)?;
+            match std::fs::create_dir(&path) {
+                Ok(()) => Ok(entry_of(name_of(key), &path.metadata()?)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => TuskError::conflict().bail(),
+                Err(e) => Err(e.into())
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let path = self.path_of(key)?;
+            if path.symlink_metadata()?.is_dir() {
+                std::fs::remove_dir_all(path)?;
+            } else {
+                std::fs::remove_file(path)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let from = self.path_of(from)?;
+            let to = self.path_of(to)?;
+            from.symlink_metadata()?;
+            if to.symlink_metadata().is_ok() { return TuskError::conflict().bail(); }
+
+            std::fs::rename(from, to)?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::LocalStorage;
+    use crate::storage::tests::check_backend;
+
+    #[actix_web::test]
+    async fn local_storage() {
+        let root = tempfile::tempdir().unwrap();
+        check_backend(&LocalStorage::new(root.path())).await;
+    }
+}
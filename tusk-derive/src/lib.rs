@@ -2,6 +2,7 @@ use proc_macro::TokenStream;
 use proc_macro2::{Ident, Literal};
 use quote::quote;
 use syn::{ImplItem, ItemImpl, parse_macro_input};
+use syn::ext::IdentExt;
 
 #[proc_macro_attribute]
 pub fn rest_resource(args: TokenStream, input: TokenStream) -> TokenStream {
@@ -12,13 +13,17 @@ pub fn rest_resource(args: TokenStream, input: TokenStream) -> TokenStream {
     let methods: Vec<Ident> = body.items.iter()
         .filter_map(|item| {
             match item {
-                ImplItem::Fn(method) => match method.sig.ident.to_string().as_str() {
+                ImplItem::Fn(method) => match method.sig.ident.unraw().to_string().as_str() {
                     "get" | "post" | "put" | "head" | "delete" | "patch" | "options" | "connect" | "trace" => Some(method.sig.ident.clone()),
+                    "propfind" | "proppatch" | "mkcol" | "copy" | "move" | "lock" | "unlock" => Some(method.sig.ident.clone()),
                     _ => None
                 },
                 _ => None
             }
         }).collect();
+    let verbs: Vec<Literal> = methods.iter()
+        .map(|method| Literal::byte_string(method.unraw().to_string().to_uppercase().as_bytes()))
+        .collect();
 
     quote! {
         #body
@@ -26,7 +31,7 @@ pub fn rest_resource(args: TokenStream, input: TokenStream) -> TokenStream {
         impl actix_web::dev::HttpServiceFactory for #self_ty {
             fn register(self, config: &mut actix_web::dev::AppService) {
                 actix_web::web::resource(#path)
-                    #(.route(actix_web::web::#methods().to(Self::#methods)))*
+                    #(.route(actix_web::web::method(actix_web::http::Method::from_bytes(#verbs).unwrap()).to(Self::#methods)))*
                     .register(config)
             }
         }
@@ -8,17 +8,60 @@
 pub mod session;
 pub mod storage;
 pub mod account;
+pub mod share;
+pub mod trash;
+pub mod upload;
+pub mod dav;
+pub mod grant;
+pub mod version;
+pub mod search;
+pub mod events;
+pub mod changes;
+pub mod team;
+pub mod deduplication;
+pub mod encryption;
+pub mod preview;
 
 use actix_web::web::ServiceConfig;
 use crate::api::account::AccountPasswordResource;
-use crate::api::storage::StorageResource;
+use crate::api::changes::StorageChangesResource;
+use crate::api::dav::DavResource;
+use crate::api::deduplication::StorageDeduplicationResource;
+use crate::api::encryption::StorageEncryptionResource;
+use crate::api::events::StorageEventsResource;
+use crate::api::grant::{StorageGrantResource, StorageGrantsResource, StorageSharedResource};
+use crate::api::search::StorageSearchResource;
+use crate::api::storage::{StorageQuotaResource, StorageResource};
 use crate::api::session::SessionResource;
+use crate::api::share::{ShareLinkResource, SharesResource};
+use crate::api::team::StorageTeamsResource;
+use crate::api::trash::{TrashItemResource, TrashResource};
+use crate::api::upload::{UploadResource, UploadsResource};
+use crate::api::version::StorageVersionResource;
 
 /// Configures the server by adding the corresponding API resources.
 pub fn configure(cfg: &mut ServiceConfig) {
     cfg
         .service(AccountPasswordResource)
         .service(SessionResource)
+        .service(StorageQuotaResource)
+        .service(StorageDeduplicationResource)
+        .service(StorageEncryptionResource)
+        .service(StorageSharedResource)
+        .service(StorageTeamsResource)
+        .service(StorageSearchResource)
+        .service(StorageEventsResource)
+        .service(StorageChangesResource)
         .service(StorageResource)
+        .service(StorageVersionResource)
+        .service(StorageGrantsResource)
+        .service(StorageGrantResource)
+        .service(SharesResource)
+        .service(ShareLinkResource)
+        .service(TrashResource)
+        .service(TrashItemResource)
+        .service(UploadsResource)
+        .service(UploadResource)
+        .service(DavResource)
     ;
 }
\ No newline at end of file
@@ -10,7 +10,7 @@ use uuid::Uuid;
 use tusk_core::config::Tusk;
 use tusk_core::{Connection, Message};
 use tusk_core::error::{HttpOkOr, TuskError, TuskHttpResult};
-use tusk_core::resources::{PasswordResetRequest, User};
+use tusk_core::resources::{PasswordResetRequest, User, UserKey};
 use tusk_derive::rest_resource;
 
 /// Returns a result that is `Ok` if the given password is strong enough, and `Err` otherwise.
@@ -58,7 +58,9 @@ pub struct AccountPasswordPutData {
     password: Option<Secret<String>>,
     proof: Option<Secret<String>>,
     #[serde(default)]
-    proof_type: AccountProofType
+    proof_type: AccountProofType,
+    #[serde(default)]
+    discard_encrypted_files: bool
 }
 impl AccountPasswordPutData {
     /// Returns the email of the user.
@@ -80,6 +82,11 @@ impl AccountPasswordPutData {
     pub fn proof_type(&self) -> AccountProofType {
         self.proof_type
     }
+    /// Returns `true` if the user confirmed that a password reset makes their encrypted files
+    /// unreadable, and `false` otherwise.
+    pub fn discard_encrypted_files(&self) -> bool {
+        self.discard_encrypted_files
+    }
 }
 
 /// Represents the `/account/password` REST resource.
@@ -112,6 +119,7 @@ impl AccountPasswordResource {
                         let user_inputs = vec![target.email(), target.display(), "Tusk"];
                         verify_password_strength(password, &user_inputs)?;
                         target.update_password(db, password)?;
+                        UserKey::rewrap(db, target.id(), proof, password)?;
                         // Send password update confirmation email.
                         let message = Message::builder()
                             .from(format!("Tusk Server <{contact_noreply}>").parse().unwrap())
@@ -156,6 +164,11 @@ Tusk"#))
                     // This means that this is a legitimate and well-formed password RECOVERY request.
                     let request = target.request_password_reset(db)?;
                     let token = request.token();
+                    let encrypted_files = if UserKey::from_user(db, target.id())?.is_some() {
+                        "\nWARNING: your account contains encrypted files, which can only be read with your current password. If you reset your password, they will be lost forever.\n"
+                    } else {
+                        ""
+                    };
                     let message = Message::builder()
                         .from(format!("Tusk Server <{contact_noreply}>").parse().unwrap())
                         .to(target.mailbox()?)
@@ -164,7 +177,7 @@ Tusk"#))
 A password reset request has been sent for your account.
 If you requested the reset, you can set up a new password by visiting https://{server_address}/password_reset/verify?token={token} and following the steps.
 If this is not the case, you can simply ignore this email.
-
+{encrypted_files}
 Note: the above link expires after 24 hours. In this case, you can request a new link by visiting https://{server_address}/password_reset/request and following the steps.
 
 Best,
@@ -189,7 +202,19 @@ Tusk"#))
                     let request = PasswordResetRequest::from_token(db, token)?;
                     let user_inputs = vec![target.email(), target.display(), "Tusk"];
                     verify_password_strength(password, &user_inputs)?;
+                    // The data key cannot be recovered without the old password, so the user
+                    // has to confirm that their encrypted files will be lost.
+                    let user_key = UserKey::from_user(db, target.id())?;
+                    if user_key.is_some() && !data.discard_encrypted_files() {
+                        return TuskError::conflict()
+                            .with_text("The account contains encrypted files, which cannot be read after a password reset.")
+                            .bail();
+                    }
                     target.update_password(db, password)?;
+                    if let Some(user_key) = user_key {
+                        log::warn!("Deleting the data key of user `{target}` after a password reset");
+                        user_key.delete(db)?;
+                    }
                     // Remove the reset token from the database.
                     request.delete(db)?;
                     // Send password update confirmation email.
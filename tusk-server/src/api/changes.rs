@@ -0,0 +1,149 @@
+//! Contains the CRUD structures relative to the `/storage-changes` REST resource.
+//!
+//! Every change to the items of the storages is recorded in a change journal, so that sync
+//! clients can ask for the changes following the ones they already know, instead of listing
+//! every storage again.
+//! Changes are recorded by the `/storage` resource when it modifies an item, and by the watcher
+//! of the [`events`](crate::api::events) module for the changes made outside of the server.
+//!
+//! Changes are identified by a cursor, which increases with every change.
+//! A client first asks for the changes without a cursor, which returns the current cursor and no
+//! changes, then lists the storages it synchronizes; from then on, it asks for the changes
+//! `since` the last cursor it received.
+//! Changes are returned in the order in which they happened, in pages of `limit` changes (500 by
+//! default, at most 1000); if `has_more` is `true`, the following page is returned by asking for
+//! the changes since the returned cursor.
+//!
+//! A change to a storage applies to everything inside it: for example, when a storage is moved,
+//! only the deletion of the old path and the creation of the new one are recorded.
+//!
+//! # Security
+//! ## Access
+//! By default, the changes inside all the storages the user has access to are returned, that is,
+//! inside the user's root, the public root, the storages shared with the user and the team folders
+//! of which the user is a member.
+//! The changes inside a single storage are returned by giving its path; the same rules as in the
+//! Access section of the [`storage`](crate::api::storage) module apply to that storage, so that,
+//! for example, the changes inside the storages shared with the user can be returned as well.
+
+use actix_web::{HttpResponse, web};
+use tusk_core::PgConnection;
+use serde::{Deserialize, Serialize};
+use tusk_core::config::Tusk;
+use tusk_core::error::{TuskError, TuskHttpResult};
+use tusk_core::resources::{StorageChange, StorageOperation};
+use tusk_derive::rest_resource;
+use crate::api::events::{self, StorageEvents};
+use crate::api::storage::{PathInfo, StoragePathRead};
+use crate::api::trash::epoch_delta;
+
+/// Default number of changes in a page.
+const DEFAULT_LIMIT: usize = 500;
+/// Maximum number of changes in a page.
+const MAX_LIMIT: usize = 1000;
+
+/// Records the creation or the modification of the item with the given request path, whose
+/// attributes after the change are `item`, in the change journal.
+///
+/// Errors are logged and otherwise ignored, as the change has already been applied to the
+/// storage.
+pub fn record_change(db: &mut PgConnection, request_path: &str, item: &StoragePathRead, operation: StorageOperation) {
+    events::journaled(request_path);
+    if let Err(e) = StorageChange::record(db, request_path, operation, item.is_directory(), item.size(), Some(item.last_modified())) {
+        log::error!("Cannot record the change to `{request_path}`: {e}");
+    }
+}
+
+/// Records the deletion of the item with the given request path in the change journal.
+///
+/// Errors are logged and otherwise ignored, as the change has already been applied to the
+/// storage.
+pub fn record_deletion(db: &mut PgConnection, request_path: &str, directory: bool) {
+    events::journaled(request_path);
+    if let Err(e) = StorageChange::record(db, request_path, StorageOperation::Deleted, directory, None, None) {
+        log::error!("Cannot record the change to `{request_path}`: {e}");
+    }
+}
+
+/// Describes the item after the change as a file or a storage.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageChangeKind {
+    /// The item is a file.
+    File,
+    /// The item is a storage.
+    Directory
+}
+
+/// Represents a single change returned by the `/storage-changes` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct StorageChangeRead {
+    cursor: i64,
+    path: String,
+    operation: StorageOperation,
+    kind: StorageChangeKind,
+    size: Option<u64>,
+    last_modified: Option<i64>
+}
+impl From<StorageChange> for StorageChangeRead {
+    fn from(value: StorageChange) -> Self {
+        StorageChangeRead {
+            cursor: value.sequence(),
+            path: value.path().to_owned(),
+            operation: value.operation(),
+            kind: if value.is_directory() { StorageChangeKind::Directory } else { StorageChangeKind::File },
+            size: value.size(),
+            last_modified: value.modified().map(epoch_delta)
+        }
+    }
+}
+
+/// Represents the CRUD **Read** structure relative to the `/storage-changes` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct StorageChangesRead {
+    cursor: i64,
+    has_more: bool,
+    changes: Vec<StorageChangeRead>
+}
+
+/// Represents the query of the `/storage-changes` REST resource.
+#[derive(Debug, Deserialize)]
+pub struct StorageChangesQuery {
+    since: Option<i64>,
+    path: Option<String>,
+    limit: Option<usize>
+}
+
+/// Represents the `/storage-changes` REST resource.
+///
+/// The `/storage-changes` resource is responsible for listing the changes to the items in the
+/// storages the user has access to.
+pub struct StorageChangesResource;
+#[rest_resource("/storage-changes")]
+impl StorageChangesResource {
+    async fn get(tusk: Tusk, query: web::Query<StorageChangesQuery>) -> TuskHttpResult {
+        tusk.authenticate()?;
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 || limit > MAX_LIMIT { return TuskError::bad_request().bail(); }
+
+        let roots = PathInfo::roots(&tusk, query.path.as_deref())?;
+        let scopes: Vec<String> = roots.iter().map(PathInfo::request_path).collect();
+        // The changes made outside of the server are only recorded while the storages are watched.
+        let _ = StorageEvents::shared(tusk.config());
+
+        let mut db = tusk.db()?;
+        let Some(since) = query.since else {
+            let cursor = StorageChange::latest_sequence(&mut db)?;
+            return Ok(HttpResponse::Ok().json(StorageChangesRead { cursor, has_more: false, changes: Vec::new() }));
+        };
+        let mut changes = StorageChange::list_since(&mut db, since, &scopes, limit + 1)?;
+        let has_more = changes.len() > limit;
+        changes.truncate(limit);
+        let cursor = changes.last().map_or(since, StorageChange::sequence);
+        let changes = changes.into_iter()
+            .map(StorageChangeRead::from)
+            .collect();
+
+        Ok(HttpResponse::Ok().json(StorageChangesRead { cursor, has_more, changes }))
+    }
+}
@@ -0,0 +1,767 @@
+//! Contains the structures relative to the `/dav` WebDAV resource.
+//!
+//! The `/dav` resource exposes the storage over WebDAV (class 1 and 2, see
+//! [RFC 4918](https://www.rfc-editor.org/rfc/rfc4918)), so that it can be mounted as a network
+//! drive. The root of the resource is a virtual collection containing the public root `.public/`
+//! and the user's root `<user>/`.
+//!
+//! # Security
+//! ## Authentication
+//! The user is authenticated either with the session cookie, as in the rest of the API, or with
+//! the credentials given through HTTP Basic authentication.
+//!
+//! If neither is valid, the response will be `UNAUTHORIZED`, together with a challenge for the
+//! credentials.
+//!
+//! ## Access
+//! The same rules as in the Access section of the [`storage`](crate::api::storage) module apply
+//! to every path, including the destination of `COPY` and `MOVE`.
+//!
+//! ## Locking
+//! Any item can be locked with `LOCK` and unlocked with `UNLOCK`; locking requires write access to
+//! the item, otherwise the response is `FORBIDDEN`.
+//! Any request that modifies a locked item, or the storage containing it, must submit the token of
+//! the lock in the `If` header, otherwise the response is `LOCKED`.
+//! Only the user who owns a lock can use its token.
+
+mod xml;
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use actix_files::NamedFile;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, ResponseError, web};
+use actix_web::dev::Payload;
+use actix_web::error::InternalError;
+use actix_web::http::{header, StatusCode};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use futures_util::StreamExt;
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode};
+use secrecy::Secret;
+use uuid::Uuid;
+use tusk_core::config::{BoxedAsyncBlock, Tusk};
+use tusk_core::encryption::DataKey;
+use tusk_core::error::{HttpOkOr, TuskError, TuskErrorResult, TuskHttpResult, TuskResult};
+use tusk_core::PgConnection;
+use tusk_core::resources::{DavLock, User};
+use tusk_derive::rest_resource;
+use crate::api::dav::xml::{DAV, escape, XmlElement};
+use crate::api::encryption;
+use crate::api::preview;
+use crate::api::storage::{CreateDirectoryData, PathInfo, StoragePathRead};
+use crate::api::trash::purge_expired;
+
+/// Path from which the WebDAV resource is served.
+pub const DAV_ROOT: &str = "/v1/dav/";
+/// Methods supported by the WebDAV resource.
+pub const DAV_METHODS: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, MKCOL, COPY, MOVE, LOCK, UNLOCK";
+/// Timeout of a lock, if the client does not request a different one.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+/// Maximum timeout of a lock.
+pub const MAX_LOCK_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Characters that are percent-encoded in the path segments of a `href`.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'&').add(b'<').add(b'>').add(b'?')
+    .add(b'[').add(b'\\').add(b']').add(b'^').add(b'`').add(b'{').add(b'|').add(b'}').add(b'/');
+
+/// Live properties defined by WebDAV, in the `DAV:` namespace.
+const LIVE_PROPERTIES: [&str; 8] = [
+    "creationdate",
+    "displayname",
+    "getcontentlength",
+    "getcontenttype",
+    "getlastmodified",
+    "lockdiscovery",
+    "resourcetype",
+    "supportedlock"
+];
+
+/// Depth of a request, as specified in the `Depth` header.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Depth {
+    Zero,
+    One,
+    Infinity
+}
+impl Depth {
+    /// Parses the `Depth` header, returning `default` if the header is missing.
+    ///
+    /// # Errors
+    /// If the header is not valid, this function returns an HTTP error 400 `BAD REQUEST`.
+    fn from_request(req: &HttpRequest, default: Depth) -> TuskResult<Depth> {
+        match req.headers().get("Depth").map(|value| value.to_str()) {
+            None => Ok(default),
+            Some(Ok("0")) => Ok(Depth::Zero),
+            Some(Ok("1")) => Ok(Depth::One),
+            Some(Ok(value)) if value.eq_ignore_ascii_case("infinity") => Ok(Depth::Infinity),
+            Some(_) => TuskError::bad_request().bail()
+        }
+    }
+}
+
+/// Returns the `href` of the item with the given path, relative to the storage root.
+fn href(request_path: &str, collection: bool) -> String {
+    let mut href = DAV_ROOT.to_owned();
+    let segments: Vec<String> = request_path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+        .collect();
+    href.push_str(&segments.join("/"));
+    if collection && !segments.is_empty() { href.push('/'); }
+    href
+}
+
+/// Returns the path, relative to the storage root, of the `Destination` header.
+///
+/// # Errors
+/// If the header is missing or not valid, this function returns an HTTP error 400 `BAD REQUEST`.
+///
+/// If the destination is not inside the WebDAV resource, this function returns an HTTP error
+/// 502 `BAD GATEWAY`.
+fn destination(req: &HttpRequest) -> TuskResult<String> {
+    let value = req.headers().get("Destination")
+        .and_then(|value| value.to_str().ok())
+        .or_bad_request()?;
+    let path = match value.split_once("://") {
+        Some((_, authority_and_path)) => authority_and_path.find('/')
+            .map(|index| &authority_and_path[index..])
+            .unwrap_or("/"),
+        None => value
+    };
+    let path = path.strip_prefix(DAV_ROOT)
+        .or_bad_gateway()?;
+    let path = percent_decode_str(path)
+        .decode_utf8()
+        .or_bad_request()?;
+
+    Ok(path.into_owned())
+}
+
+/// Returns `true` if the `Overwrite` header allows to replace an existing item, and
+/// `false` otherwise.
+fn overwrite(req: &HttpRequest) -> bool {
+    !matches!(req.headers().get("Overwrite"), Some(value) if value == "F" || value == "f")
+}
+
+/// Returns the timeout requested by the client in the `Timeout` header, bounded by
+/// [`MAX_LOCK_TIMEOUT`].
+fn lock_timeout(req: &HttpRequest) -> Duration {
+    let requested = req.headers().get("Timeout")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(str::trim);
+    let timeout = match requested {
+        Some(value) if value.eq_ignore_ascii_case("infinite") => MAX_LOCK_TIMEOUT,
+        Some(value) => value.strip_prefix("Second-")
+            .and_then(|seconds| seconds.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_LOCK_TIMEOUT),
+        None => DEFAULT_LOCK_TIMEOUT
+    };
+    timeout.min(MAX_LOCK_TIMEOUT)
+}
+
+/// Returns the lock tokens submitted by the client in the `If` header.
+fn submitted_tokens(req: &HttpRequest) -> Vec<Uuid> {
+    const SCHEME: &str = "opaquelocktoken:";
+
+    let Some(value) = req.headers().get("If").and_then(|value| value.to_str().ok()) else {
+        return Vec::new();
+    };
+    value.match_indices(SCHEME)
+        .filter_map(|(index, _)| value.get(index + SCHEME.len()..index + SCHEME.len() + 36))
+        .filter_map(|token| Uuid::parse_str(token).ok())
+        .collect()
+}
+
+/// Checks that the given user submitted the tokens of all the locks on the item at `request_path`.
+///
+/// If `members` is `true`, the locks on the members of the item are checked as well.
+/// If `membership` is `true`, the request changes the members of the storage containing the item,
+/// hence the locks on that storage are checked as well.
+///
+/// # Errors
+/// If a token has not been submitted, or if it belongs to another user, this function returns
+/// an HTTP error 423 `LOCKED`.
+fn check_locks(db: &mut PgConnection, user: &User, req: &HttpRequest, request_path: &str, members: bool, membership: bool) -> TuskResult<()> {
+    let tokens = submitted_tokens(req);
+    let mut locks = DavLock::locks_on(db, request_path, members)?;
+    if membership {
+        if let Some((parent, _)) = request_path.rsplit_once('/') {
+            locks.extend(DavLock::locks_on(db, parent, false)?);
+        }
+    }
+
+    if locks.iter().all(|lock| lock.user_id() == user.id() && tokens.contains(&lock.token())) {
+        Ok(())
+    } else {
+        TuskError::locked().bail()
+    }
+}
+
+/// Writes the `activelock` element describing the given lock.
+fn write_active_lock(out: &mut String, lock: &DavLock) {
+    let scope = if lock.is_exclusive() { "exclusive" } else { "shared" };
+    let depth = if lock.is_deep() { "infinity" } else { "0" };
+    let _ = write!(out, "<D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:{scope}/></D:lockscope><D:depth>{depth}</D:depth>");
+    if let Some(owner) = lock.owner() {
+        let _ = write!(out, "<D:owner>{owner}</D:owner>");
+    }
+    let _ = write!(out, "<D:timeout>Second-{}</D:timeout><D:locktoken><D:href>opaquelocktoken:{}</D:href></D:locktoken><D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock>",
+        lock.timeout().as_secs(), lock.token(), escape(&href(lock.path(), false)));
+}
+
+/// Returns a `207 MULTI-STATUS` response with the given XML body.
+fn multi_status(body: String) -> HttpResponse {
+    HttpResponse::build(StatusCode::MULTI_STATUS)
+        .content_type("application/xml; charset=utf-8")
+        .body(body)
+}
+
+/// Returns a response with the given status code and the given `precondition` element as
+/// the body.
+fn precondition_error(status: StatusCode, precondition: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("application/xml; charset=utf-8")
+        .body(format!(r#"<?xml version="1.0" encoding="utf-8"?><D:error xmlns:D="DAV:"><D:{precondition}/></D:error>"#))
+}
+
+/// Properties requested by a `PROPFIND` request.
+enum PropFind {
+    /// All the properties, with their values.
+    AllProp,
+    /// All the properties, without their values.
+    PropName,
+    /// The given properties, with their values.
+    Prop(Vec<XmlElement>)
+}
+impl PropFind {
+    /// Parses the body of a `PROPFIND` request. An empty body requests all the properties.
+    ///
+    /// # Errors
+    /// If the body is not valid, this function returns an HTTP error 400 `BAD REQUEST`.
+    fn from_body(body: &[u8]) -> TuskResult<PropFind> {
+        let body = std::str::from_utf8(body)
+            .or_bad_request()?;
+        if body.trim().is_empty() { return Ok(PropFind::AllProp); }
+
+        let root = XmlElement::parse(body)?;
+        if !root.is(DAV, "propfind") { return TuskError::bad_request().bail(); }
+
+        if root.child(DAV, "allprop").is_some() {
+            Ok(PropFind::AllProp)
+        } else if root.child(DAV, "propname").is_some() {
+            Ok(PropFind::PropName)
+        } else if let Some(prop) = root.child(DAV, "prop") {
+            Ok(PropFind::Prop(prop.children().to_vec()))
+        } else {
+            TuskError::bad_request().bail()
+        }
+    }
+}
+
+/// Item listed in a `PROPFIND` response.
+struct DavEntry {
+    request_path: String,
+    info: Option<StoragePathRead>,
+    locks: Vec<DavLock>
+}
+impl DavEntry {
+    /// Loads the information relative to the item at the given path.
+    fn from_path(db: &mut PgConnection, path: &PathInfo) -> TuskResult<DavEntry> {
+        let request_path = path.request_path();
+        let locks = DavLock::locks_on(db, &request_path, false)?;
+        Ok(DavEntry { request_path, info: Some(path.info()?), locks })
+    }
+
+    /// Returns the entry relative to the virtual root.
+    fn root() -> DavEntry {
+        DavEntry { request_path: String::new(), info: None, locks: Vec::new() }
+    }
+
+    /// Returns `true` if the item is a collection and `false` otherwise.
+    fn is_collection(&self) -> bool {
+        self.info.as_ref().is_none_or(StoragePathRead::is_directory)
+    }
+
+    /// Returns the value of the given live property, or `None` if the item does not have it.
+    fn property(&self, name: &str) -> Option<String> {
+        let info = self.info.as_ref();
+        match name {
+            "creationdate" => info.map(|info| humantime::format_rfc3339_seconds(info.created()).to_string()),
+            "displayname" => Some(escape(info.map_or("Tusk", |info| info.filename()))),
+            "getcontentlength" => info.and_then(|info| info.size()).map(|size| size.to_string()),
+            "getcontenttype" => info.filter(|info| !info.is_directory())
+                .map(|info| {
+                    let extension = info.filename().rsplit_once('.').map_or("", |(_, extension)| extension);
+                    actix_files::file_extension_to_mime(extension).to_string()
+                }),
+            "getlastmodified" => info.map(|info| httpdate::fmt_http_date(info.last_modified())),
+            "lockdiscovery" => Some({
+                let mut out = String::new();
+                for lock in &self.locks { write_active_lock(&mut out, lock); }
+                out
+            }),
+            "resourcetype" => Some(if self.is_collection() { "<D:collection/>".to_owned() } else { String::new() }),
+            "supportedlock" => Some("<D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>\
+                <D:lockentry><D:lockscope><D:shared/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>".to_owned()),
+            _ => None
+        }
+    }
+
+    /// Writes the `response` element describing the item.
+    fn write_response(&self, out: &mut String, propfind: &PropFind) {
+        let _ = write!(out, "<D:response><D:href>{}</D:href>", escape(&href(&self.request_path, self.is_collection())));
+
+        let mut found = String::new();
+        let mut missing = String::new();
+        match propfind {
+            PropFind::AllProp | PropFind::PropName => for name in LIVE_PROPERTIES {
+                if let Some(value) = self.property(name) {
+                    match propfind {
+                        PropFind::PropName => { let _ = write!(found, "<D:{name}/>"); },
+                        _ => { let _ = write!(found, "<D:{name}>{value}</D:{name}>"); }
+                    }
+                }
+            },
+            PropFind::Prop(properties) => for property in properties {
+                let value = if property.namespace() == DAV { self.property(property.name()) } else { None };
+                match value {
+                    Some(value) => { let _ = write!(found, "<D:{0}>{value}</D:{0}>", property.name()); },
+                    None => property.write_empty(&mut missing)
+                }
+            }
+        }
+
+        if !found.is_empty() {
+            let _ = write!(out, "<D:propstat><D:prop>{found}</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat>");
+        }
+        if !missing.is_empty() {
+            let _ = write!(out, "<D:propstat><D:prop>{missing}</D:prop><D:status>HTTP/1.1 404 Not Found</D:status></D:propstat>");
+        }
+        out.push_str("</D:response>");
+    }
+}
+
+/// Resource extractor for the path requested through WebDAV.
+///
+/// Authenticates the user and outputs the corresponding authorized path, or `None` if
+/// the virtual root has been requested.
+pub struct DavPath {
+    user: User,
+    password: Option<Secret<String>>,
+    path: Option<PathInfo>
+}
+impl DavPath {
+    /// Authenticates the user either through the session or through the given credentials.
+    ///
+    /// # Errors
+    /// If the user is not logged in and no valid credentials are given, this function returns
+    /// an HTTP error 401 `UNAUTHORIZED`.
+    fn authenticate(tusk: &Tusk, db: &mut PgConnection, credentials: Option<(String, Secret<String>)>) -> TuskResult<User> {
+        let error = match tusk.authenticate() {
+            Ok(auth_session) => return auth_session.user(db),
+            Err(e) => e
+        };
+        let Some((email, password)) = credentials else { return Err(error); };
+
+        let user = User::from_email(db, &email)
+            .mask_authentication_failure(&email)?
+            .mask_authentication_failure(&email)?;
+        if !user.verify_password(&password) {
+            log::warn!("Failed login attempt for user `{email}`");
+            return TuskError::unauthorized().bail();
+        }
+
+        Ok(user)
+    }
+
+    /// Returns the credentials given through HTTP Basic authentication, if any.
+    pub fn credentials(req: &HttpRequest) -> Option<(String, Secret<String>)> {
+        let value = req.headers().get(header::AUTHORIZATION)?
+            .to_str().ok()?;
+        let (scheme, encoded) = value.split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("basic") { return None; }
+        let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+        let (email, password) = decoded.split_once(':')?;
+        Some((email.to_owned(), Secret::new(password.to_owned())))
+    }
+
+    /// Returns the data key of the user, either from the session or recovered with the password
+    /// given through HTTP Basic authentication, if any.
+    ///
+    /// Keys recovered with the password are kept in the key ring, so that clients sending the
+    /// credentials with every request do not pay for the recovery every time.
+    fn data_key(&self, tusk: &Tusk) -> TuskResult<Option<DataKey>> {
+        if let Some(key) = tusk.data_key() { return Ok(Some(key)); }
+        let Some(password) = &self.password else { return Ok(None); };
+
+        let key_ring = tusk.config().key_ring();
+        if let Some(key) = key_ring.get_recovered(self.user.id(), password) { return Ok(Some(key)); }
+        let key = encryption::recover(tusk.config(), &mut *tusk.db()?, self.user.id(), password)?;
+        if let Some(key) = &key {
+            key_ring.insert_recovered(self.user.id(), password, key.clone());
+        }
+        Ok(key)
+    }
+}
+impl FromRequest for DavPath {
+    type Error = actix_web::Error;
+    type Future = BoxedAsyncBlock<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let tusk_future = Tusk::extract(req);
+        let queried_path: PathBuf = req.match_info()
+            .query("filename")
+            .into();
+        let credentials = DavPath::credentials(req);
+        let password = credentials.as_ref()
+            .map(|(_, password)| password.clone());
+
+        Box::pin(async move {
+            let tusk = tusk_future.await?;
+            let result = tusk.db().and_then(|mut db| {
+                let user = DavPath::authenticate(&tusk, &mut db, credentials)?;
+                let path = if path_clean::clean(&queried_path) == Path::new(".") {
+                    None
+                } else {
+                    Some(PathInfo::authorize(tusk.config(), &mut db, &user, queried_path)?)
+                };
+                Ok(DavPath { user, password, path })
+            });
+
+            result.map_err(|e| if e.status_code() == StatusCode::UNAUTHORIZED {
+                let challenge = HttpResponse::Unauthorized()
+                    .insert_header((header::WWW_AUTHENTICATE, r#"Basic realm="Tusk", charset="UTF-8""#))
+                    .finish();
+                InternalError::from_response(e, challenge).into()
+            } else {
+                e.into()
+            })
+        })
+    }
+}
+
+/// Represents the `/dav` WebDAV resource.
+///
+/// The `/dav` resource is responsible for exposing the storage over WebDAV.
+pub struct DavResource;
+#[rest_resource("/dav/{filename:.*}")]
+impl DavResource {
+    async fn options() -> HttpResponse {
+        HttpResponse::Ok()
+            .insert_header(("DAV", "1, 2"))
+            .insert_header((header::ALLOW, DAV_METHODS))
+            .insert_header(("MS-Author-Via", "DAV"))
+            .finish()
+    }
+
+    async fn get(tusk: Tusk, dav: DavPath, req: HttpRequest) -> TuskHttpResult {
+        let path = match &dav.path {
+            Some(path) if !path.is_directory() => path.clone(),
+            _ => return TuskError::method_not_allowed().bail()
+        };
+        if !encryption::is_encrypted(path.as_ref()) {
+            let response = NamedFile::open(&path)?.into_response(&req);
+            return Ok(preview::secure(response, &path.name(), false));
+        }
+
+        let path = path.unlocked(tusk.config(), dav.data_key(&tusk)?);
+        preview::serve(&path, &req, false)
+    }
+
+    async fn head(tusk: Tusk, dav: DavPath, req: HttpRequest) -> TuskHttpResult {
+        DavResource::get(tusk, dav, req).await
+    }
+
+    async fn propfind(tusk: Tusk, dav: DavPath, req: HttpRequest, body: web::Bytes) -> TuskHttpResult {
+        let depth = Depth::from_request(&req, Depth::Infinity)?;
+        if depth == Depth::Infinity {
+            return Ok(precondition_error(StatusCode::FORBIDDEN, "propfind-finite-depth"));
+        }
+        let propfind = PropFind::from_body(&body)?;
+        let mut db = tusk.db()?;
+
+        let mut entries = Vec::new();
+        match &dav.path {
+            None => {
+                entries.push(DavEntry::root());
+                if depth == Depth::One {
+                    for root in [".public".to_owned(), dav.user.id().to_string()] {
+                        let path = PathInfo::authorize(tusk.config(), &mut db, &dav.user, root)?;
+                        if path.exists() { entries.push(DavEntry::from_path(&mut db, &path)?); }
+                    }
+                }
+            },
+            Some(path) => {
+                if !path.exists() { return TuskError::not_found().bail(); }
+                entries.push(DavEntry::from_path(&mut db, path)?);
+                if depth == Depth::One && path.is_directory() {
+                    for child in path.list_children()? {
+                        let request_path = format!("{}/{}", path.request_path(), child.filename());
+                        let locks = DavLock::locks_on(&mut db, &request_path, false)?;
+                        entries.push(DavEntry { request_path, info: Some(child), locks });
+                    }
+                }
+            }
+        }
+
+        let mut out = r#"<?xml version="1.0" encoding="utf-8"?><D:multistatus xmlns:D="DAV:">"#.to_owned();
+        for entry in entries {
+            entry.write_response(&mut out, &propfind);
+        }
+        out.push_str("</D:multistatus>");
+
+        Ok(multi_status(out))
+    }
+
+    async fn proppatch(dav: DavPath, body: web::Bytes) -> TuskHttpResult {
+        let path = dav.path.or_forbidden()?;
+        if !path.exists() { return TuskError::not_found().bail(); }
+        let body = std::str::from_utf8(&body)
+            .or_bad_request()?;
+        let root = XmlElement::parse(body)?;
+        if !root.is(DAV, "propertyupdate") { return TuskError::bad_request().bail(); }
+
+        // Properties are computed from the file system, hence they cannot be modified.
+        let mut properties = String::new();
+        for update in root.children().iter().filter(|update| update.is(DAV, "set") || update.is(DAV, "remove")) {
+            for property in update.child(DAV, "prop").map(|prop| prop.children()).unwrap_or_default() {
+                property.write_empty(&mut properties);
+            }
+        }
+
+        let is_collection = path.is_directory();
+        Ok(multi_status(format!(r#"<?xml version="1.0" encoding="utf-8"?><D:multistatus xmlns:D="DAV:"><D:response><D:href>{}</D:href><D:propstat><D:prop>{properties}</D:prop><D:status>HTTP/1.1 403 Forbidden</D:status></D:propstat></D:response></D:multistatus>"#,
+            escape(&href(&path.request_path(), is_collection)))))
+    }
+
+    async fn mkcol(tusk: Tusk, dav: DavPath, req: HttpRequest, body: web::Bytes) -> TuskHttpResult {
+        if !body.is_empty() { return TuskError::unsupported_media_type().bail(); }
+        let path = dav.path.or_method_not_allowed()?;
+        if path.exists() { return TuskError::method_not_allowed().bail(); }
+        let parent = path.parent()
+            .or_method_not_allowed()?;
+        if !parent.is_directory() { return TuskError::conflict().bail(); }
+
+        let mut db = tusk.db()?;
+        check_locks(&mut db, &dav.user, &req, &path.request_path(), false, true)?;
+        parent.create_dir(tusk.config(), &mut db, CreateDirectoryData::new(path.name()))?;
+
+        Ok(HttpResponse::Created().finish())
+    }
+
+    async fn put(tusk: Tusk, dav: DavPath, req: HttpRequest, mut payload: web::Payload) -> TuskHttpResult {
+        let key = if tusk.config().encrypt() { dav.data_key(&tusk)? } else { None };
+        let path = dav.path.or_method_not_allowed()?
+            .unlocked(tusk.config(), key);
+        if path.is_directory() { return TuskError::method_not_allowed().bail(); }
+        let parent = path.parent()
+            .or_method_not_allowed()?;
+        if !parent.is_directory() { return TuskError::conflict().bail(); }
+
+        check_locks(&mut *tusk.db()?, &dav.user, &req, &path.request_path(), false, !path.exists())?;
+
+        let mut file = tempfile::Builder::new()
+            .prefix(".tusk-")
+            .tempfile_in(&parent)?;
+        while let Some(chunk) = payload.next().await {
+            let chunk = chunk.or_bad_request()?;
+            file.write_all(&chunk)?;
+        }
+        file.flush()?;
+
+        if path.write_file(tusk.config(), &mut *tusk.db()?, file.into_temp_path())? {
+            Ok(HttpResponse::Created().finish())
+        } else {
+            Ok(HttpResponse::NoContent().finish())
+        }
+    }
+
+    async fn delete(tusk: Tusk, dav: DavPath, req: HttpRequest) -> TuskHttpResult {
+        let path = dav.path.or_forbidden()?;
+        if !path.exists() { return TuskError::not_found().bail(); }
+        let request_path = path.request_path();
+        let mut db = tusk.db()?;
+
+        check_locks(&mut db, &dav.user, &req, &request_path, true, true)?;
+        path.delete(tusk.config(), &mut db)?;
+        DavLock::delete_under(&mut db, &request_path)?;
+        purge_expired(tusk.config(), &mut db)?;
+
+        Ok(HttpResponse::NoContent().finish())
+    }
+
+    async fn copy(tusk: Tusk, dav: DavPath, req: HttpRequest) -> TuskHttpResult {
+        let depth = Depth::from_request(&req, Depth::Infinity)?;
+        if depth == Depth::One { return TuskError::bad_request().bail(); }
+        let source = dav.path.or_forbidden()?;
+        if !source.exists() { return TuskError::not_found().bail(); }
+        let mut db = tusk.db()?;
+        let destination = PathInfo::authorize(tusk.config(), &mut db, &dav.user, destination(&req)?)?;
+        let parent = destination.parent()
+            .or_forbidden()?;
+        if !parent.is_directory() { return TuskError::conflict().bail(); }
+        if source.request_path() == destination.request_path() { return TuskError::forbidden().bail(); }
+        let overwritten = destination.exists();
+        if overwritten && !overwrite(&req) {
+            return Ok(precondition_error(StatusCode::PRECONDITION_FAILED, "no-overwrite"));
+        }
+
+        check_locks(&mut db, &dav.user, &req, &destination.request_path(), true, true)?;
+        let failures = if depth == Depth::Zero && source.is_directory() {
+            if overwritten { destination.clone().delete(tusk.config(), &mut db)?; }
+            parent.create_dir(tusk.config(), &mut db, CreateDirectoryData::new(destination.name()))?;
+            Vec::new()
+        } else {
+            source.copy_to(tusk.config(), &mut db, destination, true)?.1
+        };
+
+        if !failures.is_empty() {
+            let mut out = r#"<?xml version="1.0" encoding="utf-8"?><D:multistatus xmlns:D="DAV:">"#.to_owned();
+            for failure in failures {
+                let status = StatusCode::from_u16(failure.status())
+                    .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                let _ = write!(out, "<D:response><D:href>{}</D:href><D:status>HTTP/1.1 {status}</D:status></D:response>",
+                    escape(&href(failure.path(), false)));
+            }
+            out.push_str("</D:multistatus>");
+            Ok(multi_status(out))
+        } else if overwritten {
+            Ok(HttpResponse::NoContent().finish())
+        } else {
+            Ok(HttpResponse::Created().finish())
+        }
+    }
+
+    async fn r#move(tusk: Tusk, dav: DavPath, req: HttpRequest) -> TuskHttpResult {
+        if Depth::from_request(&req, Depth::Infinity)? != Depth::Infinity {
+            return TuskError::bad_request().bail();
+        }
+        let source = dav.path.or_forbidden()?;
+        if !source.exists() { return TuskError::not_found().bail(); }
+        let mut db = tusk.db()?;
+        let destination = PathInfo::authorize(tusk.config(), &mut db, &dav.user, destination(&req)?)?;
+        let parent = destination.parent()
+            .or_forbidden()?;
+        if !parent.is_directory() { return TuskError::conflict().bail(); }
+        if source.request_path() == destination.request_path() { return TuskError::forbidden().bail(); }
+        let overwritten = destination.exists();
+        if overwritten && !overwrite(&req) {
+            return Ok(precondition_error(StatusCode::PRECONDITION_FAILED, "no-overwrite"));
+        }
+
+        let source_path = source.request_path();
+        check_locks(&mut db, &dav.user, &req, &source_path, true, true)?;
+        check_locks(&mut db, &dav.user, &req, &destination.request_path(), true, true)?;
+        source.move_to(tusk.config(), &mut db, destination, true)?;
+        DavLock::delete_under(&mut db, &source_path)?;
+
+        if overwritten {
+            Ok(HttpResponse::NoContent().finish())
+        } else {
+            Ok(HttpResponse::Created().finish())
+        }
+    }
+
+    async fn lock(tusk: Tusk, dav: DavPath, req: HttpRequest, body: web::Bytes) -> TuskHttpResult {
+        let path = dav.path.or_method_not_allowed()?;
+        let request_path = path.request_path();
+        let timeout = lock_timeout(&req);
+        let mut db = tusk.db()?;
+        let body = std::str::from_utf8(&body)
+            .or_bad_request()?;
+
+        // A request without body refreshes an existing lock.
+        if body.trim().is_empty() {
+            let tokens = submitted_tokens(&req);
+            let mut lock = DavLock::locks_on(&mut db, &request_path, false)?
+                .into_iter()
+                .find(|lock| lock.user_id() == dav.user.id() && tokens.contains(&lock.token()))
+                .ok_or_else(TuskError::precondition_failed)?;
+            lock.refresh(&mut db, timeout)?;
+
+            let mut out = r#"<?xml version="1.0" encoding="utf-8"?><D:prop xmlns:D="DAV:"><D:lockdiscovery>"#.to_owned();
+            write_active_lock(&mut out, &lock);
+            out.push_str("</D:lockdiscovery></D:prop>");
+            return Ok(HttpResponse::Ok()
+                .content_type("application/xml; charset=utf-8")
+                .body(out));
+        }
+
+        let lock_info = XmlElement::parse(body)?;
+        if !lock_info.is(DAV, "lockinfo") { return TuskError::bad_request().bail(); }
+        let exclusive = match lock_info.child(DAV, "lockscope") {
+            Some(scope) if scope.child(DAV, "exclusive").is_some() => true,
+            Some(scope) if scope.child(DAV, "shared").is_some() => false,
+            _ => return TuskError::bad_request().bail()
+        };
+        match lock_info.child(DAV, "locktype") {
+            Some(kind) if kind.child(DAV, "write").is_some() => {},
+            _ => return TuskError::bad_request().bail()
+        }
+        let owner = lock_info.child(DAV, "owner")
+            .map(|owner| {
+                let mut out = String::new();
+                owner.write_contents(&mut out);
+                out
+            });
+        let deep = match Depth::from_request(&req, Depth::Infinity)? {
+            Depth::Zero => false,
+            Depth::Infinity => true,
+            Depth::One => return TuskError::bad_request().bail()
+        };
+
+        // Locks reserve the resource for writing, hence they require write access.
+        path.check_writable()?;
+        let created = !path.exists();
+        if created {
+            let parent = path.parent()
+                .or_conflict()?;
+            if !parent.is_directory() { return TuskError::conflict().bail(); }
+        }
+        let lock = DavLock::create(&mut db, dav.user.id(), &request_path, deep, exclusive, owner.as_deref(), timeout)?;
+        if created {
+            // Locking an unmapped URL creates an empty resource.
+            if let Err(e) = std::fs::File::options().write(true).create_new(true).open(&path) {
+                lock.delete(&mut db)?;
+                return Err(e.into());
+            }
+        }
+
+        let mut out = r#"<?xml version="1.0" encoding="utf-8"?><D:prop xmlns:D="DAV:"><D:lockdiscovery>"#.to_owned();
+        write_active_lock(&mut out, &lock);
+        out.push_str("</D:lockdiscovery></D:prop>");
+        let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+        Ok(HttpResponse::build(status)
+            .insert_header(("Lock-Token", format!("<opaquelocktoken:{}>", lock.token())))
+            .content_type("application/xml; charset=utf-8")
+            .body(out))
+    }
+
+    async fn unlock(tusk: Tusk, dav: DavPath, req: HttpRequest) -> TuskHttpResult {
+        let path = dav.path.or_method_not_allowed()?;
+        let token = req.headers().get("Lock-Token")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().strip_prefix("<opaquelocktoken:"))
+            .and_then(|value| value.strip_suffix('>'))
+            .and_then(|value| Uuid::parse_str(value).ok())
+            .or_bad_request()?;
+        let mut db = tusk.db()?;
+
+        let lock = DavLock::locks_on(&mut db, path.request_path(), false)?
+            .into_iter()
+            .find(|lock| lock.token() == token);
+        match lock {
+            Some(lock) if lock.user_id() == dav.user.id() => {
+                lock.delete(&mut db)?;
+                Ok(HttpResponse::NoContent().finish())
+            },
+            Some(_) => TuskError::forbidden().bail(),
+            None => Ok(precondition_error(StatusCode::CONFLICT, "lock-token-matches-request-uri"))
+        }
+    }
+}
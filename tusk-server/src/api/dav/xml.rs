@@ -0,0 +1,314 @@
+//! Contains a minimal XML reader and writer, sufficient to handle WebDAV request and
+//! response bodies.
+
+use std::fmt::Write;
+use tusk_core::error::{TuskError, TuskResult};
+
+/// The WebDAV namespace.
+pub const DAV: &str = "DAV:";
+
+/// Escapes the characters that have a special meaning in XML.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+/// Replaces the predefined entities and the character references with the respective characters.
+fn unescape(text: &str) -> TuskResult<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        let end = match rest[start..].find(';') {
+            Some(end) => start + end,
+            None => return TuskError::bad_request().bail()
+        };
+        let c = match &rest[start + 1..end] {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            reference => {
+                let code = if let Some(hex) = reference.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = reference.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+                match code.and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => return TuskError::bad_request().bail()
+                }
+            }
+        };
+        unescaped.push(c);
+        rest = &rest[end + 1..];
+    }
+    unescaped.push_str(rest);
+    Ok(unescaped)
+}
+
+/// Element being parsed, together with its qualified name and its namespace declarations.
+type OpenElement = (XmlElement, String, Vec<(String, String)>);
+
+/// Represents an XML element with its namespace resolved.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct XmlElement {
+    namespace: String,
+    name: String,
+    children: Vec<XmlElement>,
+    text: String
+}
+impl XmlElement {
+    /// Parses the given document and returns its root element.
+    ///
+    /// Processing instructions, comments and attributes other than namespace declarations
+    /// are ignored.
+    ///
+    /// # Errors
+    /// If the document is not well-formed or contains a document type declaration, this function
+    /// returns an HTTP error 400 `BAD REQUEST`.
+    pub fn parse(document: &str) -> TuskResult<XmlElement> {
+        let mut stack: Vec<OpenElement> = Vec::new();
+        let mut root = None;
+        let mut rest = document;
+
+        while !rest.is_empty() {
+            let Some(start) = rest.find('<') else {
+                if let Some((element, _, _)) = stack.last_mut() {
+                    element.text.push_str(&unescape(rest)?);
+                } else if !rest.trim().is_empty() {
+                    return TuskError::bad_request().bail();
+                }
+                break;
+            };
+            if let Some((element, _, _)) = stack.last_mut() {
+                element.text.push_str(&unescape(&rest[..start])?);
+            } else if !rest[..start].trim().is_empty() {
+                return TuskError::bad_request().bail();
+            }
+            rest = &rest[start..];
+
+            if rest.starts_with("<?") {
+                rest = Self::skip(rest, "?>")?;
+            } else if rest.starts_with("<!--") {
+                rest = Self::skip(rest, "-->")?;
+            } else if rest.starts_with("<![CDATA[") {
+                let end = rest.find("]]>")
+                    .ok_or_else(TuskError::bad_request)?;
+                match stack.last_mut() {
+                    Some((element, _, _)) => element.text.push_str(&rest[9..end]),
+                    None => return TuskError::bad_request().bail()
+                }
+                rest = &rest[end + 3..];
+            } else if rest.starts_with("<!") {
+                // Document type declarations are refused to avoid entity expansion attacks.
+                return TuskError::bad_request().bail();
+            } else if let Some(tag) = rest.strip_prefix("</") {
+                let end = tag.find('>')
+                    .ok_or_else(TuskError::bad_request)?;
+                let qualified_name = tag[..end].trim();
+                let (element, open_name, _) = stack.pop()
+                    .ok_or_else(TuskError::bad_request)?;
+                if open_name != qualified_name { return TuskError::bad_request().bail(); }
+                match stack.last_mut() {
+                    Some((parent, _, _)) => parent.children.push(element),
+                    None if root.is_none() => root = Some(element),
+                    None => return TuskError::bad_request().bail()
+                }
+                rest = &tag[end + 1..];
+            } else {
+                let end = Self::tag_end(rest)?;
+                let tag = &rest[1..end];
+                let (tag, self_closing) = match tag.strip_suffix('/') {
+                    Some(tag) => (tag, true),
+                    None => (tag, false)
+                };
+                let (qualified_name, attributes) = match tag.find(char::is_whitespace) {
+                    Some(index) => (&tag[..index], &tag[index..]),
+                    None => (tag, "")
+                };
+                let declarations = Self::namespace_declarations(attributes)?;
+                let (prefix, name) = qualified_name.split_once(':')
+                    .unwrap_or(("", qualified_name));
+                let namespace = declarations.iter()
+                    .rev()
+                    .chain(stack.iter().rev().flat_map(|(_, _, declarations)| declarations.iter().rev()))
+                    .find(|(declared, _)| declared == prefix)
+                    .map(|(_, namespace)| namespace.clone());
+                let namespace = match namespace {
+                    Some(namespace) => namespace,
+                    None if prefix.is_empty() => String::new(),
+                    None => return TuskError::bad_request().bail()
+                };
+                let element = XmlElement {
+                    namespace,
+                    name: name.to_owned(),
+                    children: Vec::new(),
+                    text: String::new()
+                };
+
+                if self_closing {
+                    match stack.last_mut() {
+                        Some((parent, _, _)) => parent.children.push(element),
+                        None if root.is_none() => root = Some(element),
+                        None => return TuskError::bad_request().bail()
+                    }
+                } else {
+                    if stack.is_empty() && root.is_some() { return TuskError::bad_request().bail(); }
+                    stack.push((element, qualified_name.to_owned(), declarations));
+                }
+                rest = &rest[end + 1..];
+            }
+        }
+
+        match (root, stack.is_empty()) {
+            (Some(root), true) => Ok(root),
+            _ => TuskError::bad_request().bail()
+        }
+    }
+
+    /// Skips the given document up to and including the `end` delimiter.
+    fn skip<'a>(document: &'a str, end: &str) -> TuskResult<&'a str> {
+        match document.find(end) {
+            Some(index) => Ok(&document[index + end.len()..]),
+            None => TuskError::bad_request().bail()
+        }
+    }
+
+    /// Finds the index of the `>` character closing the tag at the beginning of the document,
+    /// skipping quoted attribute values.
+    fn tag_end(document: &str) -> TuskResult<usize> {
+        let mut quote = None;
+        for (index, c) in document.char_indices() {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(q), c) if q == c => quote = None,
+                (None, '>') => return Ok(index),
+                _ => {}
+            }
+        }
+        TuskError::bad_request().bail()
+    }
+
+    /// Returns the list of prefixes and namespaces declared in the given attributes.
+    fn namespace_declarations(mut attributes: &str) -> TuskResult<Vec<(String, String)>> {
+        let mut declarations = Vec::new();
+        loop {
+            attributes = attributes.trim_start();
+            if attributes.is_empty() { return Ok(declarations); }
+
+            let (name, rest) = attributes.split_once('=')
+                .ok_or_else(TuskError::bad_request)?;
+            let rest = rest.trim_start();
+            let quote = rest.chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'')
+                .ok_or_else(TuskError::bad_request)?;
+            let end = rest[1..].find(quote)
+                .ok_or_else(TuskError::bad_request)?;
+            let value = unescape(&rest[1..end + 1])?;
+
+            match name.trim() {
+                "xmlns" => declarations.push((String::new(), value)),
+                name => if let Some(prefix) = name.strip_prefix("xmlns:") {
+                    declarations.push((prefix.to_owned(), value));
+                }
+            }
+            attributes = &rest[end + 2..];
+        }
+    }
+
+    /// Returns the namespace of the element.
+    pub fn namespace(&self) -> &str { &self.namespace }
+    /// Returns the local name of the element.
+    pub fn name(&self) -> &str { &self.name }
+    /// Returns the child elements of the element.
+    pub fn children(&self) -> &[XmlElement] { &self.children }
+    /// Returns `true` if the element has the given namespace and local name.
+    pub fn is(&self, namespace: &str, name: &str) -> bool {
+        self.namespace == namespace && self.name == name
+    }
+    /// Returns the first child element with the given namespace and local name, if any.
+    pub fn child(&self, namespace: &str, name: &str) -> Option<&XmlElement> {
+        self.children.iter()
+            .find(|child| child.is(namespace, name))
+    }
+
+    /// Writes the element as an empty element, declaring its namespace if needed.
+    pub fn write_empty(&self, out: &mut String) {
+        if self.namespace == DAV {
+            let _ = write!(out, "<D:{}/>", self.name);
+        } else {
+            let _ = write!(out, "<{} xmlns=\"{}\"/>", self.name, escape(&self.namespace));
+        }
+    }
+    /// Writes the contents of the element, that is, its text and its children.
+    pub fn write_contents(&self, out: &mut String) {
+        out.push_str(&escape(self.text.trim()));
+        for child in &self.children {
+            if child.namespace == DAV {
+                let _ = write!(out, "<D:{}>", child.name);
+                child.write_contents(out);
+                let _ = write!(out, "</D:{}>", child.name);
+            } else {
+                let _ = write!(out, "<{} xmlns=\"{}\">", child.name, escape(&child.namespace));
+                child.write_contents(out);
+                let _ = write!(out, "</{}>", child.name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::dav::xml::{DAV, XmlElement};
+
+    #[test]
+    fn parse_propfind() {
+        let document = r#"<?xml version="1.0" encoding="utf-8" ?>
+            <D:propfind xmlns:D="DAV:">
+                <D:prop xmlns:R="http://example.com/ns/">
+                    <D:getlastmodified/>
+                    <R:author />
+                </D:prop>
+            </D:propfind>"#;
+        let root = XmlElement::parse(document).expect("Valid XML");
+
+        assert!(root.is(DAV, "propfind"));
+        let prop = root.child(DAV, "prop").expect("Element");
+        assert_eq!(prop.children().len(), 2);
+        assert!(prop.children()[0].is(DAV, "getlastmodified"));
+        assert!(prop.children()[1].is("http://example.com/ns/", "author"));
+    }
+
+    #[test]
+    fn write_owner() {
+        let document = r#"<lockinfo xmlns='DAV:'><owner><href>mailto:a&amp;b@localhost</href></owner></lockinfo>"#;
+        let root = XmlElement::parse(document).expect("Valid XML");
+        let mut out = String::new();
+        root.child(DAV, "owner").expect("Element").write_contents(&mut out);
+
+        assert_eq!(out, "<D:href>mailto:a&amp;b@localhost</D:href>");
+    }
+
+    #[test]
+    fn refuse_malformed_documents() {
+        assert!(XmlElement::parse("<D:propfind xmlns:D=\"DAV:\">").is_err());
+        assert!(XmlElement::parse("<a></b>").is_err());
+        assert!(XmlElement::parse("<x:a/>").is_err());
+        assert!(XmlElement::parse("<!DOCTYPE a [<!ENTITY b \"c\">]><a>&b;</a>").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Contains the CRUD structures relative to the `/storage/deduplication` REST resource.
+//!
+//! When the `deduplicate` option of the `tusk.serve` section of `tusk.toml` is set, the contents
+//! of the files written through the `/storage`, `/uploads`, `/storage-versions` and `/dav`
+//! resources are stored once, in a blob named after their SHA-256 hash, which every file with the
+//! same contents shares.
+//! The files referring to each blob are recorded in the database by their path, and a blob is
+//! removed as soon as the last file referring to it is deleted, moved to the trash or replaced.
+//!
+//! If the filesystem supports cloning files, as Btrfs and XFS do, every file is a copy-on-write
+//! clone of its blob: clones are separate files, hence they keep their own timestamps and their
+//! own attributes.
+//! On any other filesystem, every file is a hard link to its blob, hence the files sharing a blob
+//! share their timestamps as well; for this reason, a file is only linked to an existing blob if
+//! it has the same creation and last modification times, as re-uploaded files do, and it is stored
+//! separately otherwise.
+//! In both cases, files are always replaced as a whole and never modified in place, so that
+//! writing a file never affects the others.
+//! Copies, extracted archives and items restored from the trash are stored separately, and so are
+//! encrypted files, whose contents differ even when the plaintexts are the same.
+//!
+//! The blobs must be on the same filesystem as the user directories and the uploads; otherwise,
+//! the contents are not deduplicated.
+//!
+//! # Security
+//! ## Access
+//! Any authenticated user can read the statistics of the blobs, which only contain the number of
+//! the blobs and of the files referring to them, together with their total size and the number of
+//! bytes saved.
+
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use actix_web::HttpResponse;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use tusk_core::config::{Tusk, TuskConfiguration};
+use tusk_core::error::{TuskHttpResult, TuskResult};
+use tusk_core::PgConnection;
+use tusk_core::resources::{Blob, BlobStats};
+use tusk_derive::rest_resource;
+use crate::api::storage::times;
+
+/// Prefix of the paths reserving the blobs for the files being written, which cannot be in any
+/// storage.
+const RESERVATION_ROOT: &str = ".tusk-reserved";
+
+/// Whether the filesystem of the blobs supports cloning files, checked on first use.
+static CLONING_SUPPORTED: OnceLock<bool> = OnceLock::new();
+
+/// Returns `true` if the filesystem of the blobs supports cloning files, and `false` if the files
+/// are hard links to their blobs instead.
+fn cloning_supported(config: &TuskConfiguration) -> bool {
+    *CLONING_SUPPORTED.get_or_init(|| {
+        let directory = config.blobs_directory();
+        let result = std::fs::create_dir_all(&directory)
+            .and_then(|()| tempfile::NamedTempFile::new_in(&directory))
+            .and_then(|mut from| {
+                from.write_all(b"tusk")?;
+                let to = tempfile::NamedTempFile::new_in(&directory)?;
+                clone_contents(from.path(), to.path())
+            });
+        if let Err(e) = &result {
+            log::info!("Deduplicated files are hard links, as `{}` does not support cloning files: {e}", directory.display());
+        }
+        result.is_ok()
+    })
+}
+
+/// Returns the hexadecimal SHA-256 hash and the size of the file at `path`.
+fn hash_file(path: &Path) -> std::io::Result<(String, u64)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let size = std::io::copy(&mut file, &mut hasher)?;
+    let hash = hasher.finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    Ok((hash, size))
+}
+
+/// Replaces the contents of the file at `to` with a copy-on-write clone of the contents of the file
+/// at `from`; the file at `to` keeps its timestamps and attributes.
+#[cfg(target_os = "linux")]
+fn clone_contents(from: &Path, to: &Path) -> std::io::Result<()> {
+    use std::fs::{FileTimes, OpenOptions};
+    use std::os::fd::AsRawFd;
+    nix::ioctl_write_int!(ficlone, 0x94, 9);
+
+    let source = File::open(from)?;
+    let target = OpenOptions::new().write(true).open(to)?;
+    let metadata = target.metadata()?;
+    // SAFETY: both file descriptors stay open for the whole call.
+    unsafe { ficlone(target.as_raw_fd(), source.as_raw_fd() as _) }?;
+    target.set_times(FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?))
+}
+/// Replaces the contents of the file at `to` with a copy-on-write clone of the contents of the file
+/// at `from`, which is not supported on this platform.
+#[cfg(not(target_os = "linux"))]
+fn clone_contents(_from: &Path, _to: &Path) -> std::io::Result<()> {
+    Err(ErrorKind::Unsupported.into())
+}
+
+/// Returns `true` if the files at `a` and `b` have the same creation and last modification times,
+/// so that they can be hard links to the same blob without changing the times of either of them.
+fn same_times(a: &Path, b: &Path) -> std::io::Result<bool> {
+    let (a_metadata, b_metadata) = (a.metadata()?, b.metadata()?);
+    Ok(a_metadata.modified()? == b_metadata.modified()?
+        && times::created(a, &a_metadata)? == times::created(b, &b_metadata)?)
+}
+
+/// Replaces the file at `to` with a hard link to the file at `from`, atomically.
+fn link_contents(from: &Path, to: &Path) -> std::io::Result<()> {
+    let linked = to.with_file_name(format!(".tusk-{}", Uuid::new_v4()));
+    std::fs::hard_link(from, &linked)?;
+    let result = std::fs::rename(&linked, to);
+    if result.is_err() { let _ = std::fs::remove_file(&linked); }
+    result
+}
+
+/// Stores the contents of the file at `file` as the contents of the blob at `blob_file`,
+/// atomically: the blob is a clone of the file if cloning is supported, and a hard link to the file
+/// otherwise.
+///
+/// If the blob is already stored, this function fails with [`ErrorKind::AlreadyExists`].
+fn store_blob(config: &TuskConfiguration, file: &Path, blob_file: &Path) -> std::io::Result<()> {
+    if !cloning_supported(config) { return std::fs::hard_link(file, blob_file); }
+
+    let stored = blob_file.with_file_name(format!(".tusk-{}", Uuid::new_v4()));
+    File::create_new(&stored)?;
+    let result = clone_contents(file, &stored)
+        .and_then(|()| std::fs::hard_link(&stored, blob_file));
+    let _ = std::fs::remove_file(&stored);
+    result
+}
+
+/// Makes the file at `file` share the contents of the blob stored at `blob_file`, which has the
+/// same contents, and returns `true`, or returns `false` if the file has to be stored separately,
+/// as described in the [module documentation](self).
+fn share_blob(config: &TuskConfiguration, blob_file: &Path, file: &Path) -> std::io::Result<bool> {
+    if cloning_supported(config) {
+        clone_contents(blob_file, file)?;
+    } else if same_times(blob_file, file)? {
+        link_contents(blob_file, file)?;
+    } else {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Removes the contents of the given blobs, which are no longer referred to by any file.
+fn remove_orphans(config: &TuskConfiguration, orphans: Vec<Blob>) -> TuskResult<()> {
+    for blob in orphans {
+        log::info!("Removing blob `{}`", blob.hash());
+        match std::fs::remove_file(blob.file(config)) {
+            Ok(()) => {},
+            Err(e) if e.kind() == ErrorKind::NotFound => {},
+            Err(e) => return Err(e.into())
+        }
+    }
+    Ok(())
+}
+
+/// Represents a blob returned by [`deduplicate`], reserved for the file being written until the
+/// file refers to it.
+#[derive(Clone, Debug)]
+pub struct Reservation {
+    blob: Blob,
+    path: String
+}
+
+/// Stores the contents of the temporary file at `file` in the corresponding blob, if deduplication
+/// is enabled, so that `file` shares the storage of the blob; the blob is created if the contents
+/// are not stored yet.
+///
+/// Returns the reservation of the blob, which must then be referred to by the path to which the
+/// file is moved, or forgotten, or `None` if the contents have not been deduplicated.
+pub fn deduplicate(config: &TuskConfiguration, db: &mut PgConnection, file: &Path) -> TuskResult<Option<Reservation>> {
+    if !config.deduplicate() { return Ok(None); }
+
+    let (hash, size) = hash_file(file)?;
+    let path = format!("{RESERVATION_ROOT}/{}", Uuid::new_v4());
+    let reservation = Reservation { blob: Blob::create(db, hash, size, &path)?, path };
+    let blob_file = reservation.blob.file(config);
+    let result = blob_file.parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| match store_blob(config, file, &blob_file) {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => share_blob(config, &blob_file, file),
+            result => result.map(|()| true)
+        });
+
+    match result {
+        Ok(true) => Ok(Some(reservation)),
+        Ok(false) => {
+            forget(config, db, reservation)?;
+            Ok(None)
+        },
+        Err(e) => {
+            log::warn!("Cannot deduplicate the contents of `{}`: {e}", file.display());
+            forget(config, db, reservation)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Releases the given reservation, returned by [`deduplicate`], removing the blob if no file refers
+/// to it, e.g. because the file could not be moved to its destination.
+pub fn forget(config: &TuskConfiguration, db: &mut PgConnection, reservation: Reservation) -> TuskResult<()> {
+    release(config, db, &reservation.path)
+}
+
+/// Records that the file at `request_path` refers to the blob of the given reservation, returned
+/// by [`deduplicate`], or that it refers to no blob if `reservation` is `None`, and removes the
+/// blobs that are no longer referred to.
+pub fn refer(config: &TuskConfiguration, db: &mut PgConnection, request_path: &str, reservation: Option<Reservation>) -> TuskResult<()> {
+    let orphans = match reservation {
+        Some(reservation) => {
+            // The file refers to the blob before the reservation is released, so that the blob is
+            // never left without references.
+            let mut orphans = reservation.blob.refer(db, request_path)?;
+            orphans.extend(Blob::release(db, &reservation.path)?);
+            orphans
+        },
+        None => Blob::release(db, request_path)?
+    };
+    remove_orphans(config, orphans)
+}
+
+/// Records that the item at `request_path`, together with its contents, no longer refers to any
+/// blob, and removes the blobs that are no longer referred to.
+pub fn release(config: &TuskConfiguration, db: &mut PgConnection, request_path: &str) -> TuskResult<()> {
+    let orphans = Blob::release(db, request_path)?;
+    remove_orphans(config, orphans)
+}
+
+/// Represents the CRUD **Read** structure relative to the `/storage/deduplication` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct DeduplicationRead {
+    enabled: bool,
+    blobs: u64,
+    references: u64,
+    stored_size: u64,
+    logical_size: u64,
+    saved: u64
+}
+impl DeduplicationRead {
+    /// Creates the structure from the statistics of the blobs.
+    pub fn new(enabled: bool, stats: BlobStats) -> DeduplicationRead {
+        DeduplicationRead {
+            enabled,
+            blobs: stats.blobs(),
+            references: stats.references(),
+            stored_size: stats.stored_size(),
+            logical_size: stats.logical_size(),
+            saved: stats.saved()
+        }
+    }
+}
+
+/// Represents the `/storage/deduplication` REST resource.
+///
+/// The `/storage/deduplication` resource is responsible for reporting how many bytes are saved by
+/// storing the contents of the files once.
+pub struct StorageDeduplicationResource;
+#[rest_resource("/storage/deduplication")]
+impl StorageDeduplicationResource {
+    async fn get(tusk: Tusk) -> TuskHttpResult {
+        tusk.authenticate()?;
+        let stats = BlobStats::compute(&mut *tusk.db()?)?;
+
+        Ok(HttpResponse::Ok().json(DeduplicationRead::new(tusk.config().deduplicate(), stats)))
+    }
+}
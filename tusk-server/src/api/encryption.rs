@@ -0,0 +1,241 @@
+//! Contains the CRUD structures relative to the `/storage/encryption` REST resource.
+//!
+//! When the `encrypt` option of the `tusk.serve` section of `tusk.toml` is set, the files written
+//! in the root of each user through the `/storage`, `/uploads`, `/storage-versions` and `/dav`
+//! resources are encrypted at rest with the data key of the user owning the root, whoever writes
+//! them, as described in the [`encryption`](tusk_core::encryption) module of `tusk-core`.
+//!
+//! The data key of a user is generated when the user first logs in, and is stored wrapped with
+//! a key derived from the password of the user; hence, it is only available while the user has an
+//! active session or, through WebDAV, when the user gives the credentials with HTTP Basic
+//! authentication.
+//! Data keys are kept in the memory of the server, so that they are lost when the server restarts
+//! and users have to log in again to read their encrypted files.
+//!
+//! Encrypted files are decrypted when they are downloaded, range requests included, or archived,
+//! and their size is reported as the size of their plain contents.
+//! Files written before encryption was enabled, as well as the files in the public root and in the
+//! team folders, are stored unencrypted; only the files in the user roots are ever considered
+//! encrypted, whatever the contents of the other files.
+//! Items copied or moved to another root are decrypted with the data key of the source root and
+//! encrypted with the data key of the destination root, if it encrypts its files; archives
+//! extracted in a user root are encrypted as well, and restored versions are encrypted again.
+//! Items restored from the trash return to the root they were deleted from, hence they keep their
+//! contents as they are.
+//!
+//! # Security
+//! ## Access
+//! Encrypted files can be read by the user who owns them, while the data key of the user is
+//! available, and by the users to whom the owner granted access to them, while the owner has an
+//! active session; any other attempt, including the downloads through share links, results in
+//! `LOCKED`.
+//! Similarly, writing a file in the root of a user while the data key of the owner is not
+//! available results in `LOCKED`, so that no file is stored unencrypted by mistake.
+//!
+//! Thumbnails of encrypted files are not generated, since they would be cached unencrypted, and
+//! result in `UNSUPPORTED MEDIA TYPE`; similarly, encrypted files are not indexed by the full-text
+//! search.
+//!
+//! ## Passwords
+//! When a user changes the password, the data key is wrapped with the new password.
+//! When the password is reset, instead, the data key cannot be recovered: it is deleted, and the
+//! files encrypted with it cannot be decrypted anymore; hence, users with a data key have to
+//! confirm the reset with `discard_encrypted_files`, or it results in `CONFLICT`.
+//!
+//! Data keys recovered with the credentials given through HTTP Basic authentication are kept in
+//! memory for as long as the keys of the sessions, identified by a salted digest of the
+//! credentials.
+//!
+//! ## Status
+//! Any authenticated user can read whether encryption is enabled and whether the data key of the
+//! user is currently available, by `GET`ting the `/storage/encryption` resource.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom};
+use std::path::Path;
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::http::header::{ContentRangeSpec, Range};
+use secrecy::Secret;
+use serde::Serialize;
+use uuid::Uuid;
+use tusk_core::config::{Tusk, TuskConfiguration};
+use tusk_core::encryption::{DataKey, Decryptor, encrypt, Header};
+use tusk_core::error::{TuskError, TuskHttpResult, TuskResult};
+use tusk_core::PgConnection;
+use tusk_core::resources::UserKey;
+use tusk_core::storage::reader_stream;
+use tusk_derive::rest_resource;
+
+/// Returns `true` if the file at `path` is encrypted.
+pub fn is_encrypted(path: &Path) -> bool {
+    matches!(Header::of(path), Ok(Some(_)))
+}
+
+/// Recovers the data key of the given user with the password of the user.
+///
+/// If encryption is enabled and the user has no data key yet, a new one is generated; if
+/// encryption is disabled, the data key, if any, is still recovered, so that the files encrypted
+/// in the meantime can be read.
+///
+/// Returns `None` if the user has no data key or if it cannot be recovered.
+pub fn recover(config: &TuskConfiguration, db: &mut PgConnection, user_id: Uuid, password: &Secret<String>) -> TuskResult<Option<DataKey>> {
+    let key = if config.encrypt() {
+        UserKey::unlock(db, user_id, password)?
+    } else {
+        UserKey::from_user(db, user_id)?
+            .and_then(|user_key| user_key.unwrap_with(password))
+    };
+    if key.is_none() && UserKey::from_user(db, user_id)?.is_some() {
+        log::warn!("Cannot recover the data key of user `{user_id}`");
+    }
+
+    Ok(key)
+}
+
+/// Writes into `to` the contents of the file at `from`, encrypted with the data key `key` of the
+/// user with ID `owner`.
+pub fn encrypt_file(key: &DataKey, owner: Uuid, from: &Path, to: &mut File) -> TuskResult<()> {
+    let file = File::open(from)?;
+    let size = file.metadata()?.len();
+    encrypt(key, owner, size, BufReader::new(file), BufWriter::new(to))?;
+    Ok(())
+}
+
+/// Data key with which the files of a user root are decrypted.
+#[derive(Clone, Debug)]
+pub struct Decryption {
+    owner: Option<Uuid>,
+    key: Option<DataKey>
+}
+impl Decryption {
+    /// Creates the decryption of the files in the root of the given user, whose data key is `key`,
+    /// if available.
+    pub fn new(owner: Uuid, key: Option<DataKey>) -> Decryption {
+        Decryption { owner: Some(owner), key }
+    }
+    /// Creates the decryption of the files outside of any user root, which are never encrypted.
+    pub fn plain() -> Decryption {
+        Decryption { owner: None, key: None }
+    }
+
+    /// Opens the file at `path` for decryption, and returns `None` if the file is not encrypted.
+    ///
+    /// Files outside of the user roots are never considered encrypted, whatever their contents.
+    ///
+    /// # Errors
+    /// If the file is encrypted with the data key of another user, or if the data key is not
+    /// available, this function returns an HTTP error 423 `LOCKED`.
+    pub fn open(&self, path: &Path) -> TuskResult<Option<Decryptor<File>>> {
+        let Some(owner) = self.owner else { return Ok(None); };
+        let mut file = File::open(path)?;
+        let Some(header) = Header::read(&mut file)? else { return Ok(None); };
+        let Some(key) = self.key.as_ref().filter(|_| header.owner() == owner) else {
+            return TuskError::locked().bail();
+        };
+
+        Ok(Some(Decryptor::new(key, file)?))
+    }
+}
+
+/// Returns the response to a download of the decrypted contents of the file with the given name,
+/// honoring the `Range` header of the request, if any.
+///
+/// Only single ranges are honored; requests for multiple ranges are served the whole contents.
+pub fn respond(mut decryptor: Decryptor<File>, name: &str, req: &HttpRequest) -> TuskHttpResult {
+    let size = decryptor.header().size();
+    let extension = name.rsplit_once('.').map_or("", |(_, extension)| extension);
+    let mut response = HttpResponse::Ok();
+    response.content_type(actix_files::file_extension_to_mime(extension))
+        .insert_header((header::ACCEPT_RANGES, "bytes"));
+
+    let (start, length) = match req.get_header::<Range>() {
+        Some(Range::Bytes(specs)) if specs.len() == 1 => match specs[0].to_satisfiable_range(size) {
+            Some((start, end)) => {
+                response.status(StatusCode::PARTIAL_CONTENT)
+                    .insert_header(header::ContentRange(ContentRangeSpec::Bytes { range: Some((start, end)), instance_length: Some(size) }));
+                (start, end - start + 1)
+            },
+            None => return Ok(HttpResponse::RangeNotSatisfiable()
+                .insert_header(header::ContentRange(ContentRangeSpec::Bytes { range: None, instance_length: Some(size) }))
+                .finish())
+        },
+        _ => (0, size)
+    };
+    decryptor.seek(SeekFrom::Start(start))?;
+
+    Ok(response.no_chunking(length)
+        .streaming(reader_stream(decryptor.take(length))))
+}
+
+/// Represents the CRUD **Read** structure relative to the `/storage/encryption` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct EncryptionRead {
+    enabled: bool,
+    unlocked: bool
+}
+
+/// Represents the `/storage/encryption` REST resource.
+///
+/// The `/storage/encryption` resource is responsible for reporting whether the files of the user
+/// are encrypted and whether they can currently be decrypted.
+pub struct StorageEncryptionResource;
+#[rest_resource("/storage/encryption")]
+impl StorageEncryptionResource {
+    async fn get(tusk: Tusk) -> TuskHttpResult {
+        tusk.authenticate()?;
+
+        Ok(HttpResponse::Ok().json(EncryptionRead {
+            enabled: tusk.config().encrypt(),
+            unlocked: tusk.data_key().is_some()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use actix_web::http::header::{CONTENT_RANGE, RANGE};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use actix_web::ResponseError;
+    use uuid::Uuid;
+    use tusk_core::encryption::DataKey;
+    use crate::api::encryption::{Decryption, encrypt_file, respond};
+
+    #[actix_web::test]
+    async fn test_respond() {
+        let root = tempfile::tempdir().unwrap();
+        let plain = root.path().join("notes.txt");
+        let contents: Vec<u8> = (0..100_000u32).map(|i| (i % 97) as u8).collect();
+        std::fs::File::create(&plain).unwrap().write_all(&contents).unwrap();
+        let (owner, key) = (Uuid::new_v4(), DataKey::generate());
+        let encrypted = root.path().join("notes.enc.txt");
+        encrypt_file(&key, owner, &plain, &mut std::fs::File::create(&encrypted).unwrap()).unwrap();
+
+        assert!(Decryption::new(owner, Some(key.clone())).open(&plain).unwrap().is_none());
+        let locked = Decryption::new(Uuid::new_v4(), Some(key.clone())).open(&encrypted).unwrap_err();
+        assert_eq!(locked.status_code(), StatusCode::LOCKED);
+        let locked = Decryption::new(owner, None).open(&encrypted).unwrap_err();
+        assert_eq!(locked.status_code(), StatusCode::LOCKED);
+
+        let decryption = Decryption::new(owner, Some(key));
+        let req = TestRequest::get().to_http_request();
+        let response = respond(decryption.open(&encrypted).unwrap().unwrap(), "notes.txt", &req).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body.as_ref(), contents.as_slice());
+
+        let req = TestRequest::get().insert_header((RANGE, "bytes=65530-65545")).to_http_request();
+        let response = respond(decryption.open(&encrypted).unwrap().unwrap(), "notes.txt", &req).unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get(CONTENT_RANGE).unwrap(), "bytes 65530-65545/100000");
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body.as_ref(), &contents[65530..=65545]);
+
+        let req = TestRequest::get().insert_header((RANGE, "bytes=200000-")).to_http_request();
+        let response = respond(decryption.open(&encrypted).unwrap().unwrap(), "notes.txt", &req).unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers().get(CONTENT_RANGE).unwrap(), "bytes */100000");
+    }
+}
@@ -0,0 +1,358 @@
+//! Contains the CRUD structures relative to the `/storage-events` REST resource.
+//!
+//! The changes to the items of the storages are streamed to the client as
+//! [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html).
+//! Every change is sent as an event named `created`, `deleted` or `modified`, whose data is the
+//! JSON object `{ "kind": <kind>, "path": <request path of the item> }`.
+//! Moving an item is reported as the deletion of the old path and the creation of the new one.
+//! If the client does not keep up with the changes, some events are dropped and an event named
+//! `lagged` is sent instead, after which the client should reload the storages it shows.
+//! A comment is sent every few seconds when nothing changes, to keep the connection open.
+//!
+//! The changes are detected by watching the user directories on disk, so that changes made
+//! outside of the server are reported as well; the same watcher records them in the change journal
+//! of the [`changes`](crate::api::changes) module.
+//! Temporary files created by the server while writing an item are not reported.
+//!
+//! Changes are journaled in batches, every [`JOURNAL_DELAY`] at most, so that an item changing
+//! several times in a row is journaled once, with its final state; the changes to the items that
+//! the server itself journaled shortly before, or to the items inside them, are not journaled
+//! again.
+//!
+//! # Security
+//! ## Access
+//! By default, the changes inside all the storages the user has access to are streamed, that is,
+//! inside the user's root, the public root, the storages shared with the user and the team folders
+//! of which the user is a member.
+//! The changes inside a single storage are streamed by giving its path; the same rules as in the
+//! Access section of the [`storage`](crate::api::storage) module apply to that storage, so that,
+//! for example, the changes inside the storages shared with the user can be streamed as well.
+//!
+//! Access is checked when the stream is opened.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+use actix_web::{HttpResponse, web};
+use actix_web::http::header;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::event::{ModifyKind, RenameMode};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, Sender};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tusk_core::config::{Tusk, TuskConfiguration};
+use tusk_core::error::{TuskError, TuskHttpResult, TuskResult};
+use tusk_core::resources::{StorageChange, StorageOperation};
+use tusk_derive::rest_resource;
+use crate::api::storage::PathInfo;
+
+/// Maximum number of events kept for the clients which are behind.
+const CAPACITY: usize = 1024;
+/// Interval after which a comment is sent to the clients if nothing changed.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+/// Interval during which the changes are collected before being journaled together.
+pub const JOURNAL_DELAY: Duration = Duration::from_millis(500);
+/// Interval during which the changes to an item journaled by the server are not journaled again.
+const JOURNALED_TTL: Duration = Duration::from_secs(5);
+
+/// Watcher shared by all the workers of the server, started on first use.
+static STORAGE_EVENTS: OnceLock<Option<StorageEvents>> = OnceLock::new();
+/// Request paths of the items whose changes have been journaled by the server, with the moment
+/// they were journaled.
+static JOURNALED: Mutex<BTreeMap<String, Instant>> = Mutex::new(BTreeMap::new());
+
+/// Notes that the server journaled a change to the item with the given request path, so that the
+/// watcher does not journal it again.
+pub fn journaled(request_path: &str) {
+    let mut journaled = JOURNALED.lock().unwrap_or_else(PoisonError::into_inner);
+    journaled.retain(|_, at| at.elapsed() < JOURNALED_TTL);
+    journaled.insert(request_path.to_owned(), Instant::now());
+}
+
+/// Returns `true` if the server recently journaled a change to the item with the given request
+/// path, or to a storage containing it, and `false` otherwise.
+fn recently_journaled(request_path: &str) -> bool {
+    let journaled = JOURNALED.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut ancestor = Some(request_path);
+    while let Some(path) = ancestor {
+        if journaled.get(path).is_some_and(|at| at.elapsed() < JOURNALED_TTL) { return true; }
+        ancestor = path.rsplit_once('/').map(|(parent, _)| parent);
+    }
+    false
+}
+
+/// Describes the change to an item.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageEventKind {
+    /// The item has been created, or moved to its path.
+    Created,
+    /// The item has been deleted, or moved from its path.
+    Deleted,
+    /// The contents or the attributes of the item have been modified.
+    Modified
+}
+impl StorageEventKind {
+    /// Returns the name of the Server-Sent Event corresponding to this change.
+    fn name(&self) -> &'static str {
+        match self {
+            StorageEventKind::Created => "created",
+            StorageEventKind::Deleted => "deleted",
+            StorageEventKind::Modified => "modified"
+        }
+    }
+}
+
+/// Represents the CRUD **Read** structure relative to the `/storage-events` REST resource, that
+/// is, a single change to an item of the storages.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct StorageEventRead {
+    kind: StorageEventKind,
+    path: String
+}
+impl StorageEventRead {
+    /// Returns `true` if the changed item is one of the storages with request paths `scopes`, or
+    /// is inside one of them, and `false` otherwise.
+    pub fn is_inside(&self, scopes: &[String]) -> bool {
+        scopes.iter().any(|scope| {
+            self.path.strip_prefix(scope.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    /// Returns the message of the Server-Sent Event corresponding to this change.
+    fn to_message(&self) -> web::Bytes {
+        let data = serde_json::to_string(self).unwrap_or_default();
+        web::Bytes::from(format!("event: {}\ndata: {data}\n\n", self.kind.name()))
+    }
+}
+
+/// Returns the request path of the item at `path`, where `root` is the root of the request
+/// paths, or `None` if the item is outside `root` or is a temporary file.
+fn request_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let components: Vec<_> = relative.iter().map(|s| s.to_string_lossy()).collect();
+    if components.is_empty() || components.iter().any(|c| c.starts_with(".tusk-")) { return None; }
+    Some(components.join("/"))
+}
+
+/// Records the change to the item at `path`, whose request path is `request_path`, in the change
+/// journal.
+///
+/// The change is recorded according to the current state of the item, so that `created` is only
+/// used to tell a new item from a modified one.
+fn record(db: &mut tusk_core::PgConnection, created: bool, request_path: &str, path: &Path) {
+    let result = match path.symlink_metadata() {
+        Ok(metadata) => {
+            let operation = if created { StorageOperation::Created } else { StorageOperation::Modified };
+            let size = metadata.is_file().then_some(metadata.len());
+            StorageChange::record(db, request_path, operation, metadata.is_dir(), size, metadata.modified().ok())
+        },
+        Err(_) => StorageChange::record(db, request_path, StorageOperation::Deleted, false, None, None)
+    };
+    if let Err(e) = result {
+        log::error!("Cannot record the change to `{request_path}`: {e}");
+    }
+}
+
+/// Journals the changes received from the watcher, in batches collected during
+/// [`JOURNAL_DELAY`], until the watcher stops.
+///
+/// Each change is identified by the request path of the item, its path and `true` if the item
+/// has been created.
+async fn journal(config: TuskConfiguration, mut receiver: UnboundedReceiver<(String, PathBuf, bool)>) {
+    while let Some(first) = receiver.recv().await {
+        actix_web::rt::time::sleep(JOURNAL_DELAY).await;
+        let mut batch: HashMap<String, (PathBuf, bool)> = HashMap::new();
+        let mut next = Some(first);
+        while let Some((request_path, path, created)) = next {
+            let entry = batch.entry(request_path).or_insert((path, false));
+            entry.1 |= created;
+            next = receiver.try_recv().ok();
+        }
+        batch.retain(|request_path, _| !recently_journaled(request_path));
+        if batch.is_empty() { continue; }
+
+        let mut db = match config.db() {
+            Ok(db) => db,
+            Err(e) => { log::error!("{e}"); continue; }
+        };
+        for (request_path, (path, created)) in batch {
+            record(&mut db, created, &request_path, &path);
+        }
+    }
+}
+
+/// Returns the changes described by the given event of the watcher.
+fn changes(event: Event) -> Vec<(StorageEventKind, PathBuf)> {
+    let kind = match event.kind {
+        EventKind::Create(_) => StorageEventKind::Created,
+        EventKind::Remove(_) => StorageEventKind::Deleted,
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => StorageEventKind::Deleted,
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => StorageEventKind::Created,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let mut paths = event.paths.into_iter();
+            return match (paths.next(), paths.next()) {
+                (Some(from), Some(to)) => vec![(StorageEventKind::Deleted, from), (StorageEventKind::Created, to)],
+                _ => Vec::new()
+            };
+        },
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            return event.paths.into_iter()
+                .map(|path| if path.exists() {
+                    (StorageEventKind::Created, path)
+                } else {
+                    (StorageEventKind::Deleted, path)
+                })
+                .collect();
+        },
+        EventKind::Modify(_) => StorageEventKind::Modified,
+        _ => return Vec::new()
+    };
+    event.paths.into_iter()
+        .map(|path| (kind, path))
+        .collect()
+}
+
+/// Represents the watcher of the changes to the items of the storages, which streams them to the
+/// clients and records them in the change journal.
+pub struct StorageEvents {
+    _watcher: RecommendedWatcher,
+    sender: Sender<StorageEventRead>
+}
+impl StorageEvents {
+    /// Starts watching the user directories, together with the thread which journals the changes.
+    fn start(config: &TuskConfiguration) -> TuskResult<StorageEvents> {
+        let root = config.user_directories()
+            .canonicalize()?;
+        let (sender, _) = broadcast::channel(CAPACITY);
+        let events = sender.clone();
+        let watch_root = root.clone();
+
+        let (journal_sender, receiver) = mpsc::unbounded_channel();
+        let config = config.clone();
+        std::thread::Builder::new()
+            .name(String::from("tusk-journal"))
+            .spawn(move || match actix_web::rt::Runtime::new() {
+                Ok(runtime) => runtime.block_on(journal(config, receiver)),
+                Err(e) => log::error!("Cannot start journaling the changes: {e}")
+            })?;
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            match res {
+                Ok(event) => {
+                    for (kind, path) in changes(event) {
+                        if let Some(request_path) = request_path(&root, &path) {
+                            let _ = journal_sender.send((request_path.clone(), path, kind == StorageEventKind::Created));
+                            // Sending only fails if no client is listening.
+                            let _ = events.send(StorageEventRead { kind, path: request_path });
+                        }
+                    }
+                },
+                Err(e) => {
+                    log::error!("{e}");
+                }
+            }
+        }).map_err(|e| TuskError::internal_server_error().with_error(e))?;
+        watcher.watch(&watch_root, RecursiveMode::Recursive)
+            .map_err(|e| TuskError::internal_server_error().with_error(e))?;
+        log::info!("Starting watcher for storage `{}`", watch_root.display());
+
+        Ok(StorageEvents { _watcher: watcher, sender })
+    }
+
+    /// Returns the watcher shared by the whole server, starting it if it is not started yet.
+    ///
+    /// # Errors
+    /// If the user directories cannot be watched, this function returns an HTTP error
+    /// 503 `SERVICE UNAVAILABLE`.
+    pub fn shared(config: &TuskConfiguration) -> TuskResult<&'static StorageEvents> {
+        STORAGE_EVENTS.get_or_init(|| StorageEvents::start(config)
+            .map_err(|e| log::error!("Cannot watch the user directories: {e}"))
+            .ok())
+            .as_ref()
+            .ok_or_else(TuskError::service_unavailable)
+    }
+}
+
+/// Represents the query of the `/storage-events` REST resource.
+#[derive(Debug, Deserialize)]
+pub struct StorageEventsQuery {
+    path: Option<String>
+}
+
+/// Represents the `/storage-events` REST resource.
+///
+/// The `/storage-events` resource is responsible for streaming the changes to the items in the
+/// storages the user has access to.
+pub struct StorageEventsResource;
+#[rest_resource("/storage-events")]
+impl StorageEventsResource {
+    async fn get(tusk: Tusk, query: web::Query<StorageEventsQuery>) -> TuskHttpResult {
+        tusk.authenticate()?;
+
+        let roots = PathInfo::roots(&tusk, query.path.as_deref())?;
+        let scopes: Vec<String> = roots.iter().map(PathInfo::request_path).collect();
+
+        let receiver = StorageEvents::shared(tusk.config())?.sender.subscribe();
+        let stream = futures_util::stream::unfold((receiver, scopes), |(mut receiver, scopes)| async move {
+            loop {
+                let message = match actix_web::rt::time::timeout(KEEP_ALIVE, receiver.recv()).await {
+                    Err(_) => web::Bytes::from_static(b": keep-alive\n\n"),
+                    Ok(Ok(event)) if event.is_inside(&scopes) => event.to_message(),
+                    Ok(Ok(_)) => continue,
+                    Ok(Err(RecvError::Lagged(_))) => web::Bytes::from_static(b"event: lagged\ndata: {}\n\n"),
+                    Ok(Err(RecvError::Closed)) => return None
+                };
+                return Some((Ok::<_, actix_web::Error>(message), (receiver, scopes)));
+            }
+        });
+
+        Ok(HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .streaming(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use notify::{Event, EventKind};
+    use notify::event::{CreateKind, ModifyKind, RenameMode};
+    use crate::api::events::{changes, journaled, recently_journaled, request_path, StorageEventKind, StorageEventRead};
+
+    #[test]
+    fn test_storage_events() {
+        let root = Path::new("/srv/storage");
+        assert_eq!(request_path(root, Path::new("/srv/storage/.public/Notes.txt")).as_deref(), Some(".public/Notes.txt"));
+        assert_eq!(request_path(root, Path::new("/srv/storage/.public/.tusk-a1b2c3")), None);
+        assert_eq!(request_path(root, Path::new("/srv/storage")), None);
+        assert_eq!(request_path(root, Path::new("/srv/trash/Notes.txt")), None);
+
+        let event = Event::new(EventKind::Create(CreateKind::File))
+            .add_path(PathBuf::from("/srv/storage/.public/Notes.txt"));
+        assert_eq!(changes(event), vec![(StorageEventKind::Created, PathBuf::from("/srv/storage/.public/Notes.txt"))]);
+        let event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(PathBuf::from("/srv/storage/.public/Notes.txt"))
+            .add_path(PathBuf::from("/srv/storage/.public/Old Notes.txt"));
+        assert_eq!(changes(event), vec![
+            (StorageEventKind::Deleted, PathBuf::from("/srv/storage/.public/Notes.txt")),
+            (StorageEventKind::Created, PathBuf::from("/srv/storage/.public/Old Notes.txt"))
+        ]);
+
+        let event = StorageEventRead { kind: StorageEventKind::Modified, path: String::from(".public/Notes.txt") };
+        assert!(event.is_inside(&[String::from(".public")]));
+        assert!(event.is_inside(&[String::from("user"), String::from(".public/Notes.txt")]));
+        assert!(!event.is_inside(&[String::from(".pub")]));
+        assert_eq!(event.to_message(), "event: modified\ndata: {\"kind\":\"modified\",\"path\":\".public/Notes.txt\"}\n\n");
+
+        journaled(".public/Journaled");
+        assert!(recently_journaled(".public/Journaled"));
+        assert!(recently_journaled(".public/Journaled/Notes.txt"));
+        assert!(!recently_journaled(".public/Journaled notes.txt"));
+        assert!(!recently_journaled(".public"));
+    }
+}
@@ -0,0 +1,181 @@
+//! Contains the CRUD structures relative to the `/storage-grants` REST resource.
+//!
+//! A user can grant another user, or all the users with a given role, access to a storage inside
+//! the user's root.
+//! The access is either read-only or read-write, and extends to everything inside the storage.
+//!
+//! # Security
+//! ## Creation
+//! Access is granted by `POST`ing the path of the storage, together with either the email of the
+//! user or the name of the role, to the `/storage-grants` resource.
+//! Granting access again to the same user or role replaces the previous access.
+//!
+//! The storage must be an existing subdirectory of the user's root, otherwise the response is
+//! `FORBIDDEN` (for paths outside the user's root, including the root itself) or `NOT FOUND`.
+//! If the role does not exist, the response is `NOT FOUND`.
+//!
+//! Access granted to a user results in `ACCEPTED`, without any content, whether or not a user with
+//! the given email exists, so that the existence of users does not leak; the grant, if any, is
+//! listed together with the other grants of the user.
+//!
+//! ## Access
+//! A user can only list and revoke the grants that they gave.
+//! If any other user tries to access a grant, the response will be `NOT FOUND`.
+//!
+//! The storages shared with the user are listed by the `/storage/shared` resource, and can be
+//! accessed through the `/storage` resource with their full path; see the Access section of the
+//! [`storage`](crate::api::storage) module.
+//! A shared storage itself cannot be moved or deleted by the users with which it is shared.
+//!
+//! ## Moving and deleting
+//! When a shared storage, or a storage containing it, is moved inside the user's root, the grants
+//! follow it; when it is moved to another root, deleted or replaced, the grants are revoked, and
+//! they are not given back if the storage is restored from the trash.
+
+use actix_web::{HttpResponse, web};
+use actix_web::http::header;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use tusk_core::config::Tusk;
+use tusk_core::error::{HttpOkOr, TuskError, TuskHttpResult};
+use tusk_core::resources::{Role, StorageGrant, User};
+use tusk_derive::rest_resource;
+use crate::api::storage::{PathInfo, StoragePathRead};
+
+/// Represents the CRUD **Create** structure relative to the `/storage-grants` REST resource.
+#[derive(Debug, Deserialize)]
+pub struct CreateStorageGrantData {
+    path: String,
+    user: Option<String>,
+    role: Option<String>,
+    #[serde(default)]
+    writable: bool
+}
+
+/// Represents the CRUD **Read** structure relative to the `/storage-grants` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct StorageGrantRead {
+    id: Uuid,
+    path: String,
+    user: Option<Uuid>,
+    role: Option<Uuid>,
+    writable: bool
+}
+impl From<&StorageGrant> for StorageGrantRead {
+    fn from(grant: &StorageGrant) -> Self {
+        StorageGrantRead {
+            id: grant.id(),
+            path: grant.path().to_owned(),
+            user: grant.user_id(),
+            role: grant.role_id(),
+            writable: grant.writable()
+        }
+    }
+}
+
+/// Represents the CRUD **Read** structure relative to the `/storage/shared` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct SharedStorageRead {
+    path: String,
+    owner: String,
+    owner_id: Uuid,
+    writable: bool,
+    item: StoragePathRead
+}
+
+/// Represents the `/storage-grants` REST resource.
+///
+/// The `/storage-grants` resource is responsible for granting access to the storages in the
+/// user's root and for listing the grants given by the user.
+pub struct StorageGrantsResource;
+#[rest_resource("/storage-grants")]
+impl StorageGrantsResource {
+    async fn get(tusk: Tusk) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+
+        let grants: Vec<StorageGrantRead> = StorageGrant::list_for_owner(&mut db, user_id)?
+            .iter()
+            .map(StorageGrantRead::from)
+            .collect();
+
+        Ok(HttpResponse::Ok().json(grants))
+    }
+
+    async fn post(tusk: Tusk, web::Json(data): web::Json<CreateStorageGrantData>) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let path = PathInfo::from_queried_path(&tusk, &data.path)?;
+        let request_path = path.request_path();
+        if !request_path.starts_with(&format!("{user_id}/")) { return TuskError::forbidden().bail(); }
+        if !path.exists() { return TuskError::not_found().bail(); }
+        if !path.is_directory() { return TuskError::bad_request().bail(); }
+
+        let mut db = tusk.db()?;
+        let grant = match (&data.user, &data.role) {
+            (Some(email), None) => {
+                if let Some(grantee) = User::from_email(&mut db, email)? {
+                    if grantee.id() == user_id { return TuskError::bad_request().bail(); }
+                    StorageGrant::set_for_user(&mut db, user_id, &request_path, grantee.id(), data.writable)?;
+                }
+                return Ok(HttpResponse::Accepted().finish());
+            },
+            (None, Some(name)) => {
+                let role = Role::from_name(&mut db, name)?
+                    .or_not_found()?;
+                StorageGrant::set_for_role(&mut db, user_id, &request_path, role.id(), data.writable)?
+            },
+            _ => return TuskError::bad_request().bail()
+        };
+
+        Ok(HttpResponse::Created()
+            .insert_header((header::LOCATION, format!("/v1/storage-grants/{}", grant.id())))
+            .json(StorageGrantRead::from(&grant)))
+    }
+}
+
+/// Represents the `/storage-grants/{storage_grant_id}` REST resource.
+///
+/// The `/storage-grants/{storage_grant_id}` resource is responsible for revoking a single grant.
+pub struct StorageGrantResource;
+#[rest_resource("/storage-grants/{storage_grant_id}")]
+impl StorageGrantResource {
+    async fn delete(tusk: Tusk, storage_grant_id: web::Path<Uuid>) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+        let grant = StorageGrant::from_id(&mut db, *storage_grant_id, user_id)?;
+        grant.delete(&mut db)?;
+
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+/// Represents the `/storage/shared` REST resource.
+///
+/// The `/storage/shared` resource is responsible for listing the storages shared with the user,
+/// i.e. the "Shared with me" virtual storage.
+pub struct StorageSharedResource;
+#[rest_resource("/storage/shared")]
+impl StorageSharedResource {
+    async fn get(tusk: Tusk) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+
+        let mut shared: Vec<SharedStorageRead> = Vec::new();
+        for grant in StorageGrant::list_for_grantee(&mut db, user_id)? {
+            if shared.iter().any(|item| item.path == grant.path()) { continue; }
+            let path = PathInfo::from_queried_path(&tusk, grant.path())?;
+            if !path.is_directory() { continue; }
+            let owner = User::from_id(&mut db, grant.owner_id())?;
+
+            shared.push(SharedStorageRead {
+                path: grant.path().to_owned(),
+                owner: owner.display().to_owned(),
+                owner_id: owner.id(),
+                writable: path.writable(),
+                item: path.info()?
+            });
+        }
+
+        Ok(HttpResponse::Ok().json(shared))
+    }
+}
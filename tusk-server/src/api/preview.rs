@@ -0,0 +1,187 @@
+//! Contains the policy with which the files are served, together with the `/preview` resource.
+//!
+//! Files are served from the same origin as the REST API; hence, a file that the browser renders
+//! as a web page, such as an HTML or an SVG file, could run scripts with the session of the user.
+//! To prevent that, every downloaded file is served with the `X-Content-Type-Options: nosniff`
+//! header, so that the browser does not guess its type, and with a sandboxing
+//! `Content-Security-Policy`, so that scripts are not run; moreover, active content, that is,
+//! HTML, XML, SVG and scripts, is always served as an attachment.
+//!
+//! # Security
+//! ## Previews
+//! When the `preview_domain` option of the `tusk` section of `tusk.toml` is set, the files can be
+//! previewed from that domain, which should be a separate site without access to the session
+//! cookies, by `GET`ting the corresponding `/storage` REST resource with the `preview` query
+//! parameter set to `true`.
+//! The response is `SEE OTHER`, redirecting to a preview link on the preview domain, where the file
+//! is served inline, active content included, but still sandboxed.
+//! Without a preview domain, the `preview` query parameter is ignored.
+//!
+//! The same rules as in the Access section of the [`storage`](crate::api::storage) module apply;
+//! requesting a preview of a storage results in `BAD REQUEST`.
+//!
+//! ## Preview links
+//! Anyone who knows a preview link can read the file, as the user who requested the preview, until
+//! the link expires, five minutes after its creation.
+//! Expired or unknown links result in `NOT FOUND`.
+//!
+//! Preview links do not keep the data key of the user: encrypted files are decrypted with the key
+//! of the session in which the preview was requested, and cannot be previewed any more, resulting
+//! in `LOCKED`, once the user logs out.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use actix_files::NamedFile;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType, TryIntoHeaderValue};
+use actix_web::web::ServiceConfig;
+use mime::Mime;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use uuid::Uuid;
+use tusk_core::config::Tusk;
+use tusk_core::error::{HttpOkOr, TuskError, TuskHttpResult};
+use tusk_derive::rest_resource;
+use crate::api::encryption;
+use crate::api::storage::PathInfo;
+
+/// Content security policy of the served files, which sandboxes them in a unique origin without
+/// scripts, and only allows them to load images and media from the same origin.
+const SANDBOX_POLICY: &str = "sandbox; default-src 'none'; img-src 'self' data:; media-src 'self'; style-src 'unsafe-inline'";
+/// Time for which a preview link is valid.
+const PREVIEW_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Paths that can be previewed, by token.
+static PREVIEWS: Mutex<BTreeMap<Uuid, Preview>> = Mutex::new(BTreeMap::new());
+
+/// Path that can be previewed through a preview link.
+#[derive(Clone, Debug)]
+struct Preview {
+    /// Path of the file, without any data key.
+    path: PathInfo,
+    /// Token identifying, in the key ring, the data key of the session in which the preview was
+    /// requested, if any.
+    key_token: Option<Uuid>,
+    /// Time at which the preview link expires.
+    expiration: Instant
+}
+
+/// Returns `true` if a browser could run scripts from contents of the given MIME type.
+pub fn is_active(content_type: &Mime) -> bool {
+    let subtype = content_type.subtype();
+    let suffix = content_type.suffix();
+    subtype == mime::HTML || subtype == mime::XML || subtype == mime::JAVASCRIPT
+        || suffix == Some(mime::XML)
+        || subtype.as_str().contains("javascript")
+        || subtype.as_str().contains("ecmascript")
+        || subtype == "x-shockwave-flash"
+}
+
+/// Applies the download policy to the response serving the file with the given `name`.
+///
+/// Active content is forced to be downloaded as an attachment unless `inline` is `true`;
+/// contents without a MIME type are considered active.
+pub fn secure(mut response: HttpResponse, name: &str, inline: bool) -> HttpResponse {
+    let active = response.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Mime>().ok())
+        .is_none_or(|mime| is_active(&mime));
+
+    let headers = response.headers_mut();
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, header::HeaderValue::from_static("nosniff"));
+    headers.insert(header::CONTENT_SECURITY_POLICY, header::HeaderValue::from_static(SANDBOX_POLICY));
+    if active && !inline {
+        let disposition = ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(name.to_owned())]
+        };
+        if let Ok(value) = disposition.try_into_value() {
+            headers.insert(header::CONTENT_DISPOSITION, value);
+        }
+    }
+
+    response
+}
+
+/// Returns the response serving the file at `path`, decrypted if needed, following the download
+/// policy.
+pub fn serve(path: &PathInfo, req: &HttpRequest, inline: bool) -> TuskHttpResult {
+    let response = match path.decryption().open(path.as_ref())? {
+        Some(decryptor) => encryption::respond(decryptor, &path.name(), req)?,
+        None => NamedFile::open(path)?
+            .use_etag(false)
+            .into_response(req)
+    };
+
+    Ok(secure(response, &path.name(), inline))
+}
+
+/// Returns the response redirecting to a new preview link, on the given domain, of the file at
+/// `path`, requested in the session whose data key is identified by `key_token`.
+pub fn redirect(domain: &str, path: PathInfo, key_token: Option<Uuid>) -> HttpResponse {
+    let now = Instant::now();
+    let token = Uuid::new_v4();
+    let name = utf8_percent_encode(&path.name(), NON_ALPHANUMERIC).to_string();
+
+    let mut previews = PREVIEWS.lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    previews.retain(|_, preview| preview.expiration > now);
+    previews.insert(token, Preview { path: path.locked(), key_token, expiration: now + PREVIEW_TTL });
+
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, format!("https://{domain}/preview/{token}/{name}")))
+        .finish()
+}
+
+/// Configures the preview domain by adding the `/preview` resource.
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(PreviewResource);
+}
+
+/// Represents the `/preview` resource.
+///
+/// The `/preview` resource is responsible for serving the previews of the files on the preview
+/// domain.
+pub struct PreviewResource;
+#[rest_resource("/preview/{token}/{filename}")]
+impl PreviewResource {
+    async fn get(tusk: Tusk, req: HttpRequest) -> TuskHttpResult {
+        let token: Uuid = req.match_info().query("token")
+            .parse()
+            .or_not_found()?;
+        let preview = PREVIEWS.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&token)
+            .filter(|preview| preview.expiration > Instant::now())
+            .cloned()
+            .or_not_found()?;
+        let key = preview.key_token
+            .and_then(|key_token| tusk.config().key_ring().get(key_token, preview.path.initiator()));
+        let path = preview.path.unlocked(tusk.config(), key);
+        if path.is_directory() || !path.exists() { return TuskError::not_found().bail(); }
+
+        serve(&path, &req, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mime::Mime;
+    use crate::api::preview::is_active;
+
+    #[test]
+    fn test_is_active() {
+        let active = |content_type: &str| is_active(&content_type.parse::<Mime>().unwrap());
+
+        assert!(active("text/html; charset=utf-8"));
+        assert!(active("application/xhtml+xml"));
+        assert!(active("image/svg+xml"));
+        assert!(active("text/xml"));
+        assert!(active("application/javascript"));
+        assert!(active("text/javascript"));
+        assert!(!active("text/plain; charset=utf-8"));
+        assert!(!active("image/png"));
+        assert!(!active("application/pdf"));
+        assert!(!active("video/mp4"));
+    }
+}
\ No newline at end of file
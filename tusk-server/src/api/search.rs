@@ -0,0 +1,210 @@
+//! Contains the CRUD structures relative to the `/storage-search` REST resource.
+//!
+//! The items of the storage are searched by name, kind, size and modification time.
+//! A name containing `*` (any sequence of characters) or `?` (any single character) is matched as
+//! a glob against the whole name of the items; any other name is matched as a substring.
+//! Names are matched case-insensitively.
+//!
+//! Files are also searched by their contents with the `content` parameter, through the full-text
+//! index kept by the [`index`] module; the other criteria then further restrict the results.
+//! Text is extracted from plain text files, PDF documents and office documents, as described in
+//! the [`text`] module.
+//!
+//! Results are sorted by path, or by relevance when searching by contents, and split in pages of
+//! `per_page` items (50 by default, at most 500); pages are numbered from 0.
+//!
+//! # Security
+//! ## Access
+//! By default, all the storages the user has access to are searched, that is, the user's root, the
+//! public root, the storages shared with the user and the team folders of which the user is
+//! a member.
+//! A single storage is searched by giving its path; the same rules as in the Access section of the
+//! [`storage`](crate::api::storage) module apply to that storage, so that, for example, the
+//! storages shared with the user can be searched as well.
+//!
+//! The full-text index is only queried for the files inside the searched storages, and every
+//! result is authorized again before being returned, as the index can lag behind the storages.
+
+mod index;
+mod text;
+
+use actix_web::{HttpResponse, web};
+use serde::{Deserialize, Serialize};
+use tusk_core::config::Tusk;
+use tusk_core::error::{TuskError, TuskHttpResult};
+use tusk_derive::rest_resource;
+use crate::api::storage::{PathInfo, PathKind, StoragePathRead};
+use crate::api::trash::epoch_delta;
+
+pub use index::{ContentIndex, IndexTask};
+
+/// Default number of results in a page.
+const DEFAULT_PER_PAGE: usize = 50;
+/// Maximum number of results in a page.
+const MAX_PER_PAGE: usize = 500;
+/// Maximum number of files returned by the full-text index for a single search.
+const MAX_CONTENT_RESULTS: usize = 1000;
+
+/// Returns `true` if the given name matches the glob `pattern`, where `*` matches any sequence
+/// of characters and `?` matches any single character, and `false` otherwise.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern, and of the character of the name it matched up to.
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            },
+            Some('?') => {
+                p += 1;
+                n += 1;
+            },
+            Some(&c) if c == name[n] => {
+                p += 1;
+                n += 1;
+            },
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                },
+                None => return false
+            }
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Represents the query of the `/storage-search` REST resource.
+#[derive(Debug, Deserialize)]
+pub struct StorageSearchQuery {
+    path: Option<String>,
+    name: Option<String>,
+    content: Option<String>,
+    kind: Option<PathKind>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<i64>,
+    modified_before: Option<i64>,
+    #[serde(default)]
+    page: usize,
+    per_page: Option<usize>
+}
+impl StorageSearchQuery {
+    /// Returns `true` if the given item satisfies all the criteria of the query and `false`
+    /// otherwise.
+    pub fn matches(&self, item: &StoragePathRead) -> bool {
+        if let Some(name) = &self.name {
+            let filename = item.filename().to_lowercase();
+            let name = name.to_lowercase();
+            let found = if name.contains(['*', '?']) {
+                glob_match(&name.chars().collect::<Vec<_>>(), &filename.chars().collect::<Vec<_>>())
+            } else {
+                filename.contains(&name)
+            };
+            if !found { return false; }
+        }
+        match self.kind {
+            Some(PathKind::File) if item.is_directory() => return false,
+            Some(PathKind::Directory) if !item.is_directory() => return false,
+            _ => {}
+        }
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let Some(size) = item.size() else { return false; };
+            if self.min_size.is_some_and(|min_size| size < min_size) { return false; }
+            if self.max_size.is_some_and(|max_size| size > max_size) { return false; }
+        }
+        let modified = epoch_delta(item.last_modified());
+        if self.modified_after.is_some_and(|after| modified < after) { return false; }
+        if self.modified_before.is_some_and(|before| modified > before) { return false; }
+
+        true
+    }
+}
+
+/// Represents a single result of the `/storage-search` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct StorageSearchResultRead {
+    path: String,
+    item: StoragePathRead
+}
+
+/// Represents the CRUD **Read** structure relative to the `/storage-search` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct StorageSearchRead {
+    total: usize,
+    page: usize,
+    per_page: usize,
+    results: Vec<StorageSearchResultRead>
+}
+
+/// Represents the `/storage-search` REST resource.
+///
+/// The `/storage-search` resource is responsible for searching the items in the storages the user
+/// has access to.
+pub struct StorageSearchResource;
+#[rest_resource("/storage-search")]
+impl StorageSearchResource {
+    async fn get(tusk: Tusk, query: web::Query<StorageSearchQuery>) -> TuskHttpResult {
+        tusk.authenticate()?;
+        let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE);
+        if per_page == 0 || per_page > MAX_PER_PAGE { return TuskError::bad_request().bail(); }
+
+        let roots = PathInfo::roots(&tusk, query.path.as_deref())?;
+
+        let mut results = Vec::new();
+        if let Some(content) = &query.content {
+            let scopes: Vec<String> = roots.iter().map(PathInfo::request_path).collect();
+            for path in ContentIndex::shared(tusk.config())?.search(content, &scopes, MAX_CONTENT_RESULTS)? {
+                let Ok(item) = PathInfo::from_queried_path(&tusk, &path).and_then(|path| path.info()) else { continue; };
+                if query.matches(&item) {
+                    results.push(StorageSearchResultRead { path, item });
+                }
+            }
+        } else {
+            for root in &roots {
+                root.walk(&mut |path, item| {
+                    if query.matches(&item) {
+                        results.push(StorageSearchResultRead { path, item });
+                    }
+                });
+            }
+        }
+
+        let total = results.len();
+        let results = results.into_iter()
+            .skip(query.page.saturating_mul(per_page))
+            .take(per_page)
+            .collect();
+
+        Ok(HttpResponse::Ok().json(StorageSearchRead { total, page: query.page, per_page, results }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::search::glob_match;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        glob_match(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(matches("*.txt", "notes.txt"));
+        assert!(matches("*.txt", ".txt"));
+        assert!(!matches("*.txt", "notes.txt.bak"));
+        assert!(matches("note?.txt", "notes.txt"));
+        assert!(!matches("note?.txt", "note.txt"));
+        assert!(matches("*a*b*", "xxaxxbxx"));
+        assert!(matches("*", ""));
+        assert!(!matches("a*", "ba"));
+        assert!(matches("report", "report"));
+        assert!(!matches("report", "reports"));
+    }
+}
@@ -0,0 +1,373 @@
+//! Contains the full-text index of the documents in the storages.
+//!
+//! The index is stored in the search index directory and maps the words contained in the
+//! documents, as extracted by the [`text`](crate::api::search::text) module, to the request paths
+//! of the documents.
+//! Together with every document, the index stores the request paths of all the storages containing
+//! it, so that searches can be restricted to the storages that the user is allowed to see.
+//!
+//! The index is updated in background by a single thread, which receives [`IndexTask`]s from the
+//! storage operations of [`PathInfo`](crate::api::storage::PathInfo), whichever resource requested
+//! them, and commits the changes in batches.
+//! When the server starts, the index is reconciled with the contents of the user directories, so
+//! that the changes made while the server was not running, or not through the `/storage`
+//! resource, are eventually indexed as well.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+use tantivy::{DocAddress, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+use tantivy::collector::{DocSetCollector, TopDocs};
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{AllQuery, BooleanQuery, Occur, Query, QueryParser, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, STORED, STRING, TEXT, Value};
+use tusk_core::config::TuskConfiguration;
+use tusk_core::error::{TuskError, TuskResult};
+use crate::api::search::text::{extract_text, is_supported};
+use crate::api::trash::epoch_delta;
+
+/// Amount of memory, in bytes, used by the writer of the index.
+const WRITER_MEMORY: usize = 32 << 20;
+
+/// Index shared by all the workers of the server, opened on first use.
+static CONTENT_INDEX: OnceLock<Option<ContentIndex>> = OnceLock::new();
+
+/// Converts an error of the index library into an HTTP error 500 `INTERNAL SERVER ERROR`.
+fn index_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> TuskError {
+    TuskError::internal_server_error().with_error(e)
+}
+
+/// Returns the request paths of the storages containing the item with the given request path.
+fn ancestors(request_path: &str) -> impl Iterator<Item=&str> {
+    request_path.match_indices('/')
+        .map(|(end, _)| &request_path[..end])
+}
+
+/// Calls `visitor` with the request path and the physical path of every file inside `path`,
+/// recursively, where `root` is the root of the request paths.
+///
+/// Symbolic links are skipped.
+fn visit_files<F: FnMut(String, &Path)>(root: &Path, path: &Path, visitor: &mut F) {
+    let Ok(metadata) = path.symlink_metadata() else { return; };
+    if metadata.is_file() {
+        let Ok(relative) = path.strip_prefix(root) else { return; };
+        let request_path: Vec<_> = relative.iter().map(|s| s.to_string_lossy()).collect();
+        visitor(request_path.join("/"), path);
+    } else if metadata.is_dir() {
+        let Ok(entries) = std::fs::read_dir(path) else { return; };
+        for entry in entries.filter_map(Result::ok) {
+            visit_files(root, &entry.path(), visitor);
+        }
+    }
+}
+
+/// Returns the last modification time of the file at `path`, in seconds since the epoch.
+fn modified(path: &Path) -> i64 {
+    let modified = path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    epoch_delta(modified)
+}
+
+/// Represents a change to be applied to the index.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum IndexTask {
+    /// Indexes the item with the given request path again; if the item is a storage, indexes
+    /// every file inside it.
+    Update(String),
+    /// Removes the item with the given request path, and everything inside it, from the index.
+    Remove(String),
+    /// Indexes the files that changed since they were indexed, and removes the files that do not
+    /// exist any more.
+    Reconcile
+}
+
+/// Contains the fields of the documents in the index.
+#[derive(Copy, Clone, Debug)]
+struct IndexFields {
+    path: Field,
+    ancestors: Field,
+    content: Field,
+    modified: Field
+}
+impl IndexFields {
+    /// Returns the schema of the index.
+    fn schema() -> Schema {
+        let mut schema = Schema::builder();
+        schema.add_text_field("path", STRING | STORED);
+        schema.add_text_field("ancestors", STRING);
+        schema.add_text_field("content", TEXT);
+        schema.add_i64_field("modified", STORED);
+        schema.build()
+    }
+
+    /// Returns the fields of the given index.
+    fn from_index(index: &Index) -> TuskResult<IndexFields> {
+        let schema = index.schema();
+        let field = |name| schema.get_field(name).map_err(index_error);
+        Ok(IndexFields {
+            path: field("path")?,
+            ancestors: field("ancestors")?,
+            content: field("content")?,
+            modified: field("modified")?
+        })
+    }
+}
+
+/// Opens the index stored in `directory`, creating it if it does not exist.
+fn open_index(directory: &Path) -> TuskResult<Index> {
+    std::fs::create_dir_all(directory)?;
+    let directory = MmapDirectory::open(directory)
+        .map_err(index_error)?;
+    Index::open_or_create(directory, IndexFields::schema())
+        .map_err(index_error)
+}
+
+/// Applies the [`IndexTask`]s to the index.
+struct Indexer {
+    writer: IndexWriter,
+    reader: IndexReader,
+    fields: IndexFields,
+    root: PathBuf
+}
+impl Indexer {
+    /// Creates a new indexer for the files in `root`.
+    fn new(index: &Index, reader: IndexReader, root: PathBuf) -> TuskResult<Indexer> {
+        Ok(Indexer {
+            writer: index.writer(WRITER_MEMORY).map_err(index_error)?,
+            reader,
+            fields: IndexFields::from_index(index)?,
+            root
+        })
+    }
+
+    /// Indexes the file at `path`, whose request path is `request_path`, replacing the previous
+    /// document of the file, if any.
+    fn index_file(&mut self, request_path: &str, path: &Path) -> TuskResult<()> {
+        self.writer.delete_term(Term::from_field_text(self.fields.path, request_path));
+        if !is_supported(path) { return Ok(()); }
+
+        let mut document = TantivyDocument::new();
+        document.add_text(self.fields.path, request_path);
+        for ancestor in ancestors(request_path) {
+            document.add_text(self.fields.ancestors, ancestor);
+        }
+        // Documents whose text cannot be extracted are indexed without contents, so that they
+        // are not extracted again until they change.
+        document.add_text(self.fields.content, extract_text(path).unwrap_or_default());
+        document.add_i64(self.fields.modified, modified(path));
+        self.writer.add_document(document)
+            .map_err(index_error)?;
+
+        Ok(())
+    }
+
+    /// Applies the given task to the index, without committing it.
+    fn apply(&mut self, task: IndexTask) -> TuskResult<()> {
+        match task {
+            IndexTask::Update(request_path) => {
+                let path = self.root.join(&request_path);
+                if !path.exists() { return self.apply(IndexTask::Remove(request_path)); }
+                let root = self.root.clone();
+                let mut result = Ok(());
+                visit_files(&root, &path, &mut |request_path, path| {
+                    if let Err(e) = self.index_file(&request_path, path) { result = Err(e); }
+                });
+                result
+            },
+            IndexTask::Remove(request_path) => {
+                self.writer.delete_term(Term::from_field_text(self.fields.path, &request_path));
+                self.writer.delete_term(Term::from_field_text(self.fields.ancestors, &request_path));
+                Ok(())
+            },
+            IndexTask::Reconcile => {
+                let searcher = self.reader.searcher();
+                let mut indexed: HashMap<String, i64> = HashMap::new();
+                for address in searcher.search(&AllQuery, &DocSetCollector).map_err(index_error)? {
+                    let document: TantivyDocument = searcher.doc(address).map_err(index_error)?;
+                    let path = document.get_first(self.fields.path).and_then(|value| value.as_str());
+                    let modified = document.get_first(self.fields.modified).and_then(|value| value.as_i64());
+                    if let (Some(path), Some(modified)) = (path, modified) {
+                        indexed.insert(path.to_owned(), modified);
+                    }
+                }
+
+                let root = self.root.clone();
+                let mut result = Ok(());
+                visit_files(&root, &root, &mut |request_path, path| {
+                    if !is_supported(path) { return; }
+                    if indexed.remove(&request_path) == Some(modified(path)) { return; }
+                    if let Err(e) = self.index_file(&request_path, path) { result = Err(e); }
+                });
+                for request_path in indexed.into_keys() {
+                    self.writer.delete_term(Term::from_field_text(self.fields.path, &request_path));
+                }
+                result
+            }
+        }
+    }
+
+    /// Commits the changes to the index and makes them visible to the searches.
+    fn commit(&mut self) -> TuskResult<()> {
+        self.writer.commit().map_err(index_error)?;
+        self.reader.reload().map_err(index_error)
+    }
+
+    /// Applies the tasks received from `receiver` until all the senders are dropped.
+    fn run(mut self, receiver: Receiver<IndexTask>) {
+        while let Ok(task) = receiver.recv() {
+            let mut result = self.apply(task);
+            // Applies the other pending tasks as well, to commit them together.
+            while let Ok(task) = receiver.try_recv() {
+                result = result.and(self.apply(task));
+            }
+            if let Err(e) = result.and_then(|_| self.commit()) {
+                log::error!("Cannot update the search index: {e}");
+            }
+        }
+    }
+}
+
+/// Represents the full-text index of the documents in the storages.
+pub struct ContentIndex {
+    index: Index,
+    reader: IndexReader,
+    fields: IndexFields,
+    sender: Sender<IndexTask>
+}
+impl ContentIndex {
+    /// Opens the index of the files in the user directories, and starts the thread which updates
+    /// it.
+    fn open(config: &TuskConfiguration) -> TuskResult<ContentIndex> {
+        let index = open_index(&config.search_index_directory())?;
+        let reader: IndexReader = index.reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(index_error)?;
+        let indexer = Indexer::new(&index, reader.clone(), config.user_directories())?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name(String::from("tusk-indexer"))
+            .spawn(move || indexer.run(receiver))?;
+        let _ = sender.send(IndexTask::Reconcile);
+
+        Ok(ContentIndex {
+            fields: IndexFields::from_index(&index)?,
+            index,
+            reader,
+            sender
+        })
+    }
+
+    /// Returns the index shared by the whole server, opening it if it is not open yet.
+    ///
+    /// # Errors
+    /// If the index cannot be opened, this function returns an HTTP error
+    /// 503 `SERVICE UNAVAILABLE`.
+    pub fn shared(config: &TuskConfiguration) -> TuskResult<&'static ContentIndex> {
+        CONTENT_INDEX.get_or_init(|| ContentIndex::open(config)
+            .map_err(|e| log::error!("Cannot open the search index: {e}"))
+            .ok())
+            .as_ref()
+            .ok_or_else(TuskError::service_unavailable)
+    }
+
+    /// Schedules the given task on the index shared by the whole server.
+    ///
+    /// Errors are logged and otherwise ignored, as the index is reconciled with the user
+    /// directories anyway the next time the server starts.
+    pub fn schedule(config: &TuskConfiguration, task: IndexTask) {
+        if let Ok(index) = ContentIndex::shared(config) {
+            let _ = index.sender.send(task);
+        }
+    }
+
+    /// Returns the request paths of at most `limit` files containing the given text, sorted by
+    /// relevance, among the files inside the storages with request paths `scopes`.
+    ///
+    /// The text follows the query syntax of the index: words are required by default, phrases
+    /// are written between double quotes, and words preceded by `-` are excluded.
+    pub fn search(&self, text: &str, scopes: &[String], limit: usize) -> TuskResult<Vec<String>> {
+        let mut parser = QueryParser::for_index(&self.index, vec![self.fields.content]);
+        parser.set_conjunction_by_default();
+        let (query, _) = parser.parse_query_lenient(text);
+
+        let scopes: Vec<(Occur, Box<dyn Query>)> = scopes.iter()
+            .map(|scope| {
+                let term = Term::from_field_text(self.fields.ancestors, scope);
+                (Occur::Should, Box::new(TermQuery::new(term, IndexRecordOption::Basic)) as Box<dyn Query>)
+            })
+            .collect();
+        let query = BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (Occur::Must, Box::new(BooleanQuery::new(scopes)))
+        ]);
+
+        let searcher = self.reader.searcher();
+        let hits: Vec<(f32, DocAddress)> = searcher.search(&query, &TopDocs::with_limit(limit))
+            .map_err(index_error)?;
+        let mut paths = Vec::with_capacity(hits.len());
+        for (_, address) in hits {
+            let document: TantivyDocument = searcher.doc(address).map_err(index_error)?;
+            if let Some(path) = document.get_first(self.fields.path).and_then(|value| value.as_str()) {
+                paths.push(path.to_owned());
+            }
+        }
+
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use tantivy::ReloadPolicy;
+    use crate::api::search::index::{ContentIndex, IndexFields, Indexer, IndexTask, open_index};
+
+    #[test]
+    fn test_index() {
+        let root = tempfile::tempdir().unwrap();
+        let storage = root.path().join("storage");
+        std::fs::create_dir_all(storage.join("alice/Notes")).unwrap();
+        std::fs::create_dir_all(storage.join("bob")).unwrap();
+        std::fs::write(storage.join("alice/Notes/shopping.txt"), "Apples and oranges").unwrap();
+        std::fs::write(storage.join("alice/recipe.md"), "Slice the apples").unwrap();
+        std::fs::write(storage.join("alice/apples.png"), "Apples").unwrap();
+        std::fs::write(storage.join("bob/apples.txt"), "Apples everywhere").unwrap();
+
+        let index = open_index(&root.path().join("index")).unwrap();
+        let reader: tantivy::IndexReader = index.reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .unwrap();
+        let mut indexer = Indexer::new(&index, reader.clone(), storage.clone()).unwrap();
+        let (sender, _receiver) = mpsc::channel();
+        let content_index = ContentIndex { fields: IndexFields::from_index(&index).unwrap(), index, reader, sender };
+        let search = |text: &str, scopes: &[&str]| {
+            let scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+            let mut paths = content_index.search(text, &scopes, 10).unwrap();
+            paths.sort();
+            paths
+        };
+
+        indexer.apply(IndexTask::Reconcile).unwrap();
+        indexer.commit().unwrap();
+        assert_eq!(search("apples", &["alice"]), ["alice/Notes/shopping.txt", "alice/recipe.md"]);
+        assert_eq!(search("apples oranges", &["alice"]), ["alice/Notes/shopping.txt"]);
+        assert_eq!(search("apples", &["alice/Notes", "bob"]), ["alice/Notes/shopping.txt", "bob/apples.txt"]);
+        assert!(search("apples", &[]).is_empty());
+
+        std::fs::write(storage.join("alice/Notes/todo.txt"), "Buy apples").unwrap();
+        indexer.apply(IndexTask::Update(String::from("alice/Notes"))).unwrap();
+        indexer.apply(IndexTask::Remove(String::from("alice/recipe.md"))).unwrap();
+        indexer.commit().unwrap();
+        assert_eq!(search("apples", &["alice"]), ["alice/Notes/shopping.txt", "alice/Notes/todo.txt"]);
+
+        std::fs::remove_dir_all(storage.join("alice/Notes")).unwrap();
+        indexer.apply(IndexTask::Reconcile).unwrap();
+        indexer.commit().unwrap();
+        assert_eq!(search("apples", &["alice"]), ["alice/recipe.md"]);
+    }
+}
@@ -0,0 +1,166 @@
+//! Contains the extraction of the text from the documents in the storages.
+//!
+//! Text is extracted from plain text files, from PDF documents and from office documents, both in
+//! the Office Open XML (`.docx`, `.xlsx`, `.pptx`) and in the OpenDocument (`.odt`, `.ods`,
+//! `.odp`) formats; the format is given by the extension of the file.
+
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use zip::ZipArchive;
+use crate::api::encryption::is_encrypted;
+
+/// Maximum size, in bytes, of the files whose text is extracted.
+pub const MAX_FILE_SIZE: u64 = 32 << 20;
+/// Maximum length, in bytes, of the text extracted from a single file.
+const MAX_TEXT_LENGTH: usize = 4 << 20;
+
+/// Extensions of the plain text files.
+const PLAIN_TEXT: [&str; 17] = [
+    "txt", "md", "markdown", "rst", "tex", "csv", "tsv", "log", "json", "xml", "html", "htm",
+    "yaml", "yml", "toml", "ini", "cfg"
+];
+
+/// Format of a document from which text can be extracted.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum DocumentFormat {
+    PlainText,
+    Pdf,
+    Docx,
+    Xlsx,
+    Pptx,
+    OpenDocument
+}
+impl DocumentFormat {
+    /// Returns the format of the document at `path`, as given by its extension, or `None` if no
+    /// text can be extracted from it.
+    fn from_path(path: &Path) -> Option<DocumentFormat> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "pdf" => Some(DocumentFormat::Pdf),
+            "docx" => Some(DocumentFormat::Docx),
+            "xlsx" => Some(DocumentFormat::Xlsx),
+            "pptx" => Some(DocumentFormat::Pptx),
+            "odt" | "ods" | "odp" => Some(DocumentFormat::OpenDocument),
+            extension if PLAIN_TEXT.contains(&extension) => Some(DocumentFormat::PlainText),
+            _ => None
+        }
+    }
+}
+
+/// Returns `true` if text can be extracted from the file at `path`, judging by its extension.
+pub fn is_supported(path: &Path) -> bool {
+    DocumentFormat::from_path(path).is_some()
+}
+
+/// Appends the text contained in the XML document read from `reader` to `text`, separating the
+/// contents of different elements by a space.
+fn xml_text<R: Read>(reader: R, text: &mut String) -> Option<()> {
+    let mut reader = Reader::from_reader(BufReader::new(reader));
+    let mut buffer = Vec::new();
+    loop {
+        if text.len() > MAX_TEXT_LENGTH { return Some(()); }
+        match reader.read_event_into(&mut buffer).ok()? {
+            Event::Text(contents) => text.push_str(&contents.decode().ok()?),
+            Event::CData(contents) => text.push_str(&contents.decode().ok()?),
+            Event::GeneralRef(reference) => {
+                if let Ok(Some(c)) = reference.resolve_char_ref() {
+                    text.push(c);
+                } else if let Some(entity) = quick_xml::escape::resolve_xml_entity(&reference.decode().ok()?) {
+                    text.push_str(entity);
+                }
+            },
+            Event::End(_) | Event::Empty(_) if !text.ends_with(char::is_whitespace) => text.push(' '),
+            Event::Eof => return Some(()),
+            _ => {}
+        }
+        buffer.clear();
+    }
+}
+
+/// Extracts the text of the XML documents `names` contained in the ZIP archive `archive`.
+fn office_text<R: Read + Seek, F: Fn(&str) -> bool>(archive: R, names: F) -> Option<String> {
+    let mut archive = ZipArchive::new(archive).ok()?;
+    let mut entries: Vec<String> = archive.file_names()
+        .filter(|name| names(name))
+        .map(str::to_owned)
+        .collect();
+    entries.sort();
+
+    let mut text = String::new();
+    for name in entries {
+        let entry = archive.by_name(&name).ok()?;
+        xml_text(entry.take(MAX_FILE_SIZE), &mut text)?;
+    }
+    Some(text)
+}
+
+/// Extracts the text of the file at `path`.
+///
+/// Returns `None` if the format of the file is not supported, if the file is too large, if the
+/// file is encrypted or if the file cannot be read or parsed.
+pub fn extract_text(path: &Path) -> Option<String> {
+    let format = DocumentFormat::from_path(path)?;
+    if path.metadata().ok()?.len() > MAX_FILE_SIZE || is_encrypted(path) { return None; }
+
+    let mut text = match format {
+        DocumentFormat::PlainText => String::from_utf8_lossy(&std::fs::read(path).ok()?).into_owned(),
+        DocumentFormat::Pdf => {
+            let contents = std::fs::read(path).ok()?;
+            // Malformed documents can make the PDF library panic.
+            std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(&contents)).ok()?.ok()?
+        },
+        DocumentFormat::Docx => office_text(std::fs::File::open(path).ok()?, |name| name == "word/document.xml")?,
+        DocumentFormat::Xlsx => office_text(std::fs::File::open(path).ok()?, |name| name == "xl/sharedStrings.xml")?,
+        DocumentFormat::Pptx => office_text(std::fs::File::open(path).ok()?, |name| {
+            name.starts_with("ppt/slides/slide") && name.ends_with(".xml")
+        })?,
+        DocumentFormat::OpenDocument => office_text(std::fs::File::open(path).ok()?, |name| name == "content.xml")?
+    };
+
+    if text.len() > MAX_TEXT_LENGTH {
+        let mut end = MAX_TEXT_LENGTH;
+        while !text.is_char_boundary(end) { end -= 1; }
+        text.truncate(end);
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use crate::api::search::text::{extract_text, is_supported};
+
+    #[test]
+    fn test_extract_text() {
+        let root = tempfile::tempdir().unwrap();
+
+        let path = root.path().join("notes.MD");
+        std::fs::write(&path, "# Notes\nRemember the milk").unwrap();
+        assert!(is_supported(&path));
+        assert_eq!(extract_text(&path).as_deref(), Some("# Notes\nRemember the milk"));
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("word/document.xml", SimpleFileOptions::default()).unwrap();
+        zip.write_all(br#"<?xml version="1.0"?><w:document xmlns:w="w"><w:body>
+            <w:p><w:r><w:t>Quarterly</w:t></w:r><w:r><w:t> report</w:t></w:r></w:p>
+            <w:p><w:r><w:t>Profits &amp; losses</w:t></w:r></w:p>
+        </w:body></w:document>"#).unwrap();
+        let path = root.path().join("report.docx");
+        std::fs::write(&path, zip.finish().unwrap().into_inner()).unwrap();
+        let text = extract_text(&path).unwrap();
+        let words: Vec<&str> = text.split_whitespace().collect();
+        assert_eq!(words, ["Quarterly", "report", "Profits", "&", "losses"]);
+
+        let path = root.path().join("broken.docx");
+        std::fs::write(&path, "Not an archive").unwrap();
+        assert_eq!(extract_text(&path), None);
+
+        let path = root.path().join("picture.png");
+        std::fs::write(&path, "Not text").unwrap();
+        assert!(!is_supported(&path));
+        assert_eq!(extract_text(&path), None);
+    }
+}
@@ -39,6 +39,7 @@ use tusk_core::config::Tusk;
 use tusk_core::error::{TuskError, TuskErrorResult, TuskHttpResult};
 use tusk_core::resources::{User};
 use tusk_derive::rest_resource;
+use crate::api::encryption;
 
 #[derive(Clone, Debug, Deserialize)]
 struct SessionPostData {
@@ -71,6 +72,9 @@ impl SessionResource {
         }
 
         tusk.log_in(&user)?;
+        if let Some(key) = encryption::recover(tusk.config(), &mut db_connection, user.id(), &session_create.password)? {
+            tusk.unlock(key)?;
+        }
         log::info!("User {} logged in", &session_create.email);
 
         Ok(HttpResponse::Created().finish())
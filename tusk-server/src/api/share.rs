@@ -0,0 +1,216 @@
+//! Contains the CRUD structures relative to the `/shares` REST resource.
+//!
+//! A share link gives access to a file or a storage to anyone who knows the link, without an
+//! account.
+//! Each link has a random token, an expiration date, an optional password and an optional limit on
+//! the number of downloads.
+//!
+//! # Security
+//! ## Creation
+//! A share link is created by `POST`ing the path of the item to be shared to the `/shares`
+//! resource.
+//! The same rules as in the Access section of the [`storage`](crate::api::storage) module apply
+//! to the shared item, with the additional rule that the item must be in the user's root or in the
+//! public root: items in the storages shared with the user, or in team folders, cannot be shared
+//! through links, and result in `FORBIDDEN`.
+//! Moreover, the expiration date must be in the future, otherwise the response is `BAD REQUEST`.
+//!
+//! ## Access
+//! A user can only list and revoke the links that they created.
+//! If any other user tries to access a link, the response will be `NOT FOUND`.
+//!
+//! ## Shared content
+//! The shared content is served on the www domain, under `/share/<token>/`, without a session.
+//! Shared storages are listed, and the items inside them can be accessed by appending their path
+//! to the link.
+//!
+//! If the link is protected by a password, the password must be given through HTTP Basic
+//! authentication, with any username; otherwise, the response is `UNAUTHORIZED`, together with
+//! a challenge for the credentials.
+//! If the link is expired or revoked, the response is `NOT FOUND`, while if the download limit has
+//! been reached, the response is `GONE`.
+//! Only the requests for a whole file, or for ranges starting at its beginning, count as
+//! downloads; requests for the rest of a file, as sent when resuming a download or seeking in a
+//! media file, are not counted and are served even once the limit has been reached.
+//!
+//! Links follow the shared items when they are moved inside the same root, while they are revoked
+//! when the items are moved to another root, deleted or replaced.
+//!
+//! Shared files are always served as attachments.
+//! Files encrypted at rest cannot be downloaded through share links, and result in `LOCKED`; see
+//! the [`encryption`](crate::api::encryption) module.
+
+use std::time::SystemTime;
+use actix_files::NamedFile;
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, web};
+use actix_web::http::header;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType, Range};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use tusk_core::config::{Tusk, TuskConfiguration};
+use tusk_core::error::{TuskError, TuskHttpResult};
+use tusk_core::resources::ShareLink;
+use tusk_derive::rest_resource;
+use crate::api::dav::DavPath;
+use crate::api::preview;
+use crate::api::storage::{PathInfo, system_type_from_epoch_delta};
+use crate::api::trash::epoch_delta;
+
+/// Returns `true` if the request only asks for parts of the file at `path` after its beginning,
+/// as when resuming a download, and `false` otherwise.
+fn is_resumed(req: &HttpRequest, path: &PathInfo) -> bool {
+    let Some(Range::Bytes(specs)) = req.get_header::<Range>() else { return false; };
+    let Ok(metadata) = path.as_ref().metadata() else { return false; };
+    metadata.is_file() && specs.iter()
+        .all(|spec| spec.to_satisfiable_range(metadata.len()).is_none_or(|(start, _)| start > 0))
+}
+
+/// Represents the CRUD **Create** structure relative to the `/shares` REST resource.
+#[derive(Debug, Deserialize)]
+pub struct CreateShareLinkData {
+    path: String,
+    expiration: i64,
+    password: Option<Secret<String>>,
+    max_downloads: Option<u32>
+}
+
+/// Represents the CRUD **Read** structure relative to the `/shares` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct ShareLinkRead {
+    id: Uuid,
+    token: String,
+    url: String,
+    path: String,
+    expiration: i64,
+    has_password: bool,
+    max_downloads: Option<u32>,
+    downloads: u32
+}
+impl ShareLinkRead {
+    /// Collects the information relative to the given link.
+    pub fn from_link(link: &ShareLink, config: &TuskConfiguration) -> ShareLinkRead {
+        ShareLinkRead {
+            id: link.id(),
+            token: link.token().to_owned(),
+            url: format!("https://{}/share/{}/", config.www_domain(), link.token()),
+            path: link.path().to_owned(),
+            expiration: epoch_delta(link.expiration()),
+            has_password: link.has_password(),
+            max_downloads: link.max_downloads(),
+            downloads: link.downloads()
+        }
+    }
+}
+
+/// Represents the `/shares` REST resource.
+///
+/// The `/shares` resource is responsible for creating share links and for listing the links
+/// created by the user.
+pub struct SharesResource;
+#[rest_resource("/shares")]
+impl SharesResource {
+    async fn get(tusk: Tusk) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+
+        let links: Vec<ShareLinkRead> = ShareLink::list_for_user(&mut db, user_id)?
+            .iter()
+            .map(|link| ShareLinkRead::from_link(link, tusk.config()))
+            .collect();
+
+        Ok(HttpResponse::Ok().json(links))
+    }
+
+    async fn post(tusk: Tusk, web::Json(data): web::Json<CreateShareLinkData>) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let path = PathInfo::from_queried_path(&tusk, &data.path)?;
+        let request_path = path.request_path();
+        let user_root = user_id.to_string();
+        let shareable = [user_root.as_str(), ".public"].into_iter()
+            .any(|root| request_path == root || request_path.starts_with(&format!("{root}/")));
+        if !shareable { return TuskError::forbidden().bail(); }
+        if !path.exists() { return TuskError::not_found().bail(); }
+        let expiration = system_type_from_epoch_delta(data.expiration);
+        if expiration <= SystemTime::now() { return TuskError::bad_request().bail(); }
+
+        let mut db = tusk.db()?;
+        let link = ShareLink::create(&mut db, user_id, request_path, expiration, data.password.as_ref(), data.max_downloads)?;
+
+        Ok(HttpResponse::Created()
+            .insert_header((header::LOCATION, format!("/v1/shares/{}", link.id())))
+            .json(ShareLinkRead::from_link(&link, tusk.config())))
+    }
+}
+
+/// Represents the `/shares/{share_link_id}` REST resource.
+///
+/// The `/shares/{share_link_id}` resource is responsible for reading or revoking a single
+/// share link.
+pub struct ShareLinkResource;
+#[rest_resource("/shares/{share_link_id}")]
+impl ShareLinkResource {
+    async fn get(tusk: Tusk, share_link_id: web::Path<Uuid>) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+        let link = ShareLink::from_id(&mut db, *share_link_id, user_id)?;
+
+        Ok(HttpResponse::Ok().json(ShareLinkRead::from_link(&link, tusk.config())))
+    }
+
+    async fn delete(tusk: Tusk, share_link_id: web::Path<Uuid>) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+        let link = ShareLink::from_id(&mut db, *share_link_id, user_id)?;
+        link.delete(&mut db)?;
+
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+/// Represents the `/share/{token}` resource on the www domain.
+///
+/// The `/share/{token}` resource is responsible for serving the shared content to anyone who
+/// knows the link.
+pub struct SharedContentResource;
+#[rest_resource("/share/{token}{filename:.*}")]
+impl SharedContentResource {
+    async fn get(tusk: Tusk, req: HttpRequest) -> TuskHttpResult {
+        let token = req.match_info().query("token");
+        let filename = req.match_info().query("filename")
+            .trim_start_matches('/');
+        let mut db = tusk.db()?;
+        let mut link = ShareLink::from_token(&mut db, token)?;
+
+        let password = DavPath::credentials(&req)
+            .map(|(_, password)| password);
+        if !link.verify_password(password.as_ref()) {
+            return Ok(HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, r#"Basic realm="Tusk share", charset="UTF-8""#))
+                .finish());
+        }
+
+        let path = PathInfo::from_share_link(tusk.config(), &link, filename)?;
+        let resumed = is_resumed(&req, &path);
+        if !link.valid() && !resumed { return TuskError::gone().bail(); }
+        if !path.exists() { return TuskError::not_found().bail(); }
+
+        if path.is_directory() {
+            let children = path.list_children()?;
+
+            Ok(HttpResponse::Ok().json(children))
+        } else {
+            if path.decryption().open(path.as_ref())?.is_some() { return TuskError::locked().bail(); }
+            if !resumed && !link.register_download(&mut db)? { return TuskError::gone().bail(); }
+            let disposition = ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(path.name())]
+            };
+
+            let response = NamedFile::open(&path)?
+                .set_content_disposition(disposition)
+                .into_response(&req);
+            Ok(preview::secure(response, &path.name(), false))
+        }
+    }
+}
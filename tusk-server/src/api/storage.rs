@@ -5,18 +5,60 @@
 //! Any user can access any file or storage, under the following conditions:
 //! - the user must be logged in;
 //! - the path should be a valid children of `srv/storage` (i.e. no traversal allowed);
-//! - the path of the resource is either in the public root `/.public/` or in the user's root
-//!   `/<user>/`, where `<user>` is the username of the user;
+//! - the path of the resource is either in the public root `/.public/`, in the user's root
+//!   `/<user>/`, where `<user>` is the username of the user, in a storage that another user
+//!   shared with the user (see the [`grant`](crate::api::grant) module), or in a team folder
+//!   `/teams/<name>/` of which the user is a member, either directly or through one of its roles;
 //! - the path of the resource exists.
 //!
 //! If any of these conditions fail, the response will be `UNAUTHORIZED`, if the user is not
 //! authenticated,  `FORBIDDEN`, if the user tried to access another user's storage, or
 //! `NOT FOUND`, if the resource the user is trying to access does not exist.
 //!
+//! Storages shared with read-only access, and team folders of which the user is a read-only
+//! member, cannot be modified; any attempt to create, copy into,
+//! move, delete or replace items inside them fails with `FORBIDDEN`.
+//!
+//! ## Archives
+//! A storage is downloaded as a single archive by `GET`ting the corresponding REST resource with
+//! the `archive` query parameter set to the format of the archive, either `zip` or `tar.gz`.
+//! The archive is streamed while it is written, and contains all the files and subdirectories of
+//! the storage; items that are neither regular files nor directories, such as symbolic links, are
+//! skipped.
+//!
+//! The same rules as in the Access section apply; requesting an archive of a file results in
+//! `BAD REQUEST`.
+//!
+//! ## Listings
+//! The items of a storage are listed by `GET`ting the corresponding REST resource, and are sorted
+//! by name unless the `sort` query parameter is set to `size` or `modified`; the `order` query
+//! parameter, either `asc` (default) or `desc`, sets the direction of the sorting.
+//! Listings are restricted to files or to storages by setting the `kind` query parameter, and to
+//! the items whose name starts, case-insensitively, with the `prefix` query parameter.
+//!
+//! Listings are paginated by setting the `limit` query parameter to the number of items in a page,
+//! at most 1000; if more items follow, the response contains the `Tusk-Next-Cursor` header, whose
+//! value is passed as the `cursor` query parameter to list the following page with the same
+//! sorting.
+//! An invalid limit or cursor results in `BAD REQUEST`.
+//!
+//! ## Thumbnails
+//! A thumbnail of an image is downloaded by `GET`ting the corresponding REST resource with the
+//! `thumbnail` query parameter set to the size of the thumbnail, in pixels; the available sizes
+//! are 64, 128, 256, 512 and 1024.
+//! Thumbnails are JPEG images that fit in a square of the given size, and are cached until the
+//! image is modified.
+//!
+//! The same rules as in the Access section apply; requesting a thumbnail of a storage, or of
+//! a size that is not available, results in `BAD REQUEST`, while requesting a thumbnail of a file
+//! that is not a JPEG, PNG, GIF or WebP image results in `UNSUPPORTED MEDIA TYPE`.
+//!
 //! ## Creation
 //! A subdirectory is created by `POST`ing the relative metadata to the storage in which the
 //! subdirectory should be created.
 //! Similarly, a file is created by `POST`ing the metadata and the contents of the file.
+//! The `created`, `last_access` and `last_modified` fields of the metadata, if present, are
+//! applied to the new item, so that uploads preserve the timestamps of the original.
 //!
 //! The same rules as in the Access section apply, with the additional rule that:
 //! - no item should exist in the storage with the same name.
@@ -24,27 +66,160 @@
 //! Response upon failure is, again, the same as in the Access section, with the additional
 //! response `CONFLICT` in case the item that the user is trying to create already exists.
 //!
+//! ## Extraction
+//! An archive is extracted into a storage by `POST`ing the metadata of a file, with the `extract`
+//! field set to the format of the archive (either `zip` or `tar.gz`), together with the contents of
+//! the archive.
+//! The directories of the archive are merged with the existing directories of the storage.
+//!
+//! The same rules as in the Creation section apply to every entry of the archive, with the
+//! additional rules that:
+//! - every entry must be extracted inside the storage, otherwise the response is `BAD REQUEST`;
+//! - the archive cannot have more than `max_archive_entries` entries, and its files cannot exceed
+//!   `max_archive_size` bytes in total, as set in the `tusk.serve` section of `tusk.toml`,
+//!   otherwise the response is `PAYLOAD TOO LARGE`.
+//!
+//! Entries that are neither regular files nor directories, such as symbolic links, are skipped.
+//! If any entry cannot be extracted, nothing is extracted.
+//!
+//! ## Copy
+//! A file or subdirectory is copied by `POST`ing the relative metadata, together with the path of
+//! the item to be copied, to the storage in which the copy should be created.
+//! Directories are copied recursively.
+//!
+//! The same rules as in the Creation section apply to the new item, and the same rules as in the
+//! Access section apply to the item to be copied, with the additional rule that:
+//! - a directory cannot be copied inside itself.
+//!
+//! Items inside a copied directory that cannot be copied are skipped and reported in the response.
+//! If the server runs out of space while copying, the partial copy is removed and the response is
+//! `INSUFFICIENT STORAGE`.
+//!
+//! ## Replacement
+//! The contents of an existing file are replaced by `PUT`ting the new contents to the
+//! corresponding REST resource.
+//! The new contents are written to a temporary file, which then atomically replaces the file, so
+//! that the file is never seen partially written.
+//!
+//! The request must be conditional, either with an `If-Match` header holding the entity tag of
+//! the file or with a `last_modified` query parameter holding its last modification time, as
+//! reported when listing the storage; otherwise, the response is `PRECONDITION REQUIRED`.
+//! If the file changed in the meantime, the response is `PRECONDITION FAILED` and the file is
+//! left untouched.
+//!
+//! The same rules as in the Access section apply, with the additional response `CONFLICT` in case
+//! the path points to a storage.
+//!
 //! ## Deletion
 //! A file or subdirectory is deleted by `DELETE`ing the corresponding REST resource.
 //!
 //! The same rules as in the Access section apply.
+//!
+//! Deleted items are not removed immediately, but moved to the trash of the user; see the
+//! [`trash`](crate::api::trash) module.
+//!
+//! ## Moving
+//! A file or subdirectory is moved (or renamed) by `PATCH`ing the corresponding REST resource
+//! with the new path of the item.
+//!
+//! The same rules as in the Access section apply to both the source and the destination, with
+//! the additional rules that:
+//! - neither the source nor the destination can be a user root;
+//! - a directory cannot be moved inside itself;
+//! - no item should exist at the destination, unless overwriting is explicitly requested.
+//!
+//! Response upon failure is the same as in the Creation section, with the additional response
+//! `BAD REQUEST` in case the user tried to move a directory inside itself.
+//!
+//! ## Conditional requests
+//! Files and storages are tagged with strong entity tags, sent in the `ETag` header of the
+//! responses to `GET`: the tag of a file changes whenever its contents change, while the tag of
+//! a storage changes whenever its listing changes.
+//!
+//! `GET` requests with an `If-None-Match` header matching the current tag result in
+//! `NOT MODIFIED`.
+//! Creations and extractions into a storage, copies into a storage, moves and deletions are only
+//! performed if the `If-Match` and `If-None-Match` headers, if any, hold for the target storage,
+//! for the moved item or for the deleted item respectively; otherwise, the response is
+//! `PRECONDITION FAILED`.
+//!
+//! ## Downloads
+//! Files are served with a download policy that keeps the browser from running the scripts they
+//! contain: active content, such as HTML and SVG, is always downloaded as an attachment, and can
+//! only be previewed from a separate domain; see the [`preview`](crate::api::preview) module.
+//!
+//! ## Encryption
+//! When encryption at rest is enabled, the files written in the root of the user are encrypted
+//! with the data key of the user, and encrypted files are decrypted when downloaded; see the
+//! [`encryption`](crate::api::encryption) module.
+//!
+//! ## Quota
+//! The files in a user root cannot exceed the quota of the user, if any; when no quota is assigned
+//! to the user, the largest quota among the roles of the user applies.
+//! The items deleted from the user root and the previous versions of its files count towards the
+//! quota as well, until they are purged.
+//! The public root `/.public/` and the team folders have no quota.
+//!
+//! Any creation, copy or move that would exceed the quota fails with `INSUFFICIENT STORAGE`.
+//! The current usage is reported by the `/storage/quota` resource.
+//!
+//! ## Versions
+//! Whenever the contents of a file are replaced, the previous contents are kept as a new version
+//! of the file; see the [`version`](crate::api::version) module.
+//!
+//! ## Storage backends
+//! Files are stored in the `user_directories` of the local filesystem, which is the only backend
+//! available in the `tusk.storage` section of `tusk.toml` (see the
+//! [`storage`](tusk_core::storage) module of `tusk-core`).
 
-use std::io::{ErrorKind};
+mod archive;
+mod listing;
+mod thumbnail;
+pub mod times;
+
+use std::io::{ErrorKind, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
 use std::time::{Duration, SystemTime};
 use actix_files::NamedFile;
 use actix_multipart::form::json::Json;
 use actix_multipart::form::MultipartForm;
 use actix_multipart::form::tempfile::TempFile;
-use actix_web::{FromRequest, HttpRequest, HttpResponse};
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse, web};
 use actix_web::dev::Payload;
-use actix_web::http::header;
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType, EntityTag, ETag, IfMatch, IfNoneMatch, TryIntoHeaderValue};
 use path_clean::clean;
 use serde::{Deserialize, Serialize, Serializer};
 use serde::ser::SerializeMap;
-use tusk_core::config::{BoxedAsyncBlock, Tusk};
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+use tempfile::TempPath;
+use uuid::Uuid;
+use tusk_core::PgConnection;
+use tusk_core::config::{BoxedAsyncBlock, Tusk, TuskConfiguration};
+use actix_web::ResponseError;
+use tusk_core::encryption::{DataKey, Header, HEADER_SIZE};
 use tusk_core::error::{HttpOkOr, TuskError, TuskHttpResult, TuskResult};
+use tusk_core::resources::{Blob, FileVersion, Quota, ShareLink, StorageGrant, StorageOperation, TeamFolder, TrashItem, User};
 use tusk_derive::rest_resource;
+use crate::api::changes::{record_change, record_deletion};
+use crate::api::deduplication::{self, Reservation};
+use crate::api::encryption::{self, Decryption};
+use crate::api::preview;
+use crate::api::search::{ContentIndex, IndexTask};
+use crate::api::trash::{epoch_delta, purge_expired};
+use crate::api::version::prune_versions;
+pub use archive::{ArchiveEntry, ArchiveEntryKind, ArchiveFormat};
+pub use listing::{ListingOptions, ListingOrder, ListingPage, ListingSort};
+pub use times::PathTimes;
+
+/// Header containing the cursor of the following page of a listing.
+const NEXT_CURSOR: &str = "Tusk-Next-Cursor";
+
+/// Serializes the replacements of files, so that the preconditions of a replacement are evaluated
+/// and the file is replaced without other replacements in between.
+static REPLACE_LOCK: Mutex<()> = Mutex::new(());
 
 /// Interprets the specified integer into a signed distance, in seconds, from
 /// [`SystemTime::UNIX_EPOCH`], and converts it into a [`SystemTime`].
@@ -56,6 +231,160 @@ pub fn system_type_from_epoch_delta(delta: i64) -> SystemTime {
     }
 }
 
+/// Returns `true` if the given name is a valid name for a file or a directory, that is,
+/// if it does not contain the symbols `\`, `/` and it is not `.` or `..`.
+fn is_valid_name(name: &str) -> bool {
+    !name.contains(['/', '\\']) && name != "." && name != ".."
+}
+
+/// Returns the total size, in bytes, of the files at the given path, recursively.
+///
+/// Items that cannot be read are not counted.
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = path.symlink_metadata() else { return 0; };
+    if !metadata.is_dir() { return metadata.len(); }
+
+    std::fs::read_dir(path)
+        .map(|entries| entries
+            .filter_map(Result::ok)
+            .map(|entry| disk_usage(&entry.path()))
+            .sum())
+        .unwrap_or(0)
+}
+
+/// Moves the item at `from` to `to`, which must not exist, as [`std::fs::rename`] does.
+///
+/// If the two paths are on different filesystems, as the trash can be, the item is copied
+/// together with its times and then removed; if the copy fails, the partial copy is removed and
+/// the item is left in place.
+fn move_item(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {},
+        result => return result
+    }
+    if to.symlink_metadata().is_ok() { return Err(ErrorKind::AlreadyExists.into()); }
+    if let Err(e) = copy_item(from, to) {
+        let _ = remove_item(to);
+        return Err(e);
+    }
+    remove_item(from)
+}
+/// Copies the item at `from` to `to`, copying directories recursively, together with the times of
+/// the items.
+fn copy_item(from: &Path, to: &Path) -> std::io::Result<()> {
+    let metadata = from.symlink_metadata()?;
+    if metadata.is_dir() {
+        std::fs::create_dir(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_item(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else if metadata.is_file() {
+        std::fs::copy(from, to)?;
+    } else {
+        return Err(ErrorKind::Unsupported.into());
+    }
+    times::copy(from, to)
+}
+/// Removes the item at `path`, removing directories recursively.
+fn remove_item(path: &Path) -> std::io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+/// Returns the size, in bytes, of the plain contents of the file at `path`, whose size on disk is
+/// `size`, if the file is encrypted, or `size` otherwise.
+///
+/// Only the files in the user roots can be encrypted, hence this function must not be called
+/// on the other files.
+fn plain_size(path: &Path, size: u64) -> u64 {
+    if size < HEADER_SIZE { return size; }
+    Header::of(path).ok().flatten()
+        .filter(|header| header.encrypted_size() == size)
+        .map_or(size, |header| header.size())
+}
+
+/// Returns the access of the given user to the team folder containing `queried_path`, relative to
+/// the storage root: `Some(true)` for read-write access, `Some(false)` for read-only access and
+/// `None` if the path is not inside a team folder of which the user is a member.
+fn team_access(db: &mut PgConnection, initiator: &User, queried_path: &Path) -> TuskResult<Option<bool>> {
+    let mut components = queried_path.iter();
+    if components.next() != Some(TeamFolder::ROOT.as_ref()) { return Ok(None); }
+    let Some(name) = components.next().and_then(|name| name.to_str()) else { return Ok(None); };
+
+    match TeamFolder::from_name(db, name)? {
+        Some(team_folder) => team_folder.access_for(db, initiator.id()),
+        None => Ok(None)
+    }
+}
+
+/// Returns the strong entity tag of a storage, given the items of its listing.
+///
+/// Only the name, the kind, the size and the last modification time of the items are tagged, so
+/// that reading a file, which changes its last access time, does not change the tag.
+fn listing_etag(items: &[StoragePathRead]) -> EntityTag {
+    let mut hasher = Sha256::new();
+    for item in items {
+        let kind: &[u8] = match item.kind {
+            StoragePathReadKind::File { .. } => b"f",
+            StoragePathReadKind::Directory { .. } => b"d",
+            StoragePathReadKind::None => b"n"
+        };
+        hasher.update((item.filename.len() as u64).to_be_bytes());
+        hasher.update(item.filename.as_bytes());
+        hasher.update(kind);
+        hasher.update(item.size().unwrap_or_default().to_be_bytes());
+        hasher.update(item.last_modified.to_be_bytes());
+    }
+    let digest = hasher.finalize();
+    EntityTag::new_strong(digest[..16].iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Evaluates the `If-Match` and `If-None-Match` headers of the request against the entity tag of
+/// the requested item, where `etag` is `None` if the item does not exist.
+///
+/// Returns `false` if the request is a `GET` or `HEAD` request whose `If-None-Match` header
+/// matches the item, i.e. if the response should be 304 `NOT MODIFIED`, and `true` otherwise.
+///
+/// # Errors
+/// If the `If-Match` header does not match the item, or if the `If-None-Match` header matches the
+/// item on any other request, this function returns an HTTP error 412 `PRECONDITION FAILED`.
+pub fn evaluate_preconditions(req: &HttpRequest, etag: Option<&EntityTag>) -> TuskResult<bool> {
+    let if_match = match req.get_header::<IfMatch>() {
+        None => true,
+        Some(IfMatch::Any) => etag.is_some(),
+        Some(IfMatch::Items(tags)) => etag.is_some_and(|etag| tags.iter().any(|tag| tag.strong_eq(etag)))
+    };
+    if !if_match { return TuskError::precondition_failed().bail(); }
+
+    let if_none_match = match req.get_header::<IfNoneMatch>() {
+        None => false,
+        Some(IfNoneMatch::Any) => etag.is_some(),
+        Some(IfNoneMatch::Items(tags)) => etag.is_some_and(|etag| tags.iter().any(|tag| tag.weak_eq(etag)))
+    };
+    if !if_none_match { return Ok(true); }
+    if req.method() == Method::GET || req.method() == Method::HEAD { return Ok(false); }
+
+    TuskError::precondition_failed().bail()
+}
+
+/// Evaluates the `If-Match` and `If-None-Match` headers of the request against the entity tag of
+/// the item at `path`, as in [`evaluate_preconditions`]; the entity tag, which is costly for large
+/// storages, is only computed if the request has any of those headers.
+///
+/// # Errors
+/// See [`evaluate_preconditions`].
+fn check_preconditions(req: &HttpRequest, path: &PathInfo) -> TuskResult<bool> {
+    let headers = req.headers();
+    if !headers.contains_key(header::IF_MATCH) && !headers.contains_key(header::IF_NONE_MATCH) {
+        return Ok(true);
+    }
+    evaluate_preconditions(req, path.etag()?.as_ref())
+}
+
 /// Resource extractor for the requested path.
 ///
 /// Performs the necessary checks and then outputs a valid, authorized path to an existing resource.
@@ -63,7 +392,12 @@ pub fn system_type_from_epoch_delta(delta: i64) -> SystemTime {
 pub struct PathInfo {
     depth: usize,
     root: PathBuf,
-    path: PathBuf
+    path: PathBuf,
+    quota: Option<u64>,
+    writable: bool,
+    initiator: Uuid,
+    owner: Option<Uuid>,
+    key: Option<DataKey>
 }
 impl PathInfo {
     /// Creates a directory in the path.
@@ -77,23 +411,34 @@ impl PathInfo {
     ///
     /// If the storage already exists, this function returns an HTTP error 409 `CONFLICT`.
     ///
+    /// If this path is read-only, this function returns an HTTP error 403 `FORBIDDEN`.
+    ///
+    /// If the user root is already over its quota, this function returns an HTTP error
+    /// 507 `INSUFFICIENT STORAGE`.
+    ///
     /// Finally, for any other error, the function returns 500 `INTERNAL SERVER ERROR`.
-    pub fn create_dir(&self, data: CreateDirectoryData) -> TuskResult<Self> {
+    pub fn create_dir(&self, config: &TuskConfiguration, db: &mut PgConnection, data: CreateDirectoryData) -> TuskResult<Self> {
+        self.check_writable()?;
         let name = data.name();
-        if name.contains(|c| c == '/' || c == '\\') || name == "." || name == ".." {
+        if !is_valid_name(name) {
             return TuskError::bad_request().bail();
         }
+        self.check_quota(config, db, 0)?;
 
         let mut path = self.path.clone();
         path.push(name);
 
         match std::fs::create_dir(&path) {
-            Ok(()) => Ok({
+            Ok(()) => {
+                if let Err(e) = data.times().apply(&path) {
+                    let _ = std::fs::remove_dir(&path);
+                    return TuskError::internal_server_error().with_error(e).log_error().bail();
+                }
                 let mut child = self.clone();
                 child.path = path;
                 child.depth += 1;
-                child
-            }),
+                Ok(child)
+            },
             Err(e) if e.kind() == ErrorKind::AlreadyExists => TuskError::conflict().bail(),
             Err(e) if e.kind() == ErrorKind::NotFound => TuskError::not_found().bail(),
             Err(e) if e.kind() == ErrorKind::PermissionDenied => TuskError::forbidden().bail(),
@@ -101,69 +446,853 @@ impl PathInfo {
         }
     }
 
-    /// Creates a file in the path.
+    /// Creates a file in the path.
+    ///
+    /// # Errors
+    /// If the directory to be created contains the symbols `\`, `/` or is `.` or `..`, then
+    /// the name of the file is not valid and this function returns an HTTP error 400 `BAD REQUEST`.
+    ///
+    /// If the parent of this directory does not exist, this function returns an HTTP error
+    /// 404 `NOT FOUND`.
+    ///
+    /// If the storage already exists, this function returns an HTTP error 409 `CONFLICT`.
+    ///
+    /// If this path is read-only, this function returns an HTTP error 403 `FORBIDDEN`.
+    ///
+    /// If the file would exceed the quota of the user root, this function returns an HTTP error
+    /// 507 `INSUFFICIENT STORAGE`.
+    ///
+    /// Finally, for any other error, the function returns 500 `INTERNAL SERVER ERROR`.
+    pub fn create_file(&self, config: &TuskConfiguration, db: &mut PgConnection, data: CreateFileData) -> TuskResult<Self> {
+        let times = data.times();
+        let payload = data.into_payload();
+        let name = payload.file_name
+            .or_bad_request()?;
+        let file = payload.file.into_temp_path();
+        // The timestamps are applied before persisting the file, so that it never appears with
+        // the wrong timestamps.
+        times.apply(&file)
+            .map_err(|e| TuskError::internal_server_error().with_error(e).log_error())?;
+
+        self.persist_file(config, db, &name, file)
+    }
+
+    /// Checks whether an item with the given `name` can be created in the path, without
+    /// creating it.
+    ///
+    /// # Errors
+    /// If the item contains the symbols `\`, `/` or is `.` or `..`, then the name is not valid
+    /// and this function returns an HTTP error 400 `BAD REQUEST`.
+    ///
+    /// If this path does not exist, this function returns an HTTP error 404 `NOT FOUND`.
+    ///
+    /// If this path is not a storage or the item already exists, this function returns an HTTP
+    /// error 409 `CONFLICT`.
+    ///
+    /// If this path is read-only, this function returns an HTTP error 403 `FORBIDDEN`.
+    pub fn check_new_child(&self, name: &str) -> TuskResult<()> {
+        self.check_writable()?;
+        if !is_valid_name(name) { return TuskError::bad_request().bail(); }
+        if !self.path.exists() { return TuskError::not_found().bail(); }
+        if !self.path.is_dir() { return TuskError::conflict().bail(); }
+
+        let mut path = self.path.clone();
+        path.push(name);
+        if path.symlink_metadata().is_ok() { return TuskError::conflict().bail(); }
+
+        Ok(())
+    }
+
+    /// Moves the temporary `file` into the path, with the given `name`.
+    ///
+    /// # Errors
+    /// If the file to be created contains the symbols `\`, `/` or is `.` or `..`, then
+    /// the name of the file is not valid and this function returns an HTTP error 400 `BAD REQUEST`.
+    ///
+    /// If this path does not exist, this function returns an HTTP error 404 `NOT FOUND`.
+    ///
+    /// If the file already exists, this function returns an HTTP error 409 `CONFLICT`.
+    ///
+    /// If this path is read-only, this function returns an HTTP error 403 `FORBIDDEN`.
+    ///
+    /// If the file would exceed the quota of the user root, this function returns an HTTP error
+    /// 507 `INSUFFICIENT STORAGE`.
+    ///
+    /// Finally, for any other error, the function returns 500 `INTERNAL SERVER ERROR`.
+    pub fn persist_file(&self, config: &TuskConfiguration, db: &mut PgConnection, name: &str, file: TempPath) -> TuskResult<Self> {
+        self.check_writable()?;
+        if !is_valid_name(name) {
+            return TuskError::bad_request().bail();
+        }
+        let file = self.encrypt(config, file)?;
+        self.check_quota(config, db, disk_usage(&file))?;
+
+        let mut path = self.path.clone();
+        path.push(name);
+
+        let blob = self.deduplicate(config, db, &file)?;
+        let result = file.persist_noclobber(&path);
+        if let (Err(_), Some(blob)) = (&result, blob.clone()) {
+            deduplication::forget(config, db, blob)?;
+        }
+        match result {
+            Ok(_) => {
+                let mut child = self.clone();
+                child.path = path;
+                child.depth += 1;
+                deduplication::refer(config, db, &child.request_path(), blob)?;
+                ContentIndex::schedule(config, IndexTask::Update(child.request_path()));
+                Ok(child)
+            },
+            Err(e) if e.error.kind() == ErrorKind::AlreadyExists => TuskError::conflict().bail(),
+            Err(e) if e.error.kind() == ErrorKind::NotFound => TuskError::not_found().bail(),
+            Err(e) if e.error.kind() == ErrorKind::PermissionDenied => TuskError::forbidden().bail(),
+            Err(e) => TuskError::internal_server_error().with_error(e).log_error().bail()
+        }
+    }
+
+    /// Creates in this path a copy of the item at path `source`, copying directories recursively.
+    ///
+    /// Returns the path of the copy, together with the list of the items that could not be copied.
+    ///
+    /// # Errors
+    /// If the copy to be created contains the symbols `\`, `/` or is `.` or `..`, if its kind does
+    /// not match the kind of `source`, or if it would be created inside `source`, this function
+    /// returns an HTTP error 400 `BAD REQUEST`.
+    ///
+    /// If `source` or this path do not exist, this function returns an HTTP error 404 `NOT FOUND`.
+    ///
+    /// If the copy already exists, this function returns an HTTP error 409 `CONFLICT`.
+    ///
+    /// If this path is read-only, this function returns an HTTP error 403 `FORBIDDEN`.
+    ///
+    /// If the copy would exceed the quota of the user root, this function returns an HTTP error
+    /// 507 `INSUFFICIENT STORAGE`; similarly, if the server runs out of space during the copy,
+    /// the partial copy is removed and this function returns the same error.
+    ///
+    /// If the copy is created in another root, and either the data key of the user who owns this
+    /// path or the data key needed to decrypt `source` is not available, this function returns an
+    /// HTTP error 423 `LOCKED`; see [`PathInfo::convert`].
+    ///
+    /// Finally, for any other error, the function returns 500 `INTERNAL SERVER ERROR`.
+    pub fn create_copy(&self, config: &TuskConfiguration, db: &mut PgConnection, source: &PathInfo, data: CopyPathData) -> TuskResult<(Self, Vec<CopyFailure>)> {
+        self.check_writable()?;
+        let name = data.name();
+        if !is_valid_name(name) {
+            return TuskError::bad_request().bail();
+        }
+        if !source.path.exists() { return TuskError::not_found().bail(); }
+        if source.is_directory() != (data.kind() == PathKind::Directory) {
+            return TuskError::bad_request().bail();
+        }
+
+        let mut path = self.path.clone();
+        path.push(name);
+        if path.starts_with(&source.path) { return TuskError::bad_request().bail(); }
+        self.check_quota(config, db, disk_usage(&source.path))?;
+        let converted = source.owner != self.owner;
+        if converted { self.check_conversion(config, &source.decryption(), &source.path)?; }
+
+        let mut failures = Vec::new();
+        let result = if source.is_directory() {
+            std::fs::create_dir(&path)
+                .map_err(Self::copy_error)
+                .and_then(|()| self.copy_children(&source.path, &path, &mut failures))
+        } else {
+            Self::copy_file(&source.path, &path)
+                .map_err(Self::copy_error)
+        };
+        let result = result.and_then(|()| if converted {
+            self.convert(config, db, &source.decryption(), &path)
+        } else {
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => {
+                let mut child = self.clone();
+                child.path = path;
+                child.depth += 1;
+                ContentIndex::schedule(config, IndexTask::Update(child.request_path()));
+                Ok((child, failures))
+            },
+            Err(e) => {
+                // Do not leave a partial copy behind; the item did not exist before the copy.
+                if e.status_code() != StatusCode::CONFLICT {
+                    let _ = if path.is_dir() { std::fs::remove_dir_all(&path) } else { std::fs::remove_file(&path) };
+                }
+                Err(e)
+            }
+        }
+    }
+    /// Recursively copies the children of the directory `from` into the directory `to`, adding to
+    /// `failures` the children that cannot be copied.
+    ///
+    /// Stops and returns an error only if the server runs out of space.
+    fn copy_children(&self, from: &Path, to: &Path, failures: &mut Vec<CopyFailure>) -> TuskResult<()> {
+        let entries = match std::fs::read_dir(from) {
+            Ok(entries) => entries,
+            Err(e) => {
+                failures.push(CopyFailure { path: self.request_path_of(from), status: TuskError::from(e).status_code().as_u16() });
+                return Ok(());
+            }
+        };
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    failures.push(CopyFailure { path: self.request_path_of(from), status: TuskError::from(e).status_code().as_u16() });
+                    continue;
+                }
+            };
+            let from = entry.path();
+            let mut to = to.to_path_buf();
+            to.push(entry.file_name());
+
+            let result = match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => std::fs::create_dir(&to)
+                    .map_err(Self::copy_error)
+                    .and_then(|()| self.copy_children(&from, &to, failures)),
+                Ok(file_type) if file_type.is_file() => Self::copy_file(&from, &to)
+                    .map_err(Self::copy_error),
+                Ok(_) => TuskError::unprocessable_entity().bail(),
+                Err(e) => Err(Self::copy_error(e))
+            };
+            match result {
+                Ok(()) => {},
+                Err(e) if e.status_code() == StatusCode::INSUFFICIENT_STORAGE => return Err(e),
+                Err(e) => failures.push(CopyFailure { path: self.request_path_of(&from), status: e.status_code().as_u16() })
+            }
+        }
+
+        Ok(())
+    }
+    /// Copies the contents of the file `from` into the new file `to`, without overwriting `to`
+    /// if it already exists.
+    fn copy_file(from: &Path, to: &Path) -> std::io::Result<()> {
+        let mut source = std::fs::File::open(from)?;
+        let mut destination = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(to)?;
+        if let Err(e) = std::io::copy(&mut source, &mut destination) {
+            let _ = std::fs::remove_file(to);
+            return Err(e);
+        }
+        Ok(())
+    }
+    /// Converts an error occurred while copying into the corresponding HTTP error.
+    fn copy_error(e: std::io::Error) -> TuskError {
+        match e.kind() {
+            ErrorKind::AlreadyExists => TuskError::conflict(),
+            ErrorKind::NotFound => TuskError::not_found(),
+            ErrorKind::PermissionDenied => TuskError::forbidden(),
+            ErrorKind::StorageFull | ErrorKind::QuotaExceeded => TuskError::insufficient_storage().with_error(e).log_error(),
+            _ => TuskError::internal_server_error().with_error(e).log_error()
+        }
+    }
+
+    /// Extracts the uploaded archive into this path, merging the directories of the archive with
+    /// the existing ones.
+    ///
+    /// Returns the information relative to the items at the top level of the archive.
+    ///
+    /// # Errors
+    /// If the archive is malformed, or if any of its entries would be extracted outside this path,
+    /// this function returns an HTTP error 400 `BAD REQUEST`.
+    ///
+    /// If this path does not exist, this function returns an HTTP error 404 `NOT FOUND`.
+    ///
+    /// If this path is not a storage, or if any file of the archive already exists, this function
+    /// returns an HTTP error 409 `CONFLICT`.
+    ///
+    /// If this path is read-only, this function returns an HTTP error 403 `FORBIDDEN`.
+    ///
+    /// If the archive has too many entries, or its files are too large in total, according to the
+    /// `max_archive_entries` and `max_archive_size` options of `tusk.toml`, this function returns
+    /// an HTTP error 413 `PAYLOAD TOO LARGE`.
+    ///
+    /// If the files of the archive would exceed the quota of the user root, this function returns
+    /// an HTTP error 507 `INSUFFICIENT STORAGE`.
+    ///
+    /// If the files of the archive have to be encrypted but the data key of the user who owns this
+    /// path is not available, this function returns an HTTP error 423 `LOCKED`.
+    ///
+    /// In all of these cases, nothing is extracted; if an error occurs during the extraction, the
+    /// items extracted up to that point are removed.
+    ///
+    /// Finally, for any other error, the function returns 500 `INTERNAL SERVER ERROR`.
+    pub fn extract_archive(&self, config: &TuskConfiguration, db: &mut PgConnection, data: ExtractArchiveData) -> TuskResult<Vec<StoragePathRead>> {
+        self.check_writable()?;
+        if !self.path.exists() { return TuskError::not_found().bail(); }
+        if !self.path.is_dir() { return TuskError::conflict().bail(); }
+
+        let archive = data.payload.file.path();
+        let entries = archive::read_entries(archive, data.format, config.max_archive_entries(), config.max_archive_size())?;
+        for entry in &entries {
+            archive::check_entry(&self.path, entry)?;
+        }
+        self.check_quota(config, db, entries.iter()
+            .filter(|entry| entry.kind() == ArchiveEntryKind::File)
+            .map(ArchiveEntry::size)
+            .sum())?;
+        self.check_unlocked(config)?;
+
+        // The contents of the archive are plain, whatever they look like.
+        let mut created = Vec::new();
+        let result = archive::extract(archive, data.format, &self.path, &mut created)
+            .and_then(|()| if self.encrypted(config) {
+                created.iter()
+                    .filter(|path| path.is_file())
+                    .try_for_each(|path| self.convert(config, db, &Decryption::plain(), path))
+            } else {
+                Ok(())
+            });
+        if let Err(e) = result {
+            for path in created.iter().rev() {
+                let _ = if path.is_dir() { std::fs::remove_dir(path) } else { std::fs::remove_file(path) };
+            }
+            return Err(e);
+        }
+
+        let mut items: Vec<&std::ffi::OsStr> = entries.iter()
+            .filter_map(|entry| entry.path().iter().next())
+            .collect();
+        items.sort();
+        items.dedup();
+        for name in &items {
+            let request_path = format!("{}/{}", self.request_path(), name.to_string_lossy());
+            ContentIndex::schedule(config, IndexTask::Update(request_path));
+        }
+        items.into_iter()
+            .map(|name| self.item_info(&self.path.join(name)))
+            .collect()
+    }
+
+    /// Returns the information relative to the path.
+    ///
+    /// See [`StoragePathRead::from_path`] for more information.
+    pub fn info(&self) -> TuskResult<StoragePathRead> {
+        self.item_info(&self.path)
+    }
+    /// Returns the information relative to the item at `path`, inside this path; encrypted files
+    /// report the size of their plain contents.
+    fn item_info(&self, path: &Path) -> TuskResult<StoragePathRead> {
+        let item = StoragePathRead::from_path(path)?;
+        if self.owner.is_none() { return Ok(item); }
+        Ok(item.decrypted(path))
+    }
+
+    /// Returns the strong entity tag of the item at this path, or `None` if the item does not
+    /// exist.
+    ///
+    /// Files are tagged by their size and last modification time, while storages are tagged by
+    /// their listing; encrypted files are tagged by the size of their encrypted contents, so that
+    /// they need not be read.
+    pub fn etag(&self) -> TuskResult<Option<EntityTag>> {
+        let Ok(metadata) = self.path.metadata() else { return Ok(None); };
+        if metadata.is_dir() {
+            let listing = listing::list(&self.path, &ListingOptions::default(), false)?;
+            return Ok(Some(listing_etag(&listing.items)));
+        }
+
+        let modified = metadata.modified()?
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Ok(Some(EntityTag::new_strong(format!("{:x}-{modified:x}", metadata.len()))))
+    }
+
+    /// Deletes the item at this path, moving it to the trash of the user owning the user root
+    /// containing it or, in the public root and in team folders, of the user who requested the
+    /// path.
+    ///
+    /// Returns the record of the deleted item, which can be used to restore it.
+    ///
+    /// # Errors
+    /// If this path is a user root or is read-only, this function returns an HTTP error
+    /// 403 `FORBIDDEN`.
+    ///
+    /// If the path does not exist, this function returns an HTTP error 404 `NOT FOUND`.
+    ///
+    /// Finally, for any other error, the function returns 500 `INTERNAL SERVER ERROR`.
+    pub fn delete(self, config: &TuskConfiguration, db: &mut PgConnection) -> TuskResult<TrashItem> {
+        if self.depth == 0 { return TuskError::forbidden().bail(); }
+        self.check_writable()?;
+        if self.path.symlink_metadata().is_err() { return TuskError::not_found().bail(); }
+
+        let item = self.trash(config, db, &self.path)?;
+        deduplication::release(config, db, &self.request_path())?;
+        StorageGrant::delete_under(db, self.request_path())?;
+        ShareLink::delete_under(db, self.request_path())?;
+        thumbnail::evict(&config.thumbnails_directory(), &self.request_path());
+        ContentIndex::schedule(config, IndexTask::Remove(self.request_path()));
+        Ok(item)
+    }
+    /// Moves the item stored at `stored`, which was at this path, to the trash as in
+    /// [`PathInfo::delete`].
+    fn trash(&self, config: &TuskConfiguration, db: &mut PgConnection, stored: &Path) -> TuskResult<TrashItem> {
+        let item = TrashItem::create(db, self.owner.unwrap_or(self.initiator), self.request_path())?;
+        let trashed = item.file(config);
+        let result = trashed.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| move_item(stored, &trashed));
+
+        match result {
+            Ok(()) => Ok(item),
+            Err(e) => {
+                item.delete(db)?;
+                match e.kind() {
+                    ErrorKind::NotFound => TuskError::not_found().bail(),
+                    ErrorKind::PermissionDenied => TuskError::forbidden().bail(),
+                    _ => TuskError::internal_server_error().with_error(e).log_error().bail()
+                }
+            }
+        }
+    }
+    /// Moves the deleted item stored at `trashed` back to this path.
+    ///
+    /// # Errors
+    /// If this path is a user root or is read-only, this function returns an HTTP error
+    /// 403 `FORBIDDEN`.
+    ///
+    /// If the deleted item does not exist, this function returns an HTTP error 404 `NOT FOUND`.
+    ///
+    /// If the parent of this path is not an existing storage, or if an item already exists at this
+    /// path, this function returns an HTTP error 409 `CONFLICT`.
+    ///
+    /// If the item would exceed the quota of the user root, this function returns an HTTP error
+    /// 507 `INSUFFICIENT STORAGE`.
+    ///
+    /// Finally, for any other error, the function returns 500 `INTERNAL SERVER ERROR`.
+    pub fn restore(&self, config: &TuskConfiguration, db: &mut PgConnection, trashed: &Path) -> TuskResult<()> {
+        if self.depth == 0 { return TuskError::forbidden().bail(); }
+        self.check_writable()?;
+        if trashed.symlink_metadata().is_err() { return TuskError::not_found().bail(); }
+        if !self.path.parent().is_some_and(Path::is_dir) || self.path.symlink_metadata().is_ok() {
+            return TuskError::conflict().bail();
+        }
+        self.check_quota(config, db, disk_usage(trashed))?;
+
+        match move_item(trashed, &self.path) {
+            Ok(()) => {
+                ContentIndex::schedule(config, IndexTask::Update(self.request_path()));
+                Ok(())
+            },
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => TuskError::forbidden().bail(),
+            Err(e) => TuskError::internal_server_error().with_error(e).log_error().bail()
+        }
+    }
+
+    /// Moves the item at this path to the given `destination`, and returns the new path of the item.
+    ///
+    /// If `overwrite` is `true` and an item already exists at the destination, that item is
+    /// replaced by the moved one; if both items are files, the previous contents of the
+    /// destination are kept as a new version, otherwise the replaced item is moved to the trash.
+    /// If the item cannot be moved, the replaced item is left in place.
+    ///
+    /// # Errors
+    /// If either this path or the destination is a user root or is read-only, this function
+    /// returns an HTTP error 403 `FORBIDDEN`.
+    ///
+    /// If the destination is this path or one of its children, this function returns an HTTP error
+    /// 400 `BAD REQUEST`.
+    ///
+    /// If this path or the parent of the destination do not exist, this function returns an HTTP
+    /// error 404 `NOT FOUND`.
+    ///
+    /// If an item already exists at the destination and `overwrite` is `false`, this function
+    /// returns an HTTP error 409 `CONFLICT`.
+    ///
+    /// If the item is moved to another user root and would exceed its quota, this function
+    /// returns an HTTP error 507 `INSUFFICIENT STORAGE`.
+    ///
+    /// If the item is moved to another root, and either the data key of the user who owns the
+    /// destination or the data key needed to decrypt the item is not available, this function
+    /// returns an HTTP error 423 `LOCKED`; see [`PathInfo::convert`].
+    ///
+    /// Finally, for any other error, the function returns 500 `INTERNAL SERVER ERROR`.
+    pub fn move_to(self, config: &TuskConfiguration, db: &mut PgConnection, destination: PathInfo, overwrite: bool) -> TuskResult<Self> {
+        if self.depth == 0 || destination.depth == 0 { return TuskError::forbidden().bail(); }
+        self.check_writable()?;
+        destination.check_writable()?;
+        if !self.path.exists() { return TuskError::not_found().bail(); }
+        if destination.path.starts_with(&self.path) { return TuskError::bad_request().bail(); }
+        if self.user_root() != destination.user_root() {
+            destination.check_quota(config, db, disk_usage(&self.path).saturating_sub(disk_usage(&destination.path)))?;
+        }
+        let converted = self.owner != destination.owner;
+        if converted { destination.check_conversion(config, &self.decryption(), &self.path)?; }
+
+        let replaced = destination.set_aside(overwrite)?;
+        if let Err(e) = std::fs::rename(&self.path, &destination.path) {
+            if let Some(replaced) = &replaced { destination.put_back(replaced); }
+            return match e.kind() {
+                ErrorKind::NotFound => TuskError::not_found().bail(),
+                ErrorKind::PermissionDenied => TuskError::forbidden().bail(),
+                _ => TuskError::internal_server_error().with_error(e).log_error().bail()
+            };
+        }
+
+        if let Some(replaced) = replaced {
+            destination.dispose_replaced(config, db, &replaced)?;
+        }
+        Blob::rename(db, self.request_path(), destination.request_path())?;
+        // Grants and links are given by the owner of the root, hence they do not follow items to
+        // other roots.
+        if converted {
+            StorageGrant::delete_under(db, self.request_path())?;
+            ShareLink::delete_under(db, self.request_path())?;
+            destination.convert(config, db, &self.decryption(), &destination.path)?;
+        } else {
+            StorageGrant::rename(db, self.request_path(), destination.request_path())?;
+            ShareLink::rename(db, self.request_path(), destination.request_path())?;
+        }
+        thumbnail::evict(&config.thumbnails_directory(), &self.request_path());
+        ContentIndex::schedule(config, IndexTask::Remove(self.request_path()));
+        ContentIndex::schedule(config, IndexTask::Update(destination.request_path()));
+        Ok(destination)
+    }
+
+    /// Moves aside the item at this path, if any, so that it can be replaced by another item
+    /// without being lost if the replacement fails, and returns where the item has been moved.
+    ///
+    /// # Errors
+    /// If an item exists at this path and `overwrite` is `false`, this function returns an HTTP
+    /// error 409 `CONFLICT`.
+    fn set_aside(&self, overwrite: bool) -> TuskResult<Option<PathBuf>> {
+        if self.path.symlink_metadata().is_err() { return Ok(None); }
+        if !overwrite { return TuskError::conflict().bail(); }
+
+        let aside = self.path.with_file_name(format!(".tusk-{}", Uuid::new_v4()));
+        match std::fs::rename(&self.path, &aside) {
+            Ok(()) => Ok(Some(aside)),
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => TuskError::forbidden().bail(),
+            Err(e) => TuskError::internal_server_error().with_error(e).log_error().bail()
+        }
+    }
+    /// Moves the item set aside by [`PathInfo::set_aside`] back to this path, after its replacement
+    /// failed.
+    fn put_back(&self, aside: &Path) {
+        if let Err(e) = std::fs::rename(aside, &self.path) {
+            log::error!("Cannot restore `{}` from `{}`: {e}", self.path.display(), aside.display());
+        }
+    }
+    /// Disposes of the item set aside by [`PathInfo::set_aside`], now replaced by the item at this
+    /// path: if both items are files, the replaced contents are kept as a new version, otherwise the
+    /// replaced item is moved to the trash.
+    fn dispose_replaced(&self, config: &TuskConfiguration, db: &mut PgConnection, aside: &Path) -> TuskResult<()> {
+        deduplication::release(config, db, &self.request_path())?;
+        StorageGrant::delete_under(db, self.request_path())?;
+        ShareLink::delete_under(db, self.request_path())?;
+        thumbnail::evict(&config.thumbnails_directory(), &self.request_path());
+        // The replaced item could contain files that the new item does not.
+        ContentIndex::schedule(config, IndexTask::Remove(self.request_path()));
+        if aside.is_file() && self.path.is_file() {
+            self.keep_version_of(config, db, aside)?;
+            std::fs::remove_file(aside)?;
+        } else {
+            self.trash(config, db, aside)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the item at this path to the given `destination`, copying directories recursively,
+    /// and returns the path of the copy, together with the list of the items that could not be
+    /// copied.
+    ///
+    /// If `overwrite` is `true` and an item already exists at the destination, that item is
+    /// replaced by the copy; if both items are files, the previous contents of the destination
+    /// are kept as a new version, otherwise the replaced item is moved to the trash.
+    /// If the item cannot be copied, the replaced item is left in place.
+    ///
+    /// # Errors
+    /// If the destination is a user root or is read-only, this function returns an HTTP error
+    /// 403 `FORBIDDEN`.
+    ///
+    /// If an item already exists at the destination and `overwrite` is `false`, this function
+    /// returns an HTTP error 409 `CONFLICT`.
+    ///
+    /// Otherwise, the errors are the same as in [`PathInfo::create_copy`].
+    pub fn copy_to(&self, config: &TuskConfiguration, db: &mut PgConnection, destination: PathInfo, overwrite: bool) -> TuskResult<(Self, Vec<CopyFailure>)> {
+        if !self.path.exists() { return TuskError::not_found().bail(); }
+        if destination.path.starts_with(&self.path) { return TuskError::bad_request().bail(); }
+        let parent = destination.parent()
+            .or_forbidden()?;
+        destination.check_writable()?;
+
+        let replaced = destination.set_aside(overwrite)?;
+        let kind = if self.is_directory() { PathKind::Directory } else { PathKind::File };
+        let data = CopyPathData { kind, name: destination.name(), source: self.request_path() };
+        let result = parent.create_copy(config, db, self, data);
+        if let Some(replaced) = replaced {
+            match &result {
+                Ok(_) => destination.dispose_replaced(config, db, &replaced)?,
+                Err(_) => destination.put_back(&replaced)
+            }
+        }
+        result
+    }
+
+    /// Replaces the contents of the file at this path with the temporary `file`, creating the file
+    /// if it does not exist.
+    /// The previous contents of the file, if any, are kept as a new version.
+    ///
+    /// Returns `true` if the file has been created and `false` if it has been replaced.
+    ///
+    /// # Errors
+    /// If this path is a user root or a storage, this function returns an HTTP error
+    /// 409 `CONFLICT`.
+    ///
+    /// If the parent of this path does not exist, this function returns an HTTP error
+    /// 404 `NOT FOUND`.
+    ///
+    /// If this path is read-only, this function returns an HTTP error 403 `FORBIDDEN`.
+    ///
+    /// If the new contents would exceed the quota of the user root, this function returns an HTTP
+    /// error 507 `INSUFFICIENT STORAGE`.
+    ///
+    /// Finally, for any other error, the function returns 500 `INTERNAL SERVER ERROR`.
+    pub fn write_file(&self, config: &TuskConfiguration, db: &mut PgConnection, file: TempPath) -> TuskResult<bool> {
+        if self.depth == 0 || self.is_directory() { return TuskError::conflict().bail(); }
+        self.check_writable()?;
+        let created = !self.path.exists();
+        let file = self.encrypt(config, file)?;
+        self.check_quota(config, db, disk_usage(&file).saturating_sub(disk_usage(&self.path)))?;
+        self.keep_version(config, db)?;
+
+        let blob = self.deduplicate(config, db, &file)?;
+        let result = file.persist(&self.path);
+        if let (Err(_), Some(blob)) = (&result, blob.clone()) {
+            deduplication::forget(config, db, blob)?;
+        }
+        match result {
+            Ok(_) => {
+                deduplication::refer(config, db, &self.request_path(), blob)?;
+                ContentIndex::schedule(config, IndexTask::Update(self.request_path()));
+                Ok(created)
+            },
+            Err(e) if e.error.kind() == ErrorKind::NotFound => TuskError::not_found().bail(),
+            Err(e) if e.error.kind() == ErrorKind::PermissionDenied => TuskError::forbidden().bail(),
+            Err(e) => TuskError::internal_server_error().with_error(e).log_error().bail()
+        }
+    }
+
+    /// Keeps the current contents of the file at this path as a new version, replaced by the user
+    /// who requested the path, and prunes the old versions of the file.
+    ///
+    /// Nothing happens if this path is not a file.
+    fn keep_version(&self, config: &TuskConfiguration, db: &mut PgConnection) -> TuskResult<()> {
+        self.keep_version_of(config, db, &self.path)
+    }
+    /// Keeps the contents of the file stored at `stored`, which was at this path, as a new version
+    /// of the file at this path, as in [`PathInfo::keep_version`].
+    fn keep_version_of(&self, config: &TuskConfiguration, db: &mut PgConnection, stored: &Path) -> TuskResult<()> {
+        let metadata = match stored.symlink_metadata() {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return Ok(())
+        };
+
+        let request_path = self.request_path();
+        let version = FileVersion::create(db, &request_path, metadata.len(), metadata.modified()?, self.initiator)?;
+        let kept = version.file(config);
+        let result = kept.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::hard_link(stored, &kept)
+                .or_else(|_| std::fs::copy(stored, &kept).map(|_| ())));
+
+        if let Err(e) = result {
+            version.delete(db)?;
+            return TuskError::internal_server_error().with_error(e).log_error().bail();
+        }
+        prune_versions(config, db, &request_path)
+    }
+
+    /// Encrypts the temporary `file`, whose contents are plain, with the data key of the user who
+    /// owns this path, if the path is in the root of a user and encryption is enabled.
+    ///
+    /// # Errors
+    /// If the file has to be encrypted but the data key of the owner is not available, this
+    /// function returns an HTTP error 423 `LOCKED`.
+    fn encrypt(&self, config: &TuskConfiguration, file: TempPath) -> TuskResult<TempPath> {
+        let Some(owner) = self.owner.filter(|_| self.encrypted(config)) else { return Ok(file); };
+        let Some(key) = &self.key else { return TuskError::locked().bail(); };
+
+        let parent = file.parent().or_internal_server_error()?;
+        let mut encrypted = tempfile::Builder::new()
+            .prefix(".tusk-")
+            .tempfile_in(parent)?;
+        encryption::encrypt_file(key, owner, &file, encrypted.as_file_mut())?;
+        times::copy(&file, encrypted.path())?;
+
+        Ok(encrypted.into_temp_path())
+    }
+    /// Returns `true` if the files written in this path are encrypted and `false` otherwise.
+    fn encrypted(&self, config: &TuskConfiguration) -> bool {
+        config.encrypt() && self.owner.is_some()
+    }
+    /// Checks that the files written in this path can be encrypted, if needed.
     ///
     /// # Errors
-    /// If the directory to be created contains the symbols `\`, `/` or is `.` or `..`, then
-    /// the name of the file is not valid and this function returns an HTTP error 400 `BAD REQUEST`.
+    /// If the files have to be encrypted but the data key of the user who owns this path is not
+    /// available, this function returns an HTTP error 423 `LOCKED`.
+    fn check_unlocked(&self, config: &TuskConfiguration) -> TuskResult<()> {
+        if self.encrypted(config) && self.key.is_none() { return TuskError::locked().bail(); }
+        Ok(())
+    }
+    /// Checks that the files at `path`, decrypted with `source`, can be converted as in
+    /// [`PathInfo::convert`], so that items are not converted partially.
     ///
-    /// If the parent of this directory does not exist, this function returns an HTTP error
-    /// 404 `NOT FOUND`.
+    /// # Errors
+    /// If any of the data keys needed by the conversion is not available, this function returns
+    /// an HTTP error 423 `LOCKED`.
+    fn check_conversion(&self, config: &TuskConfiguration, source: &Decryption, path: &Path) -> TuskResult<()> {
+        self.check_unlocked(config)?;
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                self.check_conversion(config, source, &entry?.path())?;
+            }
+        } else if path.is_file() {
+            source.open(path)?;
+        }
+        Ok(())
+    }
+    /// Converts the files at `path`, recursively, which have been copied or moved in this path from
+    /// a root whose files are decrypted with `source`, so that they are stored as the files written
+    /// in this path: encrypted files are decrypted, and every file is encrypted again with the data
+    /// key of the user who owns this path, if needed.
     ///
-    /// If the storage already exists, this function returns an HTTP error 409 `CONFLICT`.
+    /// Converted files keep their times, but they no longer share their storage with other files.
     ///
-    /// Finally, for any other error, the function returns 500 `INTERNAL SERVER ERROR`.
-    pub fn create_file(&self, data: CreateFileData) -> TuskResult<Self> {
-        let payload = data.into_payload();
-        let name = payload.file_name
-            .or_bad_request()?;
-        if name.contains(|c| c == '/' || c == '\\') || name == "." || name == ".." {
-            return TuskError::bad_request().bail();
+    /// # Errors
+    /// If any of the data keys needed by the conversion is not available, this function returns
+    /// an HTTP error 423 `LOCKED`.
+    fn convert(&self, config: &TuskConfiguration, db: &mut PgConnection, source: &Decryption, path: &Path) -> TuskResult<()> {
+        let metadata = path.symlink_metadata()?;
+        if metadata.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                self.convert(config, db, source, &entry?.path())?;
+            }
+            return Ok(());
         }
+        if !metadata.is_file() { return Ok(()); }
 
-        let mut path = self.path.clone();
-        path.push(name);
+        let decryptor = source.open(path)?;
+        if decryptor.is_none() && !self.encrypted(config) { return Ok(()); }
+        let parent = path.parent().or_internal_server_error()?;
+        let mut plain = tempfile::Builder::new()
+            .prefix(".tusk-")
+            .tempfile_in(parent)?;
+        match decryptor {
+            Some(mut decryptor) => std::io::copy(&mut decryptor, &mut plain)?,
+            None => std::io::copy(&mut std::fs::File::open(path)?, &mut plain)?
+        };
+        times::copy(path, plain.path())?;
 
-        match payload.file.persist_noclobber(&path) {
-            Ok(_) => Ok({
-                let mut child = self.clone();
-                child.path = path;
-                child.depth += 1;
-                child
-            }),
-            Err(e) if e.error.kind() == ErrorKind::AlreadyExists => TuskError::conflict().bail(),
-            Err(e) if e.error.kind() == ErrorKind::NotFound => TuskError::not_found().bail(),
-            Err(e) if e.error.kind() == ErrorKind::PermissionDenied => TuskError::forbidden().bail(),
-            Err(e) => TuskError::internal_server_error().with_error(e).log_error().bail()
-        }
+        let converted = self.encrypt(config, plain.into_temp_path())?;
+        converted.persist(path).map_err(|e| e.error)?;
+        deduplication::release(config, db, &self.request_path_of(path))
+    }
+    /// Stores the contents of the temporary file at `file`, which is being written in this path,
+    /// once, as in [`deduplication::deduplicate`]; encrypted files are never deduplicated.
+    fn deduplicate(&self, config: &TuskConfiguration, db: &mut PgConnection, file: &Path) -> TuskResult<Option<Reservation>> {
+        if self.encrypted(config) { return Ok(None); }
+        deduplication::deduplicate(config, db, file)
+    }
+    /// Returns the decryption of the files in this path, with the data key of the user who owns
+    /// it, if any.
+    pub fn decryption(&self) -> Decryption {
+        self.owner.map_or_else(Decryption::plain, |owner| Decryption::new(owner, self.key.clone()))
+    }
+    /// Makes the data key of the user who owns this path available to decrypt and encrypt the files
+    /// in it: `key`, the data key of the user who requested the path, if they own it, and the data
+    /// key of the owner while they have an active session otherwise.
+    pub fn unlocked(mut self, config: &TuskConfiguration, key: Option<DataKey>) -> Self {
+        self.key = match self.owner {
+            Some(owner) if owner != self.initiator => config.key_ring().find(owner),
+            _ => key
+        };
+        self
+    }
+    /// Forgets the data key made available by [`PathInfo::unlocked`], if any.
+    pub fn locked(mut self) -> Self {
+        self.key = None;
+        self
     }
 
-    /// Returns the information relative to the path.
+    /// Returns the ID of the user who requested this path.
+    pub fn initiator(&self) -> Uuid { self.initiator }
+    /// Returns `true` if this path points to a directory and `false` otherwise.
+    pub fn is_directory(&self) -> bool { self.path.is_dir() }
+    /// Returns `true` if an item exists at this path and `false` otherwise.
+    pub fn exists(&self) -> bool { self.path.exists() }
+    /// Returns the maximum number of bytes that can be stored in the user root containing this
+    /// path, or `None` if there is no limit.
+    pub fn quota(&self) -> Option<u64> { self.quota }
+    /// Returns `true` if the user who requested this path can modify it and `false` otherwise.
+    pub fn writable(&self) -> bool { self.writable }
+    /// Checks that the user who requested this path can modify it.
     ///
-    /// See [`StoragePathRead::from_path`] for more information.
-    pub fn info(&self) -> TuskResult<StoragePathRead> {
-        StoragePathRead::from_path(&self.path)
+    /// # Errors
+    /// If this path is read-only, this function returns an HTTP error 403 `FORBIDDEN`.
+    pub fn check_writable(&self) -> TuskResult<()> {
+        if self.writable { Ok(()) } else { TuskError::forbidden().bail() }
     }
+    /// Returns the number of bytes currently used by the user root containing this path, counting
+    /// the items deleted from it and the previous versions of its files as well.
+    pub fn usage(&self, config: &TuskConfiguration, db: &mut PgConnection) -> TuskResult<u64> {
+        let user_root = self.user_root();
+        let request_path = self.request_path_of(&user_root);
+        let trashed: u64 = TrashItem::list_inside(db, &request_path)?
+            .iter()
+            .map(|item| disk_usage(&item.file(config)))
+            .sum();
+        let versions = FileVersion::total_size_inside(db, &request_path)?;
 
-    /// Deletes the item at this path.
+        Ok(disk_usage(&user_root) + trashed + versions)
+    }
+    /// Returns the physical path of the user root containing this path.
+    fn user_root(&self) -> PathBuf {
+        self.path.iter()
+            .take(self.root.iter().count() + 1)
+            .collect()
+    }
+    /// Checks that `additional` bytes can be stored in the user root containing this path.
     ///
     /// # Errors
-    /// If the path does not exist, this function returns an HTTP error 404 `NOT FOUND`.
-    pub fn delete(self) -> TuskResult<()> {
-        if self.depth == 0 { return TuskError::forbidden().bail(); }
-        if self.is_directory() {
-            std::fs::remove_dir_all(&self.path)?;
-        } else {
-            std::fs::remove_file(&self.path)?;
+    /// If the quota of the user root would be exceeded, this function returns an HTTP error
+    /// 507 `INSUFFICIENT STORAGE`.
+    pub fn check_quota(&self, config: &TuskConfiguration, db: &mut PgConnection, additional: u64) -> TuskResult<()> {
+        match self.quota {
+            Some(quota) if self.usage(config, db)?.saturating_add(additional) > quota => TuskError::insufficient_storage().bail(),
+            _ => Ok(())
         }
-        Ok(())
     }
+    /// Returns the name of the item at this path.
+    pub fn name(&self) -> String {
+        self.path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+    /// Returns the path of the storage containing this path, or `None` if this path is
+    /// a user root.
+    pub fn parent(&self) -> Option<PathInfo> {
+        if self.depth == 0 { return None; }
 
-    /// Returns `true` if this path points to a directory and `false` otherwise.
-    pub fn is_directory(&self) -> bool { self.path.is_dir() }
+        let mut parent = self.clone();
+        parent.path.pop();
+        parent.depth -= 1;
+        Some(parent)
+    }
     /// Returns a request path relative to this path.
     pub fn request_path(&self) -> String {
-        let result: Vec<std::borrow::Cow<str>> = self.path.iter()
+        self.request_path_of(&self.path)
+    }
+    /// Returns the request path of the given physical path, which is assumed to be in the same
+    /// storage root as this path.
+    fn request_path_of(&self, path: &Path) -> String {
+        let result: Vec<std::borrow::Cow<str>> = path.iter()
             .skip(self.root.iter().count())
             .map(|s| s.to_string_lossy())
             .collect();
@@ -180,14 +1309,213 @@ impl PathInfo {
     /// If the path points to something that is not a directory, this function returns an HTTP
     /// error 409 `CONFLICT`.
     pub fn list_children(&self) -> TuskResult<Vec<StoragePathRead>> {
+        Ok(self.list_page(&ListingOptions::default())?.items)
+    }
+    /// Lists the page of the children of the storage specified by this path given by `options`.
+    ///
+    /// # Errors
+    /// If this path is not a storage, this function returns an HTTP error 409 `CONFLICT`.
+    ///
+    /// If the options are not valid, this function returns an HTTP error 400 `BAD REQUEST`.
+    pub fn list_page(&self, options: &ListingOptions) -> TuskResult<ListingPage> {
         if !self.path.is_dir() { return TuskError::conflict().bail(); }
 
-        let result = std::fs::read_dir(&self.path)?
-            .filter_map(|dir| dir.ok())
-            .map(|dir| StoragePathRead::from_path(dir.path()))
-            .collect::<Result<Vec<StoragePathRead>, _>>()?;
+        let mut page = listing::list(&self.path, options, self.owner.is_some())?;
+        if self.owner.is_some() {
+            page.items = page.items.into_iter()
+                .map(|item| {
+                    let path = self.path.join(item.filename());
+                    item.decrypted(&path)
+                })
+                .collect();
+        }
+        Ok(page)
+    }
+
+    /// Calls `visitor` with the request path and the information of every item inside the storage
+    /// specified by this path, recursively, in alphabetical order.
+    ///
+    /// Items that are neither regular files nor directories, or that cannot be read, are skipped.
+    pub fn walk<F: FnMut(String, StoragePathRead)>(&self, visitor: &mut F) {
+        self.walk_children(&self.path, visitor);
+    }
+    /// Calls `visitor` on the children of the directory `path`, recursively.
+    fn walk_children<F: FnMut(String, StoragePathRead)>(&self, path: &Path, visitor: &mut F) {
+        let Ok(entries) = std::fs::read_dir(path) else { return; };
+        let mut children: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .collect();
+        children.sort();
+
+        for child in children {
+            let Ok(metadata) = child.symlink_metadata() else { continue; };
+            if !metadata.is_dir() && !metadata.is_file() { continue; }
+            let Ok(item) = self.item_info(&child) else { continue; };
+
+            visitor(self.request_path_of(&child), item);
+            if metadata.is_dir() { self.walk_children(&child, visitor); }
+        }
+    }
+
+    /// Performs the necessary checks on the path queried by the user and then outputs a valid,
+    /// authorized path.
+    ///
+    /// # Errors
+    /// If the user is not logged in, this function returns an HTTP error 401 `UNAUTHORIZED`.
+    ///
+    /// If the user does not have role `directory`, or if the path is neither in the public root
+    /// nor in the user's root, this function returns an HTTP error 403 `FORBIDDEN`.
+    pub fn from_queried_path<P: Into<PathBuf>>(tusk: &Tusk, queried_path: P) -> TuskResult<PathInfo> {
+        let mut db = tusk.db()?;
+        let initiator = tusk.authenticate()?
+            .user(&mut db)?;
+
+        let path = PathInfo::authorize(tusk.config(), &mut db, &initiator, queried_path)?;
+        Ok(path.unlocked(tusk.config(), tusk.data_key()))
+    }
+
+    /// Returns the storages spanned by a request of the logged user: the storage at
+    /// `queried_path`, if any, and otherwise all the storages the user has access to, that is, the
+    /// user's root, the public root, the storages shared with the user and the team folders of
+    /// which the user is a member.
+    ///
+    /// Shared storages that no longer exist, and storages inside other returned storages, are
+    /// skipped.
+    ///
+    /// # Errors
+    /// If the user is not logged in, this function returns an HTTP error 401 `UNAUTHORIZED`.
+    ///
+    /// If the queried path is not authorized, this function returns an HTTP error 403 `FORBIDDEN`.
+    ///
+    /// If the queried storage does not exist, this function returns an HTTP error 404 `NOT FOUND`,
+    /// while if it is not a storage, this function returns an HTTP error 409 `CONFLICT`.
+    pub fn roots(tusk: &Tusk, queried_path: Option<&str>) -> TuskResult<Vec<PathInfo>> {
+        let roots = match queried_path {
+            Some(path) => vec![PathInfo::from_queried_path(tusk, path)?],
+            None => {
+                let user_id = tusk.authenticate()?.user_id();
+                let mut roots = vec![
+                    PathInfo::from_queried_path(tusk, user_id.to_string())?,
+                    PathInfo::from_queried_path(tusk, ".public/")?
+                ];
+                let mut db = tusk.db()?;
+                let shared: Vec<String> = StorageGrant::list_for_grantee(&mut db, user_id)?
+                    .iter()
+                    .map(|grant| grant.path().to_owned())
+                    .chain(TeamFolder::list_for_member(&mut db, user_id)?
+                        .iter()
+                        .map(|(team_folder, _)| team_folder.path()))
+                    .collect();
+                drop(db);
+                for path in shared {
+                    let Ok(root) = PathInfo::from_queried_path(tusk, path) else { continue; };
+                    if !root.is_directory() || roots.iter().any(|other| root.path.starts_with(&other.path)) { continue; }
+                    roots.push(root);
+                }
+                roots
+            }
+        };
+        if roots.iter().any(|root| !root.exists()) { return TuskError::not_found().bail(); }
+        if roots.iter().any(|root| !root.is_directory()) { return TuskError::conflict().bail(); }
+
+        Ok(roots)
+    }
+
+    /// Performs the necessary checks on the path queried by the given user and then outputs
+    /// a valid, authorized path.
+    ///
+    /// Paths in the root of another user are authorized if that user granted access to them,
+    /// either to this user or to one of its roles; in that case, the depth of the path is relative
+    /// to the shared storage, and the path is read-only unless the access is read-write.
+    /// The same holds for paths in the team folders of which the user is a member.
+    ///
+    /// # Errors
+    /// If the user does not have role `directory`, or if the path is neither in the public root,
+    /// nor in the user's root, nor in a storage shared with the user, nor in a team folder of which
+    /// the user is a member, this function returns an HTTP error 403 `FORBIDDEN`.
+    pub fn authorize<P: Into<PathBuf>>(config: &TuskConfiguration, db: &mut PgConnection, initiator: &User, queried_path: P) -> TuskResult<PathInfo> {
+        let root = config.user_directories()
+            .canonicalize()?;
+        let queried_path = clean(queried_path.into());
+        let mut path = root.clone();
+
+        if !initiator.roles(db)?
+            .iter()
+            .any(|r| r.name() == "directory") {
+            return TuskError::forbidden().bail();
+        }
+
+        // Return early if the user is not authorized;
+        // construct physical path otherwise.
+        let user_root = format!("{}/", initiator.id());
+        let (quota, writable, base, owner) = if queried_path.starts_with(".public/") {
+            (None, true, 1, None)
+        } else if queried_path.starts_with(&user_root) {
+            (initiator.quota(db)?, true, 1, Some(initiator.id()))
+        } else if let Some(writable) = team_access(db, initiator, &queried_path)? {
+            (None, writable, 2, None)
+        } else if let Some(grant) = StorageGrant::covering(db, initiator.id(), &queried_path)? {
+            let quota = Quota::effective_for_user(db, grant.owner_id())?;
+            (quota, grant.writable(), Path::new(grant.path()).iter().count(), Some(grant.owner_id()))
+        } else {
+            log::info!("User `{initiator}` tried to access forbidden path `{}`", queried_path.display());
+            return TuskError::forbidden().bail();
+        };
+        path.push(&queried_path);
 
-        Ok(result)
+        // Get the depth to the path, relative to the user root or to the shared storage.
+        let depth = queried_path.iter().count()
+            .checked_sub(base)
+            .or_forbidden()?;
+
+        Ok(PathInfo {
+            depth,
+            root,
+            path,
+            quota,
+            writable,
+            initiator: initiator.id(),
+            owner,
+            key: None
+        })
+    }
+
+    /// Performs the necessary checks on the path queried through the given share link and then
+    /// outputs a valid path, relative to the shared item.
+    ///
+    /// # Errors
+    /// If the path points outside of the shared item, this function returns an HTTP error
+    /// 403 `FORBIDDEN`.
+    pub fn from_share_link<P: Into<PathBuf>>(config: &TuskConfiguration, link: &ShareLink, queried_path: P) -> TuskResult<PathInfo> {
+        let root = config.user_directories()
+            .canonicalize()?;
+        let queried_path = clean(queried_path.into());
+        if queried_path.is_absolute() || queried_path.starts_with("..") {
+            return TuskError::forbidden().bail();
+        }
+        let mut path = root.clone();
+        path.push(clean(link.path()));
+        if queried_path != Path::new(".") {
+            path.push(&queried_path);
+        }
+
+        let depth = path.iter().count()
+            .saturating_sub(root.iter().count() + 1);
+        let owner = Path::new(link.path())
+            .starts_with(link.user_id().to_string())
+            .then(|| link.user_id());
+
+        Ok(PathInfo {
+            depth,
+            root,
+            path,
+            quota: None,
+            writable: false,
+            initiator: link.user_id(),
+            owner,
+            key: None
+        })
     }
 }
 impl AsRef<Path> for PathInfo {
@@ -207,45 +1535,7 @@ impl FromRequest for PathInfo {
 
         Box::pin(async move {
             let tusk = tusk_future.await?;
-            let mut db = tusk.db()?;
-            let root = tusk.config()
-                .user_directories()
-                .canonicalize()?;
-            let queried_path = clean(queried_path);
-            let initiator = tusk.authenticate()?
-                .user(&mut db)?;
-            let mut path = root.clone();
-
-            if !initiator.roles(&mut db)?
-                .iter()
-                .any(|r| r.name() == "directory") {
-                return TuskError::forbidden().bail();
-            }
-
-            // Return early if the user is not authorized;
-            // construct physical path otherwise.
-            let user_root = format!("{}/", initiator.id());
-            if queried_path.starts_with(".public/") {
-                path.push(&queried_path);
-            } else if queried_path.starts_with(&user_root) {
-                path.push(&queried_path);
-            } else {
-                log::info!("User `{initiator}` tried to access forbidden path `{}`", queried_path.display());
-                return TuskError::forbidden().bail();
-            };
-
-            // Get the depth to the path, relative to the user root.
-            let mut depth = queried_path.iter().count();
-            if depth == 0 {
-                return TuskError::forbidden().bail();
-            }
-            depth -= 1;
-
-            Ok(PathInfo {
-                depth,
-                root,
-                path
-            })
+            PathInfo::from_queried_path(&tusk, queried_path)
         })
     }
 }
@@ -266,7 +1556,9 @@ pub struct CreatePathAttributes {
     name: String,
     created: Option<i64>,
     last_access: Option<i64>,
-    last_modified: Option<i64>
+    last_modified: Option<i64>,
+    source: Option<String>,
+    extract: Option<ArchiveFormat>
 }
 impl CreatePathAttributes {
     /// Returns the creation date and time of the file, if present in the request.
@@ -281,6 +1573,10 @@ impl CreatePathAttributes {
     pub fn last_modified(&self) -> Option<SystemTime> {
         Some(system_type_from_epoch_delta(self.last_modified?))
     }
+    /// Returns the timestamps present in the request.
+    pub fn times(&self) -> PathTimes {
+        PathTimes::new(self.created(), self.last_access(), self.last_modified())
+    }
 }
 /// Represents the CRUD **Create** structure relative to the `/storage` REST resource.
 ///
@@ -301,7 +1597,9 @@ impl CreatePathData {
                 name: name.as_ref().to_string(),
                 created: None,
                 last_access: None,
-                last_modified: None
+                last_modified: None,
+                source: None,
+                extract: None
             })),
             payload: None
         }
@@ -319,7 +1617,9 @@ impl CreatePathData {
                 name: name.as_ref().to_string(),
                 created: None,
                 last_access: None,
-                last_modified: None
+                last_modified: None,
+                source: None,
+                extract: None
             })),
             payload: Some(TempFile {
                 file,
@@ -336,7 +1636,25 @@ impl CreatePathData {
             Some(metadata) => metadata,
             None => return false
         };
-        metadata.kind == PathKind::Directory
+        metadata.kind == PathKind::Directory && metadata.source.is_none()
+    }
+    /// Returns `true` if the resource is a copy of an existing item and `false` otherwise.
+    pub fn is_copy(&self) -> bool {
+        if self.payload.is_some() { return false; }
+        let metadata = match &self.metadata {
+            Some(metadata) => metadata,
+            None => return false
+        };
+        metadata.source.is_some()
+    }
+    /// Returns `true` if the uploaded resource is an archive to be extracted and `false` otherwise.
+    pub fn is_archive(&self) -> bool {
+        if self.payload.is_none() { return false; }
+        let metadata = match &self.metadata {
+            Some(metadata) => metadata,
+            None => return false
+        };
+        metadata.kind == PathKind::File && metadata.extract.is_some()
     }
     /// Returns `true if the uploaded resource is a file and `false` otherwise.
     pub fn is_file(&self) -> bool {
@@ -353,9 +1671,14 @@ impl CreatePathData {
 /// This structure contains the necessary information to create a file.
 #[derive(Debug)]
 pub struct CreateFileData {
-    payload: TempFile
+    payload: TempFile,
+    times: PathTimes
 }
 impl CreateFileData {
+    /// Returns the timestamps requested for the file.
+    pub fn times(&self) -> PathTimes {
+        self.times
+    }
     /// Returns the temporary file created by the upload request.
     pub fn into_payload(self) -> TempFile {
         self.payload
@@ -374,23 +1697,63 @@ impl TryFrom<CreatePathData> for CreateFileData {
             return TuskError::bad_request().bail();
         }
         match value.payload {
-            Some(payload) => Ok(CreateFileData { payload }),
+            Some(payload) => Ok(CreateFileData { payload, times: metadata.times() }),
             None => TuskError::bad_request().bail()
         }
     }
 }
 /// Represents the CRUD **Create** structure relative to the `/storage` REST resource.
 ///
+/// This structure contains the necessary information to extract an archive into a storage.
+#[derive(Debug)]
+pub struct ExtractArchiveData {
+    format: ArchiveFormat,
+    payload: TempFile
+}
+impl ExtractArchiveData {
+    /// Returns the format of the archive.
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+}
+impl TryFrom<CreatePathData> for ExtractArchiveData {
+    type Error = TuskError;
+
+    fn try_from(value: CreatePathData) -> Result<Self, Self::Error> {
+        let Json(metadata) = match value.metadata {
+            Some(metadata) => metadata,
+            None => return TuskError::bad_request().bail()
+        };
+        if metadata.kind != PathKind::File {
+            return TuskError::bad_request().bail();
+        }
+        match (metadata.extract, value.payload) {
+            (Some(format), Some(payload)) => Ok(ExtractArchiveData { format, payload }),
+            _ => TuskError::bad_request().bail()
+        }
+    }
+}
+/// Represents the CRUD **Create** structure relative to the `/storage` REST resource.
+///
 /// This structure contains the necessary information to create a storage.
 #[derive(Debug)]
 pub struct CreateDirectoryData {
-    name: String
+    name: String,
+    times: PathTimes
 }
 impl CreateDirectoryData {
+    /// Creates the information needed to create a storage with the given name.
+    pub fn new<S: Into<String>>(name: S) -> CreateDirectoryData {
+        CreateDirectoryData { name: name.into(), times: PathTimes::default() }
+    }
     /// Returns the name of the storage to be created.
     pub fn name(&self) -> &str {
         &self.name
     }
+    /// Returns the timestamps requested for the storage.
+    pub fn times(&self) -> PathTimes {
+        self.times
+    }
 }
 impl TryFrom<CreatePathData> for CreateDirectoryData {
     type Error = TuskError;
@@ -401,14 +1764,100 @@ impl TryFrom<CreatePathData> for CreateDirectoryData {
             Some(metadata) => metadata,
             None => return TuskError::bad_request().bail()
         };
-        if directory_item_create.kind != PathKind::Directory {
+        if directory_item_create.kind != PathKind::Directory || directory_item_create.source.is_some() {
             TuskError::bad_request().bail()
         } else {
-            Ok(CreateDirectoryData { name: directory_item_create.name })
+            let times = directory_item_create.times();
+            Ok(CreateDirectoryData { name: directory_item_create.name, times })
+        }
+    }
+}
+
+/// Represents the CRUD **Create** structure relative to the `/storage` REST resource.
+///
+/// This structure contains the necessary information to create a copy of an existing file or
+/// storage.
+#[derive(Debug)]
+pub struct CopyPathData {
+    kind: PathKind,
+    name: String,
+    source: String
+}
+impl CopyPathData {
+    /// Returns the kind of the item to be copied.
+    pub fn kind(&self) -> PathKind {
+        self.kind
+    }
+    /// Returns the name of the copy.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Returns the path of the item to be copied, relative to the storage root.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+impl TryFrom<CreatePathData> for CopyPathData {
+    type Error = TuskError;
+
+    fn try_from(value: CreatePathData) -> Result<Self, Self::Error> {
+        if value.payload.is_some() { return TuskError::bad_request().bail(); }
+        let Json(copy_item_create) = match value.metadata {
+            Some(metadata) => metadata,
+            None => return TuskError::bad_request().bail()
+        };
+        match copy_item_create.source {
+            Some(source) => Ok(CopyPathData { kind: copy_item_create.kind, name: copy_item_create.name, source }),
+            None => TuskError::bad_request().bail()
         }
     }
 }
 
+/// Describes an item that could not be copied.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct CopyFailure {
+    path: String,
+    status: u16
+}
+impl CopyFailure {
+    /// Returns the path of the item that could not be copied, relative to the storage root.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Returns the HTTP status code describing why the item could not be copied.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+}
+/// Represents the result of a copy, that is, the newly created item together with the list of
+/// items that could not be copied.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct CopyPathRead {
+    item: StoragePathRead,
+    failures: Vec<CopyFailure>
+}
+
+/// Represents the (JSON) data that is sent to the server with a `PATCH` request to `/storage`.
+///
+/// This structure contains the necessary information to move or rename a file or a storage.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MovePathData {
+    destination: String,
+    #[serde(default)]
+    overwrite: bool
+}
+impl MovePathData {
+    /// Returns the new path of the item, relative to the storage root.
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+    /// Returns `true` if an existing item at the destination should be replaced,
+    /// and `false` otherwise.
+    pub fn overwrite(&self) -> bool {
+        self.overwrite
+    }
+}
+
 /// Type of storage item.
 #[derive(Clone, Eq, PartialEq, Debug)]
 enum StoragePathReadKind {
@@ -460,9 +1909,7 @@ impl StoragePathRead {
 
             StoragePathReadKind::Directory { children }
         } else if attr.is_file() {
-            let size = attr.len();
-
-            StoragePathReadKind::File { size }
+            StoragePathReadKind::File { size: attr.len() }
         } else {
             StoragePathReadKind::None
         };
@@ -470,11 +1917,45 @@ impl StoragePathRead {
         Ok(StoragePathRead {
             filename: path.file_name().or_not_found()?.to_string_lossy().into_owned(),
             kind,
-            created: into_lossy_secs(attr.created()),
+            created: into_lossy_secs(times::created(path, &attr)),
             last_access: into_lossy_secs(attr.accessed()),
             last_modified: into_lossy_secs(attr.modified())
         })
     }
+    /// Reports the size of the plain contents of the file at `path`, which this item describes, if
+    /// the file is encrypted.
+    ///
+    /// Only the files in the user roots can be encrypted, hence this function must not be called
+    /// on the other files.
+    fn decrypted(mut self, path: &Path) -> StoragePathRead {
+        let StoragePathReadKind::File { size } = &mut self.kind else { return self; };
+        *size = plain_size(path, *size);
+        self
+    }
+
+    /// Returns the name of the item.
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+    /// Returns `true` if the item is a storage and `false` otherwise.
+    pub fn is_directory(&self) -> bool {
+        matches!(self.kind, StoragePathReadKind::Directory { .. })
+    }
+    /// Returns the size, in bytes, of the item if it is a file, and `None` otherwise.
+    pub fn size(&self) -> Option<u64> {
+        match self.kind {
+            StoragePathReadKind::File { size } => Some(size),
+            _ => None
+        }
+    }
+    /// Returns the creation date and time of the item.
+    pub fn created(&self) -> SystemTime {
+        system_type_from_epoch_delta(self.created)
+    }
+    /// Returns the last modification date and time of the item.
+    pub fn last_modified(&self) -> SystemTime {
+        system_type_from_epoch_delta(self.last_modified)
+    }
 }
 impl serde::Serialize for StoragePathRead {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
@@ -503,6 +1984,81 @@ impl TryFrom<&Path> for StoragePathRead {
     }
 }
 
+/// Represents the CRUD **Read** structure relative to the `/storage/quota` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct StorageQuotaRead {
+    used: u64,
+    available: Option<u64>,
+    quota: Option<u64>
+}
+impl StorageQuotaRead {
+    /// Computes the usage of the user root containing the given path.
+    pub fn from_path(config: &TuskConfiguration, db: &mut PgConnection, path: &PathInfo) -> TuskResult<StorageQuotaRead> {
+        let used = path.usage(config, db)?;
+        let quota = path.quota();
+        Ok(StorageQuotaRead {
+            used,
+            available: quota.map(|quota| quota.saturating_sub(used)),
+            quota
+        })
+    }
+
+    /// Returns the number of bytes stored in the user root.
+    pub fn used(&self) -> u64 { self.used }
+    /// Returns the number of bytes that can still be stored in the user root, or `None` if there
+    /// is no limit.
+    pub fn available(&self) -> Option<u64> { self.available }
+    /// Returns the maximum number of bytes that can be stored in the user root, or `None` if there
+    /// is no limit.
+    pub fn quota(&self) -> Option<u64> { self.quota }
+}
+
+/// Represents the query parameters of the `/storage` REST resource.
+#[derive(Debug, Deserialize)]
+pub struct StorageQuery {
+    archive: Option<ArchiveFormat>,
+    thumbnail: Option<u32>,
+    last_modified: Option<i64>,
+    #[serde(default)]
+    sort: ListingSort,
+    #[serde(default)]
+    order: ListingOrder,
+    kind: Option<PathKind>,
+    prefix: Option<String>,
+    limit: Option<usize>,
+    cursor: Option<String>,
+    #[serde(default)]
+    preview: bool
+}
+impl StorageQuery {
+    /// Returns the options of the listing requested by the query.
+    pub fn listing_options(&self) -> ListingOptions {
+        ListingOptions {
+            sort: self.sort,
+            order: self.order,
+            kind: self.kind,
+            prefix: self.prefix.clone(),
+            limit: self.limit,
+            cursor: self.cursor.clone()
+        }
+    }
+}
+
+/// Represents the `/storage/quota` REST resource.
+///
+/// The `/storage/quota` resource is responsible for reporting how much of the user's quota is
+/// used by the files in the user's root, including the deleted items and the previous versions.
+pub struct StorageQuotaResource;
+#[rest_resource("/storage/quota")]
+impl StorageQuotaResource {
+    async fn get(tusk: Tusk) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let path = PathInfo::from_queried_path(&tusk, user_id.to_string())?;
+
+        Ok(HttpResponse::Ok().json(StorageQuotaRead::from_path(tusk.config(), &mut *tusk.db()?, &path)?))
+    }
+}
+
 /// Represents the `/storage` REST resource.
 ///
 /// The `/storage` resource is responsible for creating, downloading, uploading or deleting
@@ -510,34 +2066,176 @@ impl TryFrom<&Path> for StoragePathRead {
 pub struct StorageResource;
 #[rest_resource("/storage/{filename:.*}")]
 impl StorageResource {
-    async fn get(path: PathInfo, req: HttpRequest) -> TuskHttpResult {
-        if path.is_directory() {
-            let children = path.list_children()?;
+    async fn get(tusk: Tusk, path: PathInfo, query: web::Query<StorageQuery>, req: HttpRequest) -> TuskHttpResult {
+        if let Some(format) = query.archive {
+            if !path.is_directory() { return TuskError::bad_request().bail(); }
+            let disposition = ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(format!("{}.{}", path.name(), format.extension()))]
+            };
+
+            Ok(HttpResponse::Ok()
+                .content_type(format.content_type())
+                .insert_header(disposition)
+                .streaming(archive::stream_archive(path.path.clone(), path.name(), format, path.decryption())))
+        } else if let Some(size) = query.thumbnail {
+            if path.is_directory() { return TuskError::bad_request().bail(); }
+            let thumbnails = tusk.config().thumbnails_directory();
+            let request_path = path.request_path();
+            let thumbnail = web::block(move || thumbnail::thumbnail(&thumbnails, &path.path, &request_path, size)).await
+                .map_err(|e| TuskError::internal_server_error().with_error(e).log_error())??;
 
-            Ok(HttpResponse::Ok().json(children))
+            Ok(NamedFile::open(thumbnail)?.into_response(&req))
+        } else if let Some(domain) = tusk.config().preview_domain().filter(|_| query.preview) {
+            if path.is_directory() { return TuskError::bad_request().bail(); }
+
+            Ok(preview::redirect(domain, path, tusk.key_token()))
+        } else if path.is_directory() {
+            let page = path.list_page(&query.listing_options())?;
+            // The storage has the same tag whatever the page, the sorting and the filters.
+            let etag = path.etag()?.or_not_found()?;
+            if !evaluate_preconditions(&req, Some(&etag))? {
+                return Ok(HttpResponse::NotModified().insert_header(ETag(etag)).finish());
+            }
+
+            let mut response = HttpResponse::Ok();
+            response.insert_header(ETag(etag));
+            if let Some(next) = page.next {
+                response.insert_header((NEXT_CURSOR, next));
+            }
+            Ok(response.json(page.items))
         } else {
-            Ok(NamedFile::open(&path)?.into_response(&req))
+            let etag = path.etag()?.or_not_found()?;
+            if !evaluate_preconditions(&req, Some(&etag))? {
+                return Ok(HttpResponse::NotModified().insert_header(ETag(etag)).finish());
+            }
+
+            let mut response = preview::serve(&path, &req, false)?;
+            if let Ok(value) = ETag(etag).try_into_value() {
+                response.headers_mut().insert(header::ETAG, value);
+            }
+            Ok(response)
+        }
+    }
+
+    async fn put(tusk: Tusk, path: PathInfo, query: web::Query<StorageQuery>, req: HttpRequest, mut payload: web::Payload) -> TuskHttpResult {
+        if path.is_directory() { return TuskError::conflict().bail(); }
+        if !path.exists() { return TuskError::not_found().bail(); }
+        if req.headers().get(header::IF_MATCH).is_none() && query.last_modified.is_none() {
+            return TuskError::precondition_required().bail();
+        }
+        path.check_writable()?;
+        let check_preconditions = || {
+            check_preconditions(&req, &path)?;
+            match query.last_modified {
+                Some(last_modified) if epoch_delta(path.info()?.last_modified()) != last_modified => {
+                    TuskError::precondition_failed().bail()
+                },
+                _ => Ok(())
+            }
+        };
+        check_preconditions()?;
+
+        let parent = path.path.parent()
+            .or_not_found()?;
+        let mut file = tempfile::Builder::new()
+            .prefix(".tusk-")
+            .tempfile_in(parent)?;
+        while let Some(chunk) = payload.next().await {
+            let chunk = chunk.or_bad_request()?;
+            file.write_all(&chunk)?;
         }
+        file.flush()?;
+
+        {
+            let _lock = REPLACE_LOCK.lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            // The file could have been replaced while the new contents were being received.
+            check_preconditions()?;
+            path.write_file(tusk.config(), &mut *tusk.db()?, file.into_temp_path())?;
+        }
+        let attr = path.info()?;
+        record_change(&mut *tusk.db()?, &path.request_path(), &attr, StorageOperation::Modified);
+
+        let etag = path.etag()?.or_not_found()?;
+        Ok(HttpResponse::Ok()
+            .insert_header(ETag(etag))
+            .json(attr))
     }
 
-    async fn delete(path: PathInfo) -> TuskHttpResult {
-        path.delete()?;
+    async fn delete(tusk: Tusk, path: PathInfo, req: HttpRequest) -> TuskHttpResult {
+        check_preconditions(&req, &path)?;
+        let mut db = tusk.db()?;
+        let request_path = path.request_path();
+        let directory = path.is_directory();
+        path.delete(tusk.config(), &mut db)?;
+        record_deletion(&mut db, &request_path, directory);
+        purge_expired(tusk.config(), &mut db)?;
 
         Ok(HttpResponse::NoContent().finish())
     }
 
-    async fn post(path: PathInfo, MultipartForm(data): MultipartForm<CreatePathData>) -> TuskHttpResult {
-        let response = if data.is_directory() {
+    async fn patch(tusk: Tusk, path: PathInfo, web::Json(data): web::Json<MovePathData>, req: HttpRequest) -> TuskHttpResult {
+        check_preconditions(&req, &path)?;
+        let destination = PathInfo::from_queried_path(&tusk, data.destination())?;
+        let mut db = tusk.db()?;
+        let request_path = path.request_path();
+        let directory = path.is_directory();
+        let child = path.move_to(tusk.config(), &mut db, destination, data.overwrite())?;
+        let attr = child.info()?;
+        record_deletion(&mut db, &request_path, directory);
+        record_change(&mut db, &child.request_path(), &attr, StorageOperation::Created);
+        let location = if child.is_directory() {
+            format!("/v1/storage/{}/", child.request_path())
+        } else {
+            format!("/v1/storage/{}", child.request_path())
+        };
+
+        Ok(HttpResponse::Created()
+            .insert_header((header::LOCATION, location))
+            .json(attr))
+    }
+
+    async fn post(tusk: Tusk, path: PathInfo, MultipartForm(data): MultipartForm<CreatePathData>, req: HttpRequest) -> TuskHttpResult {
+        check_preconditions(&req, &path)?;
+        let response = if data.is_copy() {
+            let copy_data: CopyPathData = data.try_into()?;
+            let source = PathInfo::from_queried_path(&tusk, copy_data.source())?;
+            let (child, failures) = path.create_copy(tusk.config(), &mut *tusk.db()?, &source, copy_data)?;
+            let item = child.info()?;
+            record_change(&mut *tusk.db()?, &child.request_path(), &item, StorageOperation::Created);
+            let location = if child.is_directory() {
+                format!("/v1/storage/{}/", child.request_path())
+            } else {
+                format!("/v1/storage/{}", child.request_path())
+            };
+            HttpResponse::Created()
+                .insert_header((header::LOCATION, location))
+                .json(CopyPathRead { item, failures })
+        } else if data.is_directory() {
             let directory_data: CreateDirectoryData = data.try_into()?;
-            let child = path.create_dir(directory_data)?;
+            let child = path.create_dir(tusk.config(), &mut *tusk.db()?, directory_data)?;
             let attr = child.info()?;
+            record_change(&mut *tusk.db()?, &child.request_path(), &attr, StorageOperation::Created);
             HttpResponse::Created()
                 .insert_header((header::LOCATION, format!("/v1/storage/{}/", child.request_path())))
                 .json(attr)
+        } else if data.is_archive() {
+            let archive_data: ExtractArchiveData = data.try_into()?;
+            let mut db = tusk.db()?;
+            let items = path.extract_archive(tusk.config(), &mut db, archive_data)?;
+            for item in &items {
+                let request_path = format!("{}/{}", path.request_path(), item.filename());
+                record_change(&mut db, &request_path, item, StorageOperation::Created);
+            }
+            HttpResponse::Created()
+                .insert_header((header::LOCATION, format!("/v1/storage/{}/", path.request_path())))
+                .json(items)
         } else if data.is_file() {
             let file_data: CreateFileData = data.try_into()?;
-            let child = path.create_file(file_data)?;
+            let child = path.create_file(tusk.config(), &mut *tusk.db()?, file_data)?;
             let attr = child.info()?;
+            record_change(&mut *tusk.db()?, &child.request_path(), &attr, StorageOperation::Created);
             HttpResponse::Created()
                 .insert_header((header::LOCATION, format!("/v1/storage/{}", child.request_path())))
                 .json(attr)
@@ -552,7 +2250,12 @@ impl StorageResource {
 #[cfg(test)]
 mod tests {
     use std::time::{Duration, SystemTime};
-    use crate::api::storage::system_type_from_epoch_delta;
+    use actix_web::http::header::{EntityTag, IF_MATCH, IF_NONE_MATCH};
+    use actix_web::http::Method;
+    use actix_web::ResponseError;
+    use actix_web::test::TestRequest;
+    use tusk_core::error::TuskError;
+    use crate::api::storage::{evaluate_preconditions, system_type_from_epoch_delta};
 
     #[test]
     fn test_system_type_from_epoch_delta() {
@@ -566,5 +2269,27 @@ mod tests {
         assert_eq!(time, SystemTime::UNIX_EPOCH - Duration::from_secs(60));
     }
 
+    #[test]
+    fn test_evaluate_preconditions() {
+        let etag = EntityTag::new_strong(String::from("abc"));
+        let precondition_failed = TuskError::precondition_failed().status_code();
+
+        let req = TestRequest::get().to_http_request();
+        assert!(evaluate_preconditions(&req, Some(&etag)).unwrap());
+
+        let req = TestRequest::get().insert_header((IF_NONE_MATCH, "\"xyz\", W/\"abc\"")).to_http_request();
+        assert!(!evaluate_preconditions(&req, Some(&etag)).unwrap());
+        assert!(evaluate_preconditions(&req, None).unwrap());
+        let req = TestRequest::default().method(Method::DELETE).insert_header((IF_NONE_MATCH, "*")).to_http_request();
+        assert_eq!(evaluate_preconditions(&req, Some(&etag)).unwrap_err().status_code(), precondition_failed);
+
+        let req = TestRequest::default().method(Method::DELETE).insert_header((IF_MATCH, "\"abc\"")).to_http_request();
+        assert!(evaluate_preconditions(&req, Some(&etag)).unwrap());
+        assert_eq!(evaluate_preconditions(&req, None).unwrap_err().status_code(), precondition_failed);
+        // Weak tags never match `If-Match`.
+        let req = TestRequest::default().method(Method::DELETE).insert_header((IF_MATCH, "W/\"abc\"")).to_http_request();
+        assert_eq!(evaluate_preconditions(&req, Some(&etag)).unwrap_err().status_code(), precondition_failed);
+    }
+
 
 }
\ No newline at end of file
@@ -0,0 +1,555 @@
+//! Contains the archives of the storages.
+//!
+//! A storage is archived on the fly while it is downloaded: the archive is written by a blocking
+//! task into a bounded channel, one chunk at a time, so that neither the archive nor the files are
+//! ever held entirely in memory or on the disk.
+//!
+//! Uploaded archives are read twice: the first time, to validate the names and the sizes of the
+//! entries before anything is written to the disk, the second time, to extract them.
+
+use std::fs::{File, Metadata};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use actix_web::web::Bytes;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use futures_util::Stream;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use tusk_core::error::{TuskError, TuskResult};
+use crate::api::encryption::Decryption;
+use crate::api::storage::{is_valid_name, PathInfo};
+
+/// Size of the chunks in which an archive is streamed.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Number of chunks that can be written before the client reads them.
+const CHANNEL_CAPACITY: usize = 4;
+
+/// Format of the archive of a storage.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
+pub enum ArchiveFormat {
+    /// ZIP archive, with deflated files.
+    #[serde(rename = "zip")]
+    Zip,
+    /// TAR archive, compressed with gzip.
+    #[serde(rename = "tar.gz")]
+    TarGz
+}
+impl ArchiveFormat {
+    /// Returns the MIME type of the archive.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "application/zip",
+            ArchiveFormat::TarGz => "application/gzip"
+        }
+    }
+    /// Returns the file extension of the archive.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz"
+        }
+    }
+}
+
+/// Writer that sends the written data through a channel, in chunks of [`CHUNK_SIZE`] bytes.
+///
+/// Writing fails with [`ErrorKind::BrokenPipe`] as soon as the receiving end is closed.
+struct ChannelWriter {
+    sender: mpsc::Sender<std::io::Result<Bytes>>,
+    buffer: Vec<u8>
+}
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE { self.flush()?; }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() { return Ok(()); }
+
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        self.sender.blocking_send(Ok(Bytes::from(chunk)))
+            .map_err(|_| ErrorKind::BrokenPipe.into())
+    }
+}
+
+/// Converts the given time into the date and time of a ZIP entry, in UTC.
+///
+/// Times that cannot be represented in a ZIP archive are replaced by 1980-01-01 00:00:00.
+fn zip_time(time: SystemTime) -> DateTime {
+    let Ok(elapsed) = time.duration_since(SystemTime::UNIX_EPOCH) else { return DateTime::default(); };
+    let seconds = elapsed.as_secs();
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+    // Converts the days since the epoch into a civil date, with years starting on March 1st.
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let (year, month) = if month < 10 {
+        (era * 400 + year_of_era, month + 3)
+    } else {
+        (era * 400 + year_of_era + 1, month - 9)
+    };
+
+    u16::try_from(year).ok()
+        .and_then(|year| DateTime::from_date_and_time(year, month as u8, day as u8,
+            (seconds / 3600) as u8, (seconds / 60 % 60) as u8, (seconds % 60) as u8).ok())
+        .unwrap_or_default()
+}
+
+/// Calls `visitor` on the item at `path`, which has the given `name` in the archive, and then
+/// on all of its children, recursively.
+///
+/// Items that are neither regular files nor directories, or that cannot be read, are skipped.
+fn visit<F>(path: &Path, name: &str, visitor: &mut F) -> std::io::Result<()>
+    where F: FnMut(&Path, &str, &Metadata) -> std::io::Result<()> {
+    let Ok(metadata) = path.symlink_metadata() else { return Ok(()); };
+
+    if metadata.is_dir() {
+        let Ok(entries) = std::fs::read_dir(path) else { return Ok(()); };
+        visitor(path, name, &metadata)?;
+
+        let mut children: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name())
+            .collect();
+        children.sort();
+        for child in children {
+            visit(&path.join(&child), &format!("{name}/{}", child.to_string_lossy()), visitor)?;
+        }
+    } else if metadata.is_file() {
+        visitor(path, name, &metadata)?;
+    }
+
+    Ok(())
+}
+
+/// Opens the file at `path`, decrypting it with `decryption` if needed, and returns a reader of
+/// its plain contents together with their size.
+///
+/// Returns `None` if the file cannot be read, as well as if it cannot be decrypted.
+fn open_plain(decryption: &Decryption, path: &Path) -> Option<(Box<dyn Read>, u64)> {
+    match decryption.open(path) {
+        Ok(Some(decryptor)) => {
+            let size = decryptor.header().size();
+            Some((Box::new(decryptor), size))
+        },
+        Ok(None) => {
+            let file = File::open(path).ok()?;
+            let size = file.metadata().ok()?.len();
+            Some((Box::new(file), size))
+        },
+        Err(_) => None
+    }
+}
+
+/// Writes into `writer` a ZIP archive of the storage at `path`, whose items are placed in the
+/// directory `name` of the archive.
+///
+/// Encrypted files are decrypted with `decryption`, and skipped if they cannot be decrypted.
+pub fn write_zip<W: Write>(path: &Path, name: &str, decryption: &Decryption, writer: W) -> std::io::Result<W> {
+    let mut zip = ZipWriter::new_stream(writer);
+    visit(path, name, &mut |path, name, metadata| {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(metadata.modified().map(zip_time).unwrap_or_default());
+
+        if metadata.is_dir() {
+            zip.add_directory(name, options)?;
+        } else if let Some((mut file, size)) = open_plain(decryption, path) {
+            zip.start_file(name, options.large_file(size >= u32::MAX as u64))?;
+            std::io::copy(&mut file, &mut zip)?;
+        }
+        Ok(())
+    })?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Writes into `writer` a TAR archive, compressed with gzip, of the storage at `path`, whose items
+/// are placed in the directory `name` of the archive.
+///
+/// Encrypted files are decrypted with `decryption`, and skipped if they cannot be decrypted.
+pub fn write_tar_gz<W: Write>(path: &Path, name: &str, decryption: &Decryption, writer: W) -> std::io::Result<W> {
+    let mut tar = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+    visit(path, name, &mut |path, name, metadata| {
+        if metadata.is_dir() {
+            tar.append_dir(name, path)?;
+        } else if let Some((file, size)) = open_plain(decryption, path) {
+            let mut header = tar::Header::new_gnu();
+            header.set_metadata(metadata);
+            header.set_size(size);
+            tar.append_data(&mut header, name, file)?;
+        }
+        Ok(())
+    })?;
+
+    tar.into_inner()?.finish()
+}
+
+/// Streams an archive of the storage at `path`, in the given format, whose items are placed in
+/// the directory `name` of the archive; encrypted files are decrypted with `decryption`.
+///
+/// If an error occurs while the archive is written, the error is logged and the stream ends with
+/// that error, so that the client does not receive a truncated archive as if it were complete.
+pub fn stream_archive(path: PathBuf, name: String, format: ArchiveFormat, decryption: Decryption) -> impl Stream<Item = std::io::Result<Bytes>> {
+    let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+
+    tokio::task::spawn_blocking(move || {
+        let writer = ChannelWriter { sender: sender.clone(), buffer: Vec::with_capacity(CHUNK_SIZE) };
+        let result = match format {
+            ArchiveFormat::Zip => write_zip(&path, &name, &decryption, writer),
+            ArchiveFormat::TarGz => write_tar_gz(&path, &name, &decryption, writer)
+        }.and_then(|mut writer| writer.flush());
+
+        match result {
+            Ok(()) => {},
+            // The client went away, there is nobody to report the error to.
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {},
+            Err(e) => {
+                log::error!("Cannot archive `{}`: {e}", path.display());
+                let _ = sender.blocking_send(Err(e));
+            }
+        }
+    });
+
+    futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await
+            .map(|chunk| (chunk, receiver))
+    })
+}
+
+/// Kind of an entry of an uploaded archive.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ArchiveEntryKind {
+    /// The entry is a regular file.
+    File,
+    /// The entry is a directory.
+    Directory
+}
+
+/// Represents an entry of an uploaded archive that can be extracted.
+#[derive(Clone, Debug)]
+pub struct ArchiveEntry {
+    path: PathBuf,
+    kind: ArchiveEntryKind,
+    size: u64
+}
+impl ArchiveEntry {
+    /// Returns the path of the entry, relative to the directory in which the archive is extracted.
+    pub fn path(&self) -> &Path { &self.path }
+    /// Returns the kind of the entry.
+    pub fn kind(&self) -> ArchiveEntryKind { self.kind }
+    /// Returns the size, in bytes, of the entry, as declared by the archive.
+    pub fn size(&self) -> u64 { self.size }
+}
+
+/// Converts the name of an archive entry into a path relative to the directory in which the
+/// archive is extracted.
+///
+/// Returns `None` if the name is absolute, contains `..` or contains an invalid file name, that
+/// is, if the entry could be extracted outside the directory.
+fn entry_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(name) => {
+                let name = name.to_str()?;
+                if !is_valid_name(name) { return None; }
+                path.push(name);
+            },
+            Component::CurDir => {},
+            _ => return None
+        }
+    }
+    Some(path)
+}
+
+/// Converts an error of the ZIP reader into the corresponding HTTP error.
+fn zip_error(e: ZipError) -> TuskError {
+    match e {
+        ZipError::Io(e) => extraction_error(e),
+        _ => TuskError::bad_request()
+    }
+}
+
+/// Converts an error occurred while extracting an archive into the corresponding HTTP error.
+fn extraction_error(e: std::io::Error) -> TuskError {
+    match e.kind() {
+        ErrorKind::InvalidData | ErrorKind::InvalidInput | ErrorKind::UnexpectedEof => TuskError::bad_request(),
+        ErrorKind::NotADirectory => TuskError::conflict(),
+        _ => PathInfo::copy_error(e)
+    }
+}
+
+/// Reads the entries of the archive at `archive`, in the given format.
+///
+/// Entries that are neither regular files nor directories, such as symbolic links, are skipped,
+/// but still count towards `max_entries`.
+///
+/// # Errors
+/// If the archive is malformed, or if the name of an entry would place it outside the directory
+/// in which the archive is extracted, this function returns an HTTP error 400 `BAD REQUEST`.
+///
+/// If the archive has more than `max_entries` entries, or if its files are larger than `max_size`
+/// bytes in total, this function returns an HTTP error 413 `PAYLOAD TOO LARGE`.
+pub fn read_entries(archive: &Path, format: ArchiveFormat, max_entries: usize, max_size: u64) -> TuskResult<Vec<ArchiveEntry>> {
+    let mut entries = Vec::new();
+    let mut count = 0;
+    let mut total_size: u64 = 0;
+    let mut add_entry = |name: &str, kind: Option<ArchiveEntryKind>, size: u64| {
+        count += 1;
+        if count > max_entries { return TuskError::payload_too_large().bail(); }
+        let path = entry_path(name)
+            .ok_or_else(TuskError::bad_request)?;
+        let Some(kind) = kind else { return Ok(()); };
+        // Skips the entry of the directory itself, e.g. `./` in TAR archives.
+        if path.as_os_str().is_empty() { return Ok(()); }
+
+        if kind == ArchiveEntryKind::File {
+            total_size = total_size.saturating_add(size);
+            if total_size > max_size { return TuskError::payload_too_large().bail(); }
+        }
+        entries.push(ArchiveEntry { path, kind, size });
+        Ok(())
+    };
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipArchive::new(File::open(archive)?)
+                .map_err(zip_error)?;
+            for index in 0..zip.len() {
+                let file = zip.by_index_raw(index)
+                    .map_err(zip_error)?;
+                let kind = if file.is_dir() {
+                    Some(ArchiveEntryKind::Directory)
+                } else if file.is_symlink() {
+                    None
+                } else {
+                    Some(ArchiveEntryKind::File)
+                };
+                add_entry(file.name(), kind, file.size())?;
+            }
+        },
+        ArchiveFormat::TarGz => {
+            let mut tar = tar::Archive::new(GzDecoder::new(File::open(archive)?));
+            for entry in tar.entries().map_err(extraction_error)? {
+                let entry = entry.map_err(extraction_error)?;
+                let name = entry.path_bytes();
+                let name = std::str::from_utf8(&name)
+                    .map_err(|_| TuskError::bad_request())?;
+                let kind = match entry.header().entry_type() {
+                    tar::EntryType::Regular | tar::EntryType::Continuous => Some(ArchiveEntryKind::File),
+                    tar::EntryType::Directory => Some(ArchiveEntryKind::Directory),
+                    _ => None
+                };
+                add_entry(name, kind, entry.size())?;
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Checks that the given entry can be extracted into the directory `target`, that is, that it
+/// would not replace an existing file, and that all the existing directories containing it are
+/// actual directories and not, for example, symbolic links.
+///
+/// # Errors
+/// If the entry cannot be extracted into `target`, this function returns an HTTP error
+/// 409 `CONFLICT`.
+pub fn check_entry(target: &Path, entry: &ArchiveEntry) -> TuskResult<()> {
+    let mut path = target.to_path_buf();
+    let mut components = entry.path.components().peekable();
+    while let Some(component) = components.next() {
+        path.push(component);
+        let is_last = components.peek().is_none();
+        match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() && (!is_last || entry.kind == ArchiveEntryKind::Directory) => {},
+            Ok(_) => return TuskError::conflict().bail(),
+            Err(_) => return Ok(())
+        }
+    }
+    Ok(())
+}
+
+/// Creates the directory at `path`, adding it to `created`, unless it already exists.
+fn create_directory(path: &Path, created: &mut Vec<PathBuf>) -> TuskResult<()> {
+    match std::fs::create_dir(path) {
+        Ok(()) => {
+            created.push(path.to_path_buf());
+            Ok(())
+        },
+        Err(e) if e.kind() == ErrorKind::AlreadyExists && path.symlink_metadata().is_ok_and(|metadata| metadata.is_dir()) => Ok(()),
+        Err(e) => Err(extraction_error(e))
+    }
+}
+
+/// Extracts the given entry of an archive into the directory `target`, reading at most `size`
+/// bytes from `reader`, and adds the items it creates to `created`.
+fn extract_entry<R: Read>(target: &Path, name: &str, kind: Option<ArchiveEntryKind>, size: u64, reader: R, created: &mut Vec<PathBuf>) -> TuskResult<()> {
+    let Some(kind) = kind else { return Ok(()); };
+    let relative = entry_path(name)
+        .ok_or_else(TuskError::bad_request)?;
+    if relative.as_os_str().is_empty() { return Ok(()); }
+
+    let mut path = target.to_path_buf();
+    for component in relative.parent().into_iter().flat_map(Path::components) {
+        path.push(component);
+        create_directory(&path, created)?;
+    }
+    path = target.join(&relative);
+
+    match kind {
+        ArchiveEntryKind::Directory => create_directory(&path, created),
+        ArchiveEntryKind::File => {
+            let mut file = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .map_err(extraction_error)?;
+            created.push(path);
+
+            // The declared size of the entry was validated when the entries were read, so that
+            // the entry is not allowed to grow past it.
+            let copied = std::io::copy(&mut reader.take(size.saturating_add(1)), &mut file)
+                .map_err(extraction_error)?;
+            if copied > size { return TuskError::bad_request().bail(); }
+            Ok(())
+        }
+    }
+}
+
+/// Extracts the archive at `archive`, in the given format, into the directory `target`, and adds
+/// the items it creates to `created`.
+///
+/// The entries of the archive must have been validated with [`read_entries`] and [`check_entry`].
+///
+/// # Errors
+/// If an entry of the archive already exists as a file, this function returns an HTTP error
+/// 409 `CONFLICT`; if the server runs out of space, it returns an HTTP error
+/// 507 `INSUFFICIENT STORAGE`.
+/// In both cases, the items created up to that point are listed in `created`.
+pub fn extract(archive: &Path, format: ArchiveFormat, target: &Path, created: &mut Vec<PathBuf>) -> TuskResult<()> {
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipArchive::new(File::open(archive)?)
+                .map_err(zip_error)?;
+            for index in 0..zip.len() {
+                let file = zip.by_index(index)
+                    .map_err(zip_error)?;
+                let name = file.name().to_owned();
+                let kind = if file.is_dir() {
+                    Some(ArchiveEntryKind::Directory)
+                } else if file.is_symlink() {
+                    None
+                } else {
+                    Some(ArchiveEntryKind::File)
+                };
+                let size = file.size();
+                extract_entry(target, &name, kind, size, file, created)?;
+            }
+        },
+        ArchiveFormat::TarGz => {
+            let mut tar = tar::Archive::new(GzDecoder::new(File::open(archive)?));
+            for entry in tar.entries().map_err(extraction_error)? {
+                let entry = entry.map_err(extraction_error)?;
+                let name = String::from_utf8(entry.path_bytes().into_owned())
+                    .map_err(|_| TuskError::bad_request())?;
+                let kind = match entry.header().entry_type() {
+                    tar::EntryType::Regular | tar::EntryType::Continuous => Some(ArchiveEntryKind::File),
+                    tar::EntryType::Directory => Some(ArchiveEntryKind::Directory),
+                    _ => None
+                };
+                let size = entry.size();
+                extract_entry(target, &name, kind, size, entry, created)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+    use uuid::Uuid;
+    use zip::{DateTime, ZipArchive};
+    use tusk_core::encryption::DataKey;
+    use crate::api::encryption::{Decryption, encrypt_file};
+    use crate::api::storage::archive::{entry_path, write_zip, zip_time};
+
+    #[test]
+    fn test_zip_time() {
+        assert_eq!(zip_time(SystemTime::UNIX_EPOCH), DateTime::default());
+
+        // 2023-10-17 12:34:56 UTC
+        let time = zip_time(SystemTime::UNIX_EPOCH + Duration::from_secs(1697546096));
+        assert_eq!(time, DateTime::from_date_and_time(2023, 10, 17, 12, 34, 56).unwrap());
+
+        // 2024-02-29 23:59:58 UTC
+        let time = zip_time(SystemTime::UNIX_EPOCH + Duration::from_secs(1709251198));
+        assert_eq!(time, DateTime::from_date_and_time(2024, 2, 29, 23, 59, 58).unwrap());
+    }
+
+    #[test]
+    fn test_write_zip() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("sub")).unwrap();
+        std::fs::write(root.path().join("a.txt"), "Hello, world!").unwrap();
+        std::fs::write(root.path().join("sub/b.txt"), "Nested file.".repeat(1000)).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.path().join("a.txt"), root.path().join("link")).unwrap();
+        let (owner, key) = (Uuid::new_v4(), DataKey::generate());
+        let plain = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(plain.path(), "Secret file.").unwrap();
+        encrypt_file(&key, owner, plain.path(), &mut std::fs::File::create(root.path().join("c.txt")).unwrap()).unwrap();
+
+        let data = write_zip(root.path(), "root", &Decryption::new(owner, Some(key)), Vec::new()).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, ["root/", "root/a.txt", "root/c.txt", "root/sub/", "root/sub/b.txt"]);
+
+        let mut contents = String::new();
+        archive.by_name("root/sub/b.txt").unwrap()
+            .read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "Nested file.".repeat(1000));
+        let mut contents = String::new();
+        archive.by_name("root/c.txt").unwrap()
+            .read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "Secret file.");
+
+        // Encrypted files that cannot be decrypted are skipped.
+        let data = write_zip(root.path(), "root", &Decryption::new(owner, None), Vec::new()).unwrap();
+        let archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        assert!(archive.index_for_name("root/c.txt").is_none());
+    }
+
+    #[test]
+    fn test_entry_path() {
+        assert_eq!(entry_path("a/b.txt"), Some(PathBuf::from("a/b.txt")));
+        assert_eq!(entry_path("./a/"), Some(PathBuf::from("a")));
+        assert_eq!(entry_path("./"), Some(PathBuf::new()));
+
+        // Entries that would escape the directory in which the archive is extracted.
+        assert_eq!(entry_path("../a.txt"), None);
+        assert_eq!(entry_path("a/../../b.txt"), None);
+        assert_eq!(entry_path("/etc/passwd"), None);
+        assert_eq!(entry_path("a\\..\\..\\b.txt"), None);
+    }
+}
@@ -0,0 +1,252 @@
+//! Contains the sorting, filtering and pagination of the listings of the storages.
+//!
+//! Items are sorted by name, size or last modification time, in ascending or descending order;
+//! items with the same size or modification time are further sorted by name, and names are
+//! compared case-insensitively.
+//! Encrypted files are sorted by the size of their plain contents, which is the size listed.
+//!
+//! Pages are given by a cursor, which encodes the position of the last item of the previous page
+//! in the sorting order, so that listing the following page neither skips nor repeats items when
+//! other items are created or deleted in the meantime.
+
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::Deserialize;
+use tusk_core::error::{TuskError, TuskResult};
+use crate::api::storage::{PathKind, plain_size, StoragePathRead};
+use crate::api::trash::epoch_delta;
+
+/// Maximum number of items in a page.
+pub const MAX_LIMIT: usize = 1000;
+
+/// Describes the attribute by which the items are sorted.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListingSort {
+    /// Items are sorted by name.
+    #[default]
+    Name,
+    /// Items are sorted by size, with storages before files.
+    Size,
+    /// Items are sorted by last modification time.
+    Modified
+}
+impl ListingSort {
+    /// Returns the name of the attribute, as used in the cursors.
+    fn name(&self) -> &'static str {
+        match self {
+            ListingSort::Name => "name",
+            ListingSort::Size => "size",
+            ListingSort::Modified => "modified"
+        }
+    }
+}
+
+/// Describes the direction in which the items are sorted.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListingOrder {
+    /// Items are sorted in ascending order.
+    #[default]
+    Asc,
+    /// Items are sorted in descending order.
+    Desc
+}
+
+/// Contains the sorting, the filters and the page of a listing.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct ListingOptions {
+    /// Attribute by which the items are sorted.
+    pub sort: ListingSort,
+    /// Direction in which the items are sorted.
+    pub order: ListingOrder,
+    /// Kind of the listed items, or `None` to list both files and storages.
+    pub kind: Option<PathKind>,
+    /// Case-insensitive prefix of the names of the listed items.
+    pub prefix: Option<String>,
+    /// Maximum number of items in the page, or `None` to list all the items.
+    pub limit: Option<usize>,
+    /// Cursor returned with the previous page, or `None` to list the first page.
+    pub cursor: Option<String>
+}
+
+/// Represents a page of a listing.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ListingPage {
+    /// Items in the page.
+    pub items: Vec<StoragePathRead>,
+    /// Cursor of the following page, or `None` if this is the last page.
+    pub next: Option<String>
+}
+
+/// Represents the position of an item in the sorting order.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+struct SortKey {
+    value: i128,
+    folded: String,
+    name: String
+}
+impl SortKey {
+    /// Returns the position of the item with the given name, metadata and listed size.
+    fn new(sort: ListingSort, name: String, metadata: &Metadata, size: u64) -> SortKey {
+        let value = match sort {
+            ListingSort::Name => 0,
+            ListingSort::Size if metadata.is_dir() => -1,
+            ListingSort::Size => size as i128,
+            ListingSort::Modified => epoch_delta(metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)) as i128
+        };
+        SortKey::from_parts(value, name)
+    }
+    /// Returns the position of the item with the given sorting value and name.
+    fn from_parts(value: i128, name: String) -> SortKey {
+        SortKey { value, folded: name.to_lowercase(), name }
+    }
+
+    /// Returns the cursor of the page following this position.
+    fn to_cursor(&self, sort: ListingSort) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}:{}:{}", sort.name(), self.value, self.name))
+    }
+    /// Returns the position encoded by the given cursor.
+    ///
+    /// # Errors
+    /// If the cursor is malformed, or has been returned for a listing sorted by another attribute,
+    /// this function returns an HTTP error 400 `BAD REQUEST`.
+    fn from_cursor(cursor: &str, sort: ListingSort) -> TuskResult<SortKey> {
+        let decoded = URL_SAFE_NO_PAD.decode(cursor).ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .ok_or_else(TuskError::bad_request)?;
+        let mut parts = decoded.splitn(3, ':');
+        let (Some(name), Some(value), Some(item)) = (parts.next(), parts.next(), parts.next()) else {
+            return TuskError::bad_request().bail();
+        };
+        if name != sort.name() { return TuskError::bad_request().bail(); }
+        let value = value.parse().map_err(|_| TuskError::bad_request())?;
+        Ok(SortKey::from_parts(value, item.to_owned()))
+    }
+}
+
+/// Lists the page of the items inside the directory at `path` given by `options`.
+///
+/// If `decrypted` is `true`, the files are sorted by the size of their plain contents, as in the
+/// user roots; the sizes of the listed items are left to the caller.
+///
+/// # Errors
+/// If the limit is `0` or greater than [`MAX_LIMIT`], or if the cursor is not valid, this function
+/// returns an HTTP error 400 `BAD REQUEST`.
+pub fn list(path: &Path, options: &ListingOptions, decrypted: bool) -> TuskResult<ListingPage> {
+    if options.limit.is_some_and(|limit| limit == 0 || limit > MAX_LIMIT) {
+        return TuskError::bad_request().bail();
+    }
+    let after = options.cursor.as_deref()
+        .map(|cursor| SortKey::from_cursor(cursor, options.sort))
+        .transpose()?;
+    let prefix = options.prefix.as_deref().map(str::to_lowercase);
+
+    // Only the items in the page are read completely, which makes large storages faster to list.
+    let mut entries: Vec<(SortKey, PathBuf)> = std::fs::read_dir(path)?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let metadata = path.metadata().ok()?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            match options.kind {
+                Some(PathKind::File) if metadata.is_dir() => return None,
+                Some(PathKind::Directory) if !metadata.is_dir() => return None,
+                _ => {}
+            }
+            if prefix.as_ref().is_some_and(|prefix| !name.to_lowercase().starts_with(prefix)) { return None; }
+            let size = if decrypted && options.sort == ListingSort::Size && metadata.is_file() {
+                plain_size(&path, metadata.len())
+            } else {
+                metadata.len()
+            };
+            Some((SortKey::new(options.sort, name, &metadata, size), path))
+        })
+        .filter(|(key, _)| match (&after, options.order) {
+            (None, _) => true,
+            (Some(after), ListingOrder::Asc) => key > after,
+            (Some(after), ListingOrder::Desc) => key < after
+        })
+        .collect();
+    entries.sort_unstable_by(|(a, _), (b, _)| match options.order {
+        ListingOrder::Asc => a.cmp(b),
+        ListingOrder::Desc => b.cmp(a)
+    });
+
+    let next = match options.limit {
+        Some(limit) if entries.len() > limit => {
+            entries.truncate(limit);
+            entries.last().map(|(key, _)| key.to_cursor(options.sort))
+        },
+        _ => None
+    };
+    let items = entries.into_iter()
+        .map(|(_, path)| StoragePathRead::from_path(path))
+        .collect::<TuskResult<Vec<_>>>()?;
+
+    Ok(ListingPage { items, next })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+    use uuid::Uuid;
+    use tusk_core::encryption::{DataKey, encrypt};
+    use crate::api::storage::listing::{list, ListingOptions, ListingOrder, ListingSort};
+    use crate::api::storage::PathKind;
+
+    #[test]
+    fn test_listing() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("albums")).unwrap();
+        for (name, contents, modified) in [("b.jpg", "bb", 300), ("A.jpg", "aaa", 100), ("c.png", "c", 200)] {
+            let path = root.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(SystemTime::UNIX_EPOCH + Duration::from_secs(modified))).unwrap();
+        }
+        let names = |options: &ListingOptions| {
+            let page = list(root.path(), options, false).unwrap();
+            (page.items.iter().map(|item| item.filename().to_owned()).collect::<Vec<_>>(), page.next)
+        };
+
+        assert_eq!(names(&ListingOptions::default()).0, ["A.jpg", "albums", "b.jpg", "c.png"]);
+        let options = ListingOptions { sort: ListingSort::Size, order: ListingOrder::Desc, ..Default::default() };
+        assert_eq!(names(&options).0, ["A.jpg", "b.jpg", "c.png", "albums"]);
+        let options = ListingOptions { sort: ListingSort::Modified, kind: Some(PathKind::File), ..Default::default() };
+        assert_eq!(names(&options).0, ["A.jpg", "c.png", "b.jpg"]);
+        let options = ListingOptions { prefix: Some(String::from("a")), ..Default::default() };
+        assert_eq!(names(&options).0, ["A.jpg", "albums"]);
+
+        let mut options = ListingOptions { sort: ListingSort::Size, limit: Some(3), ..Default::default() };
+        let (first, next) = names(&options);
+        assert_eq!(first, ["albums", "c.png", "b.jpg"]);
+        options.cursor = next;
+        assert_eq!(names(&options), (vec![String::from("A.jpg")], None));
+
+        // Cursors are only valid for the same sorting.
+        options.sort = ListingSort::Name;
+        assert!(list(root.path(), &options, false).is_err());
+        options.cursor = Some(String::from("not a cursor"));
+        assert!(list(root.path(), &options, false).is_err());
+    }
+
+    #[test]
+    fn test_listing_encrypted() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("plain.txt"), "Ten bytes!").unwrap();
+        let mut encrypted = Vec::new();
+        encrypt(&DataKey::generate(), Uuid::new_v4(), 1, &b"!"[..], &mut encrypted).unwrap();
+        std::fs::write(root.path().join("secret.txt"), encrypted).unwrap();
+
+        let options = ListingOptions { sort: ListingSort::Size, ..Default::default() };
+        let names = |decrypted: bool| list(root.path(), &options, decrypted).unwrap()
+            .items.iter()
+            .map(|item| item.filename().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names(false), ["plain.txt", "secret.txt"]);
+        assert_eq!(names(true), ["secret.txt", "plain.txt"]);
+    }
+}
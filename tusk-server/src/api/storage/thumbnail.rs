@@ -0,0 +1,181 @@
+//! Contains the thumbnails of the images in the storages.
+//!
+//! Thumbnails are generated on request and cached in the thumbnails directory, with the same
+//! layout as the storage root: the thumbnails of the file `<path>` are stored in the directory
+//! `<path>` of the thumbnails directory, and are named after their size and the entity tag of the
+//! file, that is, its size and its last modification time to the nanosecond, so that a thumbnail
+//! is generated again as soon as the file changes.
+//! The thumbnails of a file are evicted when the file is deleted, moved or replaced.
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use image::{DynamicImage, ImageError, ImageFormat, ImageReader, Limits, Rgb, RgbImage};
+use image::codecs::jpeg::JpegEncoder;
+use tusk_core::error::{TuskError, TuskResult};
+use crate::api::encryption::is_encrypted;
+
+/// Sizes, in pixels, in which thumbnails can be requested.
+pub const THUMBNAIL_SIZES: [u32; 5] = [64, 128, 256, 512, 1024];
+/// Quality of the JPEG encoding of the thumbnails.
+const JPEG_QUALITY: u8 = 85;
+/// Maximum width and height, in pixels, of the images from which thumbnails are generated.
+const MAX_IMAGE_DIMENSION: u32 = 16384;
+
+/// Converts an error of the image library into the corresponding HTTP error.
+fn image_error(e: ImageError) -> TuskError {
+    match e {
+        ImageError::IoError(e) => e.into(),
+        _ => TuskError::unsupported_media_type()
+    }
+}
+
+/// Decodes the image at `path`.
+///
+/// # Errors
+/// If the file is not an image in one of the supported formats (JPEG, PNG, GIF and WebP), or if
+/// the image is too large, this function returns an HTTP error 415 `UNSUPPORTED MEDIA TYPE`.
+fn decode(path: &Path) -> TuskResult<DynamicImage> {
+    let mut reader = ImageReader::open(path)?
+        .with_guessed_format()?;
+    match reader.format() {
+        Some(ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::Gif | ImageFormat::WebP) => {},
+        _ => return TuskError::unsupported_media_type().bail()
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    reader.limits(limits);
+
+    reader.decode()
+        .map_err(image_error)
+}
+
+/// Converts the given image into an RGB image, blending the transparent pixels over a white
+/// background.
+fn flatten(image: DynamicImage) -> RgbImage {
+    if !image.color().has_alpha() { return image.into_rgb8(); }
+
+    let image = image.into_rgba8();
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [red, green, blue, alpha] = image.get_pixel(x, y).0;
+        let blend = |channel: u8| ((channel as u32 * alpha as u32 + 255 * (255 - alpha as u32)) / 255) as u8;
+        Rgb([blend(red), blend(green), blend(blue)])
+    })
+}
+
+/// Returns the path of a JPEG thumbnail, of at most `size` × `size` pixels, of the image at
+/// `path`, whose request path is `request_path`, generating the thumbnail in the `thumbnails`
+/// directory if it is not cached yet.
+///
+/// # Errors
+/// If `size` is not one of the [`THUMBNAIL_SIZES`], this function returns an HTTP error
+/// 400 `BAD REQUEST`.
+///
+/// If the file is not an image in one of the supported formats, if the image is too large, or if
+/// the file is encrypted, this function returns an HTTP error 415 `UNSUPPORTED MEDIA TYPE`.
+pub fn thumbnail(thumbnails: &Path, path: &Path, request_path: &str, size: u32) -> TuskResult<PathBuf> {
+    if !THUMBNAIL_SIZES.contains(&size) { return TuskError::bad_request().bail(); }
+    // Thumbnails are cached unencrypted, hence they would disclose the contents of the file.
+    if is_encrypted(path) { return TuskError::unsupported_media_type().bail(); }
+
+    let metadata = path.metadata()?;
+    let modified = metadata.modified()?
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let directory = thumbnails.join(request_path);
+    let thumbnail = directory.join(format!("{size}-{:x}-{modified:x}.jpg", metadata.len()));
+    if thumbnail.is_file() { return Ok(thumbnail); }
+
+    let image = flatten(decode(path)?.thumbnail(size, size));
+    std::fs::create_dir_all(&directory)?;
+    let mut file = tempfile::Builder::new()
+        .prefix(".tusk-")
+        .tempfile_in(&directory)?;
+    JpegEncoder::new_with_quality(&mut file, JPEG_QUALITY)
+        .encode_image(&image)
+        .map_err(image_error)?;
+    file.persist(&thumbnail)
+        .map_err(|e| TuskError::from(e.error))?;
+
+    // Removes the thumbnails of the same size generated for the previous contents of the file.
+    let prefix = format!("{size}-");
+    for entry in std::fs::read_dir(&directory)?.filter_map(Result::ok) {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(&prefix) && entry.path() != thumbnail {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+
+    Ok(thumbnail)
+}
+
+/// Removes the cached thumbnails of the item whose request path is `request_path`, together with
+/// the thumbnails of the items inside it.
+///
+/// Errors are logged and otherwise ignored, as stale thumbnails are only a waste of space.
+pub fn evict(thumbnails: &Path, request_path: &str) {
+    match std::fs::remove_dir_all(thumbnails.join(request_path)) {
+        Ok(()) => {},
+        Err(e) if e.kind() == ErrorKind::NotFound => {},
+        Err(e) => log::warn!("Cannot remove the thumbnails of `{request_path}`: {e}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+    use actix_web::ResponseError;
+    use image::{GenericImageView, Rgba, RgbaImage};
+    use tusk_core::error::TuskError;
+    use crate::api::storage::thumbnail::{evict, thumbnail};
+
+    #[test]
+    fn test_thumbnail() {
+        let root = tempfile::tempdir().unwrap();
+        let thumbnails = root.path().join("thumbnails");
+        let path = root.path().join("picture.png");
+        RgbaImage::from_pixel(400, 200, Rgba([255, 0, 0, 0])).save(&path).unwrap();
+
+        let first = thumbnail(&thumbnails, &path, "user/picture.png", 128).unwrap();
+        assert!(first.starts_with(thumbnails.join("user/picture.png")));
+        let image = image::open(&first).unwrap();
+        assert_eq!(image.dimensions(), (128, 64));
+        // Transparent pixels are white.
+        assert!(image.get_pixel(64, 32).0.iter().take(3).all(|&channel| channel > 250));
+
+        // Cached thumbnails are not generated again.
+        assert_eq!(thumbnail(&thumbnails, &path, "user/picture.png", 128).unwrap(), first);
+
+        // Thumbnails of the previous contents are removed when the file changes.
+        std::fs::File::options().write(true).open(&path).unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        let second = thumbnail(&thumbnails, &path, "user/picture.png", 128).unwrap();
+        assert_ne!(second, first);
+        assert!(second.exists());
+        assert!(!first.exists());
+
+        // Thumbnails also change with the size of the file, whatever its modification time.
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        RgbaImage::from_pixel(200, 200, Rgba([0, 0, 255, 255])).save(&path).unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap()
+            .set_modified(modified).unwrap();
+        let third = thumbnail(&thumbnails, &path, "user/picture.png", 128).unwrap();
+        assert_ne!(third, second);
+        assert_eq!(image::open(&third).unwrap().dimensions(), (128, 128));
+
+        evict(&thumbnails, "user/picture.png");
+        assert!(!third.exists());
+        evict(&thumbnails, "user/picture.png");
+
+        let error = thumbnail(&thumbnails, &path, "user/picture.png", 100).unwrap_err();
+        assert_eq!(error.status_code(), TuskError::bad_request().status_code());
+
+        std::fs::write(root.path().join("text.txt"), "Not an image").unwrap();
+        let error = thumbnail(&thumbnails, &root.path().join("text.txt"), "user/text.txt", 128).unwrap_err();
+        assert_eq!(error.status_code(), TuskError::unsupported_media_type().status_code());
+    }
+}
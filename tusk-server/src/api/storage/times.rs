@@ -0,0 +1,144 @@
+//! Contains the timestamps of the items in the storages.
+//!
+//! The last access and modification times are set directly on the filesystem.
+//! The creation time, instead, cannot be changed on Unix filesystems: there, it is stored in the
+//! `user.tusk.created` extended attribute of the item, which is reported in place of the creation
+//! time known to the filesystem, and which follows the item when it is moved, deleted or restored.
+//! On Windows, the creation time is set directly on the filesystem as well.
+
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::SystemTime;
+use filetime::FileTime;
+#[cfg(unix)]
+use crate::api::storage::system_type_from_epoch_delta;
+#[cfg(unix)]
+use crate::api::trash::epoch_delta;
+
+/// Name of the extended attribute storing the creation time of an item, in seconds since the epoch.
+#[cfg(unix)]
+const CREATED_ATTRIBUTE: &str = "user.tusk.created";
+
+/// Contains the timestamps requested by the client for a new item.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+pub struct PathTimes {
+    created: Option<SystemTime>,
+    last_access: Option<SystemTime>,
+    last_modified: Option<SystemTime>
+}
+impl PathTimes {
+    /// Creates the timestamps with the given creation, last access and last modification times,
+    /// where `None` leaves the corresponding time as set by the filesystem.
+    pub fn new(created: Option<SystemTime>, last_access: Option<SystemTime>, last_modified: Option<SystemTime>) -> PathTimes {
+        PathTimes { created, last_access, last_modified }
+    }
+
+    /// Applies the timestamps to the item at `path`.
+    ///
+    /// If the filesystem does not support extended attributes, the creation time is ignored.
+    pub fn apply(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(created) = self.created {
+            set_created(path, created)?;
+        }
+        match (self.last_access, self.last_modified) {
+            (Some(last_access), Some(last_modified)) => {
+                filetime::set_file_times(path, FileTime::from_system_time(last_access), FileTime::from_system_time(last_modified))
+            },
+            (Some(last_access), None) => filetime::set_file_atime(path, FileTime::from_system_time(last_access)),
+            (None, Some(last_modified)) => filetime::set_file_mtime(path, FileTime::from_system_time(last_modified)),
+            (None, None) => Ok(())
+        }
+    }
+}
+
+/// Stores the creation time of the item at `path`.
+#[cfg(unix)]
+fn set_created(path: &Path, created: SystemTime) -> std::io::Result<()> {
+    match xattr::set(path, CREATED_ATTRIBUTE, epoch_delta(created).to_string().as_bytes()) {
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {
+            log::warn!("Cannot store the creation time of `{}`: {e}", path.display());
+            Ok(())
+        },
+        result => result
+    }
+}
+
+/// Sets the creation time of the item at `path`.
+#[cfg(windows)]
+fn set_created(path: &Path, created: SystemTime) -> std::io::Result<()> {
+    use std::fs::{FileTimes, OpenOptions};
+    use std::os::windows::fs::{FileTimesExt, OpenOptionsExt};
+
+    /// Allows directories to be opened as well as files.
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x02000000;
+
+    OpenOptions::new()
+        .write(true)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)?
+        .set_times(FileTimes::new().set_created(created))
+}
+
+/// Returns the creation time stored in the extended attribute of the item at `path`, if any.
+#[cfg(unix)]
+fn stored_created(path: &Path) -> Option<SystemTime> {
+    let value = xattr::get(path, CREATED_ATTRIBUTE).ok()??;
+    let created = std::str::from_utf8(&value).ok()?.parse().ok()?;
+
+    Some(system_type_from_epoch_delta(created))
+}
+
+/// Returns the creation time of the item at `path`, whose metadata is `metadata`.
+pub fn created(path: &Path, metadata: &Metadata) -> std::io::Result<SystemTime> {
+    #[cfg(unix)]
+    if let Some(created) = stored_created(path) {
+        return Ok(created);
+    }
+
+    metadata.created()
+}
+
+/// Copies the timestamps of the item at `from` to the item at `to`.
+///
+/// On Unix filesystems, the creation time is copied only if it is stored in the extended
+/// attribute of the item at `from`.
+pub fn copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    let metadata = from.metadata()?;
+    #[cfg(unix)]
+    let created = stored_created(from);
+    #[cfg(windows)]
+    let created = metadata.created().ok();
+
+    PathTimes::new(created, metadata.accessed().ok(), metadata.modified().ok())
+        .apply(to)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+    use crate::api::storage::times::{created, PathTimes};
+
+    #[test]
+    fn test_path_times() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("photo.jpg");
+        std::fs::write(&path, "Photo").unwrap();
+
+        let time = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        PathTimes::new(Some(time(1_000_000_000)), Some(time(1_200_000_000)), Some(time(1_100_000_000)))
+            .apply(&path)
+            .unwrap();
+        let metadata = path.metadata().unwrap();
+        assert_eq!(metadata.accessed().unwrap(), time(1_200_000_000));
+        assert_eq!(metadata.modified().unwrap(), time(1_100_000_000));
+        assert_eq!(created(&path, &metadata).unwrap(), time(1_000_000_000));
+
+        // Missing timestamps are left untouched.
+        PathTimes::new(None, None, Some(time(1_300_000_000)))
+            .apply(&path)
+            .unwrap();
+        let metadata = path.metadata().unwrap();
+        assert_eq!(metadata.accessed().unwrap(), time(1_200_000_000));
+        assert_eq!(metadata.modified().unwrap(), time(1_300_000_000));
+    }
+}
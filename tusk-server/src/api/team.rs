@@ -0,0 +1,62 @@
+//! Contains the CRUD structures relative to the `/storage/teams` REST resource.
+//!
+//! A team folder is a storage shared by a team, whose members are users or roles, rather than
+//! owned by a single user.
+//! Team folders are created and their members are managed by the administrators, through the
+//! `team` command of `tusk-admin`.
+//!
+//! # Security
+//! ## Access
+//! A member of a team folder accesses it through the `/storage` resource with the path
+//! `/teams/<name>/`, where `<name>` is the name of the team folder; see the Access section of the
+//! [`storage`](crate::api::storage) module.
+//! Every member has either read-only or read-write access; a user who is a member both directly
+//! and through its roles has read-write access if any of the memberships is read-write.
+//! A team folder itself cannot be moved or deleted by its members.
+//!
+//! The team folders of which the user is a member are listed by the `/storage/teams` resource.
+
+use actix_web::HttpResponse;
+use serde::Serialize;
+use tusk_core::config::Tusk;
+use tusk_core::error::TuskHttpResult;
+use tusk_core::resources::TeamFolder;
+use tusk_derive::rest_resource;
+use crate::api::storage::{PathInfo, StoragePathRead};
+
+/// Represents the CRUD **Read** structure relative to the `/storage/teams` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct TeamFolderRead {
+    name: String,
+    path: String,
+    writable: bool,
+    item: StoragePathRead
+}
+
+/// Represents the `/storage/teams` REST resource.
+///
+/// The `/storage/teams` resource is responsible for listing the team folders of which the user is
+/// a member.
+pub struct StorageTeamsResource;
+#[rest_resource("/storage/teams")]
+impl StorageTeamsResource {
+    async fn get(tusk: Tusk) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+
+        let mut teams: Vec<TeamFolderRead> = Vec::new();
+        for (team_folder, writable) in TeamFolder::list_for_member(&mut db, user_id)? {
+            let path = PathInfo::from_queried_path(&tusk, team_folder.path())?;
+            if !path.is_directory() { continue; }
+
+            teams.push(TeamFolderRead {
+                name: team_folder.name().to_owned(),
+                path: team_folder.path(),
+                writable,
+                item: path.info()?
+            });
+        }
+
+        Ok(HttpResponse::Ok().json(teams))
+    }
+}
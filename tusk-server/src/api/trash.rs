@@ -0,0 +1,203 @@
+//! Contains the CRUD structures relative to the `/trash` REST resource.
+//!
+//! Items deleted from the storage are moved to the trash of the user owning the root which
+//! contained them, together with their original path and the time of the deletion; in particular,
+//! items deleted by another user from a shared storage end up in the trash of the owner.
+//! Items deleted from the public root or from a team folder, which have no owner, are moved to the
+//! trash of the user who deleted them.
+//!
+//! Deleted items keep counting towards the usage of the root which contained them until they are
+//! purged.
+//! The trash can be on another filesystem than the user directories, in which case deleted and
+//! restored items are copied, and then removed, instead of being moved.
+//!
+//! # Security
+//! ## Access
+//! A user can only list, restore and purge the items in their own trash.
+//! If any other user tries to access a deleted item, the response will be `NOT FOUND`.
+//!
+//! ## Restoring
+//! A deleted item is restored to its original path by `POST`ing to the corresponding REST
+//! resource.
+//!
+//! The same rules as in the Creation section of the [`storage`](crate::api::storage) module apply
+//! to the original path; in particular, if the original storage no longer exists or another item
+//! took the place of the deleted one, the response is `CONFLICT`.
+//!
+//! ## Purging
+//! A deleted item is permanently removed by `DELETE`ing the corresponding REST resource, while
+//! the whole trash is emptied by `DELETE`ing the `/trash` resource.
+//!
+//! Deleted items are purged automatically after the retention period set in `tusk.toml`; the
+//! expired items are looked for every hour, as well as whenever the trash is used.
+
+use std::io::ErrorKind;
+use std::time::{Duration, SystemTime};
+use actix_web::{HttpResponse, web};
+use actix_web::http::header;
+use serde::Serialize;
+use uuid::Uuid;
+use tusk_core::config::{Tusk, TuskConfiguration};
+use tusk_core::error::{TuskHttpResult, TuskResult};
+use tusk_core::PgConnection;
+use tusk_core::resources::TrashItem;
+use tusk_derive::rest_resource;
+use crate::api::storage::PathInfo;
+
+/// Interval between two automatic purges of the expired items.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Permanently removes the given deleted item from the disk.
+fn remove_data(item: &TrashItem, config: &TuskConfiguration) -> TuskResult<()> {
+    let path = item.file(config);
+    let result = match path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&path),
+        Ok(_) => std::fs::remove_file(&path),
+        Err(e) => Err(e)
+    };
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into())
+    }
+}
+
+/// Purges all the items deleted longer than the retention period ago.
+///
+/// The data of each item is removed before its record, so that the items whose data cannot be
+/// removed are still listed, and purged again later.
+pub fn purge_expired(config: &TuskConfiguration, db: &mut PgConnection) -> TuskResult<()> {
+    for item in TrashItem::list_expired(db, config.trash_retention())? {
+        log::info!("Deleted item `{}` expired", item.original_path());
+        remove_data(&item, config)?;
+        item.delete(db)?;
+    }
+    Ok(())
+}
+
+/// Starts the thread which purges the expired items every [`PURGE_INTERVAL`], so that they do not
+/// linger in the trash of the users who do not use it.
+pub fn spawn_purger(config: &TuskConfiguration) -> TuskResult<()> {
+    let config = config.clone();
+    std::thread::Builder::new()
+        .name(String::from("tusk-trash"))
+        .spawn(move || loop {
+            if let Err(e) = config.db().and_then(|mut db| purge_expired(&config, &mut db)) {
+                log::error!("Cannot purge the trash: {e}");
+            }
+            std::thread::sleep(PURGE_INTERVAL);
+        })?;
+
+    Ok(())
+}
+
+/// Converts the given time into a signed distance, in seconds, from [`SystemTime::UNIX_EPOCH`].
+pub fn epoch_delta(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64)
+    }
+}
+
+/// Represents the CRUD **Read** structure relative to the `/trash` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct TrashItemRead {
+    id: Uuid,
+    filename: String,
+    original_path: String,
+    is_directory: bool,
+    deletion: i64,
+    expiration: i64
+}
+impl TrashItemRead {
+    /// Collects the information relative to the given deleted item.
+    pub fn from_item(item: &TrashItem, config: &TuskConfiguration) -> TrashItemRead {
+        let original_path = item.original_path().to_owned();
+        let filename = original_path.rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+
+        TrashItemRead {
+            id: item.id(),
+            filename,
+            original_path,
+            is_directory: item.file(config).is_dir(),
+            deletion: epoch_delta(item.deletion()),
+            expiration: epoch_delta(item.deletion() + config.trash_retention())
+        }
+    }
+}
+
+/// Represents the `/trash` REST resource.
+///
+/// The `/trash` resource is responsible for listing the items deleted by the user and for
+/// emptying the trash.
+pub struct TrashResource;
+#[rest_resource("/trash")]
+impl TrashResource {
+    async fn get(tusk: Tusk) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+        purge_expired(tusk.config(), &mut db)?;
+
+        let items: Vec<TrashItemRead> = TrashItem::list_for_user(&mut db, user_id)?
+            .iter()
+            .map(|item| TrashItemRead::from_item(item, tusk.config()))
+            .collect();
+
+        Ok(HttpResponse::Ok().json(items))
+    }
+
+    async fn delete(tusk: Tusk) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+
+        for item in TrashItem::list_for_user(&mut db, user_id)? {
+            remove_data(&item, tusk.config())?;
+            item.delete(&mut db)?;
+        }
+
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+/// Represents the `/trash/{trash_item_id}` REST resource.
+///
+/// The `/trash/{trash_item_id}` resource is responsible for restoring or purging a single
+/// deleted item.
+pub struct TrashItemResource;
+#[rest_resource("/trash/{trash_item_id}")]
+impl TrashItemResource {
+    async fn post(tusk: Tusk, trash_item_id: web::Path<Uuid>) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+        purge_expired(tusk.config(), &mut db)?;
+        let item = TrashItem::from_id(&mut db, *trash_item_id, user_id)?;
+
+        let path = PathInfo::from_queried_path(&tusk, item.original_path())?;
+        path.restore(tusk.config(), &mut db, &item.file(tusk.config()))?;
+        item.delete(&mut db)?;
+
+        let attr = path.info()?;
+        let location = if path.is_directory() {
+            format!("/v1/storage/{}/", path.request_path())
+        } else {
+            format!("/v1/storage/{}", path.request_path())
+        };
+        Ok(HttpResponse::Created()
+            .insert_header((header::LOCATION, location))
+            .json(attr))
+    }
+
+    async fn delete(tusk: Tusk, trash_item_id: web::Path<Uuid>) -> TuskHttpResult {
+        let user_id = tusk.authenticate()?.user_id();
+        let mut db = tusk.db()?;
+        let item = TrashItem::from_id(&mut db, *trash_item_id, user_id)?;
+
+        remove_data(&item, tusk.config())?;
+        item.delete(&mut db)?;
+
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
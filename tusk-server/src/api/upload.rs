@@ -0,0 +1,323 @@
+//! Contains the CRUD structures relative to the `/uploads` REST resource.
+//!
+//! The `/uploads` resource implements the core protocol of [tus](https://tus.io/protocols/resumable-upload),
+//! version `1.0.0`, together with the `creation` and `termination` extensions.
+//! It allows to upload large files in multiple requests, resuming the upload after an interruption.
+//!
+//! # Security
+//! ## Creation
+//! An upload is created by `POST`ing to `/uploads` the total size of the file in the
+//! `Upload-Length` header, together with the `filename` of the file and the `destination` storage
+//! in the `Upload-Metadata` header.
+//!
+//! The same rules as in the Creation section of the [`storage`](crate::api::storage) module apply
+//! to the destination storage and to the file name, which are checked both when the upload is
+//! created and when it is completed.
+//! If the file would exceed the quota of the destination, the response is `INSUFFICIENT STORAGE`.
+//!
+//! ## Access
+//! An upload can only be accessed by the user that created it.
+//! If any other user tries to access the upload, the response will be `NOT FOUND`.
+//!
+//! ## Uploading
+//! Data is appended to an upload by `PATCH`ing the corresponding REST resource with the
+//! `Upload-Offset` header equal to the current offset of the upload, which can be retrieved with
+//! `HEAD`.
+//!
+//! If the offset does not match, or if another request is already appending data to the same
+//! upload, the response is `CONFLICT`. If the data exceeds the total size of the file, the
+//! response is `PAYLOAD TOO LARGE`.
+//! If the request is interrupted, the data received so far is kept.
+//!
+//! Once all the data has been received, the file is moved to its destination and the upload
+//! is deleted.
+//! If the file cannot be created, the upload is kept and the response is the same as in the
+//! Creation section; completing it can then be retried by `PATCH`ing it with no data.
+//!
+//! ## Expiration
+//! Uploads that have not been modified for 24 hours are discarded, together with their data.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Seek, SeekFrom, Write};
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::http::header;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use futures_util::StreamExt;
+use uuid::Uuid;
+use tusk_core::config::{Tusk, TuskConfiguration};
+use tusk_core::error::{HttpOkOr, TuskError, TuskHttpResult, TuskResult};
+use tusk_core::resources::Upload;
+use tusk_derive::rest_resource;
+use crate::api::storage::PathInfo;
+
+/// Version of the tus protocol implemented by the server.
+pub const TUS_VERSION: &str = "1.0.0";
+/// Extensions of the tus protocol implemented by the server.
+pub const TUS_EXTENSIONS: &str = "creation,termination";
+const TUS_RESUMABLE: &str = "Tus-Resumable";
+const UPLOAD_LENGTH: &str = "Upload-Length";
+const UPLOAD_METADATA: &str = "Upload-Metadata";
+const UPLOAD_OFFSET: &str = "Upload-Offset";
+const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+/// IDs of the uploads that are currently receiving data.
+static ACTIVE_UPLOADS: Mutex<BTreeSet<Uuid>> = Mutex::new(BTreeSet::new());
+
+/// Guard that marks an upload as receiving data for as long as it lives.
+struct ActiveUpload(Uuid);
+impl ActiveUpload {
+    /// Marks the given upload as receiving data.
+    ///
+    /// # Errors
+    /// If the upload is already receiving data from another request, this function returns
+    /// an HTTP error 409 `CONFLICT`.
+    fn acquire(upload_id: Uuid) -> TuskResult<ActiveUpload> {
+        let mut active = ACTIVE_UPLOADS.lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !active.insert(upload_id) { return TuskError::conflict().bail(); }
+        Ok(ActiveUpload(upload_id))
+    }
+}
+impl Drop for ActiveUpload {
+    fn drop(&mut self) {
+        ACTIVE_UPLOADS.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.0);
+    }
+}
+
+/// Checks that the client uses the same version of the tus protocol as the server.
+///
+/// # Errors
+/// If the `Tus-Resumable` header is missing or contains a different version, this function
+/// returns an HTTP error 412 `PRECONDITION FAILED`.
+fn check_tus_resumable(req: &HttpRequest) -> TuskResult<()> {
+    match req.headers().get(TUS_RESUMABLE) {
+        Some(version) if version == TUS_VERSION => Ok(()),
+        _ => TuskError::precondition_failed().bail()
+    }
+}
+
+/// Parses the value of the header with the given `name`.
+///
+/// # Errors
+/// If the header is missing or is not valid, this function returns an HTTP error
+/// 400 `BAD REQUEST`.
+fn parse_header<T: FromStr>(req: &HttpRequest, name: &str) -> TuskResult<T> {
+    req.headers().get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .or_bad_request()
+}
+
+/// Parses the `Upload-Metadata` header, which is a comma-separated list of keys followed by
+/// the respective base64-encoded value.
+///
+/// # Errors
+/// If a value is not a valid base64-encoded UTF-8 string, this function returns an HTTP error
+/// 400 `BAD REQUEST`.
+fn parse_metadata(req: &HttpRequest) -> TuskResult<HashMap<String, String>> {
+    let mut metadata = HashMap::new();
+    let Some(value) = req.headers().get(UPLOAD_METADATA) else { return Ok(metadata); };
+    let value = value.to_str()
+        .or_bad_request()?;
+
+    for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let (key, encoded) = pair.split_once(' ')
+            .unwrap_or((pair, ""));
+        let decoded = STANDARD.decode(encoded.trim())
+            .or_bad_request()?;
+        let decoded = String::from_utf8(decoded)
+            .or_bad_request()?;
+        metadata.insert(key.to_owned(), decoded);
+    }
+
+    Ok(metadata)
+}
+
+/// Removes the data of the given upload, if any.
+fn remove_data(upload: &Upload, config: &TuskConfiguration) -> TuskResult<()> {
+    match std::fs::remove_file(upload.file(config)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into())
+    }
+}
+
+/// Deletes all the expired uploads, together with their data.
+fn purge_expired(tusk: &Tusk) -> TuskResult<()> {
+    let mut db = tusk.db()?;
+    for upload in Upload::delete_expired(&mut db)? {
+        log::info!("Upload `{}` expired", upload.id());
+        remove_data(&upload, tusk.config())?;
+    }
+    Ok(())
+}
+
+/// Moves the data of a complete upload to its destination and deletes the upload.
+///
+/// If the file cannot be created, the upload is kept together with its data, so that completing
+/// it can be retried by `PATCH`ing it with no data; otherwise it expires as usual.
+fn complete(tusk: &Tusk, upload: Upload) -> TuskResult<PathInfo> {
+    let file = upload.file(tusk.config());
+    let data = tempfile::Builder::new()
+        .prefix(".tusk-")
+        .make_in(tusk.config().upload_directory(), |path| std::fs::hard_link(&file, path))?
+        .into_temp_path();
+    let destination = PathInfo::from_queried_path(tusk, upload.destination())?;
+    let path = destination.persist_file(tusk.config(), &mut *tusk.db()?, upload.filename(), data)?;
+
+    remove_data(&upload, tusk.config())?;
+    let mut db = tusk.db()?;
+    upload.delete(&mut db)?;
+    Ok(path)
+}
+
+/// Appends the chunks of `payload` to `file`, advancing `offset` by the number of bytes written.
+///
+/// # Errors
+/// If the payload would make the file longer than `length` bytes, this function returns an HTTP
+/// error 413 `PAYLOAD TOO LARGE`.
+///
+/// If the payload is interrupted, this function returns an HTTP error 400 `BAD REQUEST`.
+async fn write_payload(file: &mut std::fs::File, payload: &mut web::Payload, offset: &mut i64, length: i64) -> TuskResult<()> {
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.or_bad_request()?;
+        if *offset + chunk.len() as i64 > length {
+            return TuskError::payload_too_large().bail();
+        }
+        file.write_all(&chunk)?;
+        *offset += chunk.len() as i64;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Represents the `/uploads` REST resource.
+///
+/// The `/uploads` resource is responsible for creating new resumable uploads.
+pub struct UploadsResource;
+#[rest_resource("/uploads")]
+impl UploadsResource {
+    async fn options() -> HttpResponse {
+        HttpResponse::NoContent()
+            .insert_header((TUS_RESUMABLE, TUS_VERSION))
+            .insert_header(("Tus-Version", TUS_VERSION))
+            .insert_header(("Tus-Extension", TUS_EXTENSIONS))
+            .finish()
+    }
+
+    async fn post(tusk: Tusk, req: HttpRequest) -> TuskHttpResult {
+        check_tus_resumable(&req)?;
+        let length: i64 = parse_header(&req, UPLOAD_LENGTH)?;
+        if length < 0 { return TuskError::bad_request().bail(); }
+        let metadata = parse_metadata(&req)?;
+        let filename = metadata.get("filename")
+            .or_bad_request()?;
+        let destination = metadata.get("destination")
+            .or_bad_request()?;
+
+        let path = PathInfo::from_queried_path(&tusk, destination)?;
+        path.check_new_child(filename)?;
+        path.check_quota(tusk.config(), &mut *tusk.db()?, length as u64)?;
+        purge_expired(&tusk)?;
+
+        let mut db = tusk.db()?;
+        let user_id = tusk.authenticate()?.user_id();
+        let upload = Upload::create(&mut db, user_id, path.request_path(), filename, length)?;
+        let upload_id = upload.id();
+
+        std::fs::create_dir_all(tusk.config().upload_directory())?;
+        if let Err(e) = std::fs::File::create(upload.file(tusk.config())) {
+            upload.delete(&mut db)?;
+            return Err(e.into());
+        }
+        if upload.is_complete() {
+            complete(&tusk, upload)?;
+        }
+
+        Ok(HttpResponse::Created()
+            .insert_header((TUS_RESUMABLE, TUS_VERSION))
+            .insert_header((header::LOCATION, format!("/v1/uploads/{upload_id}")))
+            .finish())
+    }
+}
+
+/// Represents the `/uploads/{upload_id}` REST resource.
+///
+/// The `/uploads/{upload_id}` resource is responsible for receiving the data of an upload.
+pub struct UploadResource;
+#[rest_resource("/uploads/{upload_id}")]
+impl UploadResource {
+    async fn head(tusk: Tusk, upload_id: web::Path<Uuid>, req: HttpRequest) -> TuskHttpResult {
+        check_tus_resumable(&req)?;
+        let mut db = tusk.db()?;
+        let user_id = tusk.authenticate()?.user_id();
+        let upload = Upload::from_id(&mut db, *upload_id, user_id)?;
+
+        Ok(HttpResponse::Ok()
+            .insert_header((TUS_RESUMABLE, TUS_VERSION))
+            .insert_header((UPLOAD_OFFSET, upload.offset()))
+            .insert_header((UPLOAD_LENGTH, upload.length()))
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .finish())
+    }
+
+    async fn patch(tusk: Tusk, upload_id: web::Path<Uuid>, req: HttpRequest, mut payload: web::Payload) -> TuskHttpResult {
+        check_tus_resumable(&req)?;
+        match req.headers().get(header::CONTENT_TYPE) {
+            Some(content_type) if content_type == OFFSET_OCTET_STREAM => {},
+            _ => return TuskError::unsupported_media_type().bail()
+        }
+        let offset: i64 = parse_header(&req, UPLOAD_OFFSET)?;
+
+        let user_id = tusk.authenticate()?.user_id();
+        let _active = ActiveUpload::acquire(*upload_id)?;
+        let mut upload = Upload::from_id(&mut *tusk.db()?, *upload_id, user_id)?;
+        if offset != upload.offset() { return TuskError::conflict().bail(); }
+        if let Ok(content_length) = parse_header::<i64>(&req, header::CONTENT_LENGTH.as_str()) {
+            if offset + content_length > upload.length() {
+                return TuskError::payload_too_large().bail();
+            }
+        }
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(upload.file(tusk.config()))?;
+        file.set_len(offset as u64)?;
+        file.seek(SeekFrom::Start(offset as u64))?;
+
+        let mut written = offset;
+        let result = write_payload(&mut file, &mut payload, &mut written, upload.length()).await;
+        upload.update_offset(&mut *tusk.db()?, written)?;
+        result?;
+
+        if upload.is_complete() {
+            complete(&tusk, upload)?;
+        }
+
+        Ok(HttpResponse::NoContent()
+            .insert_header((TUS_RESUMABLE, TUS_VERSION))
+            .insert_header((UPLOAD_OFFSET, written))
+            .finish())
+    }
+
+    async fn delete(tusk: Tusk, upload_id: web::Path<Uuid>, req: HttpRequest) -> TuskHttpResult {
+        check_tus_resumable(&req)?;
+        let mut db = tusk.db()?;
+        let user_id = tusk.authenticate()?.user_id();
+        let _active = ActiveUpload::acquire(*upload_id)?;
+        let upload = Upload::from_id(&mut db, *upload_id, user_id)?;
+
+        remove_data(&upload, tusk.config())?;
+        upload.delete(&mut db)?;
+
+        Ok(HttpResponse::NoContent()
+            .insert_header((TUS_RESUMABLE, TUS_VERSION))
+            .finish())
+    }
+}
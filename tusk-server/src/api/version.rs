@@ -0,0 +1,151 @@
+//! Contains the CRUD structures relative to the `/storage-versions` REST resource.
+//!
+//! Whenever the contents of a file are replaced, the previous contents are kept as a numbered
+//! version of the file, together with their modification time, the user who wrote them (if known)
+//! and the user who replaced them.
+//! Versions are attached to the path of the file, so they are preserved when the file is deleted
+//! and restored from the trash.
+//!
+//! # Security
+//! ## Access
+//! The versions of a file are accessible under the same conditions as the file itself; see the
+//! Access section of the [`storage`](crate::api::storage) module.
+//!
+//! ## Restoring
+//! A version is restored by `POST`ing to the REST resource of the file with the number of the
+//! version in the `version` query parameter.
+//! The restored contents replace the current contents of the file, which are in turn kept as a
+//! new version.
+//!
+//! If the storage containing the file no longer exists, the response is `CONFLICT`.
+//!
+//! ## Pruning
+//! Old versions are pruned whenever a new version is created, according to the `keep_versions`
+//! and `thin_versions_after_days` options of the `tusk.serve` section of `tusk.toml`.
+
+use std::fs::File;
+use std::io::ErrorKind;
+use actix_files::NamedFile;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::http::header;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use tusk_core::config::{Tusk, TuskConfiguration};
+use tusk_core::error::{HttpOkOr, TuskError, TuskHttpResult, TuskResult};
+use tusk_core::PgConnection;
+use tusk_core::resources::FileVersion;
+use tusk_derive::rest_resource;
+use crate::api::encryption;
+use crate::api::preview;
+use crate::api::storage::PathInfo;
+use crate::api::trash::epoch_delta;
+
+/// Permanently removes the contents of the given version from the disk.
+fn remove_data(version: &FileVersion, config: &TuskConfiguration) -> TuskResult<()> {
+    match std::fs::remove_file(version.file(config)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into())
+    }
+}
+
+/// Prunes the versions of the file at `path`, relative to the storage root, according to the
+/// rules set in `tusk.toml`.
+pub fn prune_versions(config: &TuskConfiguration, db: &mut PgConnection, path: &str) -> TuskResult<()> {
+    for version in FileVersion::prune(db, path, config.keep_versions(), config.thin_versions_after())? {
+        log::info!("Pruned version {} of `{}`", version.version(), version.path());
+        remove_data(&version, config)?;
+    }
+    Ok(())
+}
+
+/// Represents the CRUD **Read** structure relative to the `/storage-versions` REST resource.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct FileVersionRead {
+    version: u32,
+    size: u64,
+    modified: i64,
+    author: Option<Uuid>,
+    replaced_by: Option<Uuid>
+}
+impl From<&FileVersion> for FileVersionRead {
+    fn from(version: &FileVersion) -> Self {
+        FileVersionRead {
+            version: version.version(),
+            size: version.size(),
+            modified: epoch_delta(version.modified()),
+            author: version.author_id(),
+            replaced_by: version.replaced_by()
+        }
+    }
+}
+
+/// Selects a single version of a file.
+#[derive(Debug, Deserialize)]
+pub struct VersionQuery {
+    version: Option<u32>
+}
+
+/// Represents the `/storage-versions` REST resource.
+///
+/// The `/storage-versions` resource is responsible for listing, downloading and restoring the
+/// previous versions of the files in the user's storage.
+pub struct StorageVersionResource;
+#[rest_resource("/storage-versions/{filename:.*}")]
+impl StorageVersionResource {
+    async fn get(tusk: Tusk, path: PathInfo, query: web::Query<VersionQuery>, req: HttpRequest) -> TuskHttpResult {
+        if path.is_directory() { return TuskError::conflict().bail(); }
+        let mut db = tusk.db()?;
+
+        if let Some(version) = query.version {
+            let version = FileVersion::from_version(&mut db, path.request_path(), version)?;
+            let stored = version.file(tusk.config());
+            let file = File::open(&stored)
+                .or_not_found()?;
+
+            let response = match path.decryption().open(&stored)? {
+                Some(decryptor) => encryption::respond(decryptor, &path.name(), &req)?,
+                None => NamedFile::from_file(file, path.name())?.into_response(&req)
+            };
+            Ok(preview::secure(response, &path.name(), false))
+        } else {
+            let versions: Vec<FileVersionRead> = FileVersion::list_for_path(&mut db, path.request_path())?
+                .iter()
+                .map(FileVersionRead::from)
+                .collect();
+
+            Ok(HttpResponse::Ok().json(versions))
+        }
+    }
+
+    async fn post(tusk: Tusk, path: PathInfo, query: web::Query<VersionQuery>) -> TuskHttpResult {
+        let version = query.version.or_bad_request()?;
+        let parent = path.parent()
+            .or_conflict()?;
+        if !parent.is_directory() { return TuskError::conflict().bail(); }
+        let mut db = tusk.db()?;
+        let version = FileVersion::from_version(&mut db, path.request_path(), version)?;
+
+        let stored = version.file(tusk.config());
+        let mut contents = File::open(&stored)
+            .or_not_found()?;
+        let mut file = tempfile::Builder::new()
+            .prefix(".tusk-")
+            .tempfile_in(&parent)?;
+        // The restored contents are encrypted again, if needed, as they are written.
+        match path.decryption().open(&stored)? {
+            Some(mut decryptor) => std::io::copy(&mut decryptor, &mut file)?,
+            None => std::io::copy(&mut contents, &mut file)?
+        };
+
+        let created = path.write_file(tusk.config(), &mut db, file.into_temp_path())?;
+        let attr = path.info()?;
+        if created {
+            Ok(HttpResponse::Created()
+                .insert_header((header::LOCATION, format!("/v1/storage/{}", path.request_path())))
+                .json(attr))
+        } else {
+            Ok(HttpResponse::Ok().json(attr))
+        }
+    }
+}
@@ -24,6 +24,8 @@ pub fn spawn_tusk() -> TuskResult<TuskConfiguration> {
 
     tusk.apply_migrations()?;
     tusk.check_user_directories()?;
+    api::search::ContentIndex::shared(&tusk)?;
+    api::trash::spawn_purger(&tusk)?;
 
     Ok(tusk)
 }
@@ -36,6 +38,7 @@ pub fn spawn_server(tusk: &TuskConfiguration) -> TuskResult<actix_web::dev::Serv
     let app_data = tusk.to_data();
     let api_domain = tusk.api_domain().to_owned();
     let www_domain = tusk.www_domain().to_owned();
+    let preview_domain = tusk.preview_domain().map(str::to_owned);
     let serve_from = tusk.static_files();
 
     let server = HttpServer::new(move || App::new()
@@ -45,7 +48,11 @@ pub fn spawn_server(tusk: &TuskConfiguration) -> TuskResult<actix_web::dev::Serv
         .service(web::scope("/v1")
             .guard(guard::Host(api_domain.clone()))
             .configure(|cfg| api::configure(cfg))
-        ).service(web::scope("")
+        ).configure(|cfg| if let Some(preview_domain) = &preview_domain {
+            cfg.service(web::scope("")
+                .guard(guard::Host(preview_domain.clone()))
+                .configure(api::preview::configure));
+        }).service(web::scope("")
         .guard(guard::Host(www_domain.clone()))
         .configure(|cfg| ui::configure(cfg, serve_from.clone()))
     )).bind_rustls(("0.0.0.0", 443), tls_config)?
@@ -59,6 +66,7 @@ pub fn spawn_test_server(tusk: &TuskConfiguration) -> actix_test::TestServer {
     let app_data = tusk.to_data();
     let api_domain = tusk.api_domain().to_owned();
     let www_domain = tusk.www_domain().to_owned();
+    let preview_domain = tusk.preview_domain().map(str::to_owned);
     let serve_from = tusk.static_files();
 
     let server = actix_test::start(move || App::new()
@@ -67,7 +75,11 @@ pub fn spawn_test_server(tusk: &TuskConfiguration) -> actix_test::TestServer {
         .service(web::scope("/v1")
             .guard(guard::Host(api_domain.clone()))
             .configure(|cfg| api::configure(cfg))
-        ).service(web::scope("")
+        ).configure(|cfg| if let Some(preview_domain) = &preview_domain {
+            cfg.service(web::scope("")
+                .guard(guard::Host(preview_domain.clone()))
+                .configure(api::preview::configure));
+        }).service(web::scope("")
         .guard(guard::Host(www_domain.clone()))
         .configure(|cfg| ui::configure(cfg, serve_from.clone()))
     ));
@@ -77,7 +89,11 @@ pub fn spawn_test_server(tusk: &TuskConfiguration) -> actix_test::TestServer {
 
 /// Spawns a watcher that watches the Tera templates storage for changes, and reloads Tera if
 /// something changed.
+///
+/// Also starts watching the user directories, whose changes are streamed by the
+/// `/storage-events` resource.
 pub fn spawn_watcher(tusk: &TuskConfiguration) -> RecommendedWatcher {
+    let _ = api::events::StorageEvents::shared(tusk);
     let tusk = tusk.to_data();
     let watch_dir = PathBuf::from(tusk.tera_templates());
     log::info!("Starting watcher for storage `{}`", watch_dir.display());
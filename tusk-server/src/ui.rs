@@ -11,6 +11,7 @@ use actix_web::web::{Path, ServiceConfig};
 use tusk_core::config::{Tusk};
 use tusk_core::error::{HttpOkOr, TuskHttpResult};
 use tusk_core::resources::{PasswordResetRequest, User};
+use crate::api::share::SharedContentResource;
 
 #[get("/login")]
 async fn login(tusk: Tusk) -> TuskHttpResult {
@@ -92,14 +93,15 @@ async fn page_handler(tusk: Tusk, page: Path<String>) -> TuskHttpResult {
     Ok(HttpResponse::Ok().body(page))
 }
 
-/// Configures the server by adding the `/static` service for serving static files and the `/*`
-/// service for serving web pages.
+/// Configures the server by adding the `/static` service for serving static files, the `/share`
+/// service for serving shared content and the `/*` service for serving web pages.
 pub fn configure(cfg: &mut ServiceConfig, serve_from: PathBuf) {
     cfg
         .service(actix_files::Files::new("/static", serve_from))
         .service(login)
         .service(password_reset_request)
         .service(password_reset_verify)
+        .service(SharedContentResource)
         .service(index)
         .service(root_page)
     ;
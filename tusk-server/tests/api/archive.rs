@@ -0,0 +1,152 @@
+use std::io::{Cursor, Read, Write};
+use actix_http::encoding::Decoder;
+use actix_http::Payload;
+use actix_web::http::{header, Method, StatusCode};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::Deserialize;
+use zip::write::SimpleFileOptions;
+use crate::{await_tusk, PASSWORD_EVE, PASSWORD_FRANK, Session, USER_EVE, USER_FRANK};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StoragePathRead {
+    filename: String
+}
+
+fn zip_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in entries {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(contents).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+fn tar_gz_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, contents) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, name, *contents).unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap()
+}
+
+async fn extract(session: &Session, path: &str, format: &str, archive: &[u8]) -> awc::ClientResponse<Decoder<Payload>> {
+    let mut body = Vec::new();
+    write!(body, "--0x0xboundary\r\n\
+    Content-Disposition: form-data; name=\"metadata\"\r\n\
+    Content-Type: application/json\r\n\
+    \r\n\
+    {{ \"kind\": \"file\", \"name\": \"archive\", \"extract\": \"{format}\" }}\r\n\
+    --0x0xboundary\r\n\
+    Content-Disposition: form-data; name=\"payload\"; filename=\"archive\"\r\n\
+    \r\n").unwrap();
+    body.extend_from_slice(archive);
+    body.extend_from_slice(b"\r\n--0x0xboundary--");
+
+    session.request(Method::POST, format!("/v1/storage/{path}"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body(body).await.unwrap()
+}
+
+#[actix_web::test]
+async fn extract_zip_archive() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/ExtractZip/Photos")).expect("Directory created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let archive = zip_archive(&[("Photos/sea.txt", b"Waves"), ("Photos/Trip/mountain.txt", b"Snow"), ("notes.txt", b"Notes")]);
+    let mut resp = extract(&session, &format!("{user_id}/ExtractZip"), "zip", &archive).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let items: Vec<StoragePathRead> = resp.json().await.expect("JSON response");
+    let items: Vec<&str> = items.iter().map(|item| item.filename.as_str()).collect();
+    assert_eq!(items, ["Photos", "notes.txt"]);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/ExtractZip/Photos/sea.txt")).unwrap(), "Waves");
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/ExtractZip/Photos/Trip/mountain.txt")).unwrap(), "Snow");
+
+    // Existing files are not replaced, and nothing is extracted.
+    let archive = zip_archive(&[("other.txt", b"Other"), ("notes.txt", b"New notes")]);
+    let resp = extract(&session, &format!("{user_id}/ExtractZip"), "zip", &archive).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/ExtractZip/notes.txt")).unwrap(), "Notes");
+    assert!(!std::path::Path::new(&format!("test_srv/storage/{user_id}/ExtractZip/other.txt")).exists());
+}
+
+#[actix_web::test]
+async fn extract_tar_gz_archive() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/ExtractTar")).expect("Directory created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let archive = tar_gz_archive(&[("./Music/song.txt", b"La la la"), ("./lyrics.txt", b"Lyrics")]);
+    let resp = extract(&session, &format!("{user_id}/ExtractTar"), "tar.gz", &archive).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/ExtractTar/Music/song.txt")).unwrap(), "La la la");
+
+    // The extracted storage can be downloaded again in the same format.
+    let mut resp = session.request(Method::GET, format!("/v1/storage/{user_id}/ExtractTar/?archive=tar.gz"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let contents = resp.body().limit(1 << 20).await.unwrap();
+    let mut tar = tar::Archive::new(GzDecoder::new(contents.as_ref()));
+    let mut song = None;
+    for entry in tar.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        if entry.path().unwrap().to_str() == Some("ExtractTar/Music/song.txt") {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents).unwrap();
+            song = Some(contents);
+        }
+    }
+    assert_eq!(song.as_deref(), Some("La la la"));
+}
+
+#[actix_web::test]
+async fn archive_cannot_escape_directory() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/ExtractSlip/Inner")).expect("Directory created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let archive = zip_archive(&[("fine.txt", b"Fine"), ("../escaped.txt", b"Escaped")]);
+    let resp = extract(&session, &format!("{user_id}/ExtractSlip/Inner"), "zip", &archive).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let archive = zip_archive(&[("/escaped.txt", b"Escaped")]);
+    let resp = extract(&session, &format!("{user_id}/ExtractSlip/Inner"), "zip", &archive).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    assert!(!std::path::Path::new(&format!("test_srv/storage/{user_id}/ExtractSlip/Inner/fine.txt")).exists());
+    assert!(!std::path::Path::new(&format!("test_srv/storage/{user_id}/ExtractSlip/escaped.txt")).exists());
+
+    // Malformed archives are rejected as well.
+    let resp = extract(&session, &format!("{user_id}/ExtractSlip/Inner"), "zip", b"Not an archive").await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn archive_limits() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/ExtractLimits")).expect("Directory created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let names: Vec<String> = (0..20).map(|i| format!("{i}.txt")).collect();
+    let entries: Vec<(&str, &[u8])> = names.iter().map(|name| (name.as_str(), b"File".as_slice())).collect();
+    let resp = extract(&session, &format!("{user_id}/ExtractLimits"), "zip", &zip_archive(&entries)).await;
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let zeros = vec![0; 2 << 20];
+    let resp = extract(&session, &format!("{user_id}/ExtractLimits"), "zip", &zip_archive(&[("zeros.bin", &zeros)])).await;
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(std::fs::read_dir(format!("test_srv/storage/{user_id}/ExtractLimits")).unwrap().next().is_none());
+
+    let user_id = USER_FRANK.id();
+    let session = Session::new_authenticated(&USER_FRANK, PASSWORD_FRANK).await;
+    let resp = extract(&session, &user_id.to_string(), "zip", &zip_archive(&[("large.txt", &[b'a'; 100])])).await;
+    assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+}
@@ -0,0 +1,118 @@
+use actix_web::http::{header, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use crate::{await_tusk, PASSWORD_EVE, Session, USER_EVE};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StorageChangeRead {
+    path: String,
+    operation: String,
+    kind: String,
+    size: Option<u64>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StorageChangesRead {
+    cursor: i64,
+    has_more: bool,
+    changes: Vec<StorageChangeRead>
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MovePathData<'a> {
+    destination: &'a str,
+    overwrite: bool
+}
+
+async fn changes(session: &Session, query: &str) -> StorageChangesRead {
+    let mut resp = session.request(Method::GET, format!("/v1/storage-changes?{query}"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    resp.json().await.expect("JSON response")
+}
+
+#[actix_web::test]
+async fn list_storage_changes() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let cursor = changes(&session, "").await.cursor;
+
+    let resp = session.request(Method::POST, format!("/v1/storage/{user_id}"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        { \"kind\": \"directory\", \"name\": \"Journal\" }\r\n\
+        --0x0xboundary--").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = session.request(Method::POST, format!("/v1/storage/{user_id}/Journal"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        { \"kind\": \"file\", \"name\": \"Entry.txt\" }\r\n\
+        --0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"payload\"; filename=\"Entry.txt\"\r\n\
+        \r\n\
+        Dear diary\r\n\
+        --0x0xboundary--").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = session.request(Method::PATCH, format!("/v1/storage/{user_id}/Journal/Entry.txt"))
+        .send_json(&MovePathData { destination: &format!("{user_id}/Journal/First Entry.txt"), overwrite: false }).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/Journal"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let journal = format!("{user_id}/Journal");
+    let listed = changes(&session, &format!("since={cursor}")).await;
+    let changes_in_journal: Vec<(String, String, String)> = listed.changes.iter()
+        .filter(|change| change.path.starts_with(&journal))
+        .map(|change| (change.operation.clone(), change.kind.clone(), change.path.clone()))
+        .collect();
+    assert_eq!(changes_in_journal, vec![
+        (String::from("created"), String::from("directory"), journal.clone()),
+        (String::from("created"), String::from("file"), format!("{journal}/Entry.txt")),
+        (String::from("deleted"), String::from("file"), format!("{journal}/Entry.txt")),
+        (String::from("created"), String::from("file"), format!("{journal}/First Entry.txt")),
+        (String::from("deleted"), String::from("directory"), journal.clone())
+    ]);
+    assert_eq!(listed.changes.iter().find(|change| change.path == format!("{journal}/Entry.txt")).unwrap().size, Some(10));
+
+    // Changes are paginated, and the returned cursor points to the following page.
+    let page = changes(&session, &format!("since={cursor}&limit=1")).await;
+    assert_eq!(page.changes.len(), 1);
+    assert!(page.has_more);
+    let next = changes(&session, &format!("since={}&limit=1", page.cursor)).await;
+    assert_ne!(next.changes[0].path, page.changes[0].path);
+
+    let resp = session.request(Method::GET, format!("/v1/storage-changes?since={cursor}&limit=0"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn journal_changes_made_outside() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let cursor = changes(&session, "").await.cursor;
+
+    // Changes in a row are journaled once, with the final state of the item.
+    let path = format!("test_srv/storage/{user_id}/Outside.txt");
+    std::fs::write(&path, "Written").unwrap();
+    std::fs::write(&path, "Written outside").unwrap();
+    actix_web::rt::time::sleep(std::time::Duration::from_secs(2)).await;
+
+    let listed = changes(&session, &format!("since={cursor}")).await;
+    let outside: Vec<&StorageChangeRead> = listed.changes.iter()
+        .filter(|change| change.path == format!("{user_id}/Outside.txt"))
+        .collect();
+    assert_eq!(outside.len(), 1);
+    assert_eq!(outside[0].operation, "created");
+    assert_eq!(outside[0].size, Some(15));
+}
@@ -0,0 +1,213 @@
+use actix_web::http::{header, Method, StatusCode};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use crate::{await_tusk, PASSWORD_DANIEL, PASSWORD_EVE, Session, USER_DANIEL, USER_EVE};
+
+fn method(name: &str) -> Method {
+    Method::from_bytes(name.as_bytes()).unwrap()
+}
+
+fn basic_credentials(email: &str, password: &str) -> String {
+    format!("Basic {}", STANDARD.encode(format!("{email}:{password}")))
+}
+
+#[actix_web::test]
+async fn options_advertise_compliance() {
+    await_tusk();
+
+    let session = Session::new();
+    let resp = session.request(Method::OPTIONS, "/v1/dav/")
+        .send().await.unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("DAV").expect("Header").to_str().unwrap(), "1, 2");
+    assert!(resp.headers().get(header::ALLOW).expect("Header").to_str().unwrap().contains("PROPFIND"));
+}
+
+#[actix_web::test]
+async fn unauthenticated_requests_are_challenged() {
+    await_tusk();
+
+    let session = Session::new();
+    let resp = session.request(method("PROPFIND"), "/v1/dav/")
+        .insert_header(("Depth", "0"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert!(resp.headers().get(header::WWW_AUTHENTICATE).expect("Header").to_str().unwrap().starts_with("Basic"));
+
+    let resp = session.request(method("PROPFIND"), "/v1/dav/")
+        .insert_header(("Depth", "0"))
+        .insert_header((header::AUTHORIZATION, basic_credentials(USER_EVE.email(), "wrong password")))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn propfind_with_basic_authentication() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new();
+    let mut resp = session.request(method("PROPFIND"), "/v1/dav/")
+        .insert_header(("Depth", "1"))
+        .insert_header((header::AUTHORIZATION, basic_credentials(USER_EVE.email(), PASSWORD_EVE)))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    let body = String::from_utf8(resp.body().await.unwrap().to_vec()).unwrap();
+    assert!(body.contains(&format!("<D:href>/v1/dav/{user_id}/</D:href>")));
+    assert!(body.contains("<D:href>/v1/dav/.public/</D:href>"));
+
+    let resp = session.request(method("PROPFIND"), "/v1/dav/")
+        .insert_header((header::AUTHORIZATION, basic_credentials(USER_EVE.email(), PASSWORD_EVE)))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn create_read_and_delete_items() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let base = format!("/v1/dav/{user_id}/DAV%20items");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(method("MKCOL"), &base)
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = session.request(method("MKCOL"), &base)
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    let resp = session.request(method("MKCOL"), format!("{base}/Missing/Folder"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let resp = session.request(Method::PUT, format!("{base}/Notes.txt"))
+        .send_body("First draft.").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = session.request(Method::PUT, format!("{base}/Notes.txt"))
+        .send_body("Second draft.").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let mut resp = session.request(Method::GET, format!("{base}/Notes.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), "Second draft.");
+
+    let mut resp = session.request(method("PROPFIND"), &base)
+        .insert_header(("Depth", "1"))
+        .send_body(r#"<?xml version="1.0"?><propfind xmlns="DAV:"><prop><getcontentlength/><author xmlns="urn:x"/></prop></propfind>"#).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    let body = String::from_utf8(resp.body().await.unwrap().to_vec()).unwrap();
+    assert!(body.contains("/DAV%20items/Notes.txt</D:href>"));
+    assert!(body.contains("<D:getcontentlength>13</D:getcontentlength>"));
+    assert!(body.contains("HTTP/1.1 404 Not Found"));
+
+    let resp = session.request(Method::DELETE, &base)
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = session.request(Method::GET, format!("{base}/Notes.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn copy_and_move_items() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let base = format!("/v1/dav/{user_id}/DAV%20copies");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(method("MKCOL"), &base)
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = session.request(Method::PUT, format!("{base}/Original.txt"))
+        .send_body("Original").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = session.request(method("COPY"), format!("{base}/Original.txt"))
+        .insert_header(("Destination", format!("http://localhost{base}/Copy.txt")))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = session.request(method("COPY"), format!("{base}/Original.txt"))
+        .insert_header(("Destination", format!("{base}/Copy.txt")))
+        .insert_header(("Overwrite", "F"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let resp = session.request(method("MOVE"), format!("{base}/Copy.txt"))
+        .insert_header(("Destination", format!("{base}/Moved.txt")))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = session.request(Method::GET, format!("{base}/Copy.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let mut resp = session.request(Method::GET, format!("{base}/Moved.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), "Original");
+
+    let resp = session.request(method("MOVE"), format!("{base}/Moved.txt"))
+        .insert_header(("Destination", "/elsewhere/Moved.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+
+    let resp = session.request(Method::DELETE, &base)
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+}
+
+#[actix_web::test]
+async fn locked_items_require_token() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let path = format!("/v1/dav/{user_id}/Locked.txt");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let mut resp = session.request(method("LOCK"), &path)
+        .insert_header(("Timeout", "Second-600"))
+        .send_body(r#"<?xml version="1.0"?><D:lockinfo xmlns:D="DAV:"><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype><D:owner>Eve</D:owner></D:lockinfo>"#).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let token = resp.headers().get("Lock-Token").expect("Header")
+        .to_str().unwrap()
+        .to_owned();
+    let body = String::from_utf8(resp.body().await.unwrap().to_vec()).unwrap();
+    assert!(body.contains("<D:owner>Eve</D:owner>"));
+
+    let resp = session.request(method("LOCK"), &path)
+        .send_body(r#"<?xml version="1.0"?><D:lockinfo xmlns:D="DAV:"><D:lockscope><D:shared/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockinfo>"#).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::LOCKED);
+
+    let resp = session.request(Method::PUT, &path)
+        .send_body("Locked contents").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::LOCKED);
+    let resp = session.request(Method::PUT, &path)
+        .insert_header(("If", format!("({token})")))
+        .send_body("Locked contents").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let resp = session.request(method("UNLOCK"), &path)
+        .insert_header(("Lock-Token", token))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let resp = session.request(Method::DELETE, &path)
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+}
+
+#[actix_web::test]
+async fn cannot_access_other_user_storage() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(method("PROPFIND"), format!("/v1/dav/{user_id}/"))
+        .insert_header(("Depth", "1"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let resp = session.request(method("PROPFIND"), format!("/v1/dav/{user_id}/"))
+        .insert_header(("Depth", "1"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+}
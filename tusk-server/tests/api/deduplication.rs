@@ -0,0 +1,117 @@
+use actix_web::http::{header, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tusk_core::resources::Blob;
+use crate::{await_tusk, PASSWORD_DANIEL, PASSWORD_EVE, Session, TUSK, USER_DANIEL, USER_EVE};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DeduplicationRead {
+    enabled: bool,
+    saved: u64
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StoragePathRead {
+    filename: String,
+    size: Option<u64>,
+    created: i64,
+    last_modified: i64
+}
+
+#[derive(Serialize)]
+struct MovePathData<'a> {
+    destination: &'a str,
+    overwrite: bool
+}
+
+async fn write_file(session: &Session, path: &str, contents: &'static str) {
+    let resp = session.request(Method::PUT, format!("/v1/dav/{path}"))
+        .send_body(contents).await.unwrap();
+    assert!(resp.status().is_success());
+}
+
+async fn upload_file(session: &Session, directory: &str, name: &str, contents: &str, created: i64, last_modified: i64) {
+    let resp = session.request(Method::POST, format!("/v1/storage/{directory}"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body(format!("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        {{ \"kind\": \"file\", \"name\": \"{name}\", \"created\": {created}, \"last_modified\": {last_modified} }}\r\n\
+        --0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"payload\"; filename=\"{name}\"\r\n\
+        \r\n\
+        {contents}\r\n\
+        --0x0xboundary--")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+}
+
+async fn item(session: &Session, directory: &str, name: &str) -> StoragePathRead {
+    let mut resp = session.request(Method::GET, format!("/v1/storage/{directory}/"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let listing: Vec<StoragePathRead> = resp.json().await.expect("JSON response");
+    listing.into_iter().find(|item| item.filename == name).expect("Listed file")
+}
+
+fn references(hash: &str) -> Option<u64> {
+    let mut db = TUSK.db().expect("Connection to database");
+    Blob::from_hash(&mut db, hash).expect("Blob read")
+        .map(|blob| blob.references(&mut db).expect("References counted"))
+}
+
+#[actix_web::test]
+async fn duplicate_contents_are_stored_once() {
+    await_tusk();
+    let contents = "The whole family on the beach, once again";
+    let hash: String = Sha256::digest(contents).iter().map(|byte| format!("{byte:02x}")).collect();
+    let (daniel, eve) = (USER_DANIEL.id(), USER_EVE.id());
+
+    let session_daniel = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let session_eve = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+
+    // The same picture uploaded twice, with the same timestamps, is stored once on any filesystem.
+    upload_file(&session_daniel, &daniel.to_string(), "Beach.jpg", contents, 1_300_000_000, 1_400_000_000).await;
+    upload_file(&session_eve, &eve.to_string(), "Beach.jpg", contents, 1_300_000_000, 1_400_000_000).await;
+    assert_eq!(references(&hash), Some(2));
+    for (session, directory) in [(&session_daniel, daniel), (&session_eve, eve)] {
+        let item = item(session, &directory.to_string(), "Beach.jpg").await;
+        assert_eq!(item.size, Some(contents.len() as u64));
+        assert_eq!(item.created, 1_300_000_000);
+        assert_eq!(item.last_modified, 1_400_000_000);
+    }
+
+    let mut resp = session_daniel.request(Method::GET, "/v1/storage/deduplication")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let stats: DeduplicationRead = resp.json().await.expect("JSON response");
+    assert!(stats.enabled);
+    assert!(stats.saved >= contents.len() as u64);
+
+    // Replacing the contents releases the blob, while moving keeps the reference.
+    write_file(&session_eve, &format!("{eve}/Beach.jpg"), "A different picture").await;
+    assert_eq!(references(&hash), Some(1));
+    let resp = session_daniel.request(Method::PATCH, format!("/v1/storage/{daniel}/Beach.jpg"))
+        .send_json(&MovePathData { destination: &format!("{daniel}/Sea.jpg"), overwrite: false }).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(references(&hash), Some(1));
+
+    let resp = session_daniel.request(Method::DELETE, format!("/v1/storage/{daniel}/Sea.jpg"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(references(&hash), None);
+
+    // The deleted file is still in the trash, even though the blob has been removed.
+    let mut resp = session_daniel.request(Method::GET, "/v1/trash")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let trash: Vec<serde_json::Value> = resp.json().await.expect("JSON response");
+    assert!(trash.iter().any(|item| item["original_path"] == format!("{daniel}/Sea.jpg")));
+
+    // Files with other timestamps keep their own, whether or not they share the contents.
+    upload_file(&session_eve, &eve.to_string(), "Dune.jpg", contents, 1_300_000_000, 1_500_000_000).await;
+    upload_file(&session_eve, &eve.to_string(), "Dune copy.jpg", contents, 1_300_000_000, 1_600_000_000).await;
+    assert!(references(&hash).is_some());
+    assert_eq!(item(&session_eve, &eve.to_string(), "Dune.jpg").await.last_modified, 1_500_000_000);
+    assert_eq!(item(&session_eve, &eve.to_string(), "Dune copy.jpg").await.last_modified, 1_600_000_000);
+}
@@ -0,0 +1,154 @@
+use std::fs::File;
+use actix_web::http::{header, Method, StatusCode};
+use secrecy::Secret;
+use serde::Deserialize;
+use tusk_core::encryption::{DataKey, encrypt};
+use tusk_core::resources::UserKey;
+use crate::{await_tusk, PASSWORD_DANIEL, PASSWORD_EVE, PASSWORD_GRACE, Session, TUSK, USER_DANIEL, USER_EVE, USER_GRACE};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EncryptionRead {
+    enabled: bool,
+    unlocked: bool
+}
+
+#[actix_web::test]
+async fn encrypted_files_are_decrypted_for_their_owner() {
+    await_tusk();
+    let daniel = USER_DANIEL.id();
+    let contents = "Lease of the apartment, signed on the first of the month";
+
+    // Files encrypted while encryption was enabled are still readable once it is disabled.
+    let key = DataKey::generate();
+    let mut db = TUSK.db().expect("Connection to database");
+    UserKey::store(&mut db, daniel, &key, &Secret::new(PASSWORD_DANIEL.to_owned())).expect("Data key stored");
+    let file = File::create(format!("test_srv/storage/{daniel}/Documents/Apartment/Lease.txt")).expect("File created");
+    encrypt(&key, daniel, contents.len() as u64, contents.as_bytes(), file).expect("File encrypted");
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let mut resp = session.request(Method::GET, "/v1/storage/encryption")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let status: EncryptionRead = resp.json().await.expect("JSON response");
+    assert!(!status.enabled);
+    assert!(status.unlocked);
+
+    let mut resp = session.request(Method::GET, format!("/v1/storage/{daniel}/Documents/Apartment/Lease.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), contents.as_bytes());
+
+    let mut resp = session.request(Method::GET, format!("/v1/storage/{daniel}/Documents/Apartment/Lease.txt"))
+        .insert_header((header::RANGE, "bytes=6-14"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resp.body().await.unwrap(), &contents.as_bytes()[6..=14]);
+
+    let mut resp = session.request(Method::GET, format!("/v1/storage/{daniel}/Documents/Apartment/"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let listing: Vec<serde_json::Value> = resp.json().await.expect("JSON response");
+    let item = listing.iter().find(|item| item["filename"] == "Lease.txt").expect("Listed file");
+    assert_eq!(item["size"], contents.len());
+
+    // Thumbnails would be cached unencrypted.
+    let resp = session.request(Method::GET, format!("/v1/storage/{daniel}/Documents/Apartment/Lease.txt?thumbnail=64"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[actix_web::test]
+async fn encrypted_previews_are_locked_after_logout() {
+    await_tusk();
+    let eve = USER_EVE.id();
+    let contents = "Deposit of the apartment, returned at the end of the lease";
+
+    let key = DataKey::generate();
+    let mut db = TUSK.db().expect("Connection to database");
+    UserKey::store(&mut db, eve, &key, &Secret::new(PASSWORD_EVE.to_owned())).expect("Data key stored");
+    let file = File::create(format!("test_srv/storage/{eve}/Deposit.txt")).expect("File created");
+    encrypt(&key, eve, contents.len() as u64, contents.as_bytes(), file).expect("File encrypted");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request_without_redirects(Method::GET, format!("/v1/storage/{eve}/Deposit.txt?preview=true"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let location = resp.headers().get(header::LOCATION).expect("Header").to_str().unwrap();
+    let preview = location.strip_prefix("https://preview.localhost").expect("Preview domain").to_owned();
+
+    let guest = Session::new();
+    let mut resp = guest.request(Method::GET, &preview)
+        .insert_header((header::HOST, "preview.localhost"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), contents.as_bytes());
+
+    // The preview link does not keep the data key once the session is deleted.
+    let resp = session.request(Method::DELETE, "/v1/session")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = guest.request(Method::GET, &preview)
+        .insert_header((header::HOST, "preview.localhost"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::LOCKED);
+}
+
+#[actix_web::test]
+async fn encrypted_files_follow_their_root() {
+    await_tusk();
+    let grace = USER_GRACE.id();
+    let daniel = USER_DANIEL.id();
+    let contents = "Will of the family, to be opened by the notary";
+
+    let key = DataKey::generate();
+    let mut db = TUSK.db().expect("Connection to database");
+    UserKey::store(&mut db, grace, &key, &Secret::new(PASSWORD_GRACE.to_owned())).expect("Data key stored");
+    std::fs::create_dir_all(format!("test_srv/storage/{grace}/Notary")).expect("Directory created");
+    let file = File::create(format!("test_srv/storage/{grace}/Notary/Will.txt")).expect("File created");
+    encrypt(&key, grace, contents.len() as u64, contents.as_bytes(), file).expect("File encrypted");
+
+    let session = Session::new_authenticated(&USER_GRACE, PASSWORD_GRACE).await;
+    let resp = session.request(Method::POST, "/v1/storage-grants")
+        .send_json(&serde_json::json!({ "path": format!("{grace}/Notary"), "user": USER_DANIEL.email(), "writable": false })).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+
+    // Grantees read the files with the data key of the owner, while the owner has an active session.
+    let notary = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let mut resp = notary.request(Method::GET, format!("/v1/storage/{grace}/Notary/Will.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), contents.as_bytes());
+
+    // Copies in another root are stored as the files written in that root.
+    let resp = notary.request(Method::POST, format!("/v1/storage/{daniel}/"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body(format!("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        {{ \"kind\": \"file\", \"name\": \"Will of Grace.txt\", \"source\": \"{grace}/Notary/Will.txt\" }}\r\n\
+        --0x0xboundary--")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{daniel}/Will of Grace.txt")).expect("File"), contents);
+
+    // Files outside of the user roots are never taken for encrypted files.
+    let lookalike = [b"TUSKENC1".as_slice(), grace.as_bytes(), &[0; 24]].concat();
+    std::fs::write("test_srv/storage/.public/Lookalike.bin", &lookalike).expect("File created");
+    let mut resp = session.request(Method::GET, "/v1/storage/.public/")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let listing: Vec<serde_json::Value> = resp.json().await.expect("JSON response");
+    let item = listing.iter().find(|item| item["filename"] == "Lookalike.bin").expect("Listed file");
+    assert_eq!(item["size"], lookalike.len());
+    let mut resp = session.request(Method::GET, "/v1/storage/.public/Lookalike.bin")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), lookalike.as_slice());
+
+    let resp = session.request(Method::DELETE, "/v1/session")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = notary.request(Method::GET, format!("/v1/storage/{grace}/Notary/Will.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::LOCKED);
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+use actix_web::http::{header, Method, StatusCode};
+use futures_util::StreamExt;
+use crate::{await_tusk, PASSWORD_EVE, Session, USER_DANIEL, USER_EVE};
+
+#[actix_web::test]
+async fn stream_storage_events() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let other_id = USER_DANIEL.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let mut resp = session.request(Method::GET, "/v1/storage-events")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).expect("Header").to_str().unwrap(), "text/event-stream");
+
+    // Changes made directly on disk are reported as well.
+    std::fs::write(format!("test_srv/storage/{other_id}/Events.txt"), "Not visible").expect("File created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Events.txt"), "Visible").expect("File created");
+
+    let expected = format!("event: created\ndata: {{\"kind\":\"created\",\"path\":\"{user_id}/Events.txt\"}}\n\n");
+    let mut received = String::new();
+    actix_web::rt::time::timeout(Duration::from_secs(30), async {
+        while !received.contains(&expected) {
+            let chunk = resp.next().await.expect("Event").expect("Chunk");
+            received.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }).await.expect("Event received in time");
+    assert!(!received.contains(&other_id.to_string()));
+
+    let resp = session.request(Method::GET, format!("/v1/storage-events?path={other_id}"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
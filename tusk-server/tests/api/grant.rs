@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use actix_web::http::{Method, StatusCode};
+use awc::ClientResponse;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{await_tusk, PASSWORD_DANIEL, PASSWORD_EVE, PASSWORD_FRANK, Session, USER_DANIEL, USER_EVE, USER_FRANK};
+
+#[derive(Clone, Debug, Serialize)]
+struct CreateStorageGrantData<'a> {
+    path: &'a str,
+    user: Option<&'a str>,
+    role: Option<&'a str>,
+    writable: bool
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize)]
+pub struct StorageGrantRead {
+    id: Uuid,
+    path: String,
+    user: Option<Uuid>,
+    role: Option<Uuid>,
+    writable: bool
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SharedStorageRead {
+    path: String,
+    owner_id: Uuid,
+    writable: bool
+}
+
+async fn grant(session: &Session, data: &CreateStorageGrantData<'_>) -> StorageGrantRead {
+    let resp = session.request(Method::POST, "/v1/storage-grants")
+        .send_json(data).await.unwrap();
+    let status = if data.user.is_some() { StatusCode::ACCEPTED } else { StatusCode::CREATED };
+    assert_eq!(resp.status(), status);
+
+    let mut resp = session.request(Method::GET, "/v1/storage-grants")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let grants: Vec<StorageGrantRead> = resp.json().await.expect("JSON response");
+    grants.into_iter()
+        .find(|grant| grant.path == data.path && (grant.role.is_some() == data.role.is_some()))
+        .expect("Grant")
+}
+
+async fn shared_with(session: &Session) -> Vec<SharedStorageRead> {
+    let mut resp = session.request(Method::GET, "/v1/storage/shared")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    resp.json().await.expect("JSON response")
+}
+
+async fn in_trash(session: &Session, original_path: &str) -> bool {
+    let mut resp = session.request(Method::GET, "/v1/trash")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let items: Vec<serde_json::Value> = resp.json().await.expect("JSON response");
+    items.iter().any(|item| item["original_path"] == original_path)
+}
+
+#[actix_web::test]
+async fn read_only_grant() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Family")).expect("Directory created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Family/recipes.txt"), "Pancakes").expect("File created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let family = format!("{user_id}/Family");
+    let created = grant(&session, &CreateStorageGrantData { path: &family, user: Some(USER_DANIEL.email()), role: None, writable: false }).await;
+    assert_eq!(created.user, Some(USER_DANIEL.id()));
+    assert!(!created.writable);
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let shared = shared_with(&session).await;
+    let item = shared.iter().find(|item| item.path == family).expect("Shared storage");
+    assert_eq!(item.owner_id, user_id);
+    assert!(!item.writable);
+
+    let mut resp = session.request(Method::GET, format!("/v1/storage/{family}/recipes.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), "Pancakes");
+
+    // Shared storages cannot be published through share links.
+    let expiration = (SystemTime::now() + Duration::from_secs(60 * 60))
+        .duration_since(SystemTime::UNIX_EPOCH).unwrap()
+        .as_secs();
+    let resp = session.request(Method::POST, "/v1/shares")
+        .send_json(&serde_json::json!({ "path": family, "expiration": expiration })).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = session.request(Method::PUT, format!("/v1/dav/{family}/intruder.txt"))
+        .send_body("Hello").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let lock = Method::from_bytes(b"LOCK").unwrap();
+    for name in ["intruder.txt", "recipes.txt"] {
+        let resp = session.request(lock.clone(), format!("/v1/dav/{family}/{name}"))
+            .send_body(r#"<?xml version="1.0"?><D:lockinfo xmlns:D="DAV:"><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockinfo>"#).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+    assert!(std::fs::symlink_metadata(format!("test_srv/storage/{family}/intruder.txt")).is_err());
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{family}/recipes.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = session.request(Method::GET, format!("/v1/storage/{user_id}/"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn read_write_grant() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Team")).expect("Directory created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let team = format!("{user_id}/Team");
+    let created = grant(&session, &CreateStorageGrantData { path: &team, user: Some(USER_FRANK.email()), role: None, writable: true }).await;
+
+    let frank = Session::new_authenticated(&USER_FRANK, PASSWORD_FRANK).await;
+    let resp = frank.request(Method::PUT, format!("/v1/dav/{team}/minutes.txt"))
+        .send_body("Minutes").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{team}/minutes.txt")).expect("File"), "Minutes");
+
+    let resp = frank.request(Method::DELETE, format!("/v1/storage/{team}"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = frank.request(Method::DELETE, format!("/v1/storage/{team}/minutes.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    // Items deleted by the grantee end up in the trash of the owner.
+    assert!(in_trash(&session, &format!("{team}/minutes.txt")).await);
+    assert!(!in_trash(&frank, &format!("{team}/minutes.txt")).await);
+
+    let resp = session.request(Method::DELETE, format!("/v1/storage-grants/{}", created.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = frank.request(Method::GET, format!("/v1/storage/{team}/"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(shared_with(&frank).await.iter().all(|item| item.path != team));
+}
+
+#[actix_web::test]
+async fn grants_follow_moved_storages() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Journeys/Alps")).expect("Directory created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Journeys/Alps/plan.txt"), "Hiking").expect("File created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let alps = format!("{user_id}/Journeys/Alps");
+    grant(&session, &CreateStorageGrantData { path: &alps, user: Some(USER_DANIEL.email()), role: None, writable: false }).await;
+
+    let resp = session.request(Method::PATCH, format!("/v1/storage/{user_id}/Journeys"))
+        .send_json(&serde_json::json!({ "destination": format!("{user_id}/Trips"), "overwrite": false })).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let moved = format!("{user_id}/Trips/Alps");
+    let mut resp = session.request(Method::GET, "/v1/storage-grants")
+        .send().await.unwrap();
+    let grants: Vec<StorageGrantRead> = resp.json().await.expect("JSON response");
+    let moved_grant = grants.iter().find(|grant| grant.path == moved).expect("Grant");
+    assert!(grants.iter().all(|grant| grant.path != alps));
+
+    let daniel = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let shared = shared_with(&daniel).await;
+    assert!(shared.iter().any(|item| item.path == moved));
+    assert!(shared.iter().all(|item| item.path != alps));
+    let mut resp = daniel.request(Method::GET, format!("/v1/storage/{moved}/plan.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), "Hiking");
+
+    // A storage created where the shared one was is not shared.
+    std::fs::create_dir_all(format!("test_srv/storage/{alps}")).expect("Directory created");
+    let resp = daniel.request(Method::GET, format!("/v1/storage/{alps}/"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/Trips"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert!(shared_with(&daniel).await.iter().all(|item| item.path != moved));
+    let resp = session.request(Method::DELETE, format!("/v1/storage-grants/{}", moved_grant.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn grant_to_role() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Announcements")).expect("Directory created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let announcements = format!("{user_id}/Announcements");
+    let created = grant(&session, &CreateStorageGrantData { path: &announcements, user: None, role: Some("directory"), writable: false }).await;
+    assert!(created.role.is_some());
+
+    let mut resp = session.request(Method::GET, "/v1/storage-grants")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let grants: Vec<StorageGrantRead> = resp.json().await.expect("JSON response");
+    assert!(grants.contains(&created));
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let resp = session.request(Method::GET, format!("/v1/storage/{announcements}/"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn cannot_grant_invalid_paths() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let daniel_id = USER_DANIEL.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Grantable")).expect("Directory created");
+    let grantable = format!("{user_id}/Grantable");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let cases = [
+        (CreateStorageGrantData { path: &user_id.to_string(), user: Some(USER_DANIEL.email()), role: None, writable: false }, StatusCode::FORBIDDEN),
+        (CreateStorageGrantData { path: &format!("{daniel_id}/Documents"), user: Some(USER_FRANK.email()), role: None, writable: false }, StatusCode::FORBIDDEN),
+        (CreateStorageGrantData { path: &format!("{user_id}/Missing"), user: Some(USER_DANIEL.email()), role: None, writable: false }, StatusCode::NOT_FOUND),
+        (CreateStorageGrantData { path: &grantable, user: Some("nobody@example.com"), role: None, writable: false }, StatusCode::ACCEPTED),
+        (CreateStorageGrantData { path: &grantable, user: None, role: Some("nobody"), writable: false }, StatusCode::NOT_FOUND),
+        (CreateStorageGrantData { path: &grantable, user: Some(USER_DANIEL.email()), role: Some("directory"), writable: false }, StatusCode::BAD_REQUEST),
+        (CreateStorageGrantData { path: &grantable, user: Some(USER_EVE.email()), role: None, writable: false }, StatusCode::BAD_REQUEST),
+    ];
+    for (data, status) in cases {
+        let resp = session.request(Method::POST, "/v1/storage-grants")
+            .send_json(&data).await.unwrap();
+        assert_eq!(resp.status(), status, "{data:?}");
+    }
+}
+
+
+#[actix_web::test]
+async fn existence_of_grantee_does_not_leak() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Secret")).expect("Directory created");
+    let secret = format!("{user_id}/Secret");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let mut resp_user_exists = session.request(Method::POST, "/v1/storage-grants")
+        .send_json(&CreateStorageGrantData { path: &secret, user: Some(USER_FRANK.email()), role: None, writable: false }).await.unwrap();
+    let mut resp_user_does_not_exist = session.request(Method::POST, "/v1/storage-grants")
+        .send_json(&CreateStorageGrantData { path: &secret, user: Some("no-user@localhost"), role: None, writable: false }).await.unwrap();
+    assert_eq!(resp_user_exists.status(), StatusCode::ACCEPTED);
+    assert_eq!(resp_user_does_not_exist.status(), StatusCode::ACCEPTED);
+
+    // Check that headers and body are the same.
+    let headers = |resp: &ClientResponse<_>| resp.headers()
+        .iter()
+        .filter(|(k, _)| k.as_str() != "set-cookie" && k.as_str() != "date")
+        .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_owned()))
+        .collect::<HashMap<String, String>>();
+    assert_eq!(headers(&resp_user_exists), headers(&resp_user_does_not_exist));
+    assert_eq!(resp_user_exists.body().await.unwrap(), resp_user_does_not_exist.body().await.unwrap());
+}
\ No newline at end of file
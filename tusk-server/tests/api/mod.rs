@@ -1,3 +1,17 @@
 mod account;
+mod archive;
+mod changes;
+mod dav;
+mod deduplication;
+mod encryption;
+mod events;
+mod grant;
+mod preview;
+mod search;
 mod session;
-mod storage;
\ No newline at end of file
+mod share;
+mod storage;
+mod team;
+mod trash;
+mod upload;
+mod version;
\ No newline at end of file
@@ -0,0 +1,75 @@
+use actix_web::http::{header, Method, StatusCode};
+use crate::{await_tusk, PASSWORD_DANIEL, Session, USER_DANIEL};
+
+const PAGE: &str = "<html><body><script>fetch('/v1/session')</script></body></html>";
+
+async fn write_file(session: &Session, path: &str, contents: &'static str) {
+    let resp = session.request(Method::PUT, format!("/v1/dav/{path}"))
+        .send_body(contents).await.unwrap();
+    assert!(resp.status().is_success());
+}
+
+#[actix_web::test]
+async fn active_content_is_downloaded() {
+    await_tusk();
+    let daniel = USER_DANIEL.id();
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    write_file(&session, &format!("{daniel}/Documents/Other/Page.html"), PAGE).await;
+    write_file(&session, &format!("{daniel}/Documents/Other/Page.txt"), PAGE).await;
+
+    let resp = session.request(Method::GET, format!("/v1/storage/{daniel}/Documents/Other/Page.html"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(header::CONTENT_DISPOSITION).expect("Header").to_str().unwrap().starts_with("attachment"));
+    assert_eq!(resp.headers().get(header::X_CONTENT_TYPE_OPTIONS).expect("Header"), "nosniff");
+    assert!(resp.headers().get(header::CONTENT_SECURITY_POLICY).expect("Header").to_str().unwrap().starts_with("sandbox"));
+
+    let resp = session.request(Method::GET, format!("/v1/dav/{daniel}/Documents/Other/Page.html"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(header::CONTENT_DISPOSITION).expect("Header").to_str().unwrap().starts_with("attachment"));
+
+    // Passive content is still shown inline, but sandboxed.
+    let resp = session.request(Method::GET, format!("/v1/storage/{daniel}/Documents/Other/Page.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(header::CONTENT_DISPOSITION).expect("Header").to_str().unwrap().starts_with("inline"));
+    assert_eq!(resp.headers().get(header::X_CONTENT_TYPE_OPTIONS).expect("Header"), "nosniff");
+}
+
+#[actix_web::test]
+async fn previews_are_served_from_the_preview_domain() {
+    await_tusk();
+    let daniel = USER_DANIEL.id();
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    write_file(&session, &format!("{daniel}/Documents/Other/Preview.html"), PAGE).await;
+
+    let resp = session.request_without_redirects(Method::GET, format!("/v1/storage/{daniel}/Documents/Other/Preview.html?preview=true"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let location = resp.headers().get(header::LOCATION).expect("Header").to_str().unwrap();
+    let preview = location.strip_prefix("https://preview.localhost").expect("Preview domain");
+    assert!(preview.starts_with("/preview/"));
+    assert!(preview.ends_with("/Preview%2Ehtml"));
+
+    // Previews are served without the session, and only on the preview domain.
+    let guest = Session::new();
+    let mut resp = guest.request(Method::GET, preview)
+        .insert_header((header::HOST, "preview.localhost"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), PAGE.as_bytes());
+    assert!(!resp.headers().get(header::CONTENT_DISPOSITION).expect("Header").to_str().unwrap().starts_with("attachment"));
+    assert!(resp.headers().get(header::CONTENT_SECURITY_POLICY).expect("Header").to_str().unwrap().starts_with("sandbox"));
+    let resp = guest.request(Method::GET, preview)
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = guest.request(Method::GET, "/preview/00000000-0000-0000-0000-000000000000/Preview.html")
+        .insert_header((header::HOST, "preview.localhost"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = session.request_without_redirects(Method::GET, format!("/v1/storage/{daniel}/Documents/Other/?preview=true"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
\ No newline at end of file
@@ -0,0 +1,192 @@
+use std::time::Duration;
+use actix_web::http::{header, Method, StatusCode};
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::{await_tusk, PASSWORD_DANIEL, PASSWORD_EVE, Session, USER_DANIEL, USER_EVE};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StoragePathRead {
+    filename: String
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StorageSearchResultRead {
+    path: String,
+    item: StoragePathRead
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StorageSearchRead {
+    total: usize,
+    results: Vec<StorageSearchResultRead>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TrashItemRead {
+    id: Uuid,
+    original_path: String
+}
+
+async fn search(session: &Session, query: &str) -> StorageSearchRead {
+    let mut resp = session.request(Method::GET, format!("/v1/storage-search?{query}"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    resp.json().await.expect("JSON response")
+}
+
+#[actix_web::test]
+async fn search_storage() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Search/Reports/2024")).expect("Directory created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Search/Reports/summary.txt"), "Summary").expect("File created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Search/Reports/2024/report.pdf"), "Large report contents").expect("File created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Search/notes.TXT"), "Notes").expect("File created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let base = format!("path={user_id}/Search");
+
+    let found = search(&session, &format!("{base}&name=*.txt")).await;
+    let paths: Vec<&str> = found.results.iter().map(|result| result.path.as_str()).collect();
+    assert_eq!(paths, [format!("{user_id}/Search/Reports/summary.txt"), format!("{user_id}/Search/notes.TXT")]);
+
+    let found = search(&session, &format!("{base}&name=REPORT")).await;
+    let names: Vec<&str> = found.results.iter().map(|result| result.item.filename.as_str()).collect();
+    assert_eq!(names, ["Reports", "report.pdf"]);
+
+    let found = search(&session, &format!("{base}&kind=directory")).await;
+    assert_eq!(found.total, 2);
+
+    let found = search(&session, &format!("{base}&min_size=10")).await;
+    let names: Vec<&str> = found.results.iter().map(|result| result.item.filename.as_str()).collect();
+    assert_eq!(names, ["report.pdf"]);
+
+    let found = search(&session, &format!("{base}&modified_before=0")).await;
+    assert_eq!(found.total, 0);
+
+    // Results are paginated.
+    let found = search(&session, &format!("{base}&kind=file&per_page=2&page=1")).await;
+    assert_eq!(found.total, 3);
+    assert_eq!(found.results.len(), 1);
+    assert_eq!(found.results[0].item.filename, "notes.TXT");
+
+    // The user's root is searched by default.
+    let found = search(&session, "name=summary").await;
+    assert_eq!(found.total, 1);
+}
+
+/// Searches until the full-text index, which is updated in background, returns `expected` results.
+async fn search_content(session: &Session, query: &str, expected: usize) -> StorageSearchRead {
+    for _ in 0..50 {
+        let found = search(session, query).await;
+        if found.total == expected { return found; }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    search(session, query).await
+}
+
+#[actix_web::test]
+async fn search_storage_content() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/SearchContent")).expect("Directory created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::POST, format!("/v1/storage/{user_id}/SearchContent"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        { \"kind\": \"file\", \"name\": \"Minutes.md\" }\r\n\
+        --0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"payload\"; filename=\"Minutes.md\"\r\n\
+        \r\n\
+        # Minutes\nThe committee approved the zeppelin budget.\r\n\
+        --0x0xboundary--").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let found = search_content(&session, "content=zeppelin%20budget", 1).await;
+    assert_eq!(found.total, 1);
+    assert_eq!(found.results[0].path, format!("{user_id}/SearchContent/Minutes.md"));
+    let found = search(&session, "content=zeppelin&name=*.txt").await;
+    assert_eq!(found.total, 0);
+
+    // Other users cannot find the contents of the files they cannot see.
+    let other = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let found = search(&other, "content=zeppelin").await;
+    assert_eq!(found.total, 0);
+
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/SearchContent/Minutes.md"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let found = search_content(&session, "content=zeppelin", 0).await;
+    assert_eq!(found.total, 0);
+}
+
+#[actix_web::test]
+async fn search_content_written_through_other_resources() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/SearchDav")).expect("Directory created");
+    let base = format!("/v1/dav/{user_id}/SearchDav");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::PUT, format!("{base}/Logbook.txt"))
+        .send_body("The lighthouse keeper fed the albatross.").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let found = search_content(&session, "content=albatross", 1).await;
+    assert_eq!(found.total, 1);
+    assert_eq!(found.results[0].path, format!("{user_id}/SearchDav/Logbook.txt"));
+
+    let resp = session.request(Method::from_bytes(b"MOVE").unwrap(), format!("{base}/Logbook.txt"))
+        .insert_header(("Destination", format!("{base}/Journal.txt")))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let found = search_content(&session, "content=albatross", 1).await;
+    assert_eq!(found.results[0].path, format!("{user_id}/SearchDav/Journal.txt"));
+
+    let resp = session.request(Method::DELETE, format!("{base}/Journal.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let found = search_content(&session, "content=albatross", 0).await;
+    assert_eq!(found.total, 0);
+
+    // Items restored from the trash are indexed again.
+    let mut resp = session.request(Method::GET, "/v1/trash")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let items: Vec<TrashItemRead> = resp.json().await.expect("JSON response");
+    let item = items.into_iter()
+        .find(|item| item.original_path == format!("{user_id}/SearchDav/Journal.txt"))
+        .expect("Deleted item");
+    let resp = session.request(Method::POST, format!("/v1/trash/{}", item.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let found = search_content(&session, "content=albatross", 1).await;
+    assert_eq!(found.results[0].path, format!("{user_id}/SearchDav/Journal.txt"));
+}
+
+#[actix_web::test]
+async fn search_storage_authorization() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+
+    let session = Session::new();
+    let resp = session.request(Method::GET, "/v1/storage-search?name=README")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::GET, format!("/v1/storage-search?path={user_id}&name=README"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = session.request(Method::GET, "/v1/storage-search?per_page=0")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let found = search(&session, "name=README").await;
+    assert!(found.results.iter().all(|result| !result.path.starts_with(&format!("{}/", USER_EVE.id()))));
+    assert!(found.results.iter().any(|result| result.path == format!("{user_id}/Documents/README.txt")));
+}
@@ -0,0 +1,235 @@
+use std::time::{Duration, SystemTime};
+use actix_web::http::{header, Method, StatusCode};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{await_tusk, PASSWORD_DANIEL, PASSWORD_EVE, Session, USER_DANIEL, USER_EVE};
+
+#[derive(Clone, Debug, Serialize)]
+struct CreateShareLinkData<'a> {
+    path: &'a str,
+    expiration: i64,
+    password: Option<&'a str>,
+    max_downloads: Option<u32>
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize)]
+pub struct ShareLinkRead {
+    id: Uuid,
+    token: String,
+    url: String,
+    path: String,
+    expiration: i64,
+    has_password: bool,
+    max_downloads: Option<u32>,
+    downloads: u32
+}
+
+fn in_one_day() -> i64 {
+    (SystemTime::now() + Duration::from_secs(24 * 60 * 60))
+        .duration_since(SystemTime::UNIX_EPOCH).unwrap()
+        .as_secs() as i64
+}
+
+fn basic_credentials(password: &str) -> String {
+    format!("Basic {}", STANDARD.encode(format!("guest:{password}")))
+}
+
+async fn write_file(session: &Session, path: &str, contents: &'static str) {
+    let resp = session.request(Method::PUT, format!("/v1/dav/{path}"))
+        .send_body(contents).await.unwrap();
+    assert!(resp.status().is_success());
+}
+
+async fn create_link(session: &Session, data: &CreateShareLinkData<'_>) -> ShareLinkRead {
+    let mut resp = session.request(Method::POST, "/v1/shares")
+        .send_json(data).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    resp.json().await.expect("JSON response")
+}
+
+#[actix_web::test]
+async fn share_and_revoke_file() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let path = format!("{user_id}/Shared.txt");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    write_file(&session, &path, "For everyone").await;
+    let link = create_link(&session, &CreateShareLinkData { path: &path, expiration: in_one_day(), password: None, max_downloads: None }).await;
+    assert_eq!(link.path, path);
+    assert!(link.url.ends_with(&format!("/share/{}/", link.token)));
+    assert!(!link.has_password);
+
+    let guest = Session::new();
+    let mut resp = guest.request(Method::GET, format!("/share/{}", link.token))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(header::CONTENT_DISPOSITION).expect("Header").to_str().unwrap().starts_with("attachment"));
+    assert_eq!(resp.body().await.unwrap(), "For everyone");
+
+    let mut resp = session.request(Method::GET, "/v1/shares")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let links: Vec<ShareLinkRead> = resp.json().await.expect("JSON response");
+    let listed = links.iter().find(|listed| listed.id == link.id).expect("Listed link");
+    assert_eq!(listed.downloads, 1);
+
+    let resp = session.request(Method::DELETE, format!("/v1/shares/{}", link.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = guest.request(Method::GET, format!("/share/{}", link.token))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn password_protected_link() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let path = format!("{user_id}/Protected share.txt");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    write_file(&session, &path.replace(' ', "%20"), "Top secret").await;
+    let link = create_link(&session, &CreateShareLinkData { path: &path, expiration: in_one_day(), password: Some("open sesame"), max_downloads: None }).await;
+    assert!(link.has_password);
+
+    let guest = Session::new();
+    let resp = guest.request(Method::GET, format!("/share/{}", link.token))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert!(resp.headers().get(header::WWW_AUTHENTICATE).expect("Header").to_str().unwrap().starts_with("Basic"));
+    let resp = guest.request(Method::GET, format!("/share/{}", link.token))
+        .insert_header((header::AUTHORIZATION, basic_credentials("wrong")))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let mut resp = guest.request(Method::GET, format!("/share/{}", link.token))
+        .insert_header((header::AUTHORIZATION, basic_credentials("open sesame")))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), "Top secret");
+}
+
+#[actix_web::test]
+async fn download_limit_is_enforced() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let path = format!("{user_id}/Once.txt");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    write_file(&session, &path, "Only once").await;
+    let link = create_link(&session, &CreateShareLinkData { path: &path, expiration: in_one_day(), password: None, max_downloads: Some(1) }).await;
+
+    // Resuming a download does not count as another download.
+    let guest = Session::new();
+    let mut resp = guest.request(Method::GET, format!("/share/{}", link.token))
+        .insert_header((header::RANGE, "bytes=0-4"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resp.body().await.unwrap(), "Only ");
+    let mut resp = guest.request(Method::GET, format!("/share/{}", link.token))
+        .insert_header((header::RANGE, "bytes=5-"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resp.body().await.unwrap(), "once");
+    let resp = guest.request(Method::GET, format!("/share/{}", link.token))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::GONE);
+    let resp = guest.request(Method::GET, format!("/share/{}", link.token))
+        .insert_header((header::RANGE, "bytes=0-"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::GONE);
+}
+
+#[actix_web::test]
+async fn links_follow_moved_items() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::from_bytes(b"MKCOL").unwrap(), format!("/v1/dav/{user_id}/Drafts"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    write_file(&session, &format!("{user_id}/Drafts/Essay.txt"), "First draft").await;
+    let link = create_link(&session, &CreateShareLinkData { path: &format!("{user_id}/Drafts"), expiration: in_one_day(), password: None, max_downloads: None }).await;
+
+    let resp = session.request(Method::PATCH, format!("/v1/storage/{user_id}/Drafts"))
+        .send_json(&serde_json::json!({ "destination": format!("{user_id}/Essays"), "overwrite": false })).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let mut resp = session.request(Method::GET, "/v1/shares")
+        .send().await.unwrap();
+    let links: Vec<ShareLinkRead> = resp.json().await.expect("JSON response");
+    assert_eq!(links.iter().find(|listed| listed.id == link.id).expect("Listed link").path, format!("{user_id}/Essays"));
+
+    let guest = Session::new();
+    let mut resp = guest.request(Method::GET, format!("/share/{}/Essay.txt", link.token))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), "First draft");
+
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/Essays"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = guest.request(Method::GET, format!("/share/{}/Essay.txt", link.token))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn shared_directory_is_confined() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::from_bytes(b"MKCOL").unwrap(), format!("/v1/dav/{user_id}/Shared%20folder"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    write_file(&session, &format!("{user_id}/Shared%20folder/inside.txt"), "Inside").await;
+    write_file(&session, &format!("{user_id}/outside.txt"), "Outside").await;
+    let link = create_link(&session, &CreateShareLinkData { path: &format!("{user_id}/Shared folder"), expiration: in_one_day(), password: None, max_downloads: None }).await;
+
+    let guest = Session::new();
+    let mut resp = guest.request(Method::GET, format!("/share/{}/", link.token))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(resp.body().await.unwrap().to_vec()).unwrap();
+    assert!(body.contains("inside.txt"));
+
+    let mut resp = guest.request(Method::GET, format!("/share/{}/inside.txt", link.token))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), "Inside");
+
+    let resp = guest.request(Method::GET, format!("/share/{}/%2E%2E/outside.txt", link.token))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn cannot_share_or_revoke_for_others() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let path = format!("{user_id}/Not yours.txt");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    write_file(&session, &path.replace(' ', "%20"), "Eve's").await;
+    let resp = session.request(Method::POST, "/v1/shares")
+        .send_json(&CreateShareLinkData { path: &path, expiration: 0, password: None, max_downloads: None }).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let link = create_link(&session, &CreateShareLinkData { path: &path, expiration: in_one_day(), password: None, max_downloads: None }).await;
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let resp = session.request(Method::POST, "/v1/shares")
+        .send_json(&CreateShareLinkData { path: &path, expiration: in_one_day(), password: None, max_downloads: None }).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = session.request(Method::DELETE, format!("/v1/shares/{}", link.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let session = Session::new();
+    let resp = session.request(Method::GET, "/v1/shares")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
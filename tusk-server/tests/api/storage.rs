@@ -1,9 +1,13 @@
 use std::collections::HashMap;
+use std::io::Read;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 use actix_web::http::{header, Method, StatusCode};
 use actix_web::http::header::ContentType;
-use serde::Deserialize;
-use crate::{await_tusk, PASSWORD_ALICE, PASSWORD_DANIEL, PASSWORD_EVE, Session, USER_ALICE, USER_DANIEL, USER_EVE};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Deserialize, Serialize};
+use crate::{await_tusk, PASSWORD_ALICE, PASSWORD_DANIEL, PASSWORD_EVE, PASSWORD_FRANK, QUOTA_FRANK, Session, USER_ALICE, USER_DANIEL, USER_EVE, USER_FRANK};
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -22,6 +26,11 @@ pub struct StoragePathRead {
     last_access: i64,
     last_modified: i64
 }
+#[derive(Clone, Debug, Serialize)]
+pub struct MovePathData<'a> {
+    destination: &'a str,
+    overwrite: bool
+}
 
 #[actix_web::test]
 async fn create_directory() {
@@ -132,6 +141,75 @@ async fn read_file() {
     assert_eq!(contents, std::fs::read_to_string(format!("test_srv/storage/{user_id}/Quick Notes/Shopping List.txt")).unwrap());
 }
 
+#[actix_web::test]
+async fn read_directory_as_archive() {
+    await_tusk();
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+
+    let user_id = USER_DANIEL.id();
+    let mut resp = session.request(Method::GET, &format!("/v1/storage/{user_id}/Documents/?archive=zip"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).expect("Header").to_str().unwrap(), "application/zip");
+    assert_eq!(resp.headers().get(header::CONTENT_DISPOSITION).expect("Header").to_str().unwrap(), "attachment; filename=\"Documents.zip\"");
+    let contents = resp.body().limit(1 << 20).await.unwrap();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(contents)).expect("ZIP archive");
+    assert!(archive.index_for_name("Documents/Apartment/").is_some());
+    let mut readme = String::new();
+    archive.by_name("Documents/README.txt").expect("File in archive")
+        .read_to_string(&mut readme).unwrap();
+    assert_eq!(readme, std::fs::read_to_string(format!("test_srv/storage/{user_id}/Documents/README.txt")).unwrap());
+
+    // Only storages can be archived.
+    let resp = session.request(Method::GET, &format!("/v1/storage/{user_id}/Documents/README.txt?archive=zip"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Same access rules as the other requests.
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::GET, &format!("/v1/storage/{user_id}/Documents/?archive=zip"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn read_thumbnail() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Thumbnails")).expect("Directory created");
+    image::RgbImage::from_pixel(600, 300, image::Rgb([0, 128, 255]))
+        .save(format!("test_srv/storage/{user_id}/Thumbnails/landscape.png")).expect("Image created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Thumbnails/notes.txt"), "Not an image").expect("File created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let mut resp = session.request(Method::GET, &format!("/v1/storage/{user_id}/Thumbnails/landscape.png?thumbnail=256"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).expect("Header").to_str().unwrap(), "image/jpeg");
+    let contents = resp.body().await.unwrap();
+    let thumbnail = image::load_from_memory(&contents).expect("Thumbnail");
+    assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+
+    let resp = session.request(Method::GET, &format!("/v1/storage/{user_id}/Thumbnails/landscape.png?thumbnail=100"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = session.request(Method::GET, &format!("/v1/storage/{user_id}/Thumbnails/?thumbnail=256"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = session.request(Method::GET, &format!("/v1/storage/{user_id}/Thumbnails/notes.txt?thumbnail=256"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    // Thumbnails are evicted together with their files.
+    let cached = PathBuf::from(format!("test_srv/thumbnails/{user_id}/Thumbnails/landscape.png"));
+    assert!(cached.is_dir());
+    let resp = session.request(Method::DELETE, &format!("/v1/storage/{user_id}/Thumbnails/landscape.png"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert!(!cached.exists());
+}
+
 #[actix_web::test]
 async fn read_not_found() {
     await_tusk();
@@ -543,4 +621,571 @@ async fn cannot_create_if_parent_does_not_exist() {
         Hello Alice! How are you?\r\n\
         --0x0xboundary--").await.unwrap();
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
-}
\ No newline at end of file
+}
+#[actix_web::test]
+async fn move_file() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+    std::fs::write(format!("test_srv/storage/{user_id}/Documents/Other/To Be Moved.txt"), r#"Move me!"#)
+        .expect("File created");
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let resp = session.request(Method::PATCH, &format!("/v1/storage/{user_id}/Documents/Other/To%20Be%20Moved.txt"))
+        .send_json(&MovePathData { destination: &format!("{user_id}/Documents/Moved.txt"), overwrite: false })
+        .await.unwrap();
+
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers().get("location").expect("Header").to_str().unwrap(), &format!("/v1/storage/{user_id}/Documents/Moved.txt"));
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/Documents/Other/To Be Moved.txt")).exists());
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Documents/Moved.txt")).expect("File"), "Move me!");
+}
+
+#[actix_web::test]
+async fn rename_directory() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Quick Notes/Old Photos/Summer"))
+        .expect("Directory created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Quick Notes/Old Photos/Summer/beach.txt"), r#"Sand"#)
+        .expect("File created");
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let resp = session.request(Method::PATCH, &format!("/v1/storage/{user_id}/Quick%20Notes/Old%20Photos"))
+        .send_json(&MovePathData { destination: &format!("{user_id}/Quick Notes/New Photos"), overwrite: false })
+        .await.unwrap();
+
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers().get("location").expect("Header").to_str().unwrap(), &format!("/v1/storage/{user_id}/Quick Notes/New Photos/"));
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/Quick Notes/Old Photos")).exists());
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Quick Notes/New Photos/Summer/beach.txt")).expect("File"), "Sand");
+}
+
+#[actix_web::test]
+async fn move_requires_overwrite_on_conflict() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::write(format!("test_srv/storage/{user_id}/first.txt"), r#"First"#)
+        .expect("File created");
+    std::fs::write(format!("test_srv/storage/{user_id}/second.txt"), r#"Second"#)
+        .expect("File created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::PATCH, &format!("/v1/storage/{user_id}/first.txt"))
+        .send_json(&MovePathData { destination: &format!("{user_id}/second.txt"), overwrite: false })
+        .await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/second.txt")).expect("File"), "Second");
+
+    let resp = session.request(Method::PATCH, &format!("/v1/storage/{user_id}/first.txt"))
+        .send_json(&MovePathData { destination: &format!("{user_id}/second.txt"), overwrite: true })
+        .await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/first.txt")).exists());
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/second.txt")).expect("File"), "First");
+}
+
+#[actix_web::test]
+async fn cannot_move_outside_authorized_paths() {
+    await_tusk();
+    let eve_id = USER_EVE.id();
+    let daniel_id = USER_DANIEL.id();
+    std::fs::write(format!("test_srv/storage/{eve_id}/malware.txt"), r#"Nothing to see here"#)
+        .expect("File created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    // Moving into another user's root.
+    let resp = session.request(Method::PATCH, &format!("/v1/storage/{eve_id}/malware.txt"))
+        .send_json(&MovePathData { destination: &format!("{daniel_id}/malware.txt"), overwrite: false })
+        .await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    // Moving out of the storage via path traversal.
+    let resp = session.request(Method::PATCH, &format!("/v1/storage/{eve_id}/malware.txt"))
+        .send_json(&MovePathData { destination: &format!("{eve_id}/../../malware.txt"), overwrite: false })
+        .await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    // Moving from another user's root.
+    let resp = session.request(Method::PATCH, &format!("/v1/storage/{daniel_id}/README.txt"))
+        .send_json(&MovePathData { destination: &format!("{eve_id}/README.txt"), overwrite: false })
+        .await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(PathBuf::from(format!("test_srv/storage/{daniel_id}/README.txt")).exists());
+}
+
+#[actix_web::test]
+async fn cannot_move_user_root() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Root Target"))
+        .expect("Directory created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::PATCH, &format!("/v1/storage/{user_id}/"))
+        .send_json(&MovePathData { destination: ".public/eve", overwrite: false })
+        .await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = session.request(Method::PATCH, &format!("/v1/storage/{user_id}/Root%20Target"))
+        .send_json(&MovePathData { destination: &format!("{user_id}/"), overwrite: true })
+        .await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(PathBuf::from(format!("test_srv/storage/{user_id}/Root Target")).is_dir());
+}
+
+#[actix_web::test]
+async fn cannot_move_directory_inside_itself() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Matryoshka/Inner"))
+        .expect("Directory created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::PATCH, &format!("/v1/storage/{user_id}/Matryoshka"))
+        .send_json(&MovePathData { destination: &format!("{user_id}/Matryoshka/Inner/Matryoshka"), overwrite: false })
+        .await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(PathBuf::from(format!("test_srv/storage/{user_id}/Matryoshka/Inner")).is_dir());
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize)]
+pub struct CopyFailure {
+    path: String,
+    status: u16
+}
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize)]
+pub struct CopyPathRead {
+    item: StoragePathRead,
+    failures: Vec<CopyFailure>
+}
+
+#[actix_web::test]
+async fn copy_file_from_public() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+    std::fs::write("test_srv/storage/.public/Recipe.txt", r#"Flour, water, salt."#)
+        .expect("File created");
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let mut resp = session.request(Method::POST, &format!("/v1/storage/{user_id}/Documents/Other"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        { \"kind\": \"file\", \"name\": \"My Recipe.txt\", \"source\": \".public/Recipe.txt\" }\r\n\
+        --0x0xboundary--").await.unwrap();
+
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers().get("location").expect("Header").to_str().unwrap(), &format!("/v1/storage/{user_id}/Documents/Other/My Recipe.txt"));
+    let copy: CopyPathRead = resp.json().await.unwrap();
+    assert_eq!(&copy.item.filename, "My Recipe.txt");
+    assert_eq!(copy.item.kind, StoragePathReadKind::File);
+    assert!(copy.failures.is_empty());
+    assert_eq!(std::fs::read_to_string("test_srv/storage/.public/Recipe.txt").expect("File"), "Flour, water, salt.");
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Documents/Other/My Recipe.txt")).expect("File"), "Flour, water, salt.");
+}
+
+#[actix_web::test]
+async fn copy_directory_tree() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Album/2023/Summer"))
+        .expect("Directory created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Album/cover.txt"), r#"Cover"#)
+        .expect("File created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Album/2023/Summer/beach.txt"), r#"Beach"#)
+        .expect("File created");
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("/etc/passwd", format!("test_srv/storage/{user_id}/Album/2023/passwd"))
+        .expect("Symlink created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let mut resp = session.request(Method::POST, &format!("/v1/storage/.public/"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body(format!("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        {{ \"kind\": \"directory\", \"name\": \"Eve's Album\", \"source\": \"{user_id}/Album\" }}\r\n\
+        --0x0xboundary--")).await.unwrap();
+
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers().get("location").expect("Header").to_str().unwrap(), "/v1/storage/.public/Eve's Album/");
+    let copy: CopyPathRead = resp.json().await.unwrap();
+    assert_eq!(copy.item.kind, StoragePathReadKind::Directory);
+    assert_eq!(std::fs::read_to_string("test_srv/storage/.public/Eve's Album/cover.txt").expect("File"), "Cover");
+    assert_eq!(std::fs::read_to_string("test_srv/storage/.public/Eve's Album/2023/Summer/beach.txt").expect("File"), "Beach");
+    #[cfg(unix)]
+    {
+        // Symbolic links are not copied, and they are reported as failures.
+        assert_eq!(copy.failures, vec![CopyFailure { path: format!("{user_id}/Album/2023/passwd"), status: 422 }]);
+        assert!(!PathBuf::from("test_srv/storage/.public/Eve's Album/2023/passwd").exists());
+    }
+}
+
+#[actix_web::test]
+async fn copy_cannot_overwrite_or_recurse() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Recursive/Inner"))
+        .expect("Directory created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    // Copy into itself.
+    let resp = session.request(Method::POST, &format!("/v1/storage/{user_id}/Recursive/Inner"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body(format!("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        {{ \"kind\": \"directory\", \"name\": \"Recursive\", \"source\": \"{user_id}/Recursive\" }}\r\n\
+        --0x0xboundary--")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/Recursive/Inner/Recursive")).exists());
+    // Copy over an existing item.
+    let resp = session.request(Method::POST, &format!("/v1/storage/{user_id}/Recursive"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body(format!("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        {{ \"kind\": \"directory\", \"name\": \"Inner\", \"source\": \".public\" }}\r\n\
+        --0x0xboundary--")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    // Copy from another user's root.
+    let daniel_id = USER_DANIEL.id();
+    let resp = session.request(Method::POST, &format!("/v1/storage/{user_id}/Recursive"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body(format!("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        {{ \"kind\": \"file\", \"name\": \"stolen.txt\", \"source\": \"{daniel_id}/README.txt\" }}\r\n\
+        --0x0xboundary--")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/Recursive/stolen.txt")).exists());
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize)]
+pub struct StorageQuotaRead {
+    used: u64,
+    available: Option<u64>,
+    quota: Option<u64>
+}
+
+fn create_file_body(name: &str, contents: &str) -> String {
+    format!("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        {{ \"kind\": \"file\", \"name\": \"{name}\" }}\r\n\
+        --0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"payload\"; filename=\"{name}\"\r\n\
+        \r\n\
+        {contents}\r\n\
+        --0x0xboundary--")
+}
+
+#[actix_web::test]
+async fn quota_is_enforced() {
+    await_tusk();
+    let user_id = USER_FRANK.id();
+
+    let session = Session::new_authenticated(&USER_FRANK, PASSWORD_FRANK).await;
+    let mut resp = session.request(Method::GET, "/v1/storage/quota")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let quota: StorageQuotaRead = resp.json().await.expect("JSON response");
+    assert_eq!(quota, StorageQuotaRead { used: 0, available: Some(QUOTA_FRANK), quota: Some(QUOTA_FRANK) });
+
+    let resp = session.request(Method::POST, format!("/v1/storage/{user_id}"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body(create_file_body("First.txt", &"a".repeat(40))).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let mut resp = session.request(Method::GET, "/v1/storage/quota")
+        .send().await.unwrap();
+    let quota: StorageQuotaRead = resp.json().await.expect("JSON response");
+    assert_eq!(quota, StorageQuotaRead { used: 40, available: Some(QUOTA_FRANK - 40), quota: Some(QUOTA_FRANK) });
+
+    let resp = session.request(Method::POST, format!("/v1/storage/{user_id}"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body(create_file_body("Second.txt", &"b".repeat(40))).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/Second.txt")).exists());
+
+    let resp = session.request(Method::POST, "/v1/uploads")
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Length", 40))
+        .insert_header(("Upload-Metadata", format!("filename {},destination {}", STANDARD.encode("Third.txt"), STANDARD.encode(user_id.to_string()))))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+
+    let resp = session.request(Method::POST, format!("/v1/storage/{user_id}"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        { \"kind\": \"directory\", \"name\": \"Folder\" }\r\n\
+        --0x0xboundary--").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    // Deleted items count towards the usage until they are purged.
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/First.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let mut resp = session.request(Method::GET, "/v1/storage/quota")
+        .send().await.unwrap();
+    let quota: StorageQuotaRead = resp.json().await.expect("JSON response");
+    assert_eq!(quota.used, 40);
+
+    let resp = session.request(Method::DELETE, "/v1/trash")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let mut resp = session.request(Method::GET, "/v1/storage/quota")
+        .send().await.unwrap();
+    let quota: StorageQuotaRead = resp.json().await.expect("JSON response");
+    assert_eq!(quota.used, 0);
+}
+
+#[actix_web::test]
+async fn read_quota() {
+    await_tusk();
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let mut resp = session.request(Method::GET, "/v1/storage/quota")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let quota: StorageQuotaRead = resp.json().await.expect("JSON response");
+    assert!(quota.used > 0);
+    assert_eq!(quota.available, None);
+    assert_eq!(quota.quota, None);
+
+    let session = Session::new_authenticated(&USER_ALICE, PASSWORD_ALICE).await;
+    let resp = session.request(Method::GET, "/v1/storage/quota")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let session = Session::new();
+    let resp = session.request(Method::GET, "/v1/storage/quota")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn conditional_requests() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Conditional")).expect("Directory created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Conditional/draft.txt"), "Draft").expect("File created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Conditional/"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let listing_etag = resp.headers().get(header::ETAG).expect("ETag").to_str().unwrap().to_owned();
+    let resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Conditional/"))
+        .insert_header((header::IF_NONE_MATCH, listing_etag.as_str()))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    let resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Conditional/draft.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let file_etag = resp.headers().get(header::ETAG).expect("ETag").to_str().unwrap().to_owned();
+    assert!(!file_etag.starts_with("W/"));
+    let resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Conditional/draft.txt"))
+        .insert_header((header::IF_NONE_MATCH, file_etag.as_str()))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    // Reading a file does not change the tag of the storage.
+    std::fs::File::options().write(true).open(format!("test_srv/storage/{user_id}/Conditional/draft.txt")).expect("File")
+        .set_times(std::fs::FileTimes::new().set_accessed(SystemTime::now() + Duration::from_secs(3600))).expect("Times set");
+    let resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Conditional/"))
+        .insert_header((header::IF_NONE_MATCH, listing_etag.as_str()))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    // Mutations are only performed if the preconditions hold.
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/Conditional/draft.txt"))
+        .insert_header((header::IF_MATCH, "\"outdated\""))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+    assert!(PathBuf::from(format!("test_srv/storage/{user_id}/Conditional/draft.txt")).exists());
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/Conditional/draft.txt"))
+        .insert_header((header::IF_MATCH, file_etag.as_str()))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    // The listing changed, so that the previous tag of the storage does not hold any more.
+    let resp = session.request(Method::POST, format!("/v1/storage/{user_id}/Conditional"))
+        .insert_header((header::IF_MATCH, listing_etag.as_str()))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        { \"kind\": \"directory\", \"name\": \"Final\" }\r\n\
+        --0x0xboundary--").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/Conditional/Final")).exists());
+}
+
+#[actix_web::test]
+async fn replace_file() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Replace")).expect("Directory created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Replace/plan.txt"), "First plan").expect("File created");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::PUT, format!("/v1/storage/{user_id}/Replace/plan.txt"))
+        .send_body("Unconditional plan").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Replace/plan.txt"))
+        .send().await.unwrap();
+    let first_etag = resp.headers().get(header::ETAG).expect("ETag").to_str().unwrap().to_owned();
+    let mut resp = session.request(Method::PUT, format!("/v1/storage/{user_id}/Replace/plan.txt"))
+        .insert_header((header::IF_MATCH, first_etag.as_str()))
+        .send_body("Second plan").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let second_etag = resp.headers().get(header::ETAG).expect("ETag").to_str().unwrap().to_owned();
+    assert_ne!(second_etag, first_etag);
+    let item: HashMap<String, serde_json::Value> = resp.json().await.expect("JSON response");
+    let last_modified = item["last_modified"].as_i64().expect("Last modification time");
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Replace/plan.txt")).unwrap(), "Second plan");
+
+    // Concurrent writers holding outdated preconditions do not overwrite the file.
+    let resp = session.request(Method::PUT, format!("/v1/storage/{user_id}/Replace/plan.txt"))
+        .insert_header((header::IF_MATCH, first_etag.as_str()))
+        .send_body("Lost plan").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+    let resp = session.request(Method::PUT, format!("/v1/storage/{user_id}/Replace/plan.txt?last_modified={}", last_modified - 60))
+        .send_body("Lost plan").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Replace/plan.txt")).unwrap(), "Second plan");
+
+    let resp = session.request(Method::PUT, format!("/v1/storage/{user_id}/Replace/plan.txt?last_modified={last_modified}"))
+        .send_body("Third plan").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Replace/plan.txt")).unwrap(), "Third plan");
+
+    let resp = session.request(Method::PUT, format!("/v1/storage/{user_id}/Replace?last_modified={last_modified}"))
+        .send_body("Not a file").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+}
+
+
+#[actix_web::test]
+async fn create_with_timestamps() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::POST, format!("/v1/storage/{user_id}"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        { \"kind\": \"directory\", \"name\": \"Holidays\", \"created\": 1000000000, \"last_access\": 1200000000, \"last_modified\": 1100000000 }\r\n\
+        --0x0xboundary--").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = session.request(Method::POST, format!("/v1/storage/{user_id}/Holidays"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        { \"kind\": \"file\", \"name\": \"Beach.txt\", \"created\": 1300000000, \"last_access\": 1500000000, \"last_modified\": 1400000000 }\r\n\
+        --0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"payload\"; filename=\"Beach.txt\"\r\n\
+        \r\n\
+        Sand and sea\r\n\
+        --0x0xboundary--").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let mut resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Holidays/"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let directory: Vec<StoragePathRead> = resp.json().await.unwrap();
+    assert_eq!(directory.len(), 1);
+    assert_eq!(&directory[0].filename, "Beach.txt");
+    assert_eq!(directory[0].created, 1300000000);
+    // The last access time is not checked, as the file is read by the content index.
+    assert_eq!(directory[0].last_modified, 1400000000);
+
+    let mut resp = session.request(Method::GET, format!("/v1/storage/{user_id}/"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let directory: Vec<StoragePathRead> = resp.json().await.unwrap();
+    let holidays = directory.iter()
+        .find(|item| item.filename == "Holidays")
+        .expect("Directory");
+    // Creating the file has updated the other timestamps of the directory.
+    assert_eq!(holidays.created, 1000000000);
+}
+
+
+#[actix_web::test]
+async fn list_directory_pages() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Gallery/Albums")).expect("Directory created");
+    for (name, contents) in [("photo-3.jpg", "333"), ("Photo-1.jpg", "1"), ("photo-2.jpg", "22"), ("notes.txt", "4444")] {
+        std::fs::write(format!("test_srv/storage/{user_id}/Gallery/{name}"), contents).expect("File created");
+    }
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let mut names = Vec::new();
+    let mut query = String::from("limit=2");
+    loop {
+        let mut resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Gallery/?{query}"))
+            .send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let next = resp.headers().get("tusk-next-cursor")
+            .map(|next| next.to_str().unwrap().to_owned());
+        let page: Vec<StoragePathRead> = resp.json().await.unwrap();
+        assert!(page.len() <= 2);
+        names.extend(page.into_iter().map(|item| item.filename));
+        match next {
+            Some(next) => query = format!("limit=2&cursor={next}"),
+            None => break
+        }
+    }
+    assert_eq!(names, ["Albums", "notes.txt", "Photo-1.jpg", "photo-2.jpg", "photo-3.jpg"]);
+
+    let mut resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Gallery/?sort=size&order=desc&kind=file&prefix=PHOTO"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get("tusk-next-cursor").is_none());
+    let page_etag = resp.headers().get(header::ETAG).expect("ETag").to_str().unwrap().to_owned();
+    let page: Vec<StoragePathRead> = resp.json().await.unwrap();
+    let names: Vec<&str> = page.iter().map(|item| item.filename.as_str()).collect();
+    assert_eq!(names, ["photo-3.jpg", "photo-2.jpg", "Photo-1.jpg"]);
+
+    // The storage has the same tag in every page, which can be used to modify it.
+    let resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Gallery/"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::ETAG).expect("ETag").to_str().unwrap(), page_etag);
+    let resp = session.request(Method::POST, format!("/v1/storage/{user_id}/Gallery"))
+        .insert_header((header::IF_MATCH, page_etag.as_str()))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        { \"kind\": \"directory\", \"name\": \"Favorites\" }\r\n\
+        --0x0xboundary--").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Gallery/?limit=0"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = session.request(Method::GET, format!("/v1/storage/{user_id}/Gallery/?cursor=invalid"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,98 @@
+use actix_web::http::{Method, StatusCode};
+use serde::Deserialize;
+use tusk_core::resources::TeamFolder;
+use crate::{await_tusk, PASSWORD_DANIEL, PASSWORD_EVE, PASSWORD_FRANK, ROLE_DIRECTORY, Session, TUSK, USER_DANIEL, USER_EVE, USER_FRANK};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TeamFolderRead {
+    name: String,
+    path: String,
+    writable: bool
+}
+
+fn create_team_folder(name: &str) -> TeamFolder {
+    let mut db = TUSK.db().expect("Connection to database");
+    let team_folder = TeamFolder::create(&mut db, name).expect("Team folder created");
+    std::fs::create_dir_all(team_folder.directory(&TUSK)).expect("Directory created");
+    team_folder
+}
+
+async fn teams_of(session: &Session) -> Vec<TeamFolderRead> {
+    let mut resp = session.request(Method::GET, "/v1/storage/teams")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    resp.json().await.expect("JSON response")
+}
+
+#[actix_web::test]
+async fn team_folder_members() {
+    await_tusk();
+    let team_folder = create_team_folder("Marketing");
+    let mut db = TUSK.db().expect("Connection to database");
+    team_folder.set_user(&mut db, USER_DANIEL.id(), true).expect("Member added");
+    team_folder.set_user(&mut db, USER_EVE.id(), false).expect("Member added");
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let teams = teams_of(&session).await;
+    let team = teams.iter().find(|team| team.name == "Marketing").expect("Team folder");
+    assert_eq!(team.path, "teams/Marketing");
+    assert!(team.writable);
+
+    let resp = session.request(Method::PUT, "/v1/dav/teams/Marketing/plan.txt")
+        .send_body("Launch").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = session.request(Method::DELETE, "/v1/storage/teams/Marketing")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let teams = teams_of(&session).await;
+    assert!(teams.iter().any(|team| team.name == "Marketing" && !team.writable));
+    let mut resp = session.request(Method::GET, "/v1/storage/teams/Marketing/plan.txt")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), "Launch");
+    let resp = session.request(Method::DELETE, "/v1/storage/teams/Marketing/plan.txt")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let session = Session::new_authenticated(&USER_FRANK, PASSWORD_FRANK).await;
+    assert!(teams_of(&session).await.iter().all(|team| team.name != "Marketing"));
+    let resp = session.request(Method::GET, "/v1/storage/teams/Marketing/plan.txt")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = session.request(Method::GET, "/v1/storage/teams/Missing/")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn team_folder_role_members() {
+    await_tusk();
+    let team_folder = create_team_folder("Handbook");
+    let mut db = TUSK.db().expect("Connection to database");
+    team_folder.set_role(&mut db, ROLE_DIRECTORY.id(), false).expect("Member added");
+    team_folder.set_user(&mut db, USER_FRANK.id(), true).expect("Member added");
+
+    // Read-write access through the user takes precedence over read-only access through a role.
+    let session = Session::new_authenticated(&USER_FRANK, PASSWORD_FRANK).await;
+    let resp = session.request(Method::PUT, "/v1/dav/teams/Handbook/Onboarding.txt")
+        .send_body("Welcome").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let mut resp = session.request(Method::GET, "/v1/storage/teams/Handbook/")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let listing: Vec<serde_json::Value> = resp.json().await.expect("JSON response");
+    assert!(listing.iter().any(|item| item["filename"] == "Onboarding.txt"));
+    // Team folders are searched by default.
+    let mut resp = session.request(Method::GET, "/v1/storage-search?name=Onboarding.txt")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let found: serde_json::Value = resp.json().await.expect("JSON response");
+    assert!(found["results"].as_array().expect("Results").iter().any(|result| result["path"] == "teams/Handbook/Onboarding.txt"));
+    let resp = session.request(Method::PUT, "/v1/dav/teams/Handbook/Intruder.txt")
+        .send_body("Hello").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
\ No newline at end of file
@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+use actix_web::http::{header, Method, StatusCode};
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::{await_tusk, PASSWORD_DANIEL, PASSWORD_EVE, Session, USER_DANIEL, USER_EVE};
+
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize)]
+pub struct TrashItemRead {
+    id: Uuid,
+    filename: String,
+    original_path: String,
+    is_directory: bool,
+    deletion: i64,
+    expiration: i64
+}
+
+async fn create_file(session: &Session, storage: &str, name: &str, contents: &str) {
+    let resp = session.request(Method::POST, format!("/v1/storage/{storage}"))
+        .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=\"0x0xboundary\""))
+        .send_body(format!("--0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"metadata\"\r\n\
+        Content-Type: application/json\r\n\
+        \r\n\
+        {{ \"kind\": \"file\", \"name\": \"{name}\" }}\r\n\
+        --0x0xboundary\r\n\
+        Content-Disposition: form-data; name=\"payload\"; filename=\"{name}\"\r\n\
+        \r\n\
+        {contents}\r\n\
+        --0x0xboundary--")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+}
+
+async fn find_in_trash(session: &Session, original_path: &str) -> Option<TrashItemRead> {
+    let mut resp = session.request(Method::GET, "/v1/trash")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let items: Vec<TrashItemRead> = resp.json().await.expect("JSON response");
+    items.into_iter()
+        .find(|item| item.original_path == original_path)
+}
+
+#[actix_web::test]
+async fn delete_and_restore_file() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    create_file(&session, &user_id.to_string(), "Restorable.txt", "Keep me").await;
+
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/Restorable.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/Restorable.txt")).exists());
+
+    let item = find_in_trash(&session, &format!("{user_id}/Restorable.txt")).await
+        .expect("Deleted item");
+    assert_eq!(item.filename, "Restorable.txt");
+    assert!(!item.is_directory);
+    assert!(item.expiration > item.deletion);
+
+    let resp = session.request(Method::POST, format!("/v1/trash/{}", item.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers().get(header::LOCATION).expect("Header").to_str().unwrap(), &format!("/v1/storage/{user_id}/Restorable.txt"));
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Restorable.txt")).expect("File"), "Keep me");
+    assert!(find_in_trash(&session, &format!("{user_id}/Restorable.txt")).await.is_none());
+
+    let resp = session.request(Method::POST, format!("/v1/trash/{}", item.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn cannot_restore_over_existing_item() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    create_file(&session, &user_id.to_string(), "Replaced.txt", "Old").await;
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/Replaced.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    create_file(&session, &user_id.to_string(), "Replaced.txt", "New").await;
+
+    let item = find_in_trash(&session, &format!("{user_id}/Replaced.txt")).await
+        .expect("Deleted item");
+    let resp = session.request(Method::POST, format!("/v1/trash/{}", item.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Replaced.txt")).expect("File"), "New");
+
+    let resp = session.request(Method::DELETE, format!("/v1/trash/{}", item.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert!(find_in_trash(&session, &format!("{user_id}/Replaced.txt")).await.is_none());
+    assert!(!PathBuf::from(format!("test_srv/trash/{user_id}/{}", item.id)).exists());
+}
+
+#[actix_web::test]
+async fn deleted_directories_keep_their_contents() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Album/Summer")).expect("Directory created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Album/Summer/beach.txt"), "Sand").expect("File created");
+
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/Album"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let item = find_in_trash(&session, &format!("{user_id}/Album")).await
+        .expect("Deleted item");
+    assert!(item.is_directory);
+
+    let resp = session.request(Method::POST, format!("/v1/trash/{}", item.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Album/Summer/beach.txt")).expect("File"), "Sand");
+}
+
+#[actix_web::test]
+async fn replaced_directories_are_moved_to_trash() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Drafts")).expect("Directory created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Drafts/old.txt"), "Old").expect("File created");
+    std::fs::create_dir_all(format!("test_srv/storage/{user_id}/Finals")).expect("Directory created");
+    std::fs::write(format!("test_srv/storage/{user_id}/Finals/new.txt"), "New").expect("File created");
+
+    let resp = session.request(Method::PATCH, format!("/v1/storage/{user_id}/Finals"))
+        .send_json(&serde_json::json!({ "destination": format!("{user_id}/Drafts"), "overwrite": true })).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Drafts/new.txt")).expect("File"), "New");
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/Drafts/old.txt")).exists());
+
+    let item = find_in_trash(&session, &format!("{user_id}/Drafts")).await
+        .expect("Replaced item");
+    assert!(item.is_directory);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/trash/{user_id}/{}/old.txt", item.id)).expect("File"), "Old");
+}
+
+#[actix_web::test]
+async fn cannot_access_other_user_trash() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    create_file(&session, &user_id.to_string(), "Private.txt", "Secret").await;
+    let resp = session.request(Method::DELETE, format!("/v1/storage/{user_id}/Private.txt"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let item = find_in_trash(&session, &format!("{user_id}/Private.txt")).await
+        .expect("Deleted item");
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    assert!(find_in_trash(&session, &format!("{user_id}/Private.txt")).await.is_none());
+    let resp = session.request(Method::POST, format!("/v1/trash/{}", item.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = session.request(Method::DELETE, format!("/v1/trash/{}", item.id))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let session = Session::new();
+    let resp = session.request(Method::GET, "/v1/trash")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,248 @@
+use std::path::PathBuf;
+use actix_web::http::{header, Method, StatusCode};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use crate::{await_tusk, PASSWORD_DANIEL, PASSWORD_EVE, Session, USER_DANIEL, USER_EVE};
+
+fn metadata(filename: &str, destination: &str) -> String {
+    format!("filename {},destination {}", STANDARD.encode(filename), STANDARD.encode(destination))
+}
+
+async fn create_upload(session: &Session, filename: &str, destination: &str, length: usize) -> String {
+    let resp = session.request(Method::POST, "/v1/uploads")
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Length", length))
+        .insert_header(("Upload-Metadata", metadata(filename, destination)))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    resp.headers().get(header::LOCATION).expect("Header")
+        .to_str().unwrap()
+        .to_owned()
+}
+
+#[actix_web::test]
+async fn options_advertise_protocol() {
+    await_tusk();
+
+    let session = Session::new();
+    let resp = session.request(Method::OPTIONS, "/v1/uploads")
+        .send().await.unwrap();
+
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(resp.headers().get("Tus-Version").expect("Header").to_str().unwrap(), "1.0.0");
+    assert_eq!(resp.headers().get("Tus-Extension").expect("Header").to_str().unwrap(), "creation,termination");
+}
+
+#[actix_web::test]
+async fn upload_in_chunks() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let location = create_upload(&session, "Report.txt", &format!("{user_id}/Documents/Workplace"), 19).await;
+
+    let resp = session.request(Method::HEAD, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("Upload-Offset").expect("Header").to_str().unwrap(), "0");
+    assert_eq!(resp.headers().get("Upload-Length").expect("Header").to_str().unwrap(), "19");
+
+    let resp = session.request(Method::PATCH, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Offset", "0"))
+        .insert_header((header::CONTENT_TYPE, "application/offset+octet-stream"))
+        .send_body("Quarterly ").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(resp.headers().get("Upload-Offset").expect("Header").to_str().unwrap(), "10");
+
+    let resp = session.request(Method::PATCH, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Offset", "0"))
+        .insert_header((header::CONTENT_TYPE, "application/offset+octet-stream"))
+        .send_body("Quarterly ").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let resp = session.request(Method::PATCH, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Offset", "10"))
+        .insert_header((header::CONTENT_TYPE, "text/plain"))
+        .send_body("report.\n\n").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/Documents/Workplace/Report.txt")).exists());
+
+    let resp = session.request(Method::PATCH, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Offset", "10"))
+        .insert_header((header::CONTENT_TYPE, "application/offset+octet-stream"))
+        .send_body("report.\n\n").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(resp.headers().get("Upload-Offset").expect("Header").to_str().unwrap(), "19");
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{user_id}/Documents/Workplace/Report.txt")).expect("File"), "Quarterly report.\n\n");
+
+    let resp = session.request(Method::HEAD, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn upload_completion_can_be_retried() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let location = create_upload(&session, "Minutes.txt", &format!("{user_id}/Documents"), 16).await;
+    let destination = PathBuf::from(format!("test_srv/storage/{user_id}/Documents/Minutes.txt"));
+    std::fs::write(&destination, "Draft").unwrap();
+
+    let resp = session.request(Method::PATCH, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Offset", "0"))
+        .insert_header((header::CONTENT_TYPE, "application/offset+octet-stream"))
+        .send_body("Meeting minutes.").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(std::fs::read_to_string(&destination).expect("File"), "Draft");
+
+    let resp = session.request(Method::HEAD, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("Upload-Offset").expect("Header").to_str().unwrap(), "16");
+
+    std::fs::remove_file(&destination).unwrap();
+    let resp = session.request(Method::PATCH, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Offset", "16"))
+        .insert_header((header::CONTENT_TYPE, "application/offset+octet-stream"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(std::fs::read_to_string(&destination).expect("File"), "Meeting minutes.");
+
+    let resp = session.request(Method::HEAD, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn upload_requires_protocol_version() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let resp = session.request(Method::POST, "/v1/uploads")
+        .insert_header(("Upload-Length", 10))
+        .insert_header(("Upload-Metadata", metadata("Version.txt", &format!("{user_id}/Documents"))))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let resp = session.request(Method::POST, "/v1/uploads")
+        .insert_header(("Tus-Resumable", "0.2.2"))
+        .insert_header(("Upload-Length", 10))
+        .insert_header(("Upload-Metadata", metadata("Version.txt", &format!("{user_id}/Documents"))))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+}
+
+#[actix_web::test]
+async fn upload_checks_destination() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let resp = session.request(Method::POST, "/v1/uploads")
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Length", 10))
+        .insert_header(("Upload-Metadata", metadata("README.txt", &format!("{user_id}"))))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let resp = session.request(Method::POST, "/v1/uploads")
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Length", 10))
+        .insert_header(("Upload-Metadata", metadata("..", &format!("{user_id}/Documents"))))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = session.request(Method::POST, "/v1/uploads")
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Length", 10))
+        .insert_header(("Upload-Metadata", metadata("Missing.txt", &format!("{user_id}/Missing"))))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::POST, "/v1/uploads")
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Length", 10))
+        .insert_header(("Upload-Metadata", metadata("Malware.exe", &format!("{user_id}/Documents"))))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn upload_is_private() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let location = create_upload(&session, "Private.txt", &format!("{user_id}/Documents/Other"), 8).await;
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    let resp = session.request(Method::HEAD, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = session.request(Method::PATCH, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Offset", "0"))
+        .insert_header((header::CONTENT_TYPE, "application/offset+octet-stream"))
+        .send_body("Tampered").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert!(!PathBuf::from(format!("test_srv/storage/{user_id}/Documents/Other/Private.txt")).exists());
+}
+
+#[actix_web::test]
+async fn upload_cannot_exceed_length() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let location = create_upload(&session, "Short.txt", &format!("{user_id}/Documents/Other"), 4).await;
+
+    let resp = session.request(Method::PATCH, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .insert_header(("Upload-Offset", "0"))
+        .insert_header((header::CONTENT_TYPE, "application/offset+octet-stream"))
+        .send_body("Too long").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let resp = session.request(Method::HEAD, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .send().await.unwrap();
+    assert_eq!(resp.headers().get("Upload-Offset").expect("Header").to_str().unwrap(), "0");
+}
+
+#[actix_web::test]
+async fn upload_termination() {
+    await_tusk();
+    let user_id = USER_DANIEL.id();
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let location = create_upload(&session, "Aborted.txt", &format!("{user_id}/Documents/Other"), 16).await;
+    let upload_id = location.trim_start_matches("/v1/uploads/");
+    assert!(PathBuf::from(format!("test_srv/uploads/{upload_id}")).exists());
+
+    let resp = session.request(Method::DELETE, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert!(!PathBuf::from(format!("test_srv/uploads/{upload_id}")).exists());
+
+    let resp = session.request(Method::HEAD, &location)
+        .insert_header(("Tus-Resumable", "1.0.0"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,153 @@
+use actix_web::http::{header, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{await_tusk, PASSWORD_DANIEL, PASSWORD_EVE, Session, USER_DANIEL, USER_EVE};
+
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize)]
+pub struct FileVersionRead {
+    version: u32,
+    size: u64,
+    modified: i64,
+    author: Option<Uuid>,
+    replaced_by: Option<Uuid>
+}
+
+#[derive(Serialize)]
+struct MovePathData<'a> {
+    destination: &'a str,
+    overwrite: bool
+}
+
+async fn write_file(session: &Session, path: &str, contents: &'static str) {
+    let resp = session.request(Method::PUT, format!("/v1/dav/{path}"))
+        .send_body(contents).await.unwrap();
+    assert!(resp.status().is_success());
+}
+
+async fn list_versions(session: &Session, path: &str) -> Vec<FileVersionRead> {
+    let mut resp = session.request(Method::GET, format!("/v1/storage-versions/{path}"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    resp.json().await.expect("JSON response")
+}
+
+#[actix_web::test]
+async fn replaced_contents_are_kept() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let path = format!("{user_id}/Versioned.txt");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    write_file(&session, &path, "First").await;
+    assert!(list_versions(&session, &path).await.is_empty());
+    write_file(&session, &path, "Second").await;
+    write_file(&session, &path, "Third").await;
+
+    let versions = list_versions(&session, &path).await;
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].version, 2);
+    assert_eq!(versions[0].size, 6);
+    assert_eq!(versions[0].author, Some(user_id));
+    assert_eq!(versions[0].replaced_by, Some(user_id));
+    assert_eq!(versions[1].version, 1);
+    assert_eq!(versions[1].author, None);
+
+    let mut resp = session.request(Method::GET, format!("/v1/storage-versions/{path}?version=1"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), "First");
+    assert!(resp.headers().get(header::CONTENT_DISPOSITION).expect("Header").to_str().unwrap().contains("Versioned.txt"));
+
+    let resp = session.request(Method::GET, format!("/v1/storage-versions/{path}?version=7"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn restore_version() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let path = format!("{user_id}/Restored.txt");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    write_file(&session, &path, "Good").await;
+    write_file(&session, &path, "Bad").await;
+
+    let resp = session.request(Method::POST, format!("/v1/storage-versions/{path}"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = session.request(Method::POST, format!("/v1/storage-versions/{path}?version=1"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(std::fs::read_to_string(format!("test_srv/storage/{path}")).expect("File"), "Good");
+
+    let versions = list_versions(&session, &path).await;
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].version, 2);
+    assert_eq!(versions[0].size, 3);
+}
+
+#[actix_web::test]
+async fn old_versions_are_pruned() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let path = format!("{user_id}/Pruned.txt");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    for contents in ["1", "2", "3", "4", "5", "6"] {
+        write_file(&session, &path, contents).await;
+    }
+
+    let versions: Vec<u32> = list_versions(&session, &path).await
+        .iter()
+        .map(|version| version.version)
+        .collect();
+    assert_eq!(versions, vec![5, 4, 3]);
+
+    let resp = session.request(Method::GET, format!("/v1/storage-versions/{path}?version=1"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn overwriting_move_keeps_version() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let source = format!("{user_id}/Draft.txt");
+    let destination = format!("{user_id}/Final.txt");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    write_file(&session, &source, "New final").await;
+    write_file(&session, &destination, "Old final").await;
+
+    let resp = session.request(Method::PATCH, format!("/v1/storage/{source}"))
+        .send_json(&MovePathData { destination: &destination, overwrite: true }).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let versions = list_versions(&session, &destination).await;
+    assert_eq!(versions.len(), 1);
+    let mut resp = session.request(Method::GET, format!("/v1/storage-versions/{destination}?version=1"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body().await.unwrap(), "Old final");
+}
+
+#[actix_web::test]
+async fn cannot_access_other_user_versions() {
+    await_tusk();
+    let user_id = USER_EVE.id();
+    let path = format!("{user_id}/Private-versions.txt");
+
+    let session = Session::new_authenticated(&USER_EVE, PASSWORD_EVE).await;
+    write_file(&session, &path, "Mine").await;
+    write_file(&session, &path, "Still mine").await;
+
+    let session = Session::new_authenticated(&USER_DANIEL, PASSWORD_DANIEL).await;
+    let resp = session.request(Method::GET, format!("/v1/storage-versions/{path}"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = session.request(Method::POST, format!("/v1/storage-versions/{path}?version=1"))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
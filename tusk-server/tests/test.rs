@@ -8,7 +8,7 @@ use log::LevelFilter;
 use once_cell::sync::Lazy;
 use serde::Serialize;
 use tusk_core::config::{TuskConfiguration, TuskConfigurationFile};
-use tusk_core::resources::{Role, User};
+use tusk_core::resources::{Quota, Role, User};
 use tusk_core::test::{diesel_migrations, embed_migrations, EmbeddedMigrations, MigrationHarness};
 use tusk_server::spawn_test_server;
 
@@ -79,6 +79,19 @@ impl Session {
         }
         req
     }
+
+    pub fn request_without_redirects(&self, method: Method, path: impl AsRef<str>) -> awc::ClientRequest {
+        let client = awc::Client::builder()
+            .disable_redirects()
+            .finish();
+        let mut req = client.request(method, self.server.url(path.as_ref()))
+            .timeout(Duration::from_secs(60))
+            .insert_header((header::HOST, "localhost"));
+        if let Some(auth) = &self.auth {
+            req = req.cookie(auth.to_owned());
+        }
+        req
+    }
 }
 pub static TUSK: Lazy<TuskConfiguration> = Lazy::new(|| {
     env_logger::builder().filter_level(LevelFilter::Trace).init();
@@ -382,6 +395,66 @@ pub static USER_EVE: Lazy<User> = Lazy::new(|| {
     user
 });
 
+// ----------------------------------------------------------------
+// CREATE USER Frank
+// ----------------------------------------------------------------
+pub static PASSWORD_FRANK: &'static str = "frank#Vq81sLmZp0cW";
+pub static QUOTA_FRANK: u64 = 64;
+/// User with a small storage quota.
+pub static USER_FRANK: Lazy<User> = Lazy::new(|| {
+    let mut db = TUSK.db()
+        .expect("Connection to database");
+
+    let (user, None) = User::builder("frank@example.com")
+        .display("Frank")
+        .password(PASSWORD_FRANK)
+        .build(&mut db)
+        .expect("Created user") else { unreachable!("Password is already set") };
+
+    ROLE_USER.assign_to(&mut db, &user)
+        .expect("Role assigned");
+    ROLE_DIRECTORY.assign_to(&mut db, &user)
+        .expect("Role assigned");
+    Quota::set_for_user(&mut db, user.id(), QUOTA_FRANK)
+        .expect("Quota set");
+
+    std::fs::create_dir(format!("test_srv/storage/{}", user.id()))
+        .expect("Directory created");
+
+    log::info!("Created user `Frank <frank@example.com>` with roles `Directory, User` and a quota of {QUOTA_FRANK} bytes");
+
+    user
+});
+
+// ----------------------------------------------------------------
+// CREATE USER Grace
+// ----------------------------------------------------------------
+pub static PASSWORD_GRACE: &'static str = "grace#Lp3Xw9RtKe2m";
+/// User whose files are encrypted; no other test logs in as Grace, so that the data key of the user
+/// is always known.
+pub static USER_GRACE: Lazy<User> = Lazy::new(|| {
+    let mut db = TUSK.db()
+        .expect("Connection to database");
+
+    let (user, None) = User::builder("grace@example.com")
+        .display("Grace")
+        .password(PASSWORD_GRACE)
+        .build(&mut db)
+        .expect("Created user") else { unreachable!("Password is already set") };
+
+    ROLE_USER.assign_to(&mut db, &user)
+        .expect("Role assigned");
+    ROLE_DIRECTORY.assign_to(&mut db, &user)
+        .expect("Role assigned");
+
+    std::fs::create_dir(format!("test_srv/storage/{}", user.id()))
+        .expect("Directory created");
+
+    log::info!("Created user `Grace <grace@example.com>` with roles `Directory, User`");
+
+    user
+});
+
 /// Runs all the lazy closures for the users, actually loading them in memory and creating the respective file structure.
 pub fn await_tusk() {
     loop {
@@ -419,12 +492,16 @@ pub fn await_tusk() {
     let charlie = std::thread::spawn(|| Lazy::force(&USER_CHARLIE));
     let daniel = std::thread::spawn(|| Lazy::force(&USER_DANIEL));
     let eve = std::thread::spawn(|| Lazy::force(&USER_EVE));
+    let frank = std::thread::spawn(|| Lazy::force(&USER_FRANK));
+    let grace = std::thread::spawn(|| Lazy::force(&USER_GRACE));
 
     alice.join().unwrap();
     bob.join().unwrap();
     charlie.join().unwrap();
     daniel.join().unwrap();
     eve.join().unwrap();
+    frank.join().unwrap();
+    grace.join().unwrap();
 
     match READY_STATE.compare_exchange(READY_STATE_PENDING, READY_STATE_OK, Ordering::SeqCst, Ordering::SeqCst) {
         Ok(_) => {},